@@ -0,0 +1,34 @@
+-- Mark accounts as administrators.
+-- Administrators have to be promoted manually in the database.
+ALTER TABLE account ADD COLUMN IF NOT EXISTS admin boolean NOT NULL DEFAULT false;
+
+-- The different MTA-STS policy modes, as defined in RFC 8461.
+CREATE TYPE mta_sts_mode AS ENUM ('enforce', 'testing', 'none');
+
+-- Create the table with the domains served by this server, including their MTA-STS settings.
+CREATE TABLE IF NOT EXISTS domain (
+    name varchar(253) NOT NULL,
+    mta_sts_mode mta_sts_mode NOT NULL DEFAULT 'none',
+    mta_sts_mx text[] NOT NULL DEFAULT '{}',
+    mta_sts_max_age integer NOT NULL DEFAULT 604800,
+    mta_sts_id varchar(32) NOT NULL,
+    PRIMARY KEY (name)
+);
+
+-- Create the table storing received TLS-RPT reports (RFC 8460).
+CREATE TABLE IF NOT EXISTS tls_report (
+    id uuid UNIQUE DEFAULT uuid_generate_v4(),
+    domain varchar(253) NOT NULL,
+    organization text NOT NULL,
+    report_id text NOT NULL,
+    start_date timestamptz NOT NULL,
+    end_date timestamptz NOT NULL,
+    successful bigint NOT NULL,
+    failed bigint NOT NULL,
+    report jsonb NOT NULL,
+    received timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (id),
+    UNIQUE (domain, organization, report_id),
+    FOREIGN KEY (domain) REFERENCES domain(name) ON DELETE CASCADE
+);
+CREATE INDEX IF NOT EXISTS tls_report_domain ON tls_report(domain, start_date);
@@ -0,0 +1,40 @@
+-- The states an outgoing message can be in.
+CREATE TYPE outbound_status AS ENUM ('queued', 'delivered', 'failed');
+
+-- Create the queue of outgoing messages, with one entry per destination domain.
+CREATE TABLE IF NOT EXISTS outbound (
+    id uuid UNIQUE DEFAULT uuid_generate_v4(),
+    sender text NOT NULL,
+    domain varchar(253) NOT NULL,
+    recipients text[] NOT NULL,
+    data bytea NOT NULL,
+    status outbound_status NOT NULL DEFAULT 'queued',
+    attempts integer NOT NULL DEFAULT 0,
+    next_attempt timestamptz NOT NULL DEFAULT now(),
+    created timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (id)
+);
+CREATE INDEX IF NOT EXISTS outbound_due ON outbound(next_attempt) WHERE status = 'queued';
+
+-- Create the log of delivery attempts, including the reason of failures.
+CREATE TABLE IF NOT EXISTS delivery_attempt (
+    id uuid UNIQUE DEFAULT uuid_generate_v4(),
+    outbound uuid NOT NULL,
+    mx text,
+    success boolean NOT NULL,
+    reason text NOT NULL,
+    created timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (id),
+    FOREIGN KEY (outbound) REFERENCES outbound(id) ON DELETE CASCADE
+);
+CREATE INDEX IF NOT EXISTS delivery_attempt_outbound ON delivery_attempt(outbound);
+
+-- Create the cache of MTA-STS policies of remote domains.
+CREATE TABLE IF NOT EXISTS mta_sts_cache (
+    domain varchar(253) NOT NULL,
+    policy_id varchar(32) NOT NULL,
+    mode mta_sts_mode NOT NULL,
+    mx text[] NOT NULL,
+    expires timestamptz NOT NULL,
+    PRIMARY KEY (domain)
+);
@@ -0,0 +1,54 @@
+-- Who is allowed to post to a mailing list.
+CREATE TYPE list_policy AS ENUM ('members', 'moderated', 'open');
+
+-- The role of a subscriber of a mailing list.
+CREATE TYPE list_role AS ENUM ('member', 'moderator');
+
+-- Create the table with mailing lists, addressed as <local_part>@<domain>.
+CREATE TABLE IF NOT EXISTS mailing_list (
+    id uuid UNIQUE DEFAULT uuid_generate_v4(),
+    domain varchar(253) NOT NULL,
+    local_part varchar(64) NOT NULL,
+    name text NOT NULL,
+    description text NOT NULL DEFAULT '',
+    policy list_policy NOT NULL DEFAULT 'members',
+    last_digest timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (id),
+    UNIQUE (domain, local_part),
+    FOREIGN KEY (domain) REFERENCES domain(name) ON DELETE CASCADE
+);
+
+-- Create the table with the subscribers of mailing lists.
+-- Subscribers are disabled when their address keeps bouncing.
+CREATE TABLE IF NOT EXISTS list_member (
+    list uuid NOT NULL,
+    address text NOT NULL,
+    role list_role NOT NULL DEFAULT 'member',
+    digest boolean NOT NULL DEFAULT false,
+    disabled boolean NOT NULL DEFAULT false,
+    bounces integer NOT NULL DEFAULT 0,
+    PRIMARY KEY (list, address),
+    FOREIGN KEY (list) REFERENCES mailing_list(id) ON DELETE CASCADE
+);
+
+-- Create the table with posts waiting for moderation.
+CREATE TABLE IF NOT EXISTS list_moderation (
+    id uuid UNIQUE DEFAULT uuid_generate_v4(),
+    list uuid NOT NULL,
+    sender text NOT NULL,
+    data bytea NOT NULL,
+    created timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (id),
+    FOREIGN KEY (list) REFERENCES mailing_list(id) ON DELETE CASCADE
+);
+
+-- Create the table with posts waiting to be sent in the next digest.
+CREATE TABLE IF NOT EXISTS list_digest (
+    id uuid UNIQUE DEFAULT uuid_generate_v4(),
+    list uuid NOT NULL,
+    data bytea NOT NULL,
+    created timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (id),
+    FOREIGN KEY (list) REFERENCES mailing_list(id) ON DELETE CASCADE
+);
+CREATE INDEX IF NOT EXISTS list_digest_list ON list_digest(list, created);
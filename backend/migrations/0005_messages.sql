@@ -0,0 +1,29 @@
+-- Create the table with content addressed blobs, keyed by the SHA-256 hash of the content.
+-- The content itself lives in the blob store, blobs without references are garbage collected.
+CREATE TABLE IF NOT EXISTS blob (
+    hash char(64) NOT NULL,
+    size bigint NOT NULL,
+    refs integer NOT NULL DEFAULT 1,
+    updated timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (hash)
+);
+CREATE INDEX IF NOT EXISTS blob_unreferenced ON blob(updated) WHERE refs = 0;
+
+-- Create the table with received messages.
+-- Only the parsed metadata is kept here, the raw message is a blob.
+CREATE TABLE IF NOT EXISTS message (
+    id uuid UNIQUE DEFAULT uuid_generate_v4(),
+    account uuid NOT NULL,
+    blob char(64) NOT NULL,
+    size bigint NOT NULL,
+    sender text NOT NULL DEFAULT '',
+    recipients text NOT NULL DEFAULT '',
+    subject text NOT NULL DEFAULT '',
+    message_id text,
+    sent timestamptz,
+    received timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (id),
+    FOREIGN KEY (account) REFERENCES account(id) ON DELETE CASCADE,
+    FOREIGN KEY (blob) REFERENCES blob(hash)
+);
+CREATE INDEX IF NOT EXISTS message_account ON message(account, received);
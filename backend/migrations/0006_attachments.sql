@@ -0,0 +1,16 @@
+-- How a MIME part is meant to be presented (RFC 2183).
+CREATE TYPE disposition AS ENUM ('inline', 'attachment');
+
+-- Create the table with the MIME parts of raw messages, numbered in depth-first order.
+-- Parts belong to the blob, so they are shared by all recipients and removed with it.
+CREATE TABLE IF NOT EXISTS attachment (
+    blob char(64) NOT NULL,
+    part integer NOT NULL,
+    content_type text NOT NULL,
+    filename text,
+    size bigint NOT NULL,
+    content_id text,
+    disposition disposition NOT NULL,
+    PRIMARY KEY (blob, part),
+    FOREIGN KEY (blob) REFERENCES blob(hash) ON DELETE CASCADE
+);
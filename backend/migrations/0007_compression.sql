@@ -0,0 +1,17 @@
+-- Create the table with zstd dictionaries, trained from the stored messages.
+-- The ID is the one zstd embeds in every frame compressed with the dictionary.
+CREATE TABLE IF NOT EXISTS blob_dictionary (
+    id bigint NOT NULL,
+    data bytea NOT NULL,
+    created timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (id)
+);
+
+-- Track how blobs are stored, existing blobs are compressed in the background.
+ALTER TABLE blob
+    ADD COLUMN compressed boolean NOT NULL DEFAULT false,
+    ADD COLUMN stored_size bigint,
+    ADD COLUMN dictionary bigint REFERENCES blob_dictionary(id);
+UPDATE blob SET stored_size = size;
+ALTER TABLE blob ALTER COLUMN stored_size SET NOT NULL;
+CREATE INDEX IF NOT EXISTS blob_uncompressed ON blob(hash) WHERE NOT compressed;
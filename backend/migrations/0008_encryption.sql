@@ -0,0 +1,15 @@
+-- Create the table with the mailbox keys of accounts.
+-- The X25519 secret key is wrapped with a key derived from the password, and only unwrapped at login.
+-- The public key encrypts incoming mail, so receiving does not need the password.
+CREATE TABLE IF NOT EXISTS mailbox_key (
+    account uuid NOT NULL,
+    public_key bytea NOT NULL,
+    wrapped_key bytea NOT NULL,
+    salt bytea NOT NULL,
+    PRIMARY KEY (account),
+    FOREIGN KEY (account) REFERENCES account(id) ON DELETE CASCADE
+);
+
+-- Encrypted messages have their own blob, with a random content key sealed to the mailbox key.
+ALTER TABLE message ADD COLUMN sealed_key bytea;
+ALTER TABLE blob ADD COLUMN encrypted boolean NOT NULL DEFAULT false;
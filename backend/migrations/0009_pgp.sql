@@ -0,0 +1,11 @@
+-- Create the table with the OpenPGP public keys of accounts.
+-- Incoming mail for an account with a key is wrapped in PGP/MIME before it is stored.
+CREATE TABLE IF NOT EXISTS pgp_key (
+    account uuid NOT NULL,
+    fingerprint text NOT NULL,
+    armored text NOT NULL,
+    expires timestamptz,
+    created timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (account),
+    FOREIGN KEY (account) REFERENCES account(id) ON DELETE CASCADE
+);
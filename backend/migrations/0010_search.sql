@@ -0,0 +1,19 @@
+-- Add the full-text search document of messages.
+-- The subject and text body use the language of the message, addresses and filenames are indexed as is.
+-- Encrypted messages only have their metadata indexed, not their content.
+-- Searches are always within an account, so the documents are not indexed themselves.
+ALTER TABLE message ADD COLUMN language regconfig NOT NULL DEFAULT 'english';
+ALTER TABLE message ADD COLUMN search tsvector NOT NULL DEFAULT ''::tsvector;
+ALTER TABLE message ADD COLUMN has_attachment boolean NOT NULL DEFAULT false;
+ALTER TABLE message ADD COLUMN seen boolean NOT NULL DEFAULT false;
+
+-- Index what is known about existing messages, their bodies are not available here.
+UPDATE message m SET
+    has_attachment = EXISTS (
+        SELECT 1 FROM attachment a WHERE a.blob = m.blob AND a.disposition = 'attachment'
+    ),
+    search = setweight(to_tsvector('english', m.subject), 'A')
+        || setweight(to_tsvector('simple', m.sender || ' ' || m.recipients), 'B')
+        || setweight(to_tsvector('simple', coalesce((
+            SELECT string_agg(a.filename, ' ') FROM attachment a WHERE a.blob = m.blob
+        ), '')), 'B');
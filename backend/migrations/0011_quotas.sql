@@ -0,0 +1,20 @@
+-- Create the table with the storage used by the messages of accounts.
+-- The limits override the system default when set, the usage is counted on delivery and deletion.
+CREATE TABLE IF NOT EXISTS quota (
+    account uuid NOT NULL,
+    max_size bigint,
+    max_messages bigint,
+    size bigint NOT NULL DEFAULT 0,
+    messages bigint NOT NULL DEFAULT 0,
+    -- Set once the account was warned about its usage, and cleared when it drops again.
+    warned boolean NOT NULL DEFAULT false,
+    PRIMARY KEY (account),
+    FOREIGN KEY (account) REFERENCES account(id) ON DELETE CASCADE
+);
+
+-- Count the messages which were stored before quotas existed.
+INSERT INTO quota (account, size, messages)
+SELECT a.id, coalesce(sum(m.size), 0), count(m.id)
+FROM account a LEFT JOIN message m ON m.account = a.id
+GROUP BY a.id
+ON CONFLICT (account) DO NOTHING;
@@ -0,0 +1,53 @@
+-- The special-use roles of mailboxes (RFC 6154), every account has one mailbox of each.
+CREATE TYPE mailbox_role AS ENUM ('inbox', 'archive', 'junk', 'trash');
+
+-- What a retention policy does with messages once they are old enough.
+CREATE TYPE retention_action AS ENUM ('keep', 'delete', 'archive');
+
+-- Create the table with the mailboxes of accounts.
+-- Without a retention policy of its own, the system default of the role applies.
+CREATE TABLE IF NOT EXISTS mailbox (
+    id uuid UNIQUE DEFAULT uuid_generate_v4(),
+    account uuid NOT NULL,
+    name text NOT NULL,
+    role mailbox_role,
+    retention_action retention_action,
+    retention_days integer,
+    created timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (id),
+    UNIQUE (account, role),
+    FOREIGN KEY (account) REFERENCES account(id) ON DELETE CASCADE,
+    -- Only deleting and archiving happen after days.
+    CHECK (
+        (coalesce(retention_action, 'keep') = 'keep' AND retention_days IS NULL)
+        OR (retention_action IN ('delete', 'archive') AND retention_days > 0)
+    )
+);
+
+-- Create the mailboxes of existing accounts.
+INSERT INTO mailbox (account, name, role)
+SELECT a.id, r.name, r.role::mailbox_role
+FROM account a CROSS JOIN (VALUES ('Inbox', 'inbox'), ('Archive', 'archive'), ('Spam', 'junk'), ('Trash', 'trash')) r(name, role)
+ON CONFLICT (account, role) DO NOTHING;
+
+-- Messages are in a mailbox since they were delivered or moved there, which retention is counted from.
+ALTER TABLE message ADD COLUMN IF NOT EXISTS mailbox uuid REFERENCES mailbox(id);
+ALTER TABLE message ADD COLUMN IF NOT EXISTS moved timestamptz NOT NULL DEFAULT now();
+UPDATE message m SET mailbox = b.id, moved = m.received
+FROM mailbox b WHERE b.account = m.account AND b.role = 'inbox' AND m.mailbox IS NULL;
+ALTER TABLE message ALTER COLUMN mailbox SET NOT NULL;
+CREATE INDEX IF NOT EXISTS message_mailbox ON message(mailbox, moved);
+
+-- Create the table recording every purge of the retention policies.
+-- It is kept when the account or mailbox is removed.
+CREATE TABLE IF NOT EXISTS purge_audit (
+    id uuid UNIQUE DEFAULT uuid_generate_v4(),
+    account uuid NOT NULL,
+    mailbox uuid NOT NULL,
+    action retention_action NOT NULL,
+    messages uuid[] NOT NULL,
+    size bigint NOT NULL,
+    created timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (id)
+);
+CREATE INDEX IF NOT EXISTS purge_audit_account ON purge_audit(account, created);
@@ -0,0 +1,26 @@
+-- Messages are grouped into conversations, identified by a thread ID shared by their messages.
+-- Existing messages start in threads of their own.
+ALTER TABLE message ADD COLUMN IF NOT EXISTS thread uuid NOT NULL DEFAULT uuid_generate_v4();
+ALTER TABLE message ALTER COLUMN thread DROP DEFAULT;
+-- The subject without reply and forward prefixes, to group messages without references.
+ALTER TABLE message ADD COLUMN IF NOT EXISTS thread_subject text NOT NULL DEFAULT '';
+-- The start of the body, shown in listings.
+ALTER TABLE message ADD COLUMN IF NOT EXISTS snippet text NOT NULL DEFAULT '';
+CREATE INDEX IF NOT EXISTS message_thread ON message(thread, received);
+CREATE INDEX IF NOT EXISTS message_thread_subject ON message(account, thread_subject, received);
+
+-- Create the table with the thread of every Message-ID an account has seen.
+-- This includes referenced messages which were not received (yet),
+-- so a late parent joins the thread of its replies, and merges the threads it connects.
+CREATE TABLE IF NOT EXISTS thread_reference (
+    account uuid NOT NULL,
+    message_id text NOT NULL,
+    thread uuid NOT NULL,
+    PRIMARY KEY (account, message_id),
+    FOREIGN KEY (account) REFERENCES account(id) ON DELETE CASCADE
+);
+CREATE INDEX IF NOT EXISTS thread_reference_thread ON thread_reference(thread);
+
+INSERT INTO thread_reference (account, message_id, thread)
+SELECT account, trim(both '<> ' from message_id), thread FROM message WHERE message_id IS NOT NULL
+ON CONFLICT (account, message_id) DO NOTHING;
@@ -0,0 +1,4 @@
+-- The remaining special-use roles (RFC 6154).
+-- New enum values can not be used in the transaction adding them, the mailboxes are created by the next migration.
+ALTER TYPE mailbox_role ADD VALUE IF NOT EXISTS 'sent';
+ALTER TYPE mailbox_role ADD VALUE IF NOT EXISTS 'drafts';
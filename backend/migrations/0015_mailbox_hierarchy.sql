@@ -0,0 +1,14 @@
+-- Mailboxes can be nested, a mailbox without parent is at the top.
+-- A parent can only be deleted once it has no children.
+ALTER TABLE mailbox ADD COLUMN IF NOT EXISTS parent uuid REFERENCES mailbox(id);
+CREATE INDEX IF NOT EXISTS mailbox_parent ON mailbox(parent);
+
+-- Names are unique within their parent, regardless of case.
+-- Mailboxes at the top use the account as their parent here.
+CREATE UNIQUE INDEX IF NOT EXISTS mailbox_name ON mailbox (account, coalesce(parent, account), lower(name));
+
+-- Create the new mailboxes of existing accounts.
+INSERT INTO mailbox (account, name, role)
+SELECT a.id, r.name, r.role::mailbox_role
+FROM account a CROSS JOIN (VALUES ('Sent', 'sent'), ('Drafts', 'drafts')) r(name, role)
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,23 @@
+-- Create the table with the labels of accounts, shown in the order of their position.
+CREATE TABLE IF NOT EXISTS label (
+    id uuid UNIQUE DEFAULT uuid_generate_v4(),
+    account uuid NOT NULL,
+    name text NOT NULL,
+    color text NOT NULL CHECK (color ~ '^#[0-9a-f]{6}$'),
+    position integer NOT NULL,
+    created timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (id),
+    FOREIGN KEY (account) REFERENCES account(id) ON DELETE CASCADE
+);
+CREATE UNIQUE INDEX IF NOT EXISTS label_name ON label (account, lower(name));
+
+-- Create the table with the labels of messages, any amount per message.
+-- The index by label keeps the counts per label to the messages carrying it.
+CREATE TABLE IF NOT EXISTS message_label (
+    message uuid NOT NULL,
+    label uuid NOT NULL,
+    PRIMARY KEY (message, label),
+    FOREIGN KEY (message) REFERENCES message(id) ON DELETE CASCADE,
+    FOREIGN KEY (label) REFERENCES label(id) ON DELETE CASCADE
+);
+CREATE INDEX IF NOT EXISTS message_label_label ON message_label(label, message);
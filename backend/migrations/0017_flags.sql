@@ -0,0 +1,23 @@
+-- Every change to the messages of a mailbox increases its modification sequence (RFC 7162).
+-- Messages remember the sequence of their last change, and of when they were added to their mailbox.
+ALTER TABLE mailbox ADD COLUMN IF NOT EXISTS modseq bigint NOT NULL DEFAULT 0;
+
+-- The standard flags besides seen, and custom keywords in lowercase.
+ALTER TABLE message ADD COLUMN IF NOT EXISTS flagged boolean NOT NULL DEFAULT false;
+ALTER TABLE message ADD COLUMN IF NOT EXISTS answered boolean NOT NULL DEFAULT false;
+ALTER TABLE message ADD COLUMN IF NOT EXISTS draft boolean NOT NULL DEFAULT false;
+ALTER TABLE message ADD COLUMN IF NOT EXISTS deleted boolean NOT NULL DEFAULT false;
+ALTER TABLE message ADD COLUMN IF NOT EXISTS keywords text[] NOT NULL DEFAULT '{}';
+ALTER TABLE message ADD COLUMN IF NOT EXISTS modseq bigint NOT NULL DEFAULT 0;
+ALTER TABLE message ADD COLUMN IF NOT EXISTS created_modseq bigint NOT NULL DEFAULT 0;
+CREATE INDEX IF NOT EXISTS message_modseq ON message(mailbox, modseq);
+
+-- Create the table with the messages which left a mailbox, by being deleted or moved.
+CREATE TABLE IF NOT EXISTS message_tombstone (
+    mailbox uuid NOT NULL,
+    message uuid NOT NULL,
+    modseq bigint NOT NULL,
+    PRIMARY KEY (mailbox, message),
+    FOREIGN KEY (mailbox) REFERENCES mailbox(id) ON DELETE CASCADE
+);
+CREATE INDEX IF NOT EXISTS message_tombstone_modseq ON message_tombstone(mailbox, modseq);
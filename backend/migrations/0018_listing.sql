@@ -0,0 +1,2 @@
+-- Messages are listed per mailbox by date, with the ID to continue after the last one of a page.
+CREATE INDEX IF NOT EXISTS message_listing ON message(mailbox, received, id);
@@ -0,0 +1,9 @@
+-- Create the table with the senders of which an account loads remote images.
+-- Addresses are stored in lowercase.
+CREATE TABLE IF NOT EXISTS remote_content_sender (
+    account uuid NOT NULL,
+    address text NOT NULL,
+    created timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (account, address),
+    FOREIGN KEY (account) REFERENCES account(id) ON DELETE CASCADE
+);
@@ -0,0 +1,15 @@
+-- Create the table with the files uploaded to attach to messages.
+-- The content is a blob, uploads expire once they had time to be sent or saved into a draft.
+CREATE TABLE IF NOT EXISTS upload (
+    id uuid UNIQUE DEFAULT uuid_generate_v4(),
+    account uuid NOT NULL,
+    filename text NOT NULL,
+    content_type text NOT NULL,
+    size bigint NOT NULL,
+    blob char(64) NOT NULL,
+    created timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (id),
+    FOREIGN KEY (account) REFERENCES account(id) ON DELETE CASCADE,
+    FOREIGN KEY (blob) REFERENCES blob(hash)
+);
+CREATE INDEX IF NOT EXISTS upload_created ON upload(created);
@@ -0,0 +1,14 @@
+-- Create the table with the drafts of the accounts, as entered in the client.
+-- Every save replaces the message of the draft in the Drafts mailbox, which keeps its attachments.
+CREATE TABLE IF NOT EXISTS draft (
+    id uuid UNIQUE DEFAULT uuid_generate_v4(),
+    account uuid NOT NULL,
+    message uuid NOT NULL UNIQUE,
+    version bigint NOT NULL DEFAULT 1,
+    content jsonb NOT NULL,
+    updated timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (id),
+    FOREIGN KEY (account) REFERENCES account(id) ON DELETE CASCADE,
+    FOREIGN KEY (message) REFERENCES message(id) ON DELETE CASCADE
+);
+CREATE INDEX IF NOT EXISTS draft_account ON draft(account, updated);
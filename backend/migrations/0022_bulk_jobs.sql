@@ -0,0 +1,18 @@
+-- The states a bulk operation on messages can be in.
+CREATE TYPE bulk_status AS ENUM ('running', 'finished', 'failed');
+
+-- Create the table with the bulk operations on messages which run in the background.
+-- The results per message are appended as the batches complete.
+CREATE TABLE IF NOT EXISTS bulk_job (
+    id uuid UNIQUE DEFAULT uuid_generate_v4(),
+    account uuid NOT NULL,
+    status bulk_status NOT NULL DEFAULT 'running',
+    total integer NOT NULL,
+    results jsonb NOT NULL DEFAULT '[]',
+    error text,
+    created timestamptz NOT NULL DEFAULT now(),
+    updated timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (id),
+    FOREIGN KEY (account) REFERENCES account(id) ON DELETE CASCADE
+);
+CREATE INDEX IF NOT EXISTS bulk_job_updated ON bulk_job(status, updated);
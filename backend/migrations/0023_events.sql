@@ -0,0 +1,49 @@
+-- Publish the changes to messages to the HTTP servers, which listen on the channel.
+-- Notifications are only sent once the transaction commits, so rolled back changes are never seen.
+CREATE OR REPLACE FUNCTION notify_message() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        PERFORM pg_notify('nexium_events', json_build_object(
+            'account', OLD.account, 'type', 'messageDeleted', 'message', OLD.id, 'mailbox', OLD.mailbox
+        )::text);
+    ELSE
+        PERFORM pg_notify('nexium_events', json_build_object(
+            'account', NEW.account,
+            'type', CASE TG_OP WHEN 'INSERT' THEN 'messageCreated' ELSE 'messageUpdated' END,
+            'message', NEW.id,
+            'mailbox', NEW.mailbox
+        )::text);
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Every change to a message which is visible to clients increases its modification sequence.
+CREATE TRIGGER message_created AFTER INSERT ON message
+FOR EACH ROW EXECUTE FUNCTION notify_message();
+CREATE TRIGGER message_updated AFTER UPDATE OF modseq ON message
+FOR EACH ROW WHEN (OLD.modseq IS DISTINCT FROM NEW.modseq) EXECUTE FUNCTION notify_message();
+CREATE TRIGGER message_deleted AFTER DELETE ON message
+FOR EACH ROW EXECUTE FUNCTION notify_message();
+
+-- Publish the counters of a mailbox, whenever its messages changed.
+-- The trigger runs after the whole statement, so the counters include all of its changes.
+CREATE OR REPLACE FUNCTION notify_mailbox() RETURNS trigger AS $$
+DECLARE
+    total bigint;
+    unread bigint;
+BEGIN
+    SELECT count(*), count(*) FILTER (WHERE NOT seen) INTO total, unread
+    FROM message WHERE mailbox = NEW.id;
+
+    PERFORM pg_notify('nexium_events', json_build_object(
+        'account', NEW.account, 'type', 'mailboxChanged', 'mailbox', NEW.id,
+        'total', total, 'unread', unread
+    )::text);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Every change to the messages of a mailbox increases its modification sequence.
+CREATE TRIGGER mailbox_changed AFTER UPDATE OF modseq ON mailbox
+FOR EACH ROW WHEN (OLD.modseq IS DISTINCT FROM NEW.modseq) EXECUTE FUNCTION notify_mailbox();
@@ -0,0 +1,13 @@
+-- Create the table with subscription changes requested by mail, waiting for confirmation.
+-- The token is only sent to the address itself, so replying with it proves the request came from its owner.
+CREATE TABLE IF NOT EXISTS list_confirmation (
+    token uuid UNIQUE DEFAULT uuid_generate_v4(),
+    list uuid NOT NULL,
+    address text NOT NULL,
+    subscribe boolean NOT NULL,
+    digest boolean NOT NULL DEFAULT false,
+    created timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (token),
+    FOREIGN KEY (list) REFERENCES mailing_list(id) ON DELETE CASCADE
+);
+CREATE INDEX IF NOT EXISTS list_confirmation_created ON list_confirmation(created);
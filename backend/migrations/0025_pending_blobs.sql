@@ -0,0 +1,8 @@
+-- Create the table with content written to the blob store ahead of the transaction registering it.
+-- Entries are recorded outside of that transaction, so content of rolled back transactions can be removed.
+CREATE TABLE IF NOT EXISTS blob_pending (
+    hash char(64) NOT NULL,
+    created timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (hash)
+);
+CREATE INDEX IF NOT EXISTS blob_pending_created ON blob_pending(created);
@@ -0,0 +1,5 @@
+-- Locate the body of each part in the raw message, so its content is read without parsing the whole message.
+-- Parts registered before are located by parsing the message, their columns stay empty.
+ALTER TABLE attachment ADD COLUMN IF NOT EXISTS body_offset bigint;
+ALTER TABLE attachment ADD COLUMN IF NOT EXISTS body_length bigint;
+ALTER TABLE attachment ADD COLUMN IF NOT EXISTS transfer_encoding text;
@@ -0,0 +1,6 @@
+-- The key of the content in the store, when it differs from the hash.
+-- Blobs compressed in the background are written under a new key, so stored content is never replaced in place.
+ALTER TABLE blob ADD COLUMN IF NOT EXISTS location char(64);
+
+-- Find the blob referencing content in the store, by the key of the content.
+CREATE INDEX IF NOT EXISTS blob_content ON blob((coalesce(location, hash)));
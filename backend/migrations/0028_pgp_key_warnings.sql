@@ -0,0 +1,3 @@
+-- Remember whether the account was told that its OpenPGP key is no longer usable.
+-- It is told once per key, setting a new key resets it.
+ALTER TABLE pgp_key ADD COLUMN IF NOT EXISTS unusable_warned boolean NOT NULL DEFAULT false;
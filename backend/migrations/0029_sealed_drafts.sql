@@ -0,0 +1,6 @@
+-- The content of drafts of accounts with a mailbox key is sealed to it, like the content keys of messages.
+-- Drafts saved before are sealed when the server starts, only the public key is needed for that.
+ALTER TABLE draft ALTER COLUMN content DROP NOT NULL;
+ALTER TABLE draft ADD COLUMN IF NOT EXISTS sealed_content bytea;
+ALTER TABLE draft DROP CONSTRAINT IF EXISTS draft_content;
+ALTER TABLE draft ADD CONSTRAINT draft_content CHECK ((content IS NULL) <> (sealed_content IS NULL));
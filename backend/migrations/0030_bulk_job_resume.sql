@@ -0,0 +1,4 @@
+-- The action and the messages not changed yet of bulk operations, so they are resumed after a restart.
+-- Operations started before have no action, and fail when they are interrupted.
+ALTER TABLE bulk_job ADD COLUMN IF NOT EXISTS action jsonb;
+ALTER TABLE bulk_job ADD COLUMN IF NOT EXISTS remaining uuid[] NOT NULL DEFAULT '{}';
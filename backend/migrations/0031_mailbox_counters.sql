@@ -0,0 +1,75 @@
+-- Keep the counters of every mailbox with it, so publishing them does not count the messages again.
+ALTER TABLE mailbox ADD COLUMN IF NOT EXISTS total bigint NOT NULL DEFAULT 0;
+ALTER TABLE mailbox ADD COLUMN IF NOT EXISTS unread bigint NOT NULL DEFAULT 0;
+
+UPDATE mailbox b SET total = c.total, unread = c.unread
+FROM (
+    SELECT mailbox, count(*) AS total, count(*) FILTER (WHERE NOT seen) AS unread
+    FROM message GROUP BY mailbox
+) c
+WHERE b.id = c.mailbox;
+
+-- Apply the changes of a statement to the counters, once for every mailbox it touched.
+CREATE OR REPLACE FUNCTION count_messages() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        UPDATE mailbox b SET total = b.total + c.total, unread = b.unread + c.unread
+        FROM (
+            SELECT mailbox, count(*) AS total, count(*) FILTER (WHERE NOT seen) AS unread
+            FROM new_messages GROUP BY mailbox
+        ) c
+        WHERE b.id = c.mailbox;
+    ELSIF TG_OP = 'DELETE' THEN
+        UPDATE mailbox b SET total = b.total - c.total, unread = b.unread - c.unread
+        FROM (
+            SELECT mailbox, count(*) AS total, count(*) FILTER (WHERE NOT seen) AS unread
+            FROM old_messages GROUP BY mailbox
+        ) c
+        WHERE b.id = c.mailbox;
+    ELSE
+        -- Only moved messages and changes to the seen flag change the counters.
+        UPDATE mailbox b SET total = b.total + c.total, unread = b.unread + c.unread
+        FROM (
+            SELECT mailbox, sum(total) AS total, sum(unread) AS unread
+            FROM (
+                SELECT n.mailbox, 1 AS total, CASE WHEN n.seen THEN 0 ELSE 1 END AS unread
+                FROM new_messages n JOIN old_messages o ON o.id = n.id
+                WHERE n.mailbox <> o.mailbox OR n.seen <> o.seen
+                UNION ALL
+                SELECT o.mailbox, -1 AS total, CASE WHEN o.seen THEN 0 ELSE -1 END AS unread
+                FROM new_messages n JOIN old_messages o ON o.id = n.id
+                WHERE n.mailbox <> o.mailbox OR n.seen <> o.seen
+            ) d
+            GROUP BY mailbox
+        ) c
+        WHERE b.id = c.mailbox AND (c.total <> 0 OR c.unread <> 0);
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER messages_counted_insert AFTER INSERT ON message
+REFERENCING NEW TABLE AS new_messages
+FOR EACH STATEMENT EXECUTE FUNCTION count_messages();
+CREATE TRIGGER messages_counted_update AFTER UPDATE ON message
+REFERENCING OLD TABLE AS old_messages NEW TABLE AS new_messages
+FOR EACH STATEMENT EXECUTE FUNCTION count_messages();
+CREATE TRIGGER messages_counted_delete AFTER DELETE ON message
+REFERENCING OLD TABLE AS old_messages
+FOR EACH STATEMENT EXECUTE FUNCTION count_messages();
+
+-- Publish the counters of a mailbox whenever they changed, instead of on every change to its messages.
+CREATE OR REPLACE FUNCTION notify_mailbox() RETURNS trigger AS $$
+BEGIN
+    PERFORM pg_notify('nexium_events', json_build_object(
+        'account', NEW.account, 'type', 'mailboxChanged', 'mailbox', NEW.id,
+        'total', NEW.total, 'unread', NEW.unread
+    )::text);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS mailbox_changed ON mailbox;
+CREATE TRIGGER mailbox_changed AFTER UPDATE OF total, unread ON mailbox
+FOR EACH ROW WHEN (OLD.total IS DISTINCT FROM NEW.total OR OLD.unread IS DISTINCT FROM NEW.unread)
+EXECUTE FUNCTION notify_mailbox();
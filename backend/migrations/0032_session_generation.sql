@@ -0,0 +1,8 @@
+-- Create the table with the generation of the sessions of every account, sessions of an older generation are revoked.
+-- Accounts without a row are at generation zero, like the sessions from before generations.
+CREATE TABLE IF NOT EXISTS session_generation (
+    account uuid NOT NULL,
+    generation integer NOT NULL DEFAULT 0,
+    PRIMARY KEY (account),
+    FOREIGN KEY (account) REFERENCES account(id) ON DELETE CASCADE
+);
@@ -0,0 +1,131 @@
+use aes_gcm::{
+    aead::{generic_array::GenericArray, Aead, NewAead},
+    Aes256Gcm,
+};
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use thiserror::Error;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+/// The length of all keys, both symmetric and X25519.
+pub const KEY_LENGTH: usize = 32;
+
+/// The length of the random nonce prefixed to every ciphertext.
+const NONCE_LENGTH: usize = 12;
+
+/// A symmetric AES-256 key, or an X25519 key.
+pub type Key = [u8; KEY_LENGTH];
+
+/// Generate a random key.
+pub fn random_key() -> Key {
+    let mut key = [0; KEY_LENGTH];
+    OsRng.fill_bytes(&mut key);
+    key
+}
+
+/// Generate random bytes, like a salt.
+pub fn random_bytes(length: usize) -> Vec<u8> {
+    let mut bytes = vec![0; length];
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+}
+
+/// Encrypt with AES-256-GCM, prefixing the random nonce.
+pub fn encrypt(key: &Key, plaintext: &[u8]) -> Vec<u8> {
+    let cipher = Aes256Gcm::new(GenericArray::from_slice(key));
+    let nonce = random_bytes(NONCE_LENGTH);
+
+    let mut output = nonce.clone();
+    output.extend(
+        cipher
+            .encrypt(GenericArray::from_slice(&nonce), plaintext)
+            .expect("Encryption only fails for gigantic inputs."),
+    );
+    output
+}
+
+/// Decrypt the output of `encrypt`, which also verifies it was not tampered with.
+pub fn decrypt(key: &Key, data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    if data.len() < NONCE_LENGTH {
+        return Err(CryptoError::Invalid);
+    }
+
+    let cipher = Aes256Gcm::new(GenericArray::from_slice(key));
+    let (nonce, ciphertext) = data.split_at(NONCE_LENGTH);
+
+    cipher
+        .decrypt(GenericArray::from_slice(nonce), ciphertext)
+        .map_err(|_| CryptoError::Invalid)
+}
+
+/// Generate a new X25519 key pair, returning the secret and public key.
+pub fn generate_keypair() -> (Key, Key) {
+    let secret = StaticSecret::random_from_rng(OsRng);
+    let public = PublicKey::from(&secret);
+
+    (secret.to_bytes(), public.to_bytes())
+}
+
+/// Encrypt a small message to a public key, so only the owner of the secret key can read it.
+/// This uses an ephemeral X25519 key, and HKDF-SHA256 to derive the AES-256-GCM key.
+pub fn seal(public: &Key, plaintext: &[u8]) -> Vec<u8> {
+    let ephemeral = StaticSecret::random_from_rng(OsRng);
+    let ephemeral_public = PublicKey::from(&ephemeral).to_bytes();
+    let shared = ephemeral.diffie_hellman(&PublicKey::from(*public));
+
+    let key = sealing_key(shared.as_bytes(), &ephemeral_public, public);
+
+    let mut output = ephemeral_public.to_vec();
+    output.extend(encrypt(&key, plaintext));
+    output
+}
+
+/// Decrypt the output of `seal` with the secret key.
+pub fn open(secret: &Key, sealed: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    if sealed.len() < KEY_LENGTH {
+        return Err(CryptoError::Invalid);
+    }
+
+    let (ephemeral_public, ciphertext) = sealed.split_at(KEY_LENGTH);
+    let mut ephemeral = [0; KEY_LENGTH];
+    ephemeral.copy_from_slice(ephemeral_public);
+
+    let secret = StaticSecret::from(*secret);
+    let public = PublicKey::from(&secret).to_bytes();
+    let shared = secret.diffie_hellman(&PublicKey::from(ephemeral));
+
+    let key = sealing_key(shared.as_bytes(), &ephemeral, &public);
+    decrypt(&key, ciphertext)
+}
+
+/// Derive the key of a sealed message, bound to both public keys.
+fn sealing_key(shared: &[u8], ephemeral: &Key, recipient: &Key) -> Key {
+    let mut salt = ephemeral.to_vec();
+    salt.extend_from_slice(recipient);
+
+    let mut key = [0; KEY_LENGTH];
+    Hkdf::<Sha256>::new(Some(&salt), shared)
+        .expand(b"nexium sealed key", &mut key)
+        .expect("The key length is valid for HKDF-SHA256.");
+    key
+}
+
+/// Convert a slice to a key, if the length is right.
+pub fn to_key(bytes: &[u8]) -> Result<Key, CryptoError> {
+    let mut key = [0; KEY_LENGTH];
+
+    if bytes.len() != KEY_LENGTH {
+        return Err(CryptoError::Invalid);
+    }
+
+    key.copy_from_slice(bytes);
+    Ok(key)
+}
+
+/// Possible errors with decrypting.
+#[derive(Error, Debug)]
+pub enum CryptoError {
+    #[error("The data could not be decrypted.")]
+    Invalid,
+}
@@ -0,0 +1,65 @@
+use sqlx::PgConnection;
+
+use crate::logic::attachment::{Attachment, Disposition};
+
+/// Register the parts of a blob.
+/// Parts which were already registered by an earlier delivery are kept.
+pub async fn create(
+    conn: &mut PgConnection,
+    blob: &str,
+    attachment: &Attachment,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO attachment (
+            blob, part, content_type, filename, size, content_id, disposition,
+            body_offset, body_length, transfer_encoding
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        ON CONFLICT (blob, part) DO NOTHING",
+        blob,
+        attachment.part,
+        attachment.content_type,
+        attachment.filename,
+        attachment.size,
+        attachment.content_id,
+        attachment.disposition as Disposition,
+        attachment.body_offset,
+        attachment.body_length,
+        attachment.transfer_encoding
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// List all parts of a blob.
+pub async fn list(conn: &mut PgConnection, blob: &str) -> Result<Vec<Attachment>, sqlx::Error> {
+    sqlx::query_as!(
+        Attachment,
+        r#"SELECT part, content_type, filename, size, content_id, disposition AS "disposition: Disposition",
+            body_offset, body_length, transfer_encoding
+        FROM attachment WHERE blob = $1 ORDER BY part"#,
+        blob
+    )
+    .fetch_all(conn)
+    .await
+}
+
+/// Find a single part of a blob.
+pub async fn find(
+    conn: &mut PgConnection,
+    blob: &str,
+    part: i32,
+) -> Result<Option<Attachment>, sqlx::Error> {
+    sqlx::query_as!(
+        Attachment,
+        r#"SELECT part, content_type, filename, size, content_id, disposition AS "disposition: Disposition",
+            body_offset, body_length, transfer_encoding
+        FROM attachment WHERE blob = $1 AND part = $2"#,
+        blob,
+        part
+    )
+    .fetch_optional(conn)
+    .await
+}
@@ -31,3 +31,20 @@ pub async fn get(
     .fetch_optional(conn)
     .await
 }
+
+/// Replace the password hash of an account.
+pub async fn update(
+    conn: &mut PgConnection,
+    account: Uuid,
+    hash: String,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE auth_password SET hash = $2 WHERE account = $1",
+        &account,
+        &hash
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,146 @@
+use sqlx::PgConnection;
+
+use crate::logic::blob::Stored;
+
+/// Take another reference to an existing blob.
+/// Returns false if the blob does not exist.
+pub async fn acquire(conn: &mut PgConnection, hash: &str) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query!(
+        "UPDATE blob SET refs = refs + 1, updated = now() WHERE hash = $1",
+        hash
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+/// Register a blob which was just written compressed to the store, with a single reference.
+/// If it was registered in the meantime, a reference is added to the existing one.
+pub async fn create(
+    conn: &mut PgConnection,
+    hash: &str,
+    size: i64,
+    stored_size: i64,
+    dictionary: Option<i64>,
+    encrypted: bool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO blob (hash, size, compressed, stored_size, dictionary, encrypted)
+        VALUES ($1, $2, true, $3, $4, $5)
+        ON CONFLICT (hash) DO UPDATE SET refs = blob.refs + 1, updated = now()",
+        hash,
+        size,
+        stored_size,
+        dictionary,
+        encrypted
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Drop a reference to a blob.
+pub async fn release(conn: &mut PgConnection, hash: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE blob SET refs = refs - 1, updated = now() WHERE hash = $1",
+        hash
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Find where and how the content of a blob is stored.
+pub async fn find(conn: &mut PgConnection, hash: &str) -> Result<Option<Stored>, sqlx::Error> {
+    sqlx::query_as!(
+        Stored,
+        r#"SELECT coalesce(location, hash) AS "location!", compressed, dictionary
+        FROM blob WHERE hash = $1"#,
+        hash
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+/// Lock blobs which have been unreferenced for at least the grace period, in seconds.
+/// Returns the hashes with the keys of their content.
+/// The locks block new references until the transaction ends.
+pub async fn lock_unreferenced(
+    conn: &mut PgConnection,
+    grace: i32,
+    limit: i64,
+) -> Result<Vec<(String, String)>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"SELECT hash, coalesce(location, hash) AS "location!" FROM blob
+        WHERE refs = 0 AND updated <= now() - make_interval(secs => $1)
+        LIMIT $2
+        FOR UPDATE SKIP LOCKED"#,
+        f64::from(grace),
+        limit
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows.into_iter().map(|r| (r.hash, r.location)).collect())
+}
+
+/// Remove a blob.
+pub async fn delete(conn: &mut PgConnection, hash: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!("DELETE FROM blob WHERE hash = $1 AND refs = 0", hash)
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// List blobs which are still stored uncompressed.
+pub async fn uncompressed(conn: &mut PgConnection, limit: i64) -> Result<Vec<String>, sqlx::Error> {
+    let rows = sqlx::query!("SELECT hash FROM blob WHERE NOT compressed LIMIT $1", limit)
+        .fetch_all(conn)
+        .await?;
+
+    Ok(rows.into_iter().map(|r| r.hash).collect())
+}
+
+/// Point an uncompressed blob to its compressed content.
+/// Returns false if the blob was compressed or removed in the meantime.
+pub async fn set_compressed(
+    conn: &mut PgConnection,
+    hash: &str,
+    location: &str,
+    stored_size: i64,
+    dictionary: Option<i64>,
+) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query!(
+        "UPDATE blob SET compressed = true, location = $2, stored_size = $3, dictionary = $4
+        WHERE hash = $1 AND NOT compressed",
+        hash,
+        location,
+        stored_size,
+        dictionary
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+/// Pick random unencrypted blobs up to a size.
+pub async fn sample(
+    conn: &mut PgConnection,
+    max_size: i64,
+    limit: i64,
+) -> Result<Vec<String>, sqlx::Error> {
+    let rows = sqlx::query!(
+        "SELECT hash FROM blob WHERE size <= $1 AND NOT encrypted ORDER BY random() LIMIT $2",
+        max_size,
+        limit
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows.into_iter().map(|r| r.hash).collect())
+}
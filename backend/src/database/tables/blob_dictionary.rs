@@ -0,0 +1,33 @@
+use sqlx::PgConnection;
+
+/// Store a trained dictionary.
+/// Dictionaries are immutable, so an existing one with the same ID is kept.
+pub async fn create(conn: &mut PgConnection, id: i64, data: &[u8]) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO blob_dictionary (id, data) VALUES ($1, $2) ON CONFLICT (id) DO NOTHING",
+        id,
+        data
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Get the content of a dictionary by ID.
+pub async fn find(conn: &mut PgConnection, id: i64) -> Result<Option<Vec<u8>>, sqlx::Error> {
+    let row = sqlx::query!("SELECT data FROM blob_dictionary WHERE id = $1", id)
+        .fetch_optional(conn)
+        .await?;
+
+    Ok(row.map(|r| r.data))
+}
+
+/// Get the ID of the most recently trained dictionary.
+pub async fn latest(conn: &mut PgConnection) -> Result<Option<i64>, sqlx::Error> {
+    let row = sqlx::query!("SELECT id FROM blob_dictionary ORDER BY created DESC LIMIT 1")
+        .fetch_optional(conn)
+        .await?;
+
+    Ok(row.map(|r| r.id))
+}
@@ -0,0 +1,61 @@
+use sqlx::PgConnection;
+
+/// Record content which is about to be written to the store.
+/// Recording it again restarts the grace period, and waits for the garbage collection to finish with it.
+pub async fn record(conn: &mut PgConnection, hash: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO blob_pending (hash) VALUES ($1)
+        ON CONFLICT (hash) DO UPDATE SET created = now()",
+        hash
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Forget written content older than the grace period, in seconds, which is the content of a blob.
+pub async fn settle(conn: &mut PgConnection, grace: i32) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM blob_pending p
+        WHERE created <= now() - make_interval(secs => $1)
+        AND EXISTS (SELECT 1 FROM blob WHERE coalesce(location, hash) = p.hash)",
+        f64::from(grace)
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Lock written content older than the grace period, in seconds, which is not the content of any blob.
+/// That is content of rolled back transactions, and content replaced by a compressed copy.
+/// The locks block new writes of the same content until the transaction ends.
+pub async fn lock_abandoned(
+    conn: &mut PgConnection,
+    grace: i32,
+    limit: i64,
+) -> Result<Vec<String>, sqlx::Error> {
+    let rows = sqlx::query!(
+        "SELECT hash FROM blob_pending p
+        WHERE created <= now() - make_interval(secs => $1)
+        AND NOT EXISTS (SELECT 1 FROM blob WHERE coalesce(location, hash) = p.hash)
+        LIMIT $2
+        FOR UPDATE SKIP LOCKED",
+        f64::from(grace),
+        limit
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows.into_iter().map(|r| r.hash).collect())
+}
+
+/// Forget written content.
+pub async fn delete(conn: &mut PgConnection, hash: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!("DELETE FROM blob_pending WHERE hash = $1", hash)
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
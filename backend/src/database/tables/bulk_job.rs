@@ -0,0 +1,125 @@
+use sqlx::{types::Json, PgConnection};
+use uuid::Uuid;
+
+use crate::logic::bulk::{Action, BulkJob, BulkStatus, Interrupted, Outcome};
+
+/// Create a running bulk operation of an account, applying an action to messages.
+pub async fn create(
+    conn: &mut PgConnection,
+    account: Uuid,
+    action: &Action,
+    messages: &[Uuid],
+) -> Result<Uuid, sqlx::Error> {
+    let res = sqlx::query!(
+        "INSERT INTO bulk_job (account, total, action, remaining) VALUES ($1, $2, $3, $4) RETURNING id",
+        account,
+        messages.len() as i32,
+        Json(action) as _,
+        messages
+    )
+    .fetch_one(conn)
+    .await?;
+
+    Ok(res.id)
+}
+
+/// Find a bulk operation by ID, within an account.
+pub async fn find(
+    conn: &mut PgConnection,
+    account: Uuid,
+    id: Uuid,
+) -> Result<Option<BulkJob>, sqlx::Error> {
+    sqlx::query_as!(
+        BulkJob,
+        r#"SELECT id, status AS "status: BulkStatus", total, results AS "results: Json<Vec<Outcome>>",
+        error, created, updated
+        FROM bulk_job WHERE account = $1 AND id = $2"#,
+        account,
+        id
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+/// Append the results of a completed batch to a bulk operation.
+/// The batch is the start of the remaining messages, which are removed from them.
+pub async fn append(
+    conn: &mut PgConnection,
+    id: Uuid,
+    results: &[Outcome],
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE bulk_job SET results = results || $2, remaining = remaining[$3:], updated = now()
+        WHERE id = $1",
+        id,
+        Json(results) as _,
+        results.len() as i32 + 1
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Set the final status of a bulk operation, with the error it failed with.
+pub async fn finish(
+    conn: &mut PgConnection,
+    id: Uuid,
+    status: BulkStatus,
+    error: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE bulk_job SET status = $2, error = $3, updated = now() WHERE id = $1",
+        id,
+        status as _,
+        error
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Take over running operations without progress for an amount of minutes, to resume them.
+/// Their progress is touched, so no other server takes them over as well.
+pub async fn claim_stalled(
+    conn: &mut PgConnection,
+    minutes: i32,
+) -> Result<Vec<Interrupted>, sqlx::Error> {
+    sqlx::query_as!(
+        Interrupted,
+        r#"UPDATE bulk_job SET updated = now()
+        WHERE status = 'running' AND action IS NOT NULL AND updated <= now() - make_interval(mins => $1)
+        RETURNING id, account, action AS "action!: Json<Action>", remaining"#,
+        minutes
+    )
+    .fetch_all(conn)
+    .await
+}
+
+/// Mark running operations without progress for an amount of minutes as failed, when they can not be resumed.
+/// Returns the amount of operations marked.
+pub async fn fail_stalled(conn: &mut PgConnection, minutes: i32) -> Result<u64, sqlx::Error> {
+    let res = sqlx::query!(
+        "UPDATE bulk_job SET status = 'failed', error = 'interrupted', updated = now()
+        WHERE status = 'running' AND action IS NULL AND updated <= now() - make_interval(mins => $1)",
+        minutes
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(res.rows_affected())
+}
+
+/// Delete completed operations older than an amount of days.
+/// Returns the amount of deleted operations.
+pub async fn delete_expired(conn: &mut PgConnection, days: i32) -> Result<u64, sqlx::Error> {
+    let res = sqlx::query!(
+        "DELETE FROM bulk_job WHERE status <> 'running' AND updated <= now() - make_interval(days => $1)",
+        days
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(res.rows_affected())
+}
@@ -0,0 +1,23 @@
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+/// Log an attempt to deliver an outbound message.
+pub async fn create(
+    conn: &mut PgConnection,
+    outbound: Uuid,
+    mx: Option<&str>,
+    success: bool,
+    reason: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO delivery_attempt (outbound, mx, success, reason) VALUES ($1, $2, $3, $4)",
+        outbound,
+        mx,
+        success,
+        reason
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
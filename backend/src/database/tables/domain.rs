@@ -0,0 +1,85 @@
+use sqlx::PgConnection;
+
+use crate::logic::domain::{Domain, MtaStsMode};
+
+/// Create a new domain with the default MTA-STS settings.
+pub async fn create(
+    conn: &mut PgConnection,
+    name: &str,
+    mta_sts_id: &str,
+) -> Result<Domain, sqlx::Error> {
+    sqlx::query_as!(
+        Domain,
+        r#"INSERT INTO domain (name, mta_sts_id) VALUES ($1, $2)
+        RETURNING name, mta_sts_mode AS "mta_sts_mode: MtaStsMode", mta_sts_mx, mta_sts_max_age, mta_sts_id"#,
+        name,
+        mta_sts_id
+    )
+    .fetch_one(conn)
+    .await
+}
+
+/// Create a domain unless it exists already.
+/// Returns true if it was created.
+pub async fn create_missing(
+    conn: &mut PgConnection,
+    name: &str,
+    mta_sts_id: &str,
+) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query!(
+        "INSERT INTO domain (name, mta_sts_id) VALUES ($1, $2) ON CONFLICT (name) DO NOTHING",
+        name,
+        mta_sts_id
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+/// Find a domain by name.
+pub async fn find(conn: &mut PgConnection, name: &str) -> Result<Option<Domain>, sqlx::Error> {
+    sqlx::query_as!(
+        Domain,
+        r#"SELECT name, mta_sts_mode AS "mta_sts_mode: MtaStsMode", mta_sts_mx, mta_sts_max_age, mta_sts_id
+        FROM domain WHERE name = $1"#,
+        name
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+/// List all domains.
+pub async fn list(conn: &mut PgConnection) -> Result<Vec<Domain>, sqlx::Error> {
+    sqlx::query_as!(
+        Domain,
+        r#"SELECT name, mta_sts_mode AS "mta_sts_mode: MtaStsMode", mta_sts_mx, mta_sts_max_age, mta_sts_id
+        FROM domain ORDER BY name"#
+    )
+    .fetch_all(conn)
+    .await
+}
+
+/// Update the MTA-STS settings of a domain.
+pub async fn update_mta_sts(
+    conn: &mut PgConnection,
+    name: &str,
+    mode: MtaStsMode,
+    mx: &[String],
+    max_age: i32,
+    mta_sts_id: &str,
+) -> Result<Option<Domain>, sqlx::Error> {
+    sqlx::query_as!(
+        Domain,
+        r#"UPDATE domain SET mta_sts_mode = $2, mta_sts_mx = $3, mta_sts_max_age = $4, mta_sts_id = $5
+        WHERE name = $1
+        RETURNING name, mta_sts_mode AS "mta_sts_mode: MtaStsMode", mta_sts_mx, mta_sts_max_age, mta_sts_id"#,
+        name,
+        mode as MtaStsMode,
+        mx,
+        max_age,
+        mta_sts_id
+    )
+    .fetch_optional(conn)
+    .await
+}
@@ -0,0 +1,144 @@
+use sqlx::{types::Json, PgConnection};
+use uuid::Uuid;
+
+use crate::logic::{compose::Compose, draft::Stored};
+
+/// Create a draft of an account, with its message in the Drafts mailbox.
+/// The content is either given as is, or sealed.
+pub async fn create(
+    conn: &mut PgConnection,
+    id: Uuid,
+    account: Uuid,
+    message: Uuid,
+    content: Option<&Compose>,
+    sealed_content: Option<&[u8]>,
+) -> Result<Stored, sqlx::Error> {
+    sqlx::query_as!(
+        Stored,
+        r#"INSERT INTO draft (id, account, message, content, sealed_content) VALUES ($1, $2, $3, $4, $5)
+        RETURNING id, message, version, content AS "content?: Json<Compose>", sealed_content AS "sealed_content?", updated"#,
+        id,
+        account,
+        message,
+        content.map(Json) as _,
+        sealed_content
+    )
+    .fetch_one(conn)
+    .await
+}
+
+/// List the drafts of an account, the most recently changed first.
+pub async fn list(conn: &mut PgConnection, account: Uuid) -> Result<Vec<Stored>, sqlx::Error> {
+    sqlx::query_as!(
+        Stored,
+        r#"SELECT id, message, version, content AS "content?: Json<Compose>", sealed_content AS "sealed_content?", updated
+        FROM draft WHERE account = $1 ORDER BY updated DESC"#,
+        account
+    )
+    .fetch_all(conn)
+    .await
+}
+
+/// Find a draft by ID, within an account.
+pub async fn find(
+    conn: &mut PgConnection,
+    account: Uuid,
+    id: Uuid,
+) -> Result<Option<Stored>, sqlx::Error> {
+    sqlx::query_as!(
+        Stored,
+        r#"SELECT id, message, version, content AS "content?: Json<Compose>", sealed_content AS "sealed_content?", updated
+        FROM draft WHERE account = $1 AND id = $2"#,
+        account,
+        id
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+/// Find a draft by ID within an account, and lock it until the end of the transaction.
+pub async fn lock(
+    conn: &mut PgConnection,
+    account: Uuid,
+    id: Uuid,
+) -> Result<Option<Stored>, sqlx::Error> {
+    sqlx::query_as!(
+        Stored,
+        r#"SELECT id, message, version, content AS "content?: Json<Compose>", sealed_content AS "sealed_content?", updated
+        FROM draft WHERE account = $1 AND id = $2 FOR UPDATE"#,
+        account,
+        id
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+/// Replace the content and message of a draft, as its next version.
+pub async fn update(
+    conn: &mut PgConnection,
+    id: Uuid,
+    message: Uuid,
+    content: Option<&Compose>,
+    sealed_content: Option<&[u8]>,
+) -> Result<Stored, sqlx::Error> {
+    sqlx::query_as!(
+        Stored,
+        r#"UPDATE draft SET message = $2, content = $3, sealed_content = $4, version = version + 1, updated = now()
+        WHERE id = $1
+        RETURNING id, message, version, content AS "content?: Json<Compose>", sealed_content AS "sealed_content?", updated"#,
+        id,
+        message,
+        content.map(Json) as _,
+        sealed_content
+    )
+    .fetch_one(conn)
+    .await
+}
+
+/// Lock drafts of which the content is not sealed yet while their account has a mailbox key.
+/// Returns their ID and content, with the public mailbox key.
+pub async fn lock_unsealed(
+    conn: &mut PgConnection,
+    limit: i64,
+) -> Result<Vec<(Uuid, Json<Compose>, Vec<u8>)>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"SELECT d.id, d.content AS "content!: Json<Compose>", k.public_key
+        FROM draft d JOIN mailbox_key k ON k.account = d.account
+        WHERE d.content IS NOT NULL
+        LIMIT $1 FOR UPDATE OF d SKIP LOCKED"#,
+        limit
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|r| (r.id, r.content, r.public_key))
+        .collect())
+}
+
+/// Replace the content of a draft by the sealed content, without changing its version.
+pub async fn seal(
+    conn: &mut PgConnection,
+    id: Uuid,
+    sealed_content: &[u8],
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE draft SET content = NULL, sealed_content = $2 WHERE id = $1",
+        id,
+        sealed_content
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Delete a draft, its message is deleted separately.
+pub async fn delete(conn: &mut PgConnection, id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query!("DELETE FROM draft WHERE id = $1", id)
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
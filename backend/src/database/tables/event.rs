@@ -0,0 +1,14 @@
+use sqlx::PgConnection;
+
+/// Send a notification on a channel, to everyone listening on it.
+pub async fn notify(
+    conn: &mut PgConnection,
+    channel: &str,
+    payload: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!("SELECT pg_notify($1, $2)", channel, payload)
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,136 @@
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::logic::label::Label;
+
+/// Create a label of an account, after its other labels.
+pub async fn create(
+    conn: &mut PgConnection,
+    account: Uuid,
+    name: &str,
+    color: &str,
+) -> Result<Label, sqlx::Error> {
+    sqlx::query_as!(
+        Label,
+        r#"INSERT INTO label (account, name, color, position)
+        SELECT $1, $2, $3, coalesce(max(position) + 1, 0) FROM label WHERE account = $1
+        RETURNING id, account, name, color, position, 0::bigint AS "messages!", 0::bigint AS "unread!", created"#,
+        account,
+        name,
+        color
+    )
+    .fetch_one(conn)
+    .await
+}
+
+/// List the labels of an account in their order, with the amount of messages carrying them.
+pub async fn list(conn: &mut PgConnection, account: Uuid) -> Result<Vec<Label>, sqlx::Error> {
+    sqlx::query_as!(
+        Label,
+        r#"SELECT l.id, l.account, l.name, l.color, l.position,
+        c.messages AS "messages!", c.unread AS "unread!", l.created
+        FROM label l, LATERAL (
+            SELECT count(*) AS messages, count(*) FILTER (WHERE NOT m.seen) AS unread
+            FROM message_label ml JOIN message m ON m.id = ml.message WHERE ml.label = l.id
+        ) c
+        WHERE l.account = $1 ORDER BY l.position, lower(l.name)"#,
+        account
+    )
+    .fetch_all(conn)
+    .await
+}
+
+/// Find a label by ID, within an account.
+pub async fn find(
+    conn: &mut PgConnection,
+    account: Uuid,
+    id: Uuid,
+) -> Result<Option<Label>, sqlx::Error> {
+    sqlx::query_as!(
+        Label,
+        r#"SELECT l.id, l.account, l.name, l.color, l.position,
+        c.messages AS "messages!", c.unread AS "unread!", l.created
+        FROM label l, LATERAL (
+            SELECT count(*) AS messages, count(*) FILTER (WHERE NOT m.seen) AS unread
+            FROM message_label ml JOIN message m ON m.id = ml.message WHERE ml.label = l.id
+        ) c
+        WHERE l.account = $1 AND l.id = $2"#,
+        account,
+        id
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+/// Find the ID of the label of an account with a name, ignoring case.
+pub async fn find_name(
+    conn: &mut PgConnection,
+    account: Uuid,
+    name: &str,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let res = sqlx::query!(
+        "SELECT id FROM label WHERE account = $1 AND lower(name) = lower($2)",
+        account,
+        name
+    )
+    .fetch_optional(conn)
+    .await?;
+
+    Ok(res.map(|r| r.id))
+}
+
+/// List the IDs of all labels of an account.
+pub async fn list_ids(conn: &mut PgConnection, account: Uuid) -> Result<Vec<Uuid>, sqlx::Error> {
+    let res = sqlx::query!("SELECT id FROM label WHERE account = $1", account)
+        .fetch_all(conn)
+        .await?;
+
+    Ok(res.into_iter().map(|r| r.id).collect())
+}
+
+/// Set the name and color of a label.
+pub async fn update(
+    conn: &mut PgConnection,
+    id: Uuid,
+    name: &str,
+    color: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE label SET name = $2, color = $3 WHERE id = $1",
+        id,
+        name,
+        color
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Set the positions of the labels of an account to their index in a list.
+pub async fn reorder(
+    conn: &mut PgConnection,
+    account: Uuid,
+    ids: &[Uuid],
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE label l SET position = o.position - 1
+        FROM unnest($2::uuid[]) WITH ORDINALITY o(id, position)
+        WHERE l.account = $1 AND l.id = o.id",
+        account,
+        ids
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Delete a label, it is removed from its messages.
+pub async fn delete(conn: &mut PgConnection, id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query!("DELETE FROM label WHERE id = $1", id)
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
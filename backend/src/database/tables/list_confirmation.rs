@@ -0,0 +1,49 @@
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::logic::mailing_list::ListConfirmation;
+
+/// Store a requested subscription change, returning the token which confirms it.
+/// Expired requests of all lists are removed along the way.
+pub async fn create(
+    conn: &mut PgConnection,
+    list: Uuid,
+    address: &str,
+    subscribe: bool,
+    digest: bool,
+) -> Result<Uuid, sqlx::Error> {
+    sqlx::query!("DELETE FROM list_confirmation WHERE created < now() - interval '2 days'")
+        .execute(&mut *conn)
+        .await?;
+
+    let row = sqlx::query!(
+        "INSERT INTO list_confirmation (list, address, subscribe, digest) VALUES ($1, $2, $3, $4)
+        RETURNING token",
+        list,
+        address,
+        subscribe,
+        digest
+    )
+    .fetch_one(conn)
+    .await?;
+
+    Ok(row.token)
+}
+
+/// Remove a requested subscription change by its token, unless it expired.
+pub async fn take(
+    conn: &mut PgConnection,
+    list: Uuid,
+    token: Uuid,
+) -> Result<Option<ListConfirmation>, sqlx::Error> {
+    sqlx::query_as!(
+        ListConfirmation,
+        "DELETE FROM list_confirmation
+        WHERE list = $1 AND token = $2 AND created >= now() - interval '2 days'
+        RETURNING address, subscribe, digest",
+        list,
+        token
+    )
+    .fetch_optional(conn)
+    .await
+}
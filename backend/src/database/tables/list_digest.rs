@@ -0,0 +1,30 @@
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+/// Add a post to the next digest of a mailing list.
+pub async fn create(conn: &mut PgConnection, list: Uuid, data: &[u8]) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO list_digest (list, data) VALUES ($1, $2)",
+        list,
+        data
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Remove all posts waiting for the digest of a mailing list, oldest first.
+pub async fn take(conn: &mut PgConnection, list: Uuid) -> Result<Vec<Vec<u8>>, sqlx::Error> {
+    let rows = sqlx::query!(
+        "DELETE FROM list_digest WHERE list = $1 RETURNING data, created",
+        list
+    )
+    .fetch_all(conn)
+    .await?;
+
+    let mut rows: Vec<_> = rows.into_iter().map(|r| (r.created, r.data)).collect();
+    rows.sort_by_key(|(created, _)| *created);
+
+    Ok(rows.into_iter().map(|(_, data)| data).collect())
+}
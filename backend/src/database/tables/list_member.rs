@@ -0,0 +1,95 @@
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::logic::mailing_list::{ListMember, ListRole};
+
+/// Add a member to a mailing list, or update the existing subscription.
+/// Updating a subscription enables it again.
+pub async fn upsert(
+    conn: &mut PgConnection,
+    list: Uuid,
+    address: &str,
+    role: ListRole,
+    digest: bool,
+) -> Result<ListMember, sqlx::Error> {
+    sqlx::query_as!(
+        ListMember,
+        r#"INSERT INTO list_member (list, address, role, digest) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (list, address) DO UPDATE
+        SET role = EXCLUDED.role, digest = EXCLUDED.digest, disabled = false, bounces = 0
+        RETURNING address, role AS "role: ListRole", digest, disabled, bounces"#,
+        list,
+        address,
+        role as ListRole,
+        digest
+    )
+    .fetch_one(conn)
+    .await
+}
+
+/// Find a member of a mailing list by address.
+pub async fn find(
+    conn: &mut PgConnection,
+    list: Uuid,
+    address: &str,
+) -> Result<Option<ListMember>, sqlx::Error> {
+    sqlx::query_as!(
+        ListMember,
+        r#"SELECT address, role AS "role: ListRole", digest, disabled, bounces
+        FROM list_member WHERE list = $1 AND address = $2"#,
+        list,
+        address
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+/// List all members of a mailing list.
+pub async fn list(conn: &mut PgConnection, list: Uuid) -> Result<Vec<ListMember>, sqlx::Error> {
+    sqlx::query_as!(
+        ListMember,
+        r#"SELECT address, role AS "role: ListRole", digest, disabled, bounces
+        FROM list_member WHERE list = $1 ORDER BY address"#,
+        list
+    )
+    .fetch_all(conn)
+    .await
+}
+
+/// Remove a member from a mailing list.
+/// Returns false if the address was not subscribed.
+pub async fn delete(
+    conn: &mut PgConnection,
+    list: Uuid,
+    address: &str,
+) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query!(
+        "DELETE FROM list_member WHERE list = $1 AND address = $2",
+        list,
+        address
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+/// Count a bounce for a member, and disable it once the threshold is reached.
+pub async fn bounce(
+    conn: &mut PgConnection,
+    list: Uuid,
+    address: &str,
+    threshold: i32,
+) -> Result<Option<ListMember>, sqlx::Error> {
+    sqlx::query_as!(
+        ListMember,
+        r#"UPDATE list_member SET bounces = bounces + 1, disabled = disabled OR bounces + 1 >= $3
+        WHERE list = $1 AND address = $2
+        RETURNING address, role AS "role: ListRole", digest, disabled, bounces"#,
+        list,
+        address,
+        threshold
+    )
+    .fetch_optional(conn)
+    .await
+}
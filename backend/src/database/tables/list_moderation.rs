@@ -0,0 +1,49 @@
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::logic::mailing_list::ModeratedPost;
+
+/// Hold a post for moderation.
+pub async fn create(
+    conn: &mut PgConnection,
+    list: Uuid,
+    sender: &str,
+    data: &[u8],
+) -> Result<ModeratedPost, sqlx::Error> {
+    sqlx::query_as!(
+        ModeratedPost,
+        "INSERT INTO list_moderation (list, sender, data) VALUES ($1, $2, $3) RETURNING id, sender, data, created",
+        list,
+        sender,
+        data
+    )
+    .fetch_one(conn)
+    .await
+}
+
+/// List the posts waiting for moderation on a mailing list.
+pub async fn list(conn: &mut PgConnection, list: Uuid) -> Result<Vec<ModeratedPost>, sqlx::Error> {
+    sqlx::query_as!(
+        ModeratedPost,
+        "SELECT id, sender, data, created FROM list_moderation WHERE list = $1 ORDER BY created",
+        list
+    )
+    .fetch_all(conn)
+    .await
+}
+
+/// Remove a post from the moderation queue, returning it.
+pub async fn take(
+    conn: &mut PgConnection,
+    list: Uuid,
+    id: Uuid,
+) -> Result<Option<ModeratedPost>, sqlx::Error> {
+    sqlx::query_as!(
+        ModeratedPost,
+        "DELETE FROM list_moderation WHERE list = $1 AND id = $2 RETURNING id, sender, data, created",
+        list,
+        id
+    )
+    .fetch_optional(conn)
+    .await
+}
@@ -0,0 +1,254 @@
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::logic::mailbox::{Changes, Mailbox, RetentionAction, Role};
+
+/// Create the mailboxes with a role which an account does not have yet.
+pub async fn create_system(conn: &mut PgConnection, account: Uuid) -> Result<(), sqlx::Error> {
+    for role in Role::ALL.iter() {
+        sqlx::query!(
+            "INSERT INTO mailbox (account, name, role) VALUES ($1, $2, $3)
+            ON CONFLICT (account, role) DO NOTHING",
+            account,
+            role.name(),
+            *role as Role
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Create a mailbox without a role.
+pub async fn create(
+    conn: &mut PgConnection,
+    account: Uuid,
+    parent: Option<Uuid>,
+    name: &str,
+) -> Result<Mailbox, sqlx::Error> {
+    sqlx::query_as!(
+        Mailbox,
+        r#"INSERT INTO mailbox (account, parent, name) VALUES ($1, $2, $3)
+        RETURNING id, account, parent, name, role AS "role: Role",
+        retention_action AS "retention_action: RetentionAction", retention_days, modseq, created"#,
+        account,
+        parent,
+        name
+    )
+    .fetch_one(conn)
+    .await
+}
+
+/// List the mailboxes of an account.
+pub async fn list(conn: &mut PgConnection, account: Uuid) -> Result<Vec<Mailbox>, sqlx::Error> {
+    sqlx::query_as!(
+        Mailbox,
+        r#"SELECT id, account, parent, name, role AS "role: Role",
+        retention_action AS "retention_action: RetentionAction", retention_days, modseq, created
+        FROM mailbox WHERE account = $1 ORDER BY role, name"#,
+        account
+    )
+    .fetch_all(conn)
+    .await
+}
+
+/// Find a mailbox by ID, within an account.
+pub async fn find(
+    conn: &mut PgConnection,
+    account: Uuid,
+    id: Uuid,
+) -> Result<Option<Mailbox>, sqlx::Error> {
+    sqlx::query_as!(
+        Mailbox,
+        r#"SELECT id, account, parent, name, role AS "role: Role",
+        retention_action AS "retention_action: RetentionAction", retention_days, modseq, created
+        FROM mailbox WHERE account = $1 AND id = $2"#,
+        account,
+        id
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+/// Find the mailbox of an account with a role.
+pub async fn find_role(
+    conn: &mut PgConnection,
+    account: Uuid,
+    role: Role,
+) -> Result<Option<Mailbox>, sqlx::Error> {
+    sqlx::query_as!(
+        Mailbox,
+        r#"SELECT id, account, parent, name, role AS "role: Role",
+        retention_action AS "retention_action: RetentionAction", retention_days, modseq, created
+        FROM mailbox WHERE account = $1 AND role = $2"#,
+        account,
+        role as Role
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+/// Lock all mailboxes of an account until the end of the transaction.
+/// Used while changing the hierarchy, so concurrent moves can not form a cycle.
+pub async fn lock(conn: &mut PgConnection, account: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "SELECT id FROM mailbox WHERE account = $1 ORDER BY id FOR UPDATE",
+        account
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Find a mailbox of an account by its name within a parent, ignoring case.
+pub async fn find_name(
+    conn: &mut PgConnection,
+    account: Uuid,
+    parent: Option<Uuid>,
+    name: &str,
+) -> Result<Option<Mailbox>, sqlx::Error> {
+    sqlx::query_as!(
+        Mailbox,
+        r#"SELECT id, account, parent, name, role AS "role: Role",
+        retention_action AS "retention_action: RetentionAction", retention_days, modseq, created
+        FROM mailbox WHERE account = $1 AND parent IS NOT DISTINCT FROM $2 AND lower(name) = lower($3)"#,
+        account,
+        parent,
+        name
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+/// Check if a mailbox is an ancestor of another, or the same mailbox.
+pub async fn is_ancestor(
+    conn: &mut PgConnection,
+    ancestor: Uuid,
+    id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query!(
+        r#"WITH RECURSIVE ancestors (id, parent) AS (
+            SELECT id, parent FROM mailbox WHERE id = $2
+            UNION SELECT m.id, m.parent FROM mailbox m JOIN ancestors a ON m.id = a.parent
+        )
+        SELECT EXISTS (SELECT 1 FROM ancestors WHERE id = $1) AS "ancestor!""#,
+        ancestor,
+        id
+    )
+    .fetch_one(conn)
+    .await?;
+
+    Ok(res.ancestor)
+}
+
+/// Check if a mailbox has children.
+pub async fn has_children(conn: &mut PgConnection, id: Uuid) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query!(
+        r#"SELECT EXISTS (SELECT 1 FROM mailbox WHERE parent = $1) AS "children!""#,
+        id
+    )
+    .fetch_one(conn)
+    .await?;
+
+    Ok(res.children)
+}
+
+/// Set the name of a mailbox.
+pub async fn rename(conn: &mut PgConnection, id: Uuid, name: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!("UPDATE mailbox SET name = $2 WHERE id = $1", id, name)
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Set the parent of a mailbox.
+pub async fn set_parent(
+    conn: &mut PgConnection,
+    id: Uuid,
+    parent: Option<Uuid>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!("UPDATE mailbox SET parent = $2 WHERE id = $1", id, parent)
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Delete a mailbox, which has to be empty.
+pub async fn delete(conn: &mut PgConnection, id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query!("DELETE FROM mailbox WHERE id = $1", id)
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Get the changes to the messages of a mailbox after a modification sequence, with its current one.
+/// Each kind of change is limited to an amount of messages.
+pub async fn changes(
+    conn: &mut PgConnection,
+    id: Uuid,
+    since: i64,
+    limit: i64,
+) -> Result<Changes, sqlx::Error> {
+    sqlx::query_as!(
+        Changes,
+        r#"SELECT (SELECT modseq FROM mailbox WHERE id = $1) AS "modseq!",
+        ARRAY(
+            SELECT id FROM message WHERE mailbox = $1 AND created_modseq > $2 ORDER BY modseq LIMIT $3
+        ) AS "created!",
+        ARRAY(
+            SELECT id FROM message WHERE mailbox = $1 AND modseq > $2 AND created_modseq <= $2
+            ORDER BY modseq LIMIT $3
+        ) AS "updated!",
+        ARRAY(
+            SELECT message FROM message_tombstone WHERE mailbox = $1 AND modseq > $2 ORDER BY modseq LIMIT $3
+        ) AS "destroyed!""#,
+        id,
+        since,
+        limit
+    )
+    .fetch_one(conn)
+    .await
+}
+
+/// List the mailboxes which have a retention policy, or the trash and spam when they have a system default.
+pub async fn with_retention(
+    conn: &mut PgConnection,
+    trash: bool,
+    junk: bool,
+) -> Result<Vec<Mailbox>, sqlx::Error> {
+    sqlx::query_as!(
+        Mailbox,
+        r#"SELECT id, account, parent, name, role AS "role: Role",
+        retention_action AS "retention_action: RetentionAction", retention_days, modseq, created
+        FROM mailbox WHERE retention_action IN ('delete', 'archive')
+        OR (retention_action IS NULL AND ((role = 'trash' AND $1) OR (role = 'junk' AND $2)))"#,
+        trash,
+        junk
+    )
+    .fetch_all(conn)
+    .await
+}
+
+/// Set the retention policy of a mailbox.
+pub async fn set_retention(
+    conn: &mut PgConnection,
+    id: Uuid,
+    action: Option<RetentionAction>,
+    days: Option<i32>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE mailbox SET retention_action = $2, retention_days = $3 WHERE id = $1",
+        id,
+        action as Option<RetentionAction>,
+        days
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::logic::mailbox_key::WrappedKey;
+
+/// Store the mailbox key of an account.
+pub async fn create(
+    conn: &mut PgConnection,
+    account: Uuid,
+    key: &WrappedKey,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO mailbox_key (account, public_key, wrapped_key, salt) VALUES ($1, $2, $3, $4)",
+        account,
+        key.public_key,
+        key.wrapped_key,
+        key.salt
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Find the mailbox key of an account.
+pub async fn find(
+    conn: &mut PgConnection,
+    account: Uuid,
+) -> Result<Option<WrappedKey>, sqlx::Error> {
+    sqlx::query_as!(
+        WrappedKey,
+        "SELECT public_key, wrapped_key, salt FROM mailbox_key WHERE account = $1",
+        account
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+/// Get only the public mailbox key of an account.
+pub async fn public_key(
+    conn: &mut PgConnection,
+    account: Uuid,
+) -> Result<Option<Vec<u8>>, sqlx::Error> {
+    let row = sqlx::query!(
+        "SELECT public_key FROM mailbox_key WHERE account = $1",
+        account
+    )
+    .fetch_optional(conn)
+    .await?;
+
+    Ok(row.map(|r| r.public_key))
+}
+
+/// Replace the wrapping of the mailbox key of an account.
+pub async fn update_wrapping(
+    conn: &mut PgConnection,
+    account: Uuid,
+    wrapped_key: &[u8],
+    salt: &[u8],
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE mailbox_key SET wrapped_key = $2, salt = $3 WHERE account = $1",
+        account,
+        wrapped_key,
+        salt
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::logic::mailing_list::{ListPolicy, MailingList};
+
+/// Create a new mailing list.
+pub async fn create(
+    conn: &mut PgConnection,
+    domain: &str,
+    local_part: &str,
+    name: &str,
+    description: &str,
+    policy: ListPolicy,
+) -> Result<MailingList, sqlx::Error> {
+    sqlx::query_as!(
+        MailingList,
+        r#"INSERT INTO mailing_list (domain, local_part, name, description, policy)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id, domain, local_part, name, description, policy AS "policy: ListPolicy", last_digest"#,
+        domain,
+        local_part,
+        name,
+        description,
+        policy as ListPolicy
+    )
+    .fetch_one(conn)
+    .await
+}
+
+/// Find a mailing list by id.
+pub async fn find(conn: &mut PgConnection, id: Uuid) -> Result<Option<MailingList>, sqlx::Error> {
+    sqlx::query_as!(
+        MailingList,
+        r#"SELECT id, domain, local_part, name, description, policy AS "policy: ListPolicy", last_digest
+        FROM mailing_list WHERE id = $1"#,
+        id
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+/// Find a mailing list by its address.
+pub async fn find_address(
+    conn: &mut PgConnection,
+    local_part: &str,
+    domain: &str,
+) -> Result<Option<MailingList>, sqlx::Error> {
+    sqlx::query_as!(
+        MailingList,
+        r#"SELECT id, domain, local_part, name, description, policy AS "policy: ListPolicy", last_digest
+        FROM mailing_list WHERE local_part = $1 AND domain = $2"#,
+        local_part,
+        domain
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+/// List all mailing lists.
+pub async fn list(conn: &mut PgConnection) -> Result<Vec<MailingList>, sqlx::Error> {
+    sqlx::query_as!(
+        MailingList,
+        r#"SELECT id, domain, local_part, name, description, policy AS "policy: ListPolicy", last_digest
+        FROM mailing_list ORDER BY domain, local_part"#
+    )
+    .fetch_all(conn)
+    .await
+}
+
+/// List the mailing lists of which the last digest was sent more than a day ago.
+pub async fn due_digests(conn: &mut PgConnection) -> Result<Vec<MailingList>, sqlx::Error> {
+    sqlx::query_as!(
+        MailingList,
+        r#"SELECT id, domain, local_part, name, description, policy AS "policy: ListPolicy", last_digest
+        FROM mailing_list WHERE last_digest <= now() - interval '1 day'"#
+    )
+    .fetch_all(conn)
+    .await
+}
+
+/// Mark the digest of a mailing list as sent.
+pub async fn digest_sent(conn: &mut PgConnection, id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE mailing_list SET last_digest = now() WHERE id = $1",
+        id
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
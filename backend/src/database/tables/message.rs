@@ -0,0 +1,402 @@
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::logic::{
+    message::{FlagChanges, Message, Metadata, Usage},
+    search::{Document, Parameter, Sql},
+    summary::{Cursor, FlagFilter, Sort, Summary},
+    thread::normalize_subject,
+};
+
+/// The date of a listing cursor, from its key in microseconds.
+const MICROSECONDS: &str = "'epoch'::timestamptz + $9::bigint * interval '1 microsecond'";
+
+/// Create a new message in an account, with its search document.
+#[allow(clippy::too_many_arguments)]
+pub async fn create(
+    conn: &mut PgConnection,
+    account: Uuid,
+    mailbox: Uuid,
+    thread: Uuid,
+    blob: &str,
+    size: i64,
+    metadata: &Metadata,
+    document: &Document,
+    sealed_key: Option<&[u8]>,
+    seen: bool,
+    draft: bool,
+) -> Result<Message, sqlx::Error> {
+    sqlx::query_as!(
+        Message,
+        r#"WITH seq AS (UPDATE mailbox SET modseq = modseq + 1 WHERE id = $14 RETURNING modseq)
+        INSERT INTO message (account, blob, size, sender, recipients, subject, message_id, sent, sealed_key,
+            language, has_attachment, search, mailbox, thread, thread_subject, snippet, seen, draft, modseq, created_modseq)
+        SELECT $1, $2, $3, $4, $5, $6, $7, $8, $9, $10::text::regconfig, $11,
+            setweight(to_tsvector($10::text::regconfig, $6), 'A')
+            || setweight(to_tsvector('simple', $4 || ' ' || $5 || ' ' || $12), 'B')
+            || setweight(to_tsvector($10::text::regconfig, $13), 'D'), $14, $15, $16, $17, $18, $19, seq.modseq, seq.modseq
+        FROM seq
+        RETURNING id, mailbox, thread, blob, size, sender, recipients, subject, message_id, sent, received, sealed_key,
+        seen, flagged, answered, draft, deleted, keywords, '{}'::uuid[] AS "labels!", modseq"#,
+        account,
+        blob,
+        size,
+        metadata.sender,
+        metadata.recipients,
+        metadata.subject,
+        metadata.message_id,
+        metadata.sent,
+        sealed_key,
+        document.language,
+        document.has_attachment,
+        document.filenames,
+        document.body,
+        mailbox,
+        thread,
+        normalize_subject(&metadata.subject).0,
+        document.snippet(),
+        seen,
+        draft
+    )
+    .fetch_one(conn)
+    .await
+}
+
+/// Find a message by ID, within an account.
+pub async fn find(
+    conn: &mut PgConnection,
+    account: Uuid,
+    id: Uuid,
+) -> Result<Option<Message>, sqlx::Error> {
+    sqlx::query_as!(
+        Message,
+        r#"SELECT m.id, m.mailbox, m.thread, m.blob, m.size, m.sender, m.recipients, m.subject, m.message_id,
+        m.sent, m.received, m.sealed_key, m.seen, m.flagged, m.answered, m.draft, m.deleted, m.keywords,
+        ARRAY(SELECT label FROM message_label WHERE message = m.id) AS "labels!", m.modseq
+        FROM message m WHERE m.account = $1 AND m.id = $2"#,
+        account,
+        id
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+/// List the IDs of all messages of an account, oldest first, with whether they are encrypted.
+pub async fn ids(conn: &mut PgConnection, account: Uuid) -> Result<Vec<(Uuid, bool)>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"SELECT id, sealed_key IS NOT NULL AS "encrypted!" FROM message
+        WHERE account = $1 ORDER BY received, id"#,
+        account
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows.into_iter().map(|r| (r.id, r.encrypted)).collect())
+}
+
+/// Filter message IDs to those of an account, in no particular order.
+pub async fn existing(
+    conn: &mut PgConnection,
+    account: Uuid,
+    ids: &[Uuid],
+) -> Result<Vec<Uuid>, sqlx::Error> {
+    let rows = sqlx::query!(
+        "SELECT id FROM message WHERE account = $1 AND id = ANY($2)",
+        account,
+        ids
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows.into_iter().map(|r| r.id).collect())
+}
+
+/// Copy a message into a mailbox, with its flags and labels.
+/// The copy shares the blob, the caller has to take a reference to it.
+pub async fn copy(
+    conn: &mut PgConnection,
+    id: Uuid,
+    mailbox: Uuid,
+) -> Result<Message, sqlx::Error> {
+    sqlx::query_as!(
+        Message,
+        r#"WITH seq AS (UPDATE mailbox SET modseq = modseq + 1 WHERE id = $2 RETURNING modseq),
+        copied AS (
+            INSERT INTO message (account, blob, size, sender, recipients, subject, message_id, sent, received,
+                sealed_key, language, search, has_attachment, seen, mailbox, thread, thread_subject, snippet,
+                flagged, answered, draft, deleted, keywords, modseq, created_modseq)
+            SELECT m.account, m.blob, m.size, m.sender, m.recipients, m.subject, m.message_id, m.sent, m.received,
+                m.sealed_key, m.language, m.search, m.has_attachment, m.seen, $2, m.thread, m.thread_subject,
+                m.snippet, m.flagged, m.answered, m.draft, m.deleted, m.keywords, seq.modseq, seq.modseq
+            FROM message m, seq WHERE m.id = $1
+            RETURNING *
+        ),
+        labels AS (
+            INSERT INTO message_label (message, label)
+            SELECT c.id, l.label FROM copied c, message_label l WHERE l.message = $1
+        )
+        SELECT c.id, c.mailbox, c.thread, c.blob, c.size, c.sender, c.recipients, c.subject, c.message_id,
+        c.sent, c.received, c.sealed_key, c.seen, c.flagged, c.answered, c.draft, c.deleted, c.keywords,
+        ARRAY(SELECT label FROM message_label WHERE message = $1) AS "labels!", c.modseq
+        FROM copied c"#,
+        id,
+        mailbox
+    )
+    .fetch_one(conn)
+    .await
+}
+
+/// Delete a message.
+pub async fn delete(conn: &mut PgConnection, id: Uuid) -> Result<(), sqlx::Error> {
+    delete_many(conn, &[id]).await
+}
+
+/// Delete messages by ID, leaving a tombstone in their mailboxes.
+pub async fn delete_many(conn: &mut PgConnection, ids: &[Uuid]) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "WITH deleted AS (DELETE FROM message WHERE id = ANY($1) RETURNING id, mailbox),
+        seq AS (
+            UPDATE mailbox SET modseq = modseq + 1
+            WHERE id IN (SELECT mailbox FROM deleted) RETURNING id, modseq
+        )
+        INSERT INTO message_tombstone (mailbox, message, modseq)
+        SELECT d.mailbox, d.id, s.modseq FROM deleted d JOIN seq s ON s.id = d.mailbox
+        ON CONFLICT (mailbox, message) DO UPDATE SET modseq = EXCLUDED.modseq",
+        ids
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Move messages by ID into a mailbox.
+/// They leave a tombstone in their old mailbox, and count as created in the new one.
+pub async fn move_many(
+    conn: &mut PgConnection,
+    ids: &[Uuid],
+    mailbox: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "WITH moved AS (SELECT id, mailbox FROM message WHERE id = ANY($1) AND mailbox <> $2 FOR UPDATE),
+        source AS (
+            UPDATE mailbox SET modseq = modseq + 1
+            WHERE id IN (SELECT mailbox FROM moved) RETURNING id, modseq
+        ),
+        tombstone AS (
+            INSERT INTO message_tombstone (mailbox, message, modseq)
+            SELECT m.mailbox, m.id, s.modseq FROM moved m JOIN source s ON s.id = m.mailbox
+            ON CONFLICT (mailbox, message) DO UPDATE SET modseq = EXCLUDED.modseq
+        ),
+        target AS (
+            UPDATE mailbox SET modseq = modseq + 1
+            WHERE id = $2 AND EXISTS (SELECT 1 FROM moved) RETURNING modseq
+        ),
+        revived AS (
+            DELETE FROM message_tombstone WHERE mailbox = $2 AND message IN (SELECT id FROM moved)
+        )
+        UPDATE message SET mailbox = $2, moved = now(), modseq = t.modseq, created_modseq = t.modseq
+        FROM target t WHERE message.id IN (SELECT id FROM moved)",
+        ids,
+        mailbox
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Move all messages in a mailbox into another.
+pub async fn move_all(conn: &mut PgConnection, from: Uuid, to: Uuid) -> Result<(), sqlx::Error> {
+    let ids = sqlx::query!("SELECT id FROM message WHERE mailbox = $1", from)
+        .fetch_all(&mut *conn)
+        .await?;
+    let ids: Vec<Uuid> = ids.into_iter().map(|r| r.id).collect();
+
+    move_many(conn, &ids, to).await
+}
+
+/// Mark messages as changed in their mailboxes, after a change outside of their own columns.
+pub async fn touch(conn: &mut PgConnection, ids: &[Uuid]) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "WITH changed AS (SELECT id, mailbox FROM message WHERE id = ANY($1) FOR UPDATE),
+        seq AS (
+            UPDATE mailbox SET modseq = modseq + 1
+            WHERE id IN (SELECT mailbox FROM changed) RETURNING id, modseq
+        )
+        UPDATE message m SET modseq = s.modseq
+        FROM changed c JOIN seq s ON s.id = c.mailbox WHERE m.id = c.id",
+        ids
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Change the flags and keywords of messages of an account, None leaves a flag as it is.
+/// Only messages which actually change are updated.
+/// Returns the IDs of the changed messages.
+pub async fn set_flags(
+    conn: &mut PgConnection,
+    account: Uuid,
+    ids: &[Uuid],
+    changes: &FlagChanges,
+) -> Result<Vec<Uuid>, sqlx::Error> {
+    let res = sqlx::query!(
+        "WITH changed AS (
+            SELECT id, mailbox FROM message
+            WHERE account = $1 AND id = ANY($2) AND (
+                ($3::boolean IS NOT NULL AND seen <> $3) OR ($4::boolean IS NOT NULL AND flagged <> $4)
+                OR ($5::boolean IS NOT NULL AND answered <> $5) OR ($6::boolean IS NOT NULL AND draft <> $6)
+                OR ($7::boolean IS NOT NULL AND deleted <> $7)
+                OR NOT keywords @> $8::text[] OR keywords && $9::text[]
+            )
+            FOR UPDATE
+        ),
+        seq AS (
+            UPDATE mailbox SET modseq = modseq + 1
+            WHERE id IN (SELECT mailbox FROM changed) RETURNING id, modseq
+        )
+        UPDATE message m SET seen = coalesce($3, m.seen), flagged = coalesce($4, m.flagged),
+            answered = coalesce($5, m.answered), draft = coalesce($6, m.draft), deleted = coalesce($7, m.deleted),
+            keywords = ARRAY(SELECT DISTINCT k FROM unnest(m.keywords || $8) k WHERE k <> ALL($9) ORDER BY k),
+            modseq = s.modseq
+        FROM changed c JOIN seq s ON s.id = c.mailbox WHERE m.id = c.id
+        RETURNING m.id",
+        account,
+        ids,
+        changes.seen,
+        changes.flagged,
+        changes.answered,
+        changes.draft,
+        changes.deleted,
+        &changes.add_keywords,
+        &changes.remove_keywords
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(res.into_iter().map(|r| r.id).collect())
+}
+
+/// Lock the messages which are in a mailbox for longer than an amount of days.
+/// Messages locked by others are skipped, they are handled in a later batch.
+pub async fn lock_expired(
+    conn: &mut PgConnection,
+    mailbox: Uuid,
+    days: i32,
+    limit: i64,
+) -> Result<Vec<Message>, sqlx::Error> {
+    sqlx::query_as!(
+        Message,
+        r#"SELECT m.id, m.mailbox, m.thread, m.blob, m.size, m.sender, m.recipients, m.subject, m.message_id,
+        m.sent, m.received, m.sealed_key, m.seen, m.flagged, m.answered, m.draft, m.deleted, m.keywords,
+        ARRAY(SELECT label FROM message_label WHERE message = m.id) AS "labels!", m.modseq
+        FROM message m WHERE m.mailbox = $1 AND m.moved < now() - make_interval(days => $2)
+        ORDER BY m.moved LIMIT $3 FOR UPDATE OF m SKIP LOCKED"#,
+        mailbox,
+        days,
+        limit
+    )
+    .fetch_all(conn)
+    .await
+}
+
+/// Sum the sizes of all messages in an account.
+/// Blobs shared with other recipients count fully for every account.
+pub async fn usage(conn: &mut PgConnection, account: Uuid) -> Result<Usage, sqlx::Error> {
+    sqlx::query_as!(
+        Usage,
+        r#"SELECT count(*) AS "messages!", coalesce(sum(m.size), 0)::bigint AS "size!",
+        coalesce(sum(b.stored_size), 0)::bigint AS "stored!"
+        FROM message m JOIN blob b ON b.hash = m.blob WHERE m.account = $1"#,
+        account
+    )
+    .fetch_one(conn)
+    .await
+}
+
+/// Search the messages of an account, the best matches first.
+/// The conditions of the search use the parameters after the ones used here.
+pub async fn search(
+    conn: &mut PgConnection,
+    account: Uuid,
+    sql: &Sql,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<Message>, sqlx::Error> {
+    let query = format!(
+        "SELECT m.id, m.mailbox, m.thread, m.blob, m.size, m.sender, m.recipients, m.subject, m.message_id, m.sent, m.received,
+        m.sealed_key, m.seen, m.flagged, m.answered, m.draft, m.deleted, m.keywords,
+        ARRAY(SELECT label FROM message_label WHERE message = m.id) AS labels, m.modseq
+        FROM message m WHERE m.account = $1 AND {} ORDER BY {} DESC, m.received DESC
+        LIMIT $2 OFFSET $3",
+        sql.condition, sql.rank
+    );
+
+    let mut query = sqlx::query_as::<_, Message>(&query)
+        .bind(account)
+        .bind(limit)
+        .bind(offset);
+
+    for parameter in &sql.parameters {
+        query = match parameter {
+            Parameter::Text(text) => query.bind(text),
+            Parameter::Date(date) => query.bind(date),
+            Parameter::Role(role) => query.bind(role),
+        };
+    }
+
+    query.fetch_all(conn).await
+}
+
+/// List the summaries of the messages in a mailbox, in a sort and after a cursor.
+pub async fn list(
+    conn: &mut PgConnection,
+    mailbox: Uuid,
+    sort: Sort,
+    filter: &FlagFilter,
+    cursor: Option<Cursor>,
+    limit: i64,
+) -> Result<Vec<Summary>, sqlx::Error> {
+    // The key of the cursor is in microseconds for dates.
+    let (key, cursor_key, direction, comparison) = match sort {
+        Sort::Newest => ("m.received", MICROSECONDS, "DESC", "<"),
+        Sort::Oldest => ("m.received", MICROSECONDS, "ASC", ">"),
+        Sort::Largest => ("m.size", "$9", "DESC", "<"),
+        Sort::Smallest => ("m.size", "$9", "ASC", ">"),
+    };
+    let after = match cursor {
+        Some(_) => format!("AND ({}, m.id) {} ({}, $10)", key, comparison, cursor_key),
+        None => String::new(),
+    };
+
+    let query = format!(
+        "SELECT m.id, m.thread, m.sender, m.recipients, m.subject, m.snippet, m.sent, m.received,
+        m.seen, m.flagged, m.answered, m.draft, m.deleted, m.keywords,
+        ARRAY(SELECT label FROM message_label WHERE message = m.id) AS labels, m.has_attachment, m.size
+        FROM message m WHERE m.mailbox = $1
+        AND ($2::boolean IS NULL OR m.seen = $2) AND ($3::boolean IS NULL OR m.flagged = $3)
+        AND ($4::boolean IS NULL OR m.answered = $4) AND ($5::boolean IS NULL OR m.draft = $5)
+        AND ($6::boolean IS NULL OR m.deleted = $6) AND ($7::boolean IS NULL OR m.has_attachment = $7)
+        {} ORDER BY {} {2}, m.id {2} LIMIT $8",
+        after, key, direction
+    );
+
+    let mut query = sqlx::query_as::<_, Summary>(&query)
+        .bind(mailbox)
+        .bind(filter.seen)
+        .bind(filter.flagged)
+        .bind(filter.answered)
+        .bind(filter.draft)
+        .bind(filter.deleted)
+        .bind(filter.has_attachment)
+        .bind(limit);
+
+    if let Some(cursor) = cursor {
+        query = query.bind(cursor.key).bind(cursor.id);
+    }
+
+    query.fetch_all(conn).await
+}
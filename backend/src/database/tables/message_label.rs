@@ -0,0 +1,56 @@
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+/// Add labels to messages, both of an account.
+/// Returns the message of every label which was not on it yet.
+pub async fn add(
+    conn: &mut PgConnection,
+    account: Uuid,
+    messages: &[Uuid],
+    labels: &[Uuid],
+) -> Result<Vec<Uuid>, sqlx::Error> {
+    let res = sqlx::query!(
+        "INSERT INTO message_label (message, label)
+        SELECT m.id, l.id FROM message m, label l
+        WHERE m.account = $1 AND m.id = ANY($2) AND l.account = $1 AND l.id = ANY($3)
+        ON CONFLICT DO NOTHING RETURNING message",
+        account,
+        messages,
+        labels
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(res.into_iter().map(|r| r.message).collect())
+}
+
+/// Remove labels from messages of an account.
+/// Returns the message of every label which was on it.
+pub async fn remove(
+    conn: &mut PgConnection,
+    account: Uuid,
+    messages: &[Uuid],
+    labels: &[Uuid],
+) -> Result<Vec<Uuid>, sqlx::Error> {
+    let res = sqlx::query!(
+        "DELETE FROM message_label ml USING message m
+        WHERE m.id = ml.message AND m.account = $1 AND ml.message = ANY($2) AND ml.label = ANY($3)
+        RETURNING ml.message",
+        account,
+        messages,
+        labels
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(res.into_iter().map(|r| r.message).collect())
+}
+
+/// List the messages carrying a label.
+pub async fn messages(conn: &mut PgConnection, label: Uuid) -> Result<Vec<Uuid>, sqlx::Error> {
+    let res = sqlx::query!("SELECT message FROM message_label WHERE label = $1", label)
+        .fetch_all(conn)
+        .await?;
+
+    Ok(res.into_iter().map(|r| r.message).collect())
+}
@@ -1,2 +1,31 @@
 pub mod account;
+pub mod attachment;
 pub mod auth_password;
+pub mod blob;
+pub mod blob_dictionary;
+pub mod blob_pending;
+pub mod bulk_job;
+pub mod delivery_attempt;
+pub mod domain;
+pub mod draft;
+pub mod event;
+pub mod label;
+pub mod list_confirmation;
+pub mod list_digest;
+pub mod list_member;
+pub mod list_moderation;
+pub mod mailbox;
+pub mod mailbox_key;
+pub mod mailing_list;
+pub mod message;
+pub mod message_label;
+pub mod mta_sts_cache;
+pub mod outbound;
+pub mod pgp_key;
+pub mod purge_audit;
+pub mod quota;
+pub mod remote_content_sender;
+pub mod session_generation;
+pub mod thread;
+pub mod tls_report;
+pub mod upload;
@@ -0,0 +1,44 @@
+use sqlx::PgConnection;
+
+use crate::logic::{domain::MtaStsMode, mta_sts::MtaStsPolicy};
+
+/// Find the cached policy of a remote domain, including expired ones.
+pub async fn find(
+    conn: &mut PgConnection,
+    domain: &str,
+) -> Result<Option<MtaStsPolicy>, sqlx::Error> {
+    sqlx::query_as!(
+        MtaStsPolicy,
+        r#"SELECT policy_id, mode AS "mode: MtaStsMode", mx, expires
+        FROM mta_sts_cache WHERE domain = $1"#,
+        domain
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+/// Store or replace the cached policy of a remote domain.
+pub async fn store(
+    conn: &mut PgConnection,
+    domain: &str,
+    policy_id: &str,
+    mode: MtaStsMode,
+    mx: &[String],
+    max_age: i64,
+) -> Result<MtaStsPolicy, sqlx::Error> {
+    sqlx::query_as!(
+        MtaStsPolicy,
+        r#"INSERT INTO mta_sts_cache (domain, policy_id, mode, mx, expires)
+        VALUES ($1, $2, $3, $4, now() + make_interval(secs => $5))
+        ON CONFLICT (domain) DO UPDATE
+        SET policy_id = EXCLUDED.policy_id, mode = EXCLUDED.mode, mx = EXCLUDED.mx, expires = EXCLUDED.expires
+        RETURNING policy_id, mode AS "mode: MtaStsMode", mx, expires"#,
+        domain,
+        policy_id,
+        mode as MtaStsMode,
+        mx,
+        max_age as f64
+    )
+    .fetch_one(conn)
+    .await
+}
@@ -0,0 +1,72 @@
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::logic::outbound::{Outbound, OutboundStatus};
+
+/// Claim messages which are due for delivery.
+/// The claimed messages are leased by moving their next attempt into the future,
+/// so that other workers skip them while they are being delivered.
+pub async fn claim_due(
+    conn: &mut PgConnection,
+    limit: i64,
+    lease: i32,
+) -> Result<Vec<Outbound>, sqlx::Error> {
+    sqlx::query_as!(
+        Outbound,
+        r#"UPDATE outbound SET next_attempt = now() + make_interval(secs => $2)
+        WHERE id IN (
+            SELECT id FROM outbound
+            WHERE status = 'queued' AND next_attempt <= now()
+            ORDER BY next_attempt
+            LIMIT $1
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING id, sender, domain, recipients, data, status AS "status: OutboundStatus", attempts"#,
+        limit,
+        f64::from(lease)
+    )
+    .fetch_all(conn)
+    .await
+}
+
+/// Update the status of a message after an delivery attempt.
+/// The next attempt is scheduled after the given delay in seconds.
+pub async fn update_status(
+    conn: &mut PgConnection,
+    id: Uuid,
+    status: OutboundStatus,
+    delay: i32,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE outbound SET status = $2, attempts = attempts + 1, next_attempt = now() + make_interval(secs => $3)
+        WHERE id = $1",
+        id,
+        status as OutboundStatus,
+        f64::from(delay)
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Queue a message for delivery to recipients on a single domain.
+pub async fn create(
+    conn: &mut PgConnection,
+    sender: &str,
+    domain: &str,
+    recipients: &[String],
+    data: &[u8],
+) -> Result<Uuid, sqlx::Error> {
+    let row = sqlx::query!(
+        "INSERT INTO outbound (sender, domain, recipients, data) VALUES ($1, $2, $3, $4) RETURNING id",
+        sender,
+        domain,
+        recipients,
+        data
+    )
+    .fetch_one(conn)
+    .await?;
+
+    Ok(row.id)
+}
@@ -0,0 +1,62 @@
+use sqlx::PgConnection;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::logic::pgp_key::PgpKey;
+
+/// Store the OpenPGP key of an account, replacing an existing one.
+pub async fn set(
+    conn: &mut PgConnection,
+    account: Uuid,
+    fingerprint: &str,
+    armored: &str,
+    expires: Option<OffsetDateTime>,
+) -> Result<PgpKey, sqlx::Error> {
+    sqlx::query_as!(
+        PgpKey,
+        "INSERT INTO pgp_key (account, fingerprint, armored, expires) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (account) DO UPDATE
+        SET fingerprint = $2, armored = $3, expires = $4, created = now(), unusable_warned = false
+        RETURNING fingerprint, armored, expires, created",
+        account,
+        fingerprint,
+        armored,
+        expires
+    )
+    .fetch_one(conn)
+    .await
+}
+
+/// Find the OpenPGP key of an account.
+pub async fn find(conn: &mut PgConnection, account: Uuid) -> Result<Option<PgpKey>, sqlx::Error> {
+    sqlx::query_as!(
+        PgpKey,
+        "SELECT fingerprint, armored, expires, created FROM pgp_key WHERE account = $1",
+        account
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+/// Remove the OpenPGP key of an account.
+/// Returns false when the account had no key.
+pub async fn delete(conn: &mut PgConnection, account: Uuid) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query!("DELETE FROM pgp_key WHERE account = $1", account)
+        .execute(conn)
+        .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+/// Remember that the account was told its OpenPGP key is no longer usable.
+/// Returns false when it was told already.
+pub async fn mark_unusable(conn: &mut PgConnection, account: Uuid) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query!(
+        "UPDATE pgp_key SET unusable_warned = true WHERE account = $1 AND NOT unusable_warned",
+        account
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(res.rows_affected() > 0)
+}
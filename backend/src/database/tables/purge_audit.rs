@@ -0,0 +1,27 @@
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::logic::mailbox::RetentionAction;
+
+/// Record a purge of messages from a mailbox.
+pub async fn create(
+    conn: &mut PgConnection,
+    account: Uuid,
+    mailbox: Uuid,
+    action: RetentionAction,
+    messages: &[Uuid],
+    size: i64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO purge_audit (account, mailbox, action, messages, size) VALUES ($1, $2, $3, $4, $5)",
+        account,
+        mailbox,
+        action as RetentionAction,
+        messages,
+        size
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
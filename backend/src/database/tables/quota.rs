@@ -0,0 +1,73 @@
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::logic::quota::Counters;
+
+/// Find the usage and limits of an account.
+pub async fn find(conn: &mut PgConnection, account: Uuid) -> Result<Option<Counters>, sqlx::Error> {
+    sqlx::query_as!(
+        Counters,
+        "SELECT size, messages, max_size, max_messages, warned FROM quota WHERE account = $1",
+        account
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+/// Add to the usage of an account, negative amounts subtract from it.
+/// The row stays locked until the transaction ends.
+pub async fn add(
+    conn: &mut PgConnection,
+    account: Uuid,
+    size: i64,
+    messages: i64,
+) -> Result<Counters, sqlx::Error> {
+    sqlx::query_as!(
+        Counters,
+        "INSERT INTO quota (account, size, messages) VALUES ($1, $2, $3)
+        ON CONFLICT (account) DO UPDATE SET size = quota.size + $2, messages = quota.messages + $3
+        RETURNING size, messages, max_size, max_messages, warned",
+        account,
+        size,
+        messages
+    )
+    .fetch_one(conn)
+    .await
+}
+
+/// Set the limits of an account, None uses the system default.
+pub async fn set_limits(
+    conn: &mut PgConnection,
+    account: Uuid,
+    max_size: Option<i64>,
+    max_messages: Option<i64>,
+) -> Result<Counters, sqlx::Error> {
+    sqlx::query_as!(
+        Counters,
+        "INSERT INTO quota (account, max_size, max_messages) VALUES ($1, $2, $3)
+        ON CONFLICT (account) DO UPDATE SET max_size = $2, max_messages = $3
+        RETURNING size, messages, max_size, max_messages, warned",
+        account,
+        max_size,
+        max_messages
+    )
+    .fetch_one(conn)
+    .await
+}
+
+/// Set whether the account was warned about its usage.
+pub async fn set_warned(
+    conn: &mut PgConnection,
+    account: Uuid,
+    warned: bool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE quota SET warned = $2 WHERE account = $1",
+        account,
+        warned
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,52 @@
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+/// Check if an account loads remote images in messages from an address.
+pub async fn allowed(
+    conn: &mut PgConnection,
+    account: Uuid,
+    address: &str,
+) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query!(
+        r#"SELECT EXISTS (
+            SELECT 1 FROM remote_content_sender WHERE account = $1 AND address = $2
+        ) AS "allowed!""#,
+        account,
+        address
+    )
+    .fetch_one(conn)
+    .await?;
+
+    Ok(res.allowed)
+}
+
+/// Load remote images in messages from an address.
+pub async fn add(conn: &mut PgConnection, account: Uuid, address: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO remote_content_sender (account, address) VALUES ($1, $2)
+        ON CONFLICT DO NOTHING",
+        account,
+        address
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Block remote images in messages from an address again.
+pub async fn remove(
+    conn: &mut PgConnection,
+    account: Uuid,
+    address: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM remote_content_sender WHERE account = $1 AND address = $2",
+        account,
+        address
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
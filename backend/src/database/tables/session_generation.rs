@@ -0,0 +1,28 @@
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+/// Find the generation of the sessions of an account, if it was ever changed.
+pub async fn find(conn: &mut PgConnection, account: Uuid) -> Result<Option<i32>, sqlx::Error> {
+    let row = sqlx::query!(
+        "SELECT generation FROM session_generation WHERE account = $1",
+        account
+    )
+    .fetch_optional(conn)
+    .await?;
+
+    Ok(row.map(|r| r.generation))
+}
+
+/// Move the sessions of an account on to the next generation, returning it.
+pub async fn increment(conn: &mut PgConnection, account: Uuid) -> Result<i32, sqlx::Error> {
+    let row = sqlx::query!(
+        "INSERT INTO session_generation (account, generation) VALUES ($1, 1)
+        ON CONFLICT (account) DO UPDATE SET generation = session_generation.generation + 1
+        RETURNING generation",
+        account
+    )
+    .fetch_one(conn)
+    .await?;
+
+    Ok(row.generation)
+}
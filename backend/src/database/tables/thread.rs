@@ -0,0 +1,147 @@
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::logic::thread::Thread;
+
+/// Lock the threads of an account until the transaction ends.
+pub async fn lock(conn: &mut PgConnection, account: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "SELECT pg_advisory_xact_lock(hashtext('thread:' || $1::uuid::text))",
+        account
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Find the threads of Message-IDs, the one with the oldest message first.
+pub async fn find_references(
+    conn: &mut PgConnection,
+    account: Uuid,
+    ids: &[String],
+) -> Result<Vec<Uuid>, sqlx::Error> {
+    let threads = sqlx::query!(
+        "SELECT r.thread FROM thread_reference r LEFT JOIN message m ON m.thread = r.thread
+        WHERE r.account = $1 AND r.message_id = ANY($2)
+        GROUP BY r.thread ORDER BY min(m.received) NULLS LAST",
+        account,
+        ids
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(threads.into_iter().map(|r| r.thread).collect())
+}
+
+/// Find the newest thread with a subject, within an amount of days.
+pub async fn find_subject(
+    conn: &mut PgConnection,
+    account: Uuid,
+    subject: &str,
+    days: i32,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let thread = sqlx::query!(
+        "SELECT thread FROM message
+        WHERE account = $1 AND thread_subject = $2 AND received > now() - make_interval(days => $3)
+        ORDER BY received DESC LIMIT 1",
+        account,
+        subject,
+        days
+    )
+    .fetch_optional(conn)
+    .await?;
+
+    Ok(thread.map(|r| r.thread))
+}
+
+/// Move the messages and references of threads into another thread.
+pub async fn merge(
+    conn: &mut PgConnection,
+    account: Uuid,
+    threads: &[Uuid],
+    into: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "WITH changed AS (SELECT id, mailbox FROM message WHERE account = $1 AND thread = ANY($2) FOR UPDATE),
+        seq AS (
+            UPDATE mailbox SET modseq = modseq + 1
+            WHERE id IN (SELECT mailbox FROM changed) RETURNING id, modseq
+        )
+        UPDATE message m SET thread = $3, modseq = s.modseq
+        FROM changed c JOIN seq s ON s.id = c.mailbox WHERE m.id = c.id",
+        account,
+        threads,
+        into
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        "UPDATE thread_reference SET thread = $3 WHERE account = $1 AND thread = ANY($2)",
+        account,
+        threads,
+        into
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Record the thread of Message-IDs.
+pub async fn add_references(
+    conn: &mut PgConnection,
+    account: Uuid,
+    ids: &[String],
+    thread: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO thread_reference (account, message_id, thread) SELECT $1, unnest($2::text[]), $3
+        ON CONFLICT (account, message_id) DO UPDATE SET thread = $3",
+        account,
+        ids,
+        thread
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Summarize the threads of an account, the one with the newest message first.
+/// With a mailbox or label, only threads with a message in it or carrying it are listed.
+pub async fn list(
+    conn: &mut PgConnection,
+    account: Uuid,
+    mailbox: Option<Uuid>,
+    label: Option<Uuid>,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<Thread>, sqlx::Error> {
+    sqlx::query_as!(
+        Thread,
+        r#"SELECT m.thread AS id,
+        (array_agg(m.subject ORDER BY m.received))[1] AS "subject!",
+        array_agg(DISTINCT m.sender) AS "participants!",
+        (array_agg(m.snippet ORDER BY m.received DESC))[1] AS "snippet!",
+        count(*) AS "messages!",
+        count(*) FILTER (WHERE NOT m.seen) AS "unread!",
+        max(m.received) AS "latest!"
+        FROM message m
+        WHERE m.account = $1
+        AND ($2::uuid IS NULL OR m.thread IN (SELECT thread FROM message WHERE account = $1 AND mailbox = $2))
+        AND ($5::uuid IS NULL OR m.thread IN (
+            SELECT l.thread FROM message l JOIN message_label ml ON ml.message = l.id
+            WHERE l.account = $1 AND ml.label = $5
+        ))
+        GROUP BY m.thread ORDER BY max(m.received) DESC, m.thread LIMIT $3 OFFSET $4"#,
+        account,
+        mailbox,
+        limit,
+        offset,
+        label
+    )
+    .fetch_all(conn)
+    .await
+}
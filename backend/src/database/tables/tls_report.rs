@@ -0,0 +1,48 @@
+use sqlx::PgConnection;
+
+use crate::logic::tls_report::TlsReport;
+
+/// Store a received TLS report.
+/// The dates are passed as RFC 3339 strings and parsed by the database.
+/// Returns None when the report was already stored before.
+#[allow(clippy::too_many_arguments)]
+pub async fn create(
+    conn: &mut PgConnection,
+    domain: &str,
+    organization: &str,
+    report_id: &str,
+    start_date: &str,
+    end_date: &str,
+    successful: i64,
+    failed: i64,
+    report: &serde_json::Value,
+) -> Result<Option<TlsReport>, sqlx::Error> {
+    sqlx::query_as!(
+        TlsReport,
+        "INSERT INTO tls_report (domain, organization, report_id, start_date, end_date, successful, failed, report)
+        VALUES ($1, $2, $3, CAST($4::text AS timestamptz), CAST($5::text AS timestamptz), $6, $7, $8)
+        ON CONFLICT (domain, organization, report_id) DO NOTHING
+        RETURNING *",
+        domain,
+        organization,
+        report_id,
+        start_date,
+        end_date,
+        successful,
+        failed,
+        report
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+/// List the reports of a domain, newest first.
+pub async fn list(conn: &mut PgConnection, domain: &str) -> Result<Vec<TlsReport>, sqlx::Error> {
+    sqlx::query_as!(
+        TlsReport,
+        "SELECT * FROM tls_report WHERE domain = $1 ORDER BY start_date DESC",
+        domain
+    )
+    .fetch_all(conn)
+    .await
+}
@@ -0,0 +1,74 @@
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::logic::upload::Upload;
+
+/// Create an upload of an account, with a reference to its blob taken already.
+pub async fn create(
+    conn: &mut PgConnection,
+    account: Uuid,
+    filename: &str,
+    content_type: &str,
+    size: i64,
+    blob: &str,
+) -> Result<Upload, sqlx::Error> {
+    sqlx::query_as!(
+        Upload,
+        "INSERT INTO upload (account, filename, content_type, size, blob)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id, filename, content_type, size, blob, created",
+        account,
+        filename,
+        content_type,
+        size,
+        blob
+    )
+    .fetch_one(conn)
+    .await
+}
+
+/// Find uploads by ID, within an account, in no particular order.
+pub async fn find_many(
+    conn: &mut PgConnection,
+    account: Uuid,
+    ids: &[Uuid],
+) -> Result<Vec<Upload>, sqlx::Error> {
+    sqlx::query_as!(
+        Upload,
+        "SELECT id, filename, content_type, size, blob, created
+        FROM upload WHERE account = $1 AND id = ANY($2)",
+        account,
+        ids
+    )
+    .fetch_all(conn)
+    .await
+}
+
+/// Delete an upload, returning its blob to release.
+pub async fn delete(conn: &mut PgConnection, id: Uuid) -> Result<Option<String>, sqlx::Error> {
+    let res = sqlx::query!("DELETE FROM upload WHERE id = $1 RETURNING blob", id)
+        .fetch_optional(conn)
+        .await?;
+
+    Ok(res.map(|r| r.blob))
+}
+
+/// Delete uploads older than an amount of hours, returning their blobs to release.
+pub async fn delete_expired(
+    conn: &mut PgConnection,
+    hours: i32,
+    limit: i64,
+) -> Result<Vec<String>, sqlx::Error> {
+    let rows = sqlx::query!(
+        "DELETE FROM upload WHERE id IN (
+            SELECT id FROM upload WHERE created <= now() - make_interval(hours => $1)
+            LIMIT $2 FOR UPDATE SKIP LOCKED
+        ) RETURNING blob",
+        hours,
+        limit
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows.into_iter().map(|r| r.blob).collect())
+}
@@ -1,6 +1,12 @@
 use dotenv::dotenv;
 use std::env;
 
+use crate::{
+    logic::{quota::QuotaConfig, retention::RetentionConfig},
+    proxy::ProxyConfig,
+    storage::BlobConfig,
+};
+
 /// Get the configuration from the enviroment variables.
 /// Returns a string with an textual error if this wass not possible.
 pub fn get() -> Result<Environment, String> {
@@ -13,6 +19,40 @@ pub fn get() -> Result<Environment, String> {
         )?,
     };
     let redis_url = try_get("NEXIUM_REDIS_URL", Some("127.0.0.1:6379".to_string()))?;
+    let hostname = try_get("NEXIUM_HOSTNAME", Some("localhost".to_string()))?;
+    let proxy = ProxyConfig::parse(&try_get("NEXIUM_PROXY_TRUSTED", Some(String::new()))?)?;
+
+    // Blobs are stored in S3 when an endpoint is configured, and on disk otherwise.
+    let s3_endpoint = try_get("NEXIUM_S3_ENDPOINT", Some(String::new()))?;
+    let blob = if s3_endpoint.is_empty() {
+        BlobConfig::Local {
+            path: try_get("NEXIUM_BLOB_PATH", Some("blobs".to_string()))?,
+        }
+    } else {
+        BlobConfig::S3 {
+            endpoint: s3_endpoint,
+            bucket: try_get("NEXIUM_S3_BUCKET", None)?,
+            region: try_get("NEXIUM_S3_REGION", Some("us-east-1".to_string()))?,
+            access_key: try_get("NEXIUM_S3_ACCESS_KEY", None)?,
+            secret_key: try_get("NEXIUM_S3_SECRET_KEY", None)?,
+        }
+    };
+
+    // Training a dictionary costs memory and time once, but improves compression of small messages.
+    let compression_dictionary =
+        try_get("NEXIUM_COMPRESSION_DICTIONARY", Some("false".to_string()))? == "true";
+
+    // The default quota of accounts, 1 GiB in at most 100000 messages.
+    let quota = QuotaConfig::parse(
+        &try_get("NEXIUM_QUOTA_SIZE", Some("1073741824".to_string()))?,
+        &try_get("NEXIUM_QUOTA_MESSAGES", Some("100000".to_string()))?,
+    )?;
+
+    // Messages in the trash and spam are deleted after 30 days, unless the mailbox has its own policy.
+    let retention = RetentionConfig::parse(
+        &try_get("NEXIUM_RETENTION_TRASH_DAYS", Some("30".to_string()))?,
+        &try_get("NEXIUM_RETENTION_SPAM_DAYS", Some("30".to_string()))?,
+    )?;
 
     if secret.len() < 256 {
         return Err("The secret is required to be at least 265 characters long.".to_string());
@@ -22,6 +62,12 @@ pub fn get() -> Result<Environment, String> {
         secret,
         database_url,
         redis_url,
+        hostname,
+        proxy,
+        blob,
+        compression_dictionary,
+        quota,
+        retention,
     })
 }
 
@@ -56,4 +102,10 @@ pub struct Environment {
     pub secret: String,
     pub database_url: String,
     pub redis_url: String,
+    pub hostname: String,
+    pub proxy: ProxyConfig,
+    pub blob: BlobConfig,
+    pub compression_dictionary: bool,
+    pub quota: QuotaConfig,
+    pub retention: RetentionConfig,
 }
@@ -0,0 +1,148 @@
+use std::sync::Arc;
+
+use actix_web::{
+    get,
+    http::{
+        header::{ContentDisposition, DispositionParam, DispositionType},
+        StatusCode,
+    },
+    web::{self, Bytes},
+    HttpResponse, ResponseError,
+};
+use futures_util::{stream, StreamExt};
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::IntoResponses;
+use uuid::Uuid;
+
+use crate::http::{guarded_error_responses, ApiError, Responses, UserGuard};
+use crate::logic::{
+    blob::BlobError,
+    mailbox_key::MailboxKey,
+    message::{self, Message, ReadError},
+};
+use crate::storage::BlobStore;
+
+/// Export all messages of the current user as an mbox file, oldest first.
+/// The messages are read one at a time, decrypted and decompressed like the raw message.
+/// Accounts with encrypted messages need the unlocked mailbox key, so none are left out.
+#[utoipa::path(
+    operation_id = "account.export",
+    tag = "account",
+    responses(
+        (status = OK, description = "The messages as an mbox file.", body = Vec<u8>, content_type = "application/mbox"),
+        RouteError
+    ),
+    security(("session" = []))
+)]
+#[get("/export")]
+async fn export(
+    account: UserGuard<Uuid>,
+    pool: web::Data<Pool<Postgres>>,
+    store: web::Data<dyn BlobStore>,
+    mailbox_key: Option<MailboxKey>,
+) -> Result<HttpResponse, RouteError> {
+    let account: Uuid = account.into();
+
+    let mut conn = pool.acquire().await?;
+    let ids = Message::ids(&mut conn, account).await?;
+    drop(conn);
+
+    if mailbox_key.is_none() && ids.iter().any(|(_, encrypted)| *encrypted) {
+        return Err(RouteError::Locked);
+    }
+
+    let pool = pool.into_inner();
+    let store = store.into_inner();
+    let mailbox_key = Arc::new(mailbox_key);
+
+    let stream = stream::iter(ids).then(move |(id, _)| {
+        let pool = pool.clone();
+        let store = store.clone();
+        let mailbox_key = mailbox_key.clone();
+
+        async move {
+            let mut conn = pool.acquire().await?;
+
+            // Messages deleted during the export are left out.
+            let message = match Message::find(&mut conn, account, id).await {
+                Ok(message) => message,
+                Err(message::FindError::NotFound) => return Ok(Bytes::new()),
+                Err(message::FindError::DatabaseError(e)) => return Err(e.into()),
+            };
+            let raw = message
+                .raw(&mut conn, store.as_ref(), mailbox_key.as_ref().as_ref())
+                .await?;
+
+            Ok::<_, RouteError>(Bytes::from(message.to_mbox(&raw)))
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/mbox")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename("messages.mbox".into())],
+        })
+        .streaming(Box::pin(stream)))
+}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("Messages are encrypted and the mailbox key is not unlocked.")]
+    Locked,
+    #[error("A message could not be read from storage.")]
+    StorageError(BlobError),
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::Locked => "locked",
+            RouteError::StorageError(_) => "storageerror",
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[
+            RouteError::Locked,
+            RouteError::StorageError(BlobError::DecryptError),
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::Locked => StatusCode::FORBIDDEN,
+            RouteError::StorageError(_) => StatusCode::SERVICE_UNAVAILABLE,
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<ReadError> for RouteError {
+    fn from(err: ReadError) -> Self {
+        match err {
+            ReadError::Locked => RouteError::Locked,
+            ReadError::BlobError(BlobError::DatabaseError(e)) => RouteError::DatabaseError(e),
+            ReadError::BlobError(e) => RouteError::StorageError(e),
+        }
+    }
+}
@@ -8,61 +8,81 @@ use actix_web::{
 use serde::{Deserialize, Serialize};
 use sqlx::{Pool, Postgres};
 use thiserror::Error;
+use utoipa::{IntoResponses, ToSchema};
 use uuid::Uuid;
 
-use crate::http::{ApiError, UserGuard};
-use crate::logic::{account, auth};
+use crate::http::{error_responses, keep_mailbox_key, ApiError, Responses, UserGuard};
+use crate::logic::{
+    account, auth,
+    mailbox_key::{KeyError, MailboxKey},
+};
 
 /// Log into an existing account.
+#[utoipa::path(
+    operation_id = "account.login",
+    tag = "account",
+    request_body = inline(BodyData),
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError)
+)]
 #[post("/login")]
 async fn login(
     data: Json<BodyData>,
     user: Option<UserGuard<Uuid>>,
     session: Session,
     pool: web::Data<Pool<Postgres>>,
-) -> Result<Json<Response>, RouteError> {
+) -> Result<HttpResponse, RouteError> {
     // Check that the current session is not logged in.
     if user.is_some() {
         return Err(RouteError::LoggedIn);
     }
 
     // Get an database connection.
-    // A transation is not needed here, as it's read only.
+    // A transation is not needed here, the only write is a missing mailbox key.
     let mut conn = pool.acquire().await.map_err(RouteError::DatabaseError)?;
 
     // Find the account associated with the user.
     let account = account::Account::find_username(&mut conn, &data.username).await?;
 
-    // Run the authentication for this type.
-    match &data.auth {
+    // Run the authentication for this type, and unlock the mailbox key with it.
+    let key = match &data.auth {
         AuthType::Password { password } => {
             auth::password::AuthPassword::authenticate(&mut conn, &account, password).await?;
+            MailboxKey::unlock(&mut conn, account.id, password).await?
         }
     };
 
-    // Attach the user ID to the current session.
+    // Attach the user ID to the current session, of the current generation of the account.
+    let generation = auth::session::Generation::current(&mut conn, account.id).await?;
     session
         .insert("user", account.id)
         .map_err(|_| RouteError::InternalError)?;
+    session
+        .insert("session", Uuid::new_v4())
+        .map_err(|_| RouteError::InternalError)?;
+    session
+        .insert("generation", generation)
+        .map_err(|_| RouteError::InternalError)?;
+    let cookie = keep_mailbox_key(&session, &key).map_err(|_| RouteError::InternalError)?;
 
     info!(
         "New successful login for {} ({}).",
         account.username, account.id
     );
 
-    Ok(Json(Response { account }))
+    Ok(HttpResponse::Ok().cookie(cookie).json(Response { account }))
 }
 
 /// Requested data for this route.
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct BodyData {
     username: String,
+    #[schema(inline)]
     auth: AuthType,
 }
 
 /// Represents the different methods of authentication.
 /// One is required to create an account.
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 #[serde(tag = "type")]
 #[serde(rename_all = "camelCase")]
 enum AuthType {
@@ -70,7 +90,7 @@ enum AuthType {
 }
 
 /// Success response of this route.
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 struct Response {
     account: account::Account,
@@ -101,6 +121,18 @@ impl<'a> ApiError<'a> for RouteError {
     }
 }
 
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        error_responses(&[
+            RouteError::LoggedIn,
+            RouteError::LoginFailed,
+            RouteError::InternalError,
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
 impl ResponseError for RouteError {
     /// Translate a route error to a HTTP status.
     fn status_code(&self) -> StatusCode {
@@ -138,3 +170,14 @@ impl From<auth::password::AuthenticateError> for RouteError {
         }
     }
 }
+
+/// Convert the internal error to an route error.
+impl From<KeyError> for RouteError {
+    fn from(err: KeyError) -> Self {
+        match err {
+            KeyError::Derivation => RouteError::InternalError,
+            KeyError::CryptoError(_) => RouteError::InternalError,
+            KeyError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
@@ -1,14 +1,42 @@
 use actix_session::Session;
-use actix_web::{post, HttpResponse, Responder};
+use actix_web::{post, web, HttpResponse, Responder};
+use sqlx::{Pool, Postgres};
 use uuid::Uuid;
 
-use crate::http::UserGuard;
+use crate::http::{forget_mailbox_key, session_id, GuardError, UserGuard};
+use crate::logic::event::{Event, Notification};
 
 /// Log out the current user.
+/// The event stream of the session is told, so it ends as well.
+#[utoipa::path(
+    operation_id = "account.logout",
+    tag = "account",
+    responses((status = OK, description = "Success."), GuardError),
+    security(("session" = []))
+)]
 #[post("/logout")]
-async fn logout(_uuid: UserGuard<Uuid>, session: Session) -> impl Responder {
-    // Purge the session.
+async fn logout(
+    account: UserGuard<Uuid>,
+    session: Session,
+    pool: web::Data<Pool<Postgres>>,
+) -> impl Responder {
+    let id = session_id(&session).ok();
+
+    // Purge the session, the client forgets the key of it as well.
     session.purge();
 
-    HttpResponse::Ok()
+    // Logging out succeeds regardless, only an open event stream of the session is kept.
+    if let Some(session) = id {
+        let res = async {
+            let mut conn = pool.acquire().await?;
+            Notification::publish(&mut conn, account.into(), Event::SessionRevoked { session })
+                .await
+        }
+        .await;
+        if let Err(e) = res {
+            warn!("Failed to revoke the event stream of a session: {}", e);
+        }
+    }
+
+    HttpResponse::Ok().cookie(forget_mailbox_key()).finish()
 }
@@ -1,16 +1,48 @@
 use actix_web::{web, Scope};
+use utoipa::OpenApi;
 
+mod delete_pgp;
+mod export;
 mod login;
 mod logout;
 mod new;
+mod password;
+mod pgp;
+mod set_pgp;
+mod set_quota;
+mod storage;
 mod whoami;
 
 /// Returns the routes of this scope.
 pub fn routes() -> Scope {
     web::scope("/account")
+        .service(delete_pgp::delete_pgp)
+        .service(export::export)
         .service(login::login)
         .service(logout::logout)
         .service(new::new_account)
+        .service(password::password)
+        .service(pgp::pgp)
+        .service(set_pgp::set_pgp)
+        .service(set_quota::set_quota)
+        .service(storage::storage)
         .service(whoami::whoami)
         .default_service(web::route().to(super::not_found))
 }
+
+/// The documentation of the routes of this scope.
+#[derive(OpenApi)]
+#[openapi(paths(
+    delete_pgp::delete_pgp,
+    export::export,
+    login::login,
+    logout::logout,
+    new::new_account,
+    password::password,
+    pgp::pgp,
+    set_pgp::set_pgp,
+    set_quota::set_quota,
+    storage::storage,
+    whoami::whoami
+))]
+pub struct Api;
@@ -8,19 +8,30 @@ use actix_web::{
 use serde::{Deserialize, Serialize};
 use sqlx::{Pool, Postgres};
 use thiserror::Error;
+use utoipa::{IntoResponses, ToSchema};
 use uuid::Uuid;
 
-use crate::http::{ApiError, UserGuard};
-use crate::logic::{account, auth};
+use crate::http::{error_responses, keep_mailbox_key, ApiError, Responses, UserGuard};
+use crate::logic::{
+    account, auth,
+    mailbox::Mailbox,
+    mailbox_key::{KeyError, MailboxKey},
+};
 
 /// Create a new account.
+#[utoipa::path(
+    operation_id = "account.new",
+    tag = "account",
+    request_body = inline(BodyData),
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError)
+)]
 #[post("/new")]
 async fn new_account(
     data: Json<BodyData>,
     user: Option<UserGuard<Uuid>>,
     session: Session,
     pool: web::Data<Pool<Postgres>>,
-) -> Result<Json<Response>, RouteError> {
+) -> Result<HttpResponse, RouteError> {
     // Check that the current session is not logged in.
     if user.is_some() {
         return Err(RouteError::LoggedIn);
@@ -34,11 +45,15 @@ async fn new_account(
     // Because of the transaction we can be sure there won't be an account created without authentication.
     let account = account::Account::create(&mut conn, &data.username).await?;
 
-    // Create the authentication.
+    // Create the standard mailboxes, like the inbox and the trash.
+    Mailbox::create_system(&mut conn, account.id).await?;
+
+    // Create the authentication, and the mailbox key which is wrapped with it.
     // The specific method depends on the type.
-    match &data.auth {
+    let key = match &data.auth {
         AuthType::Password { password } => {
-            auth::password::AuthPassword::create(&mut conn, &account, password).await?
+            auth::password::AuthPassword::create(&mut conn, &account, password).await?;
+            MailboxKey::create(&mut conn, account.id, password).await?
         }
     };
 
@@ -49,25 +64,33 @@ async fn new_account(
     session
         .insert("user", account.id)
         .map_err(|_| RouteError::InternalError)?;
+    session
+        .insert("session", Uuid::new_v4())
+        .map_err(|_| RouteError::InternalError)?;
+    session
+        .insert("generation", 0)
+        .map_err(|_| RouteError::InternalError)?;
+    let cookie = keep_mailbox_key(&session, &key).map_err(|_| RouteError::InternalError)?;
 
     info!(
         "New account created for {} ({}).",
         account.username, account.id
     );
 
-    Ok(Json(Response { account }))
+    Ok(HttpResponse::Ok().cookie(cookie).json(Response { account }))
 }
 
 /// Requested data for this route.
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct BodyData {
     username: String,
+    #[schema(inline)]
     auth: AuthType,
 }
 
 /// Represents the different methods of authentication.
 /// One is required to create an account.
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 #[serde(tag = "type")]
 #[serde(rename_all = "camelCase")]
 enum AuthType {
@@ -75,7 +98,7 @@ enum AuthType {
 }
 
 /// Success response of this route.
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 struct Response {
     account: account::Account,
@@ -112,6 +135,20 @@ impl<'a> ApiError<'a> for RouteError {
     }
 }
 
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        error_responses(&[
+            RouteError::LoggedIn,
+            RouteError::InvalidUsername,
+            RouteError::PasswordComplexity,
+            RouteError::InternalError,
+            RouteError::AccountExists("alice".to_string()),
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
 impl ResponseError for RouteError {
     /// Translate a route error to a HTTP status.
     fn status_code(&self) -> StatusCode {
@@ -152,3 +189,14 @@ impl From<auth::password::CreateError> for RouteError {
         }
     }
 }
+
+/// Convert the internal error to an route error.
+impl From<KeyError> for RouteError {
+    fn from(err: KeyError) -> Self {
+        match err {
+            KeyError::Derivation => RouteError::InternalError,
+            KeyError::CryptoError(_) => RouteError::InternalError,
+            KeyError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use actix_session::Session;
+use actix_web::{
+    http::StatusCode,
+    post,
+    web::{self, Json},
+    HttpResponse, ResponseError,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::{IntoResponses, ToSchema};
+
+use crate::http::{
+    guarded_error_responses, keep_mailbox_key, session_id, ApiError, Responses, UserGuard,
+};
+use crate::logic::{
+    account::Account,
+    auth::{
+        password::{AuthPassword, AuthenticateError, ChangeError},
+        session::Generation,
+    },
+    event::{Event, Notification},
+    mailbox_key::{KeyError, MailboxKey},
+};
+
+/// Change the password of the current account.
+/// The mailbox key is wrapped with the new password, so encrypted mail stays readable.
+/// The other sessions of the account are revoked, and their event streams told so they end.
+#[utoipa::path(
+    operation_id = "account.password",
+    tag = "account",
+    request_body = inline(BodyData),
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError),
+    security(("session" = []))
+)]
+#[post("/password")]
+async fn password(
+    data: Json<BodyData>,
+    account: UserGuard<Account>,
+    session: Session,
+    pool: web::Data<Pool<Postgres>>,
+) -> Result<HttpResponse, RouteError> {
+    let account: Account = account.into();
+
+    let id = session_id(&session).map_err(|_| RouteError::InternalError)?;
+
+    // Both changes are made together, so the key is always wrapped with the current password.
+    // The other sessions are only revoked once the password changed.
+    let mut tx = pool.begin().await?;
+    AuthPassword::change(&mut tx, &account, &data.current, &data.new).await?;
+    let key = MailboxKey::rewrap(&mut tx, account.id, &data.current, &data.new).await?;
+    let generation = Generation::revoke(&mut tx, account.id).await?;
+    Notification::publish(
+        &mut tx,
+        account.id,
+        Event::OtherSessionsRevoked { session: id },
+    )
+    .await?;
+    tx.commit().await?;
+
+    // This session stays logged in, as the only one of the new generation.
+    session
+        .insert("generation", generation)
+        .map_err(|_| RouteError::InternalError)?;
+
+    // Sessions without a key, from before encryption, get it now.
+    let cookie = keep_mailbox_key(&session, &key).map_err(|_| RouteError::InternalError)?;
+
+    info!(
+        "Password changed for {} ({}).",
+        account.username, account.id
+    );
+
+    Ok(HttpResponse::Ok().cookie(cookie).json(Response {}))
+}
+
+/// Requested data for this route.
+#[derive(Deserialize, ToSchema)]
+struct BodyData {
+    current: String,
+    new: String,
+}
+
+/// Success response of this route.
+#[derive(Serialize, ToSchema)]
+struct Response {}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("The current password is incorrect.")]
+    IncorrectPassword,
+    #[error("Password is not complex enough.")]
+    PasswordComplexity,
+    #[error("Internal server error.")]
+    InternalError,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::IncorrectPassword => "incorrectpassword",
+            RouteError::PasswordComplexity => "passwordcomplexity",
+            RouteError::InternalError => "internalerror",
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[
+            RouteError::IncorrectPassword,
+            RouteError::PasswordComplexity,
+            RouteError::InternalError,
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::IncorrectPassword => StatusCode::BAD_REQUEST,
+            RouteError::PasswordComplexity => StatusCode::BAD_REQUEST,
+            RouteError::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<ChangeError> for RouteError {
+    fn from(err: ChangeError) -> Self {
+        match err {
+            ChangeError::AuthenticateError(AuthenticateError::DatabaseError(e)) => {
+                RouteError::DatabaseError(e)
+            }
+            ChangeError::AuthenticateError(_) => RouteError::IncorrectPassword,
+            ChangeError::PasswordComplexity => RouteError::PasswordComplexity,
+            ChangeError::HashError => RouteError::InternalError,
+            ChangeError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<KeyError> for RouteError {
+    fn from(err: KeyError) -> Self {
+        match err {
+            KeyError::Derivation => RouteError::InternalError,
+            KeyError::CryptoError(_) => RouteError::InternalError,
+            KeyError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use actix_web::{
+    http::StatusCode,
+    put,
+    web::{self, Json},
+    HttpResponse, ResponseError,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::{IntoResponses, ToSchema};
+use uuid::Uuid;
+
+use crate::http::{guarded_error_responses, ApiError, Responses, UserGuard};
+use crate::logic::pgp_key::{PgpKey, SetError};
+
+/// Set the OpenPGP key of the current user, replacing an existing one.
+/// Incoming mail is wrapped in PGP/MIME encrypted to this key from now on.
+#[utoipa::path(
+    operation_id = "account.set_pgp",
+    tag = "account",
+    request_body = inline(BodyData),
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError),
+    security(("session" = []))
+)]
+#[put("/pgp")]
+async fn set_pgp(
+    data: Json<BodyData>,
+    account: UserGuard<Uuid>,
+    pool: web::Data<Pool<Postgres>>,
+) -> Result<Json<Response>, RouteError> {
+    let mut conn = pool.acquire().await?;
+    let key = PgpKey::set(&mut conn, account.into(), &data.key).await?;
+
+    Ok(Json(Response { key }))
+}
+
+/// Requested data for this route.
+#[derive(Deserialize, ToSchema)]
+struct BodyData {
+    /// The ASCII armored public key.
+    key: String,
+}
+
+/// Success response of this route.
+#[derive(Serialize, ToSchema)]
+struct Response {
+    key: PgpKey,
+}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("The key is not a valid OpenPGP public key.")]
+    InvalidKey,
+    #[error("The key was revoked.")]
+    Revoked,
+    #[error("The key has expired.")]
+    Expired,
+    #[error("The key has no usable encryption key.")]
+    NoEncryptionKey,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::InvalidKey => "invalidkey",
+            RouteError::Revoked => "revoked",
+            RouteError::Expired => "expired",
+            RouteError::NoEncryptionKey => "noencryptionkey",
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[
+            RouteError::InvalidKey,
+            RouteError::Revoked,
+            RouteError::Expired,
+            RouteError::NoEncryptionKey,
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::InvalidKey => StatusCode::BAD_REQUEST,
+            RouteError::Revoked => StatusCode::BAD_REQUEST,
+            RouteError::Expired => StatusCode::BAD_REQUEST,
+            RouteError::NoEncryptionKey => StatusCode::BAD_REQUEST,
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<SetError> for RouteError {
+    fn from(err: SetError) -> Self {
+        match err {
+            SetError::InvalidKey => RouteError::InvalidKey,
+            SetError::Revoked => RouteError::Revoked,
+            SetError::Expired => RouteError::Expired,
+            SetError::NoEncryptionKey => RouteError::NoEncryptionKey,
+            SetError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use actix_web::{
+    http::StatusCode,
+    put,
+    web::{self, Json, Path},
+    HttpResponse, ResponseError,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::{IntoResponses, ToSchema};
+use uuid::Uuid;
+
+use crate::http::{guarded_error_responses, AdminGuard, ApiError, Responses};
+use crate::logic::quota::{LimitError, Quota, QuotaConfig};
+
+/// Set the quota of an account, limits left out use the system default.
+#[utoipa::path(
+    operation_id = "account.set_quota",
+    tag = "account",
+    request_body = inline(BodyData),
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError),
+    security(("session" = []))
+)]
+#[put("/{id}/quota")]
+async fn set_quota(
+    id: Path<Uuid>,
+    data: Json<BodyData>,
+    _admin: AdminGuard,
+    pool: web::Data<Pool<Postgres>>,
+    config: web::Data<QuotaConfig>,
+) -> Result<Json<Response>, RouteError> {
+    let data = data.into_inner();
+    let mut conn = pool.acquire().await?;
+
+    let quota =
+        Quota::set_limits(&mut conn, *id, data.max_size, data.max_messages, &config).await?;
+
+    Ok(Json(Response { quota }))
+}
+
+/// Requested data for this route.
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct BodyData {
+    max_size: Option<i64>,
+    max_messages: Option<i64>,
+}
+
+/// Success response of this route.
+#[derive(Serialize, ToSchema)]
+struct Response {
+    quota: Quota,
+}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("The account does not exist.")]
+    NotFound,
+    #[error("Limits can not be negative.")]
+    InvalidLimit,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::NotFound => "notfound",
+            RouteError::InvalidLimit => "invalidlimit",
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[
+            RouteError::NotFound,
+            RouteError::InvalidLimit,
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::NotFound => StatusCode::NOT_FOUND,
+            RouteError::InvalidLimit => StatusCode::BAD_REQUEST,
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<LimitError> for RouteError {
+    fn from(err: LimitError) -> Self {
+        match err {
+            LimitError::InvalidLimit => RouteError::InvalidLimit,
+            LimitError::NotFound => RouteError::NotFound,
+            LimitError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
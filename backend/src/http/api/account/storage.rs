@@ -0,0 +1,75 @@
+use actix_web::{
+    get,
+    http::StatusCode,
+    web::{self, Json},
+    HttpResponse, ResponseError,
+};
+use serde::Serialize;
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::{IntoResponses, ToSchema};
+use uuid::Uuid;
+
+use crate::http::{guarded_error_responses, ApiError, Responses, UserGuard};
+use crate::logic::message::{Message, Usage};
+
+/// Get the storage used by the messages of the current user, before and after compression.
+#[utoipa::path(
+    operation_id = "account.storage",
+    tag = "account",
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError),
+    security(("session" = []))
+)]
+#[get("/storage")]
+async fn storage(
+    account: UserGuard<Uuid>,
+    pool: web::Data<Pool<Postgres>>,
+) -> Result<Json<Response>, RouteError> {
+    let mut conn = pool.acquire().await?;
+    let usage = Message::usage(&mut conn, account.into()).await?;
+
+    Ok(Json(Response { usage }))
+}
+
+/// Success response of this route.
+#[derive(Serialize, ToSchema)]
+struct Response {
+    usage: Usage,
+}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[RouteError::DatabaseError(sqlx::Error::PoolTimedOut)])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
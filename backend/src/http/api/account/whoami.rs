@@ -1,18 +1,80 @@
-use actix_web::{get, web::Json};
+use actix_web::{
+    get,
+    http::StatusCode,
+    web::{self, Json},
+    HttpResponse, ResponseError,
+};
 use serde::Serialize;
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::{IntoResponses, ToSchema};
 
-use crate::{http::UserGuard, logic::account::Account};
+use crate::http::{guarded_error_responses, ApiError, Responses, UserGuard};
+use crate::logic::{
+    account::Account,
+    quota::{Quota, QuotaConfig},
+};
 
-/// Returns the current logged in user.
+/// Returns the current logged in user, with the usage of its quota.
+#[utoipa::path(
+    operation_id = "account.whoami",
+    tag = "account",
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError),
+    security(("session" = []))
+)]
 #[get("/whoami")]
-async fn whoami(account: UserGuard<Account>) -> Json<Response> {
-    Json(Response {
-        account: account.into(),
-    })
+async fn whoami(
+    account: UserGuard<Account>,
+    pool: web::Data<Pool<Postgres>>,
+    config: web::Data<QuotaConfig>,
+) -> Result<Json<Response>, RouteError> {
+    let account: Account = account.into();
+    let mut conn = pool.acquire().await?;
+    let quota = Quota::find(&mut conn, account.id, &config).await?;
+
+    Ok(Json(Response { account, quota }))
 }
 
 /// Success response of this route.
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct Response {
     account: Account,
+    quota: Quota,
+}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[RouteError::DatabaseError(sqlx::Error::PoolTimedOut)])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
 }
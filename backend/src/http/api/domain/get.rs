@@ -0,0 +1,96 @@
+use actix_web::{
+    get,
+    http::StatusCode,
+    web::{self, Json, Path},
+    HttpResponse, ResponseError,
+};
+use serde::Serialize;
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::{IntoResponses, ToSchema};
+
+use crate::http::{guarded_error_responses, AdminGuard, ApiError, Responses};
+use crate::logic::domain::{self, DnsRecord, Domain};
+
+/// Get a domain, together with the DNS records it requires.
+#[utoipa::path(
+    operation_id = "domain.get",
+    tag = "domain",
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError),
+    security(("session" = []))
+)]
+#[get("/{name}")]
+async fn get(
+    name: Path<String>,
+    _admin: AdminGuard,
+    pool: web::Data<Pool<Postgres>>,
+) -> Result<Json<Response>, RouteError> {
+    let mut conn = pool.acquire().await?;
+    let domain = Domain::find(&mut conn, &name).await?;
+
+    Ok(Json(Response {
+        records: domain.dns_records(),
+        domain,
+    }))
+}
+
+/// Success response of this route.
+#[derive(Serialize, ToSchema)]
+struct Response {
+    domain: Domain,
+    records: Vec<DnsRecord>,
+}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("The domain does not exist.")]
+    NotFound,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::NotFound => "notfound",
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[
+            RouteError::NotFound,
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::NotFound => StatusCode::NOT_FOUND,
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<domain::FindError> for RouteError {
+    fn from(err: domain::FindError) -> Self {
+        match err {
+            domain::FindError::NotFound => RouteError::NotFound,
+            domain::FindError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use actix_web::{web, Scope};
+use utoipa::OpenApi;
+
+mod get;
+mod list;
+mod mta_sts;
+mod new;
+mod tls_reports;
+
+/// Returns the routes of this scope.
+/// All routes in this scope are only available to administrators.
+pub fn routes() -> Scope {
+    web::scope("/domain")
+        .service(list::list)
+        .service(new::new_domain)
+        .service(get::get)
+        .service(mta_sts::mta_sts)
+        .service(tls_reports::tls_reports)
+        .default_service(web::route().to(super::not_found))
+}
+
+/// The documentation of the routes of this scope.
+#[derive(OpenApi)]
+#[openapi(paths(
+    list::list,
+    new::new_domain,
+    get::get,
+    mta_sts::mta_sts,
+    tls_reports::tls_reports
+))]
+pub struct Api;
@@ -0,0 +1,146 @@
+use actix_web::{
+    http::StatusCode,
+    put,
+    web::{self, Json, Path},
+    HttpResponse, ResponseError,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::{IntoResponses, ToSchema};
+
+use crate::http::{guarded_error_responses, AdminGuard, ApiError, Responses};
+use crate::logic::domain::{self, DnsRecord, Domain, MtaStsMode};
+
+/// Update the MTA-STS policy of a domain.
+/// The returned `_mta-sts` record contains the new policy ID, and has to be published.
+#[utoipa::path(
+    operation_id = "domain.mta_sts",
+    tag = "domain",
+    request_body = inline(BodyData),
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError),
+    security(("session" = []))
+)]
+#[put("/{name}/mtasts")]
+async fn mta_sts(
+    name: Path<String>,
+    data: Json<BodyData>,
+    _admin: AdminGuard,
+    pool: web::Data<Pool<Postgres>>,
+) -> Result<Json<Response>, RouteError> {
+    let data = data.into_inner();
+    let mut conn = pool.acquire().await?;
+
+    let mut domain = Domain::find(&mut conn, &name).await?;
+    domain
+        .update_mta_sts(&mut conn, data.mode, data.mx, data.max_age)
+        .await?;
+
+    info!(
+        "MTA-STS policy of {} updated to {}.",
+        domain.name, domain.mta_sts_id
+    );
+
+    Ok(Json(Response {
+        records: domain.dns_records(),
+        domain,
+    }))
+}
+
+/// Requested data for this route.
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct BodyData {
+    mode: MtaStsMode,
+    mx: Vec<String>,
+    max_age: i32,
+}
+
+/// Success response of this route.
+#[derive(Serialize, ToSchema)]
+struct Response {
+    domain: Domain,
+    records: Vec<DnsRecord>,
+}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("The domain does not exist.")]
+    NotFound,
+    #[error("The MX pattern {0} is not valid.")]
+    InvalidMx(String),
+    #[error("At least one MX pattern is required.")]
+    MissingMx,
+    #[error("The maximum age is out of range.")]
+    InvalidMaxAge,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::NotFound => "notfound",
+            RouteError::InvalidMx(_) => "invalidmx",
+            RouteError::MissingMx => "missingmx",
+            RouteError::InvalidMaxAge => "invalidmaxage",
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[
+            RouteError::NotFound,
+            RouteError::InvalidMx("mx..example.com".to_string()),
+            RouteError::MissingMx,
+            RouteError::InvalidMaxAge,
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::NotFound => StatusCode::NOT_FOUND,
+            RouteError::InvalidMx(_) => StatusCode::BAD_REQUEST,
+            RouteError::MissingMx => StatusCode::BAD_REQUEST,
+            RouteError::InvalidMaxAge => StatusCode::BAD_REQUEST,
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<domain::FindError> for RouteError {
+    fn from(err: domain::FindError) -> Self {
+        match err {
+            domain::FindError::NotFound => RouteError::NotFound,
+            domain::FindError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<domain::UpdateError> for RouteError {
+    fn from(err: domain::UpdateError) -> Self {
+        match err {
+            domain::UpdateError::NotFound => RouteError::NotFound,
+            domain::UpdateError::InvalidMx(mx) => RouteError::InvalidMx(mx),
+            domain::UpdateError::MissingMx => RouteError::MissingMx,
+            domain::UpdateError::InvalidMaxAge => RouteError::InvalidMaxAge,
+            domain::UpdateError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
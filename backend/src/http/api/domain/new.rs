@@ -0,0 +1,111 @@
+use actix_web::{
+    http::StatusCode,
+    post,
+    web::{self, Json},
+    HttpResponse, ResponseError,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::{IntoResponses, ToSchema};
+
+use crate::http::{guarded_error_responses, AdminGuard, ApiError, Responses};
+use crate::logic::domain::{self, DnsRecord, Domain};
+
+/// Add a new domain to this server.
+#[utoipa::path(
+    operation_id = "domain.new",
+    tag = "domain",
+    request_body = inline(BodyData),
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError),
+    security(("session" = []))
+)]
+#[post("/new")]
+async fn new_domain(
+    data: Json<BodyData>,
+    _admin: AdminGuard,
+    pool: web::Data<Pool<Postgres>>,
+) -> Result<Json<Response>, RouteError> {
+    let mut conn = pool.acquire().await?;
+    let domain = Domain::create(&mut conn, &data.name).await?;
+
+    info!("New domain {} added.", domain.name);
+
+    Ok(Json(Response {
+        records: domain.dns_records(),
+        domain,
+    }))
+}
+
+/// Requested data for this route.
+#[derive(Deserialize, ToSchema)]
+struct BodyData {
+    name: String,
+}
+
+/// Success response of this route.
+#[derive(Serialize, ToSchema)]
+struct Response {
+    domain: Domain,
+    records: Vec<DnsRecord>,
+}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("The given domain name is not valid.")]
+    InvalidName,
+    #[error("Domain {0} already exists.")]
+    DomainExists(String),
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::InvalidName => "invalidname",
+            RouteError::DomainExists(_) => "domainexists",
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[
+            RouteError::InvalidName,
+            RouteError::DomainExists("example.com".to_string()),
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::InvalidName => StatusCode::BAD_REQUEST,
+            RouteError::DomainExists(_) => StatusCode::BAD_REQUEST,
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<domain::CreateError> for RouteError {
+    fn from(err: domain::CreateError) -> Self {
+        match err {
+            domain::CreateError::InvalidName(_) => RouteError::InvalidName,
+            domain::CreateError::DomainExists(name) => RouteError::DomainExists(name),
+            domain::CreateError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use actix_web::{
+    http::StatusCode,
+    post,
+    web::{self, Json, Path},
+    HttpResponse, ResponseError,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::{IntoResponses, ToSchema};
+use uuid::Uuid;
+
+use crate::http::{guarded_error_responses, ApiError, Responses, UserGuard};
+use crate::logic::{
+    account::{self, Account},
+    blob::BlobError,
+    compose::SendError,
+    draft::{Draft, DraftError, FindError},
+    mailbox_key::MailboxKey,
+    quota::QuotaConfig,
+};
+use crate::storage::BlobStore;
+
+/// Attach uploads to the latest version of a draft of the current user.
+/// No version is needed, the rest of the draft stays as it is.
+#[utoipa::path(
+    operation_id = "drafts.attachments",
+    tag = "drafts",
+    request_body = inline(BodyData),
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError),
+    security(("session" = []))
+)]
+#[post("/{id}/attachments")]
+async fn attachments(
+    id: Path<Uuid>,
+    data: Json<BodyData>,
+    account: UserGuard<Uuid>,
+    pool: web::Data<Pool<Postgres>>,
+    store: web::Data<dyn BlobStore>,
+    quota: web::Data<QuotaConfig>,
+    mailbox_key: Option<MailboxKey>,
+) -> Result<Json<Response>, RouteError> {
+    let mut conn = pool.begin().await?;
+
+    let account = Account::find(&mut conn, &account.into()).await?;
+    let draft = Draft::lock(&mut conn, mailbox_key.as_ref(), account.id, *id).await?;
+    let draft = draft
+        .attach(
+            &mut conn,
+            store.as_ref(),
+            mailbox_key.as_ref(),
+            &account,
+            &data.uploads,
+            &quota,
+        )
+        .await?;
+
+    conn.commit().await?;
+
+    Ok(Json(Response { draft }))
+}
+
+/// Requested data for this route.
+#[derive(Deserialize, ToSchema)]
+struct BodyData {
+    uploads: Vec<Uuid>,
+}
+
+/// Success response of this route.
+#[derive(Serialize, ToSchema)]
+struct Response {
+    draft: Draft,
+}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("The draft does not exist.")]
+    NotFound,
+    #[error("The draft was saved since the given version.")]
+    Conflict,
+    #[error("The message has too many recipients.")]
+    TooManyRecipients,
+    #[error("An address or display name is invalid.")]
+    InvalidAddress,
+    #[error("The subject is too long or contains invalid characters.")]
+    InvalidSubject,
+    #[error("A Message-ID of In-Reply-To or References is invalid, or there are too many.")]
+    InvalidReference,
+    #[error("An attachment was not found, or has expired.")]
+    AttachmentNotFound,
+    #[error("The attachments are too large.")]
+    TooLarge,
+    #[error("The draft or an attached message is encrypted and the mailbox key is not unlocked.")]
+    Locked,
+    #[error("The mailbox is full.")]
+    QuotaExceeded,
+    #[error("The message could not be stored.")]
+    StorageError,
+    #[error("An internal server error occured.")]
+    InternalError,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::NotFound => "notfound",
+            RouteError::Conflict => "conflict",
+            RouteError::TooManyRecipients => "toomanyrecipients",
+            RouteError::InvalidAddress => "invalidaddress",
+            RouteError::InvalidSubject => "invalidsubject",
+            RouteError::InvalidReference => "invalidreference",
+            RouteError::AttachmentNotFound => "attachmentnotfound",
+            RouteError::TooLarge => "toolarge",
+            RouteError::Locked => "locked",
+            RouteError::QuotaExceeded => "quotaexceeded",
+            RouteError::StorageError => "storageerror",
+            RouteError::InternalError => "internalerror",
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[
+            RouteError::NotFound,
+            RouteError::Conflict,
+            RouteError::TooManyRecipients,
+            RouteError::InvalidAddress,
+            RouteError::InvalidSubject,
+            RouteError::InvalidReference,
+            RouteError::AttachmentNotFound,
+            RouteError::TooLarge,
+            RouteError::Locked,
+            RouteError::QuotaExceeded,
+            RouteError::StorageError,
+            RouteError::InternalError,
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::NotFound => StatusCode::NOT_FOUND,
+            RouteError::Conflict => StatusCode::CONFLICT,
+            RouteError::TooManyRecipients => StatusCode::BAD_REQUEST,
+            RouteError::InvalidAddress => StatusCode::BAD_REQUEST,
+            RouteError::InvalidSubject => StatusCode::BAD_REQUEST,
+            RouteError::InvalidReference => StatusCode::BAD_REQUEST,
+            RouteError::AttachmentNotFound => StatusCode::UNPROCESSABLE_ENTITY,
+            RouteError::TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            RouteError::Locked => StatusCode::FORBIDDEN,
+            RouteError::QuotaExceeded => StatusCode::INSUFFICIENT_STORAGE,
+            RouteError::StorageError => StatusCode::SERVICE_UNAVAILABLE,
+            RouteError::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<account::FindError> for RouteError {
+    fn from(err: account::FindError) -> Self {
+        match err {
+            account::FindError::NotFound => RouteError::InternalError,
+            account::FindError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<FindError> for RouteError {
+    fn from(err: FindError) -> Self {
+        match err {
+            FindError::NotFound => RouteError::NotFound,
+            FindError::Locked => RouteError::Locked,
+            FindError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<DraftError> for RouteError {
+    fn from(err: DraftError) -> Self {
+        match err {
+            DraftError::Conflict => RouteError::Conflict,
+            DraftError::SendError(e) => e.into(),
+            DraftError::MailboxKey(_) => RouteError::InternalError,
+            DraftError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<SendError> for RouteError {
+    fn from(err: SendError) -> Self {
+        match err {
+            SendError::TooManyRecipients => RouteError::TooManyRecipients,
+            SendError::InvalidAddress => RouteError::InvalidAddress,
+            SendError::InvalidSubject => RouteError::InvalidSubject,
+            SendError::InvalidReference => RouteError::InvalidReference,
+            SendError::AttachmentNotFound => RouteError::AttachmentNotFound,
+            SendError::TooLarge => RouteError::TooLarge,
+            SendError::Locked => RouteError::Locked,
+            SendError::QuotaExceeded => RouteError::QuotaExceeded,
+            SendError::BlobError(BlobError::DatabaseError(e)) => RouteError::DatabaseError(e),
+            SendError::BlobError(_) => RouteError::StorageError,
+            SendError::NoRecipients | SendError::UnknownRecipient | SendError::DeliverError(_) => {
+                RouteError::InternalError
+            }
+            SendError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use actix_web::{
+    get,
+    http::StatusCode,
+    web::{self, Json, Path},
+    HttpResponse, ResponseError,
+};
+use serde::Serialize;
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::{IntoResponses, ToSchema};
+use uuid::Uuid;
+
+use crate::http::{guarded_error_responses, ApiError, Responses, UserGuard};
+use crate::logic::{
+    draft::{Draft, FindError},
+    mailbox_key::MailboxKey,
+};
+
+/// Get the latest version of a draft of the current user.
+#[utoipa::path(
+    operation_id = "drafts.get",
+    tag = "drafts",
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError),
+    security(("session" = []))
+)]
+#[get("/{id}")]
+async fn get(
+    id: Path<Uuid>,
+    account: UserGuard<Uuid>,
+    pool: web::Data<Pool<Postgres>>,
+    mailbox_key: Option<MailboxKey>,
+) -> Result<Json<Response>, RouteError> {
+    let mut conn = pool.acquire().await?;
+    let draft = Draft::find(&mut conn, mailbox_key.as_ref(), account.into(), *id).await?;
+
+    Ok(Json(Response { draft }))
+}
+
+/// Success response of this route.
+#[derive(Serialize, ToSchema)]
+struct Response {
+    draft: Draft,
+}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("The draft does not exist.")]
+    NotFound,
+    #[error("The draft is encrypted and the mailbox key is not unlocked.")]
+    Locked,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::NotFound => "notfound",
+            RouteError::Locked => "locked",
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[
+            RouteError::NotFound,
+            RouteError::Locked,
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::NotFound => StatusCode::NOT_FOUND,
+            RouteError::Locked => StatusCode::FORBIDDEN,
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<FindError> for RouteError {
+    fn from(err: FindError) -> Self {
+        match err {
+            FindError::NotFound => RouteError::NotFound,
+            FindError::Locked => RouteError::Locked,
+            FindError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
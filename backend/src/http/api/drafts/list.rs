@@ -0,0 +1,102 @@
+use actix_web::{
+    get,
+    http::StatusCode,
+    web::{self, Json},
+    HttpResponse, ResponseError,
+};
+use serde::Serialize;
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::{IntoResponses, ToSchema};
+use uuid::Uuid;
+
+use crate::http::{guarded_error_responses, ApiError, Responses, UserGuard};
+use crate::logic::{
+    draft::{Draft, FindError},
+    mailbox_key::MailboxKey,
+};
+
+/// List the drafts of the current user, the most recently saved first.
+#[utoipa::path(
+    operation_id = "drafts.list",
+    tag = "drafts",
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError),
+    security(("session" = []))
+)]
+#[get("")]
+async fn list(
+    account: UserGuard<Uuid>,
+    pool: web::Data<Pool<Postgres>>,
+    mailbox_key: Option<MailboxKey>,
+) -> Result<Json<Response>, RouteError> {
+    let mut conn = pool.acquire().await?;
+    let drafts = Draft::list(&mut conn, mailbox_key.as_ref(), account.into()).await?;
+
+    Ok(Json(Response { drafts }))
+}
+
+/// Success response of this route.
+#[derive(Serialize, ToSchema)]
+struct Response {
+    drafts: Vec<Draft>,
+}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("The drafts are encrypted and the mailbox key is not unlocked.")]
+    Locked,
+    #[error("Internal server error.")]
+    InternalError,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::Locked => "locked",
+            RouteError::InternalError => "internalerror",
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[
+            RouteError::Locked,
+            RouteError::InternalError,
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::Locked => StatusCode::FORBIDDEN,
+            RouteError::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<FindError> for RouteError {
+    fn from(err: FindError) -> Self {
+        match err {
+            FindError::NotFound => RouteError::InternalError,
+            FindError::Locked => RouteError::Locked,
+            FindError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use actix_web::{web, Scope};
+use utoipa::OpenApi;
+
+mod attachments;
+mod delete;
+mod get;
+mod list;
+mod new;
+mod send;
+mod update;
+
+/// Returns the routes of this scope.
+pub fn routes() -> Scope {
+    web::scope("/drafts")
+        .service(list::list)
+        .service(new::new_draft)
+        .service(get::get)
+        .service(update::update)
+        .service(attachments::attachments)
+        .service(send::send)
+        .service(delete::delete)
+        .default_service(web::route().to(super::not_found))
+}
+
+/// The documentation of the routes of this scope.
+#[derive(OpenApi)]
+#[openapi(paths(
+    list::list,
+    new::new_draft,
+    get::get,
+    update::update,
+    attachments::attachments,
+    send::send,
+    delete::delete
+))]
+pub struct Api;
@@ -0,0 +1,136 @@
+use std::{convert::Infallible, time::Duration};
+
+use actix_session::Session;
+use actix_web::{
+    get,
+    http::{header, StatusCode},
+    web::{self, Bytes},
+    HttpResponse, ResponseError, Scope,
+};
+use futures_util::stream;
+use thiserror::Error;
+use tokio::{sync::broadcast::error::RecvError, time::Instant};
+use utoipa::{IntoResponses, OpenApi};
+use uuid::Uuid;
+
+use crate::http::{guarded_error_responses, session_id, ApiError, Responses, UserGuard};
+use crate::logic::event::{Event, Events};
+
+/// Interval of the comments sent to keep the stream open through proxies.
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+
+/// Returns the routes of this scope.
+pub fn routes() -> Scope {
+    web::scope("/events")
+        .service(events)
+        .default_service(web::route().to(super::not_found))
+}
+
+/// The documentation of the routes of this scope.
+#[derive(OpenApi)]
+#[openapi(paths(events))]
+pub struct Api;
+
+/// Stream the events of the current account as Server-Sent Events, each as JSON data.
+/// The stream ends after an event revoking the session, or when the client falls too far behind.
+/// A client reconnecting should ask for the changes of its mailboxes, as it may have missed events.
+#[utoipa::path(
+    operation_id = "events",
+    tag = "events",
+    responses(
+        (status = OK, description = "The events as Server-Sent Events, each as JSON data.", body = Event, content_type = "text/event-stream"),
+        RouteError
+    ),
+    security(("session" = []))
+)]
+#[get("")]
+async fn events(
+    account: UserGuard<Uuid>,
+    session: Session,
+    events: web::Data<Events>,
+) -> Result<HttpResponse, RouteError> {
+    let account: Uuid = account.into();
+
+    let id = session_id(&session).map_err(|_| RouteError::InternalError)?;
+
+    let receiver = events.subscribe(account);
+    let keep_alive = Instant::now() + KEEP_ALIVE;
+
+    let stream = stream::unfold(Some((receiver, keep_alive)), move |state| async move {
+        let (mut receiver, mut keep_alive) = state?;
+
+        loop {
+            let event = match tokio::time::timeout_at(keep_alive, receiver.recv()).await {
+                Ok(Ok(event)) => event,
+                // The client reconnects and catches up, when it missed events.
+                Ok(Err(RecvError::Lagged(_))) | Ok(Err(RecvError::Closed)) => return None,
+                Err(_) => {
+                    keep_alive = Instant::now() + KEEP_ALIVE;
+                    let chunk = Ok(Bytes::from_static(b": keep-alive\n\n"));
+                    return Some((chunk, Some((receiver, keep_alive))));
+                }
+            };
+
+            // Only the sessions revoked are told, not the one revoking them.
+            let revoked = match event {
+                Event::SessionRevoked { session } if session != id => continue,
+                Event::OtherSessionsRevoked { session } if session == id => continue,
+                Event::SessionRevoked { .. } | Event::OtherSessionsRevoked { .. } => true,
+                _ => false,
+            };
+
+            let data = serde_json::to_string(&event)
+                .expect("Serialization failed, which should not be impossible.");
+            let chunk = Ok::<_, Infallible>(Bytes::from(format!("data: {}\n\n", data)));
+
+            return match revoked {
+                true => Some((chunk, None)),
+                false => Some((chunk, Some((receiver, keep_alive)))),
+            };
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        // Compressed events would be held back until enough of them are buffered.
+        .insert_header((header::CONTENT_ENCODING, "identity"))
+        .streaming(Box::pin(stream)))
+}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("Internal server error.")]
+    InternalError,
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::InternalError => "internalerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[RouteError::InternalError])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
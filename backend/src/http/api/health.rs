@@ -1,8 +1,25 @@
-use actix_web::{web, HttpResponse, Scope};
+use actix_web::{get, web, HttpResponse, Scope};
+use utoipa::OpenApi;
 
 /// Returns the routes of this scope.
 pub fn routes() -> Scope {
     web::scope("/health")
-        .route("", web::get().to(HttpResponse::Ok))
+        .service(health)
         .default_service(web::route().to(super::not_found))
 }
+
+/// The documentation of the routes of this scope.
+#[derive(OpenApi)]
+#[openapi(paths(health))]
+pub struct Api;
+
+/// Check that the server is up.
+#[utoipa::path(
+    operation_id = "health",
+    tag = "health",
+    responses((status = OK, description = "The server is up."))
+)]
+#[get("")]
+async fn health() -> HttpResponse {
+    HttpResponse::Ok().finish()
+}
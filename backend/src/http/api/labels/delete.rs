@@ -0,0 +1,95 @@
+use actix_web::{
+    delete,
+    http::StatusCode,
+    web::{self, Json, Path},
+    HttpResponse, ResponseError,
+};
+use serde::Serialize;
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::{IntoResponses, ToSchema};
+use uuid::Uuid;
+
+use crate::http::{guarded_error_responses, ApiError, Responses, UserGuard};
+use crate::logic::label::{self, Label};
+
+/// Delete a label of the current user, its messages are kept.
+#[utoipa::path(
+    operation_id = "labels.delete",
+    tag = "labels",
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError),
+    security(("session" = []))
+)]
+#[delete("/{id}")]
+async fn delete(
+    id: Path<Uuid>,
+    account: UserGuard<Uuid>,
+    pool: web::Data<Pool<Postgres>>,
+) -> Result<Json<Response>, RouteError> {
+    let mut conn = pool.begin().await?;
+
+    let label = Label::find(&mut conn, account.into(), *id).await?;
+    label.delete(&mut conn).await?;
+
+    conn.commit().await?;
+
+    Ok(Json(Response {}))
+}
+
+/// Success response of this route.
+#[derive(Serialize, ToSchema)]
+struct Response {}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("The label does not exist.")]
+    NotFound,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::NotFound => "notfound",
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[
+            RouteError::NotFound,
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::NotFound => StatusCode::NOT_FOUND,
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<label::FindError> for RouteError {
+    fn from(err: label::FindError) -> Self {
+        match err {
+            label::FindError::NotFound => RouteError::NotFound,
+            label::FindError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
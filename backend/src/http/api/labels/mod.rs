@@ -0,0 +1,30 @@
+use actix_web::{web, Scope};
+use utoipa::OpenApi;
+
+mod delete;
+mod list;
+mod new;
+mod order;
+mod update;
+
+/// Returns the routes of this scope.
+pub fn routes() -> Scope {
+    web::scope("/labels")
+        .service(list::list)
+        .service(new::new_label)
+        .service(order::order)
+        .service(update::update)
+        .service(delete::delete)
+        .default_service(web::route().to(super::not_found))
+}
+
+/// The documentation of the routes of this scope.
+#[derive(OpenApi)]
+#[openapi(paths(
+    list::list,
+    new::new_label,
+    order::order,
+    update::update,
+    delete::delete
+))]
+pub struct Api;
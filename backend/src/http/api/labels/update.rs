@@ -0,0 +1,131 @@
+use actix_web::{
+    http::StatusCode,
+    put,
+    web::{self, Json, Path},
+    HttpResponse, ResponseError,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::{IntoResponses, ToSchema};
+use uuid::Uuid;
+
+use crate::http::{guarded_error_responses, ApiError, Responses, UserGuard};
+use crate::logic::label::{self, Label};
+
+/// Change the name and color of a label of the current user.
+#[utoipa::path(
+    operation_id = "labels.update",
+    tag = "labels",
+    request_body = inline(BodyData),
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError),
+    security(("session" = []))
+)]
+#[put("/{id}")]
+async fn update(
+    id: Path<Uuid>,
+    data: Json<BodyData>,
+    account: UserGuard<Uuid>,
+    pool: web::Data<Pool<Postgres>>,
+) -> Result<Json<Response>, RouteError> {
+    let mut conn = pool.acquire().await?;
+
+    let mut label = Label::find(&mut conn, account.into(), *id).await?;
+    label.update(&mut conn, &data.name, &data.color).await?;
+
+    Ok(Json(Response { label }))
+}
+
+/// Requested data for this route.
+#[derive(Deserialize, ToSchema)]
+struct BodyData {
+    name: String,
+    color: String,
+}
+
+/// Success response of this route.
+#[derive(Serialize, ToSchema)]
+struct Response {
+    label: Label,
+}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("The label does not exist.")]
+    NotFound,
+    #[error("The name is empty, too long or contains invalid characters.")]
+    InvalidName,
+    #[error("The color is not formatted as #rrggbb.")]
+    InvalidColor,
+    #[error("A label with the name already exists.")]
+    NameExists,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::NotFound => "notfound",
+            RouteError::InvalidName => "invalidname",
+            RouteError::InvalidColor => "invalidcolor",
+            RouteError::NameExists => "nameexists",
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[
+            RouteError::NotFound,
+            RouteError::InvalidName,
+            RouteError::InvalidColor,
+            RouteError::NameExists,
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::NotFound => StatusCode::NOT_FOUND,
+            RouteError::InvalidName => StatusCode::BAD_REQUEST,
+            RouteError::InvalidColor => StatusCode::BAD_REQUEST,
+            RouteError::NameExists => StatusCode::CONFLICT,
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<label::FindError> for RouteError {
+    fn from(err: label::FindError) -> Self {
+        match err {
+            label::FindError::NotFound => RouteError::NotFound,
+            label::FindError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<label::CreateError> for RouteError {
+    fn from(err: label::CreateError) -> Self {
+        match err {
+            label::CreateError::InvalidName => RouteError::InvalidName,
+            label::CreateError::InvalidColor => RouteError::InvalidColor,
+            label::CreateError::NameExists => RouteError::NameExists,
+            label::CreateError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use actix_web::{web, Scope};
+use utoipa::OpenApi;
+
+mod get;
+mod list;
+mod moderate;
+mod moderation;
+mod new;
+mod subscribe;
+mod unsubscribe;
+
+/// Returns the routes of this scope.
+/// All routes in this scope are only available to administrators.
+pub fn routes() -> Scope {
+    web::scope("/lists")
+        .service(list::list)
+        .service(new::new_list)
+        .service(get::get)
+        .service(subscribe::subscribe)
+        .service(unsubscribe::unsubscribe)
+        .service(moderation::moderation)
+        .service(moderate::moderate)
+        .default_service(web::route().to(super::not_found))
+}
+
+/// The documentation of the routes of this scope.
+#[derive(OpenApi)]
+#[openapi(paths(
+    list::list,
+    new::new_list,
+    get::get,
+    subscribe::subscribe,
+    unsubscribe::unsubscribe,
+    moderation::moderation,
+    moderate::moderate
+))]
+pub struct Api;
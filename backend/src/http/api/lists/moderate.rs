@@ -0,0 +1,118 @@
+use actix_web::{
+    http::StatusCode,
+    post,
+    web::{self, Json, Path},
+    HttpResponse, ResponseError,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::{IntoResponses, ToSchema};
+use uuid::Uuid;
+
+use crate::http::{guarded_error_responses, AdminGuard, ApiError, Responses};
+use crate::logic::mailing_list::{self, MailingList};
+
+/// Approve or reject a post waiting for moderation.
+/// Approved posts are distributed to the subscribers, rejected posts are discarded.
+#[utoipa::path(
+    operation_id = "lists.moderate",
+    tag = "lists",
+    request_body = inline(BodyData),
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError),
+    security(("session" = []))
+)]
+#[post("/{id}/moderation/{post}")]
+async fn moderate(
+    path: Path<(Uuid, Uuid)>,
+    data: Json<BodyData>,
+    _admin: AdminGuard,
+    pool: web::Data<Pool<Postgres>>,
+) -> Result<Json<Response>, RouteError> {
+    let (id, post) = path.into_inner();
+    let mut conn = pool.acquire().await?;
+
+    let list = MailingList::find(&mut conn, id).await?;
+    list.moderate(&mut conn, post, data.approve).await?;
+
+    Ok(Json(Response {}))
+}
+
+/// Requested data for this route.
+#[derive(Deserialize, ToSchema)]
+struct BodyData {
+    approve: bool,
+}
+
+/// Success response of this route.
+#[derive(Serialize, ToSchema)]
+struct Response {}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("The mailing list does not exist.")]
+    NotFound,
+    #[error("The post is not waiting for moderation.")]
+    PostNotFound,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::NotFound => "notfound",
+            RouteError::PostNotFound => "postnotfound",
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[
+            RouteError::NotFound,
+            RouteError::PostNotFound,
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::NotFound => StatusCode::NOT_FOUND,
+            RouteError::PostNotFound => StatusCode::NOT_FOUND,
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<mailing_list::FindError> for RouteError {
+    fn from(err: mailing_list::FindError) -> Self {
+        match err {
+            mailing_list::FindError::NotFound => RouteError::NotFound,
+            mailing_list::FindError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<mailing_list::ModerateError> for RouteError {
+    fn from(err: mailing_list::ModerateError) -> Self {
+        match err {
+            mailing_list::ModerateError::NotFound => RouteError::PostNotFound,
+            mailing_list::ModerateError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
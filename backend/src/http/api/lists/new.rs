@@ -0,0 +1,142 @@
+use actix_web::{
+    http::StatusCode,
+    post,
+    web::{self, Json},
+    HttpResponse, ResponseError,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::{IntoResponses, ToSchema};
+
+use crate::http::{guarded_error_responses, AdminGuard, ApiError, Responses};
+use crate::logic::{
+    domain::{self, Domain},
+    mailing_list::{self, ListPolicy, MailingList},
+};
+
+/// Create a new mailing list on one of the domains of this server.
+#[utoipa::path(
+    operation_id = "lists.new",
+    tag = "lists",
+    request_body = inline(BodyData),
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError),
+    security(("session" = []))
+)]
+#[post("/new")]
+async fn new_list(
+    data: Json<BodyData>,
+    _admin: AdminGuard,
+    pool: web::Data<Pool<Postgres>>,
+) -> Result<Json<Response>, RouteError> {
+    let data = data.into_inner();
+    let mut conn = pool.acquire().await?;
+
+    let domain = Domain::find(&mut conn, &data.domain).await?;
+    let list = MailingList::create(
+        &mut conn,
+        &domain,
+        &data.local_part,
+        &data.name,
+        &data.description,
+        data.policy,
+    )
+    .await?;
+
+    info!("New mailing list {} created.", list.address());
+
+    Ok(Json(Response { list }))
+}
+
+/// Requested data for this route.
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct BodyData {
+    domain: String,
+    local_part: String,
+    name: String,
+    #[serde(default)]
+    description: String,
+    policy: ListPolicy,
+}
+
+/// Success response of this route.
+#[derive(Serialize, ToSchema)]
+struct Response {
+    list: MailingList,
+}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("The domain does not exist.")]
+    DomainNotFound,
+    #[error("The given list address is not valid.")]
+    InvalidAddress,
+    #[error("List {0} already exists.")]
+    ListExists(String),
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::DomainNotFound => "domainnotfound",
+            RouteError::InvalidAddress => "invalidaddress",
+            RouteError::ListExists(_) => "listexists",
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[
+            RouteError::DomainNotFound,
+            RouteError::InvalidAddress,
+            RouteError::ListExists("news@example.com".to_string()),
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::DomainNotFound => StatusCode::BAD_REQUEST,
+            RouteError::InvalidAddress => StatusCode::BAD_REQUEST,
+            RouteError::ListExists(_) => StatusCode::BAD_REQUEST,
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<domain::FindError> for RouteError {
+    fn from(err: domain::FindError) -> Self {
+        match err {
+            domain::FindError::NotFound => RouteError::DomainNotFound,
+            domain::FindError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<mailing_list::CreateError> for RouteError {
+    fn from(err: mailing_list::CreateError) -> Self {
+        match err {
+            mailing_list::CreateError::InvalidAddress(_) => RouteError::InvalidAddress,
+            mailing_list::CreateError::ListExists(name) => RouteError::ListExists(name),
+            mailing_list::CreateError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use actix_web::{
+    http::StatusCode,
+    post,
+    web::{self, Json, Path},
+    HttpResponse, ResponseError,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::{IntoResponses, ToSchema};
+use uuid::Uuid;
+
+use crate::http::{guarded_error_responses, AdminGuard, ApiError, Responses};
+use crate::logic::mailing_list::{self, ListMember, ListRole, MailingList};
+
+/// Subscribe an address to a mailing list, or update its subscription.
+#[utoipa::path(
+    operation_id = "lists.subscribe",
+    tag = "lists",
+    request_body = inline(BodyData),
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError),
+    security(("session" = []))
+)]
+#[post("/{id}/subscribe")]
+async fn subscribe(
+    id: Path<Uuid>,
+    data: Json<BodyData>,
+    _admin: AdminGuard,
+    pool: web::Data<Pool<Postgres>>,
+) -> Result<Json<Response>, RouteError> {
+    let data = data.into_inner();
+    let mut conn = pool.acquire().await?;
+
+    let list = MailingList::find(&mut conn, *id).await?;
+    let member = list
+        .subscribe(&mut conn, &data.address, data.role, data.digest)
+        .await?;
+
+    Ok(Json(Response { member }))
+}
+
+/// Requested data for this route.
+#[derive(Deserialize, ToSchema)]
+struct BodyData {
+    address: String,
+    role: ListRole,
+    #[serde(default)]
+    digest: bool,
+}
+
+/// Success response of this route.
+#[derive(Serialize, ToSchema)]
+struct Response {
+    member: ListMember,
+}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("The mailing list does not exist.")]
+    NotFound,
+    #[error("The given address is not valid.")]
+    InvalidAddress,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::NotFound => "notfound",
+            RouteError::InvalidAddress => "invalidaddress",
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[
+            RouteError::NotFound,
+            RouteError::InvalidAddress,
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::NotFound => StatusCode::NOT_FOUND,
+            RouteError::InvalidAddress => StatusCode::BAD_REQUEST,
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<mailing_list::FindError> for RouteError {
+    fn from(err: mailing_list::FindError) -> Self {
+        match err {
+            mailing_list::FindError::NotFound => RouteError::NotFound,
+            mailing_list::FindError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<mailing_list::SubscribeError> for RouteError {
+    fn from(err: mailing_list::SubscribeError) -> Self {
+        match err {
+            mailing_list::SubscribeError::InvalidAddress(_) => RouteError::InvalidAddress,
+            mailing_list::SubscribeError::NotSubscribed(_) => RouteError::InvalidAddress,
+            mailing_list::SubscribeError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use actix_web::{
+    get,
+    http::StatusCode,
+    web::{self, Json, Path},
+    HttpResponse, ResponseError,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::{IntoParams, IntoResponses, ToSchema};
+use uuid::Uuid;
+
+use crate::http::{guarded_error_responses, ApiError, Responses, UserGuard};
+use crate::logic::mailbox::{self, Changes, ChangesError, Mailbox};
+
+/// Get the messages of a mailbox of the current user which changed after a modification sequence.
+/// Clients sync with the returned sequence the next time.
+#[utoipa::path(
+    operation_id = "mailboxes.changes",
+    tag = "mailboxes",
+    params(Params),
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError),
+    security(("session" = []))
+)]
+#[get("/{id}/changes")]
+async fn changes(
+    id: Path<Uuid>,
+    params: web::Query<Params>,
+    account: UserGuard<Uuid>,
+    pool: web::Data<Pool<Postgres>>,
+) -> Result<Json<Response>, RouteError> {
+    let mut conn = pool.acquire().await?;
+
+    let mailbox = Mailbox::find(&mut conn, account.into(), *id).await?;
+    let changes = mailbox.changes(&mut conn, params.since).await?;
+
+    Ok(Json(Response { changes }))
+}
+
+/// Query parameters of this route.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct Params {
+    since: i64,
+}
+
+/// Success response of this route.
+#[derive(Serialize, ToSchema)]
+struct Response {
+    #[serde(flatten)]
+    changes: Changes,
+}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("The mailbox does not exist.")]
+    NotFound,
+    #[error("The modification sequence is not one of the mailbox.")]
+    InvalidSince,
+    #[error("Too many messages changed, the mailbox has to be fetched again.")]
+    TooManyChanges,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::NotFound => "notfound",
+            RouteError::InvalidSince => "invalidsince",
+            RouteError::TooManyChanges => "toomanychanges",
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[
+            RouteError::NotFound,
+            RouteError::InvalidSince,
+            RouteError::TooManyChanges,
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::NotFound => StatusCode::NOT_FOUND,
+            RouteError::InvalidSince => StatusCode::BAD_REQUEST,
+            RouteError::TooManyChanges => StatusCode::CONFLICT,
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<mailbox::FindError> for RouteError {
+    fn from(err: mailbox::FindError) -> Self {
+        match err {
+            mailbox::FindError::NotFound => RouteError::NotFound,
+            mailbox::FindError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<ChangesError> for RouteError {
+    fn from(err: ChangesError) -> Self {
+        match err {
+            ChangesError::InvalidSince => RouteError::InvalidSince,
+            ChangesError::TooManyChanges => RouteError::TooManyChanges,
+            ChangesError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
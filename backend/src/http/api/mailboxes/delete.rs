@@ -0,0 +1,116 @@
+use actix_web::{
+    delete,
+    http::StatusCode,
+    web::{self, Json, Path},
+    HttpResponse, ResponseError,
+};
+use serde::Serialize;
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::{IntoResponses, ToSchema};
+use uuid::Uuid;
+
+use crate::http::{guarded_error_responses, ApiError, Responses, UserGuard};
+use crate::logic::mailbox::{self, DeleteError, Mailbox};
+
+/// Delete a mailbox of the current user, moving its messages into the trash.
+#[utoipa::path(
+    operation_id = "mailboxes.delete",
+    tag = "mailboxes",
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError),
+    security(("session" = []))
+)]
+#[delete("/{id}")]
+async fn delete(
+    id: Path<Uuid>,
+    account: UserGuard<Uuid>,
+    pool: web::Data<Pool<Postgres>>,
+) -> Result<Json<Response>, RouteError> {
+    let mut conn = pool.begin().await?;
+
+    let mailbox = Mailbox::find(&mut conn, account.into(), *id).await?;
+    mailbox.delete(&mut conn).await?;
+
+    conn.commit().await?;
+
+    Ok(Json(Response {}))
+}
+
+/// Success response of this route.
+#[derive(Serialize, ToSchema)]
+struct Response {}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("The mailbox does not exist.")]
+    NotFound,
+    #[error("Mailboxes with a role can not be deleted.")]
+    SystemMailbox,
+    #[error("The mailbox still has nested mailboxes.")]
+    HasChildren,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::NotFound => "notfound",
+            RouteError::SystemMailbox => "systemmailbox",
+            RouteError::HasChildren => "haschildren",
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[
+            RouteError::NotFound,
+            RouteError::SystemMailbox,
+            RouteError::HasChildren,
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::NotFound => StatusCode::NOT_FOUND,
+            RouteError::SystemMailbox => StatusCode::FORBIDDEN,
+            RouteError::HasChildren => StatusCode::CONFLICT,
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<mailbox::FindError> for RouteError {
+    fn from(err: mailbox::FindError) -> Self {
+        match err {
+            mailbox::FindError::NotFound => RouteError::NotFound,
+            mailbox::FindError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<DeleteError> for RouteError {
+    fn from(err: DeleteError) -> Self {
+        match err {
+            DeleteError::SystemMailbox => RouteError::SystemMailbox,
+            DeleteError::HasChildren => RouteError::HasChildren,
+            DeleteError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
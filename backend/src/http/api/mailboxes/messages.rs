@@ -0,0 +1,157 @@
+use actix_web::{
+    get,
+    http::StatusCode,
+    web::{self, Json, Path},
+    HttpResponse, ResponseError,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::{IntoParams, IntoResponses, ToSchema};
+use uuid::Uuid;
+
+use crate::http::{guarded_error_responses, ApiError, Responses, UserGuard};
+use crate::logic::{
+    mailbox::{self, Mailbox},
+    summary::{FlagFilter, ListError, Sort, Summary},
+};
+
+/// The maximum amount of messages in a single response.
+const MAX_LIMIT: i64 = 100;
+
+/// List the messages in a mailbox of the current user, a page at a time.
+/// The next page starts after the returned cursor, which is left out on the last page.
+#[utoipa::path(
+    operation_id = "mailboxes.messages",
+    tag = "mailboxes",
+    params(Params),
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError),
+    security(("session" = []))
+)]
+#[get("/{id}/messages")]
+async fn messages(
+    id: Path<Uuid>,
+    params: web::Query<Params>,
+    account: UserGuard<Uuid>,
+    pool: web::Data<Pool<Postgres>>,
+) -> Result<Json<Response>, RouteError> {
+    let params = params.into_inner();
+    let limit = params.limit.unwrap_or(50).clamp(1, MAX_LIMIT);
+    let filter = FlagFilter {
+        seen: params.seen,
+        flagged: params.flagged,
+        answered: params.answered,
+        draft: params.draft,
+        deleted: params.deleted,
+        has_attachment: params.has_attachment,
+    };
+
+    let mut conn = pool.acquire().await?;
+    let mailbox = Mailbox::find(&mut conn, account.into(), *id).await?;
+    let (messages, next) = Summary::list(
+        &mut conn,
+        &mailbox,
+        params.sort.unwrap_or_default(),
+        &filter,
+        params.cursor.as_deref(),
+        limit,
+    )
+    .await?;
+
+    Ok(Json(Response {
+        messages,
+        next: next.map(|cursor| cursor.to_string()),
+    }))
+}
+
+/// Query parameters of this route.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+#[serde(rename_all = "camelCase")]
+struct Params {
+    sort: Option<Sort>,
+    cursor: Option<String>,
+    limit: Option<i64>,
+    seen: Option<bool>,
+    flagged: Option<bool>,
+    answered: Option<bool>,
+    draft: Option<bool>,
+    deleted: Option<bool>,
+    has_attachment: Option<bool>,
+}
+
+/// Success response of this route.
+#[derive(Serialize, ToSchema)]
+struct Response {
+    messages: Vec<Summary>,
+    next: Option<String>,
+}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("The mailbox does not exist.")]
+    NotFound,
+    #[error("The cursor is not valid.")]
+    InvalidCursor,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::NotFound => "notfound",
+            RouteError::InvalidCursor => "invalidcursor",
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[
+            RouteError::NotFound,
+            RouteError::InvalidCursor,
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::NotFound => StatusCode::NOT_FOUND,
+            RouteError::InvalidCursor => StatusCode::BAD_REQUEST,
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<mailbox::FindError> for RouteError {
+    fn from(err: mailbox::FindError) -> Self {
+        match err {
+            mailbox::FindError::NotFound => RouteError::NotFound,
+            mailbox::FindError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<ListError> for RouteError {
+    fn from(err: ListError) -> Self {
+        match err {
+            ListError::InvalidCursor => RouteError::InvalidCursor,
+            ListError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use actix_web::{web, Scope};
+use utoipa::OpenApi;
+
+use crate::logic::summary::Sort;
+
+mod changes;
+mod delete;
+mod get;
+mod list;
+mod messages;
+mod move_to;
+mod new;
+mod rename;
+mod retention;
+
+/// Returns the routes of this scope.
+pub fn routes() -> Scope {
+    web::scope("/mailboxes")
+        .service(list::list)
+        .service(new::new_mailbox)
+        .service(get::get)
+        .service(changes::changes)
+        .service(messages::messages)
+        .service(rename::rename)
+        .service(move_to::move_to)
+        .service(retention::retention)
+        .service(delete::delete)
+        .default_service(web::route().to(super::not_found))
+}
+
+/// The documentation of the routes of this scope.
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        list::list,
+        new::new_mailbox,
+        get::get,
+        changes::changes,
+        messages::messages,
+        rename::rename,
+        move_to::move_to,
+        retention::retention,
+        delete::delete
+    ),
+    components(schemas(Sort))
+)]
+pub struct Api;
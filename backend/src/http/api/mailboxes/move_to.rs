@@ -0,0 +1,132 @@
+use actix_web::{
+    http::StatusCode,
+    post,
+    web::{self, Json, Path},
+    HttpResponse, ResponseError,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::{IntoResponses, ToSchema};
+use uuid::Uuid;
+
+use crate::http::{guarded_error_responses, ApiError, Responses, UserGuard};
+use crate::logic::mailbox::{self, Mailbox, MoveError};
+
+/// Move a mailbox of the current user into another, or to the top without a parent.
+#[utoipa::path(
+    operation_id = "mailboxes.move_to",
+    tag = "mailboxes",
+    request_body = inline(BodyData),
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError),
+    security(("session" = []))
+)]
+#[post("/{id}/move")]
+async fn move_to(
+    id: Path<Uuid>,
+    data: Json<BodyData>,
+    account: UserGuard<Uuid>,
+    pool: web::Data<Pool<Postgres>>,
+) -> Result<Json<Response>, RouteError> {
+    // The mailboxes of the account stay locked until the move is committed.
+    let mut tx = pool.begin().await?;
+
+    let mut mailbox = Mailbox::find(&mut tx, account.into(), *id).await?;
+    mailbox.move_to(&mut tx, data.parent).await?;
+    tx.commit().await?;
+
+    Ok(Json(Response { mailbox }))
+}
+
+/// Requested data for this route.
+#[derive(Deserialize, ToSchema)]
+struct BodyData {
+    parent: Option<Uuid>,
+}
+
+/// Success response of this route.
+#[derive(Serialize, ToSchema)]
+struct Response {
+    mailbox: Mailbox,
+}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("The mailbox does not exist.")]
+    NotFound,
+    #[error("Mailboxes with a role can not be moved.")]
+    SystemMailbox,
+    #[error("The parent does not exist, or is nested in the mailbox.")]
+    InvalidParent,
+    #[error("A mailbox with the name already exists within the parent.")]
+    NameExists,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::NotFound => "notfound",
+            RouteError::SystemMailbox => "systemmailbox",
+            RouteError::InvalidParent => "invalidparent",
+            RouteError::NameExists => "nameexists",
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[
+            RouteError::NotFound,
+            RouteError::SystemMailbox,
+            RouteError::InvalidParent,
+            RouteError::NameExists,
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::NotFound => StatusCode::NOT_FOUND,
+            RouteError::SystemMailbox => StatusCode::FORBIDDEN,
+            RouteError::InvalidParent => StatusCode::BAD_REQUEST,
+            RouteError::NameExists => StatusCode::CONFLICT,
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<mailbox::FindError> for RouteError {
+    fn from(err: mailbox::FindError) -> Self {
+        match err {
+            mailbox::FindError::NotFound => RouteError::NotFound,
+            mailbox::FindError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<MoveError> for RouteError {
+    fn from(err: MoveError) -> Self {
+        match err {
+            MoveError::SystemMailbox => RouteError::SystemMailbox,
+            MoveError::InvalidParent => RouteError::InvalidParent,
+            MoveError::NameExists => RouteError::NameExists,
+            MoveError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use actix_web::{
+    http::StatusCode,
+    post,
+    web::{self, Json},
+    HttpResponse, ResponseError,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::{IntoResponses, ToSchema};
+use uuid::Uuid;
+
+use crate::http::{guarded_error_responses, ApiError, Responses, UserGuard};
+use crate::logic::mailbox::{self, Mailbox};
+
+/// Create a new mailbox for the current user, nested in a parent or at the top.
+#[utoipa::path(
+    operation_id = "mailboxes.new",
+    tag = "mailboxes",
+    request_body = inline(BodyData),
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError),
+    security(("session" = []))
+)]
+#[post("/new")]
+async fn new_mailbox(
+    data: Json<BodyData>,
+    account: UserGuard<Uuid>,
+    pool: web::Data<Pool<Postgres>>,
+) -> Result<Json<Response>, RouteError> {
+    let mut conn = pool.acquire().await?;
+    let mailbox = Mailbox::create(&mut conn, account.into(), data.parent, &data.name).await?;
+
+    Ok(Json(Response { mailbox }))
+}
+
+/// Requested data for this route.
+#[derive(Deserialize, ToSchema)]
+struct BodyData {
+    name: String,
+    parent: Option<Uuid>,
+}
+
+/// Success response of this route.
+#[derive(Serialize, ToSchema)]
+struct Response {
+    mailbox: Mailbox,
+}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("The name is empty, too long or contains invalid characters.")]
+    InvalidName,
+    #[error("A mailbox with the name already exists within the parent.")]
+    NameExists,
+    #[error("The parent mailbox does not exist.")]
+    ParentNotFound,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::InvalidName => "invalidname",
+            RouteError::NameExists => "nameexists",
+            RouteError::ParentNotFound => "parentnotfound",
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[
+            RouteError::InvalidName,
+            RouteError::NameExists,
+            RouteError::ParentNotFound,
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::InvalidName => StatusCode::BAD_REQUEST,
+            RouteError::NameExists => StatusCode::CONFLICT,
+            RouteError::ParentNotFound => StatusCode::BAD_REQUEST,
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<mailbox::CreateError> for RouteError {
+    fn from(err: mailbox::CreateError) -> Self {
+        match err {
+            mailbox::CreateError::InvalidName => RouteError::InvalidName,
+            mailbox::CreateError::NameExists => RouteError::NameExists,
+            mailbox::CreateError::ParentNotFound => RouteError::ParentNotFound,
+            mailbox::CreateError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
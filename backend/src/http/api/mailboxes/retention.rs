@@ -0,0 +1,129 @@
+use actix_web::{
+    http::StatusCode,
+    put,
+    web::{self, Json, Path},
+    HttpResponse, ResponseError,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::{IntoResponses, ToSchema};
+use uuid::Uuid;
+
+use crate::http::{guarded_error_responses, ApiError, Responses, UserGuard};
+use crate::logic::mailbox::{self, Mailbox, RetentionAction, RetentionError};
+
+/// Set the retention policy of a mailbox of the current user.
+/// Leaving out the action uses the system default again.
+#[utoipa::path(
+    operation_id = "mailboxes.retention",
+    tag = "mailboxes",
+    request_body = inline(BodyData),
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError),
+    security(("session" = []))
+)]
+#[put("/{id}/retention")]
+async fn retention(
+    id: Path<Uuid>,
+    data: Json<BodyData>,
+    account: UserGuard<Uuid>,
+    pool: web::Data<Pool<Postgres>>,
+) -> Result<Json<Response>, RouteError> {
+    let data = data.into_inner();
+    let mut conn = pool.acquire().await?;
+
+    let mut mailbox = Mailbox::find(&mut conn, account.into(), *id).await?;
+    mailbox
+        .set_retention(&mut conn, data.action, data.days)
+        .await?;
+
+    Ok(Json(Response { mailbox }))
+}
+
+/// Requested data for this route.
+#[derive(Deserialize, ToSchema)]
+struct BodyData {
+    action: Option<RetentionAction>,
+    days: Option<i32>,
+}
+
+/// Success response of this route.
+#[derive(Serialize, ToSchema)]
+struct Response {
+    mailbox: Mailbox,
+}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("The mailbox does not exist.")]
+    NotFound,
+    #[error("Deleting and archiving need a positive amount of days, keeping none.")]
+    InvalidDays,
+    #[error("The archive can not be archived into itself.")]
+    InvalidAction,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::NotFound => "notfound",
+            RouteError::InvalidDays => "invaliddays",
+            RouteError::InvalidAction => "invalidaction",
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[
+            RouteError::NotFound,
+            RouteError::InvalidDays,
+            RouteError::InvalidAction,
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::NotFound => StatusCode::NOT_FOUND,
+            RouteError::InvalidDays => StatusCode::BAD_REQUEST,
+            RouteError::InvalidAction => StatusCode::BAD_REQUEST,
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<mailbox::FindError> for RouteError {
+    fn from(err: mailbox::FindError) -> Self {
+        match err {
+            mailbox::FindError::NotFound => RouteError::NotFound,
+            mailbox::FindError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<RetentionError> for RouteError {
+    fn from(err: RetentionError) -> Self {
+        match err {
+            RetentionError::InvalidDays => RouteError::InvalidDays,
+            RetentionError::ArchiveIntoItself => RouteError::InvalidAction,
+            RetentionError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
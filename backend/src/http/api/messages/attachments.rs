@@ -0,0 +1,134 @@
+use actix_web::{
+    get,
+    http::StatusCode,
+    web::{self, Json, Path},
+    HttpResponse, ResponseError,
+};
+use serde::Serialize;
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::{IntoResponses, ToSchema};
+use uuid::Uuid;
+
+use crate::http::{guarded_error_responses, ApiError, Responses, UserGuard};
+use crate::logic::{
+    attachment::{Attachment, ListError},
+    blob::BlobError,
+    mailbox_key::MailboxKey,
+    message::{self, Message, ReadError},
+};
+use crate::storage::BlobStore;
+
+/// List the MIME parts of a message.
+#[utoipa::path(
+    operation_id = "messages.attachments",
+    tag = "messages",
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError),
+    security(("session" = []))
+)]
+#[get("/{id}/attachments")]
+async fn attachments(
+    id: Path<Uuid>,
+    account: UserGuard<Uuid>,
+    pool: web::Data<Pool<Postgres>>,
+    store: web::Data<dyn BlobStore>,
+    mailbox_key: Option<MailboxKey>,
+) -> Result<Json<Response>, RouteError> {
+    let mut conn = pool.acquire().await?;
+    let message = Message::find(&mut conn, account.into(), *id).await?;
+    let attachments =
+        Attachment::list(&mut conn, store.as_ref(), mailbox_key.as_ref(), &message).await?;
+
+    Ok(Json(Response { attachments }))
+}
+
+/// Success response of this route.
+#[derive(Serialize, ToSchema)]
+struct Response {
+    attachments: Vec<Attachment>,
+}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("The message does not exist.")]
+    NotFound,
+    #[error("The message is encrypted and the mailbox key is not unlocked.")]
+    Locked,
+    #[error("The message could not be read from storage.")]
+    StorageError(BlobError),
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::NotFound => "notfound",
+            RouteError::Locked => "locked",
+            RouteError::StorageError(_) => "storageerror",
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[
+            RouteError::NotFound,
+            RouteError::Locked,
+            RouteError::StorageError(BlobError::DecryptError),
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::NotFound => StatusCode::NOT_FOUND,
+            RouteError::Locked => StatusCode::FORBIDDEN,
+            RouteError::StorageError(_) => StatusCode::SERVICE_UNAVAILABLE,
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<message::FindError> for RouteError {
+    fn from(err: message::FindError) -> Self {
+        match err {
+            message::FindError::NotFound => RouteError::NotFound,
+            message::FindError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<ListError> for RouteError {
+    fn from(err: ListError) -> Self {
+        match err {
+            ListError::ReadError(e) => e.into(),
+            ListError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<ReadError> for RouteError {
+    fn from(err: ReadError) -> Self {
+        match err {
+            ReadError::Locked => RouteError::Locked,
+            ReadError::BlobError(BlobError::DatabaseError(e)) => RouteError::DatabaseError(e),
+            ReadError::BlobError(e) => RouteError::StorageError(e),
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use actix_web::{
+    get,
+    http::StatusCode,
+    web::{self, Json, Path},
+    HttpResponse, ResponseError,
+};
+use serde::Serialize;
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::{IntoResponses, ToSchema};
+use uuid::Uuid;
+
+use crate::http::{guarded_error_responses, ApiError, Responses, UserGuard};
+use crate::logic::{
+    blob::BlobError,
+    mailbox_key::MailboxKey,
+    message::{self, Body, Message, ReadError},
+};
+use crate::storage::BlobStore;
+
+/// Get the readable content of a message, with the HTML sanitized.
+/// Inline images in the HTML point to the attachment route of this message,
+/// remote images are blocked unless the user allowed them from the sender.
+#[utoipa::path(
+    operation_id = "messages.body",
+    tag = "messages",
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError),
+    security(("session" = []))
+)]
+#[get("/{id}/body")]
+async fn body(
+    id: Path<Uuid>,
+    account: UserGuard<Uuid>,
+    pool: web::Data<Pool<Postgres>>,
+    store: web::Data<dyn BlobStore>,
+    mailbox_key: Option<MailboxKey>,
+) -> Result<Json<Response>, RouteError> {
+    let account = account.into();
+    let mut conn = pool.acquire().await?;
+    let message = Message::find(&mut conn, account, *id).await?;
+    let remote = message.remote_content(&mut conn, account).await?;
+    let body = message
+        .body(&mut conn, store.as_ref(), mailbox_key.as_ref(), remote)
+        .await?;
+
+    Ok(Json(Response { body }))
+}
+
+/// Success response of this route.
+#[derive(Serialize, ToSchema)]
+struct Response {
+    body: Body,
+}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("The message does not exist.")]
+    NotFound,
+    #[error("The message is encrypted and the mailbox key is not unlocked.")]
+    Locked,
+    #[error("The message could not be read from storage.")]
+    StorageError(BlobError),
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::NotFound => "notfound",
+            RouteError::Locked => "locked",
+            RouteError::StorageError(_) => "storageerror",
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[
+            RouteError::NotFound,
+            RouteError::Locked,
+            RouteError::StorageError(BlobError::DecryptError),
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::NotFound => StatusCode::NOT_FOUND,
+            RouteError::Locked => StatusCode::FORBIDDEN,
+            RouteError::StorageError(_) => StatusCode::SERVICE_UNAVAILABLE,
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<message::FindError> for RouteError {
+    fn from(err: message::FindError) -> Self {
+        match err {
+            message::FindError::NotFound => RouteError::NotFound,
+            message::FindError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<message::BodyError> for RouteError {
+    fn from(err: message::BodyError) -> Self {
+        match err {
+            message::BodyError::ReadError(e) => e.into(),
+        }
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<ReadError> for RouteError {
+    fn from(err: ReadError) -> Self {
+        match err {
+            ReadError::Locked => RouteError::Locked,
+            ReadError::BlobError(BlobError::DatabaseError(e)) => RouteError::DatabaseError(e),
+            ReadError::BlobError(e) => RouteError::StorageError(e),
+        }
+    }
+}
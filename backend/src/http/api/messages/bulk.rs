@@ -0,0 +1,178 @@
+use std::collections::HashSet;
+
+use actix_web::{
+    http::StatusCode,
+    post,
+    web::{self, Json},
+    HttpResponse, ResponseError,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::{IntoResponses, ToSchema};
+use uuid::Uuid;
+
+use crate::http::{guarded_error_responses, ApiError, Responses, UserGuard};
+use crate::logic::{
+    bulk::{self, Action, BulkError, BulkJob, Outcome},
+    quota::QuotaConfig,
+    search::{ParseError, Query},
+};
+
+/// Apply an action to many messages of the current user, given by ID or by a search query.
+/// The messages are changed in batches, with a result for every message.
+/// Large operations run in the background, their progress is available from the job.
+#[utoipa::path(
+    operation_id = "messages.bulk",
+    tag = "messages",
+    request_body = inline(BodyData),
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError),
+    security(("session" = []))
+)]
+#[post("/bulk")]
+async fn apply(
+    data: Json<BodyData>,
+    account: UserGuard<Uuid>,
+    pool: web::Data<Pool<Postgres>>,
+    quota: web::Data<QuotaConfig>,
+) -> Result<Json<Response>, RouteError> {
+    let account = account.into();
+    let data = data.into_inner();
+    let mut conn = pool.acquire().await?;
+
+    let ids = match (data.messages, &data.query) {
+        (Some(mut ids), None) => {
+            let mut seen = HashSet::new();
+            ids.retain(|id| seen.insert(*id));
+            if ids.len() > bulk::MAX_MESSAGES {
+                return Err(RouteError::TooManyMessages);
+            }
+            ids
+        }
+        (None, Some(query)) => bulk::search(&mut conn, account, &Query::parse(query)?).await?,
+        _ => return Err(RouteError::InvalidSelection),
+    };
+    data.action.validate(&mut conn, account).await?;
+    drop(conn);
+
+    if data.background {
+        let job = data
+            .action
+            .start(pool.get_ref().clone(), account, ids, *quota.get_ref())
+            .await?;
+
+        return Ok(Json(Response {
+            results: Vec::new(),
+            job: Some(job),
+        }));
+    }
+
+    if ids.len() > bulk::MAX_DIRECT {
+        return Err(RouteError::TooManyMessages);
+    }
+    let results = data.action.run(&pool, account, &ids, None, &quota).await?;
+
+    Ok(Json(Response { results, job: None }))
+}
+
+/// Requested data for this route.
+/// Either the IDs of the messages or a search query is given.
+#[derive(Deserialize, ToSchema)]
+struct BodyData {
+    messages: Option<Vec<Uuid>>,
+    query: Option<String>,
+    action: Action,
+    /// Run in the background, required for more messages than are changed within a request.
+    #[serde(default)]
+    background: bool,
+}
+
+/// Success response of this route.
+/// Operations in the background have their results in the job instead.
+#[derive(Serialize, ToSchema)]
+struct Response {
+    results: Vec<Outcome>,
+    job: Option<BulkJob>,
+}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("Either the messages or a search query has to be given.")]
+    InvalidSelection,
+    #[error("{0}")]
+    InvalidQuery(#[from] ParseError),
+    #[error("Too many messages are changed at once.")]
+    TooManyMessages,
+    #[error("The mailbox does not exist.")]
+    MailboxNotFound,
+    #[error("A label does not exist.")]
+    LabelNotFound,
+    #[error("A keyword is empty, too long or contains invalid characters.")]
+    InvalidKeyword,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::InvalidSelection => "invalidselection",
+            RouteError::InvalidQuery(_) => "invalidquery",
+            RouteError::TooManyMessages => "toomanymessages",
+            RouteError::MailboxNotFound => "mailboxnotfound",
+            RouteError::LabelNotFound => "labelnotfound",
+            RouteError::InvalidKeyword => "invalidkeyword",
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[
+            RouteError::InvalidSelection,
+            RouteError::InvalidQuery(ParseError::Empty),
+            RouteError::TooManyMessages,
+            RouteError::MailboxNotFound,
+            RouteError::LabelNotFound,
+            RouteError::InvalidKeyword,
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::InvalidSelection => StatusCode::BAD_REQUEST,
+            RouteError::InvalidQuery(_) => StatusCode::BAD_REQUEST,
+            RouteError::TooManyMessages => StatusCode::BAD_REQUEST,
+            RouteError::MailboxNotFound => StatusCode::UNPROCESSABLE_ENTITY,
+            RouteError::LabelNotFound => StatusCode::UNPROCESSABLE_ENTITY,
+            RouteError::InvalidKeyword => StatusCode::BAD_REQUEST,
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<BulkError> for RouteError {
+    fn from(err: BulkError) -> Self {
+        match err {
+            BulkError::TooManyMessages => RouteError::TooManyMessages,
+            BulkError::MailboxNotFound => RouteError::MailboxNotFound,
+            BulkError::LabelNotFound => RouteError::LabelNotFound,
+            BulkError::InvalidKeyword => RouteError::InvalidKeyword,
+            BulkError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use actix_web::{
+    get,
+    http::{
+        header::{Charset, ContentDisposition, DispositionParam, DispositionType, ExtendedValue},
+        StatusCode,
+    },
+    web::{self, Path},
+    HttpResponse, ResponseError,
+};
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::IntoResponses;
+use uuid::Uuid;
+
+use crate::http::{guarded_error_responses, ApiError, Responses, UserGuard};
+use crate::logic::{
+    attachment::{Attachment, Disposition, LoadError},
+    blob::BlobError,
+    mailbox_key::MailboxKey,
+    message::{self, Message, ReadError},
+};
+use crate::storage::BlobStore;
+
+/// Download the decoded content of a MIME part.
+/// Only images are shown inline, everything else is served as a download from a sandbox.
+#[utoipa::path(
+    operation_id = "messages.download",
+    tag = "messages",
+    responses(
+        (status = OK, description = "The decoded content, with the content type of the part.", body = Vec<u8>, content_type = "application/octet-stream"),
+        RouteError
+    ),
+    security(("session" = []))
+)]
+#[get("/{id}/attachments/{part}")]
+async fn download(
+    path: Path<(Uuid, i32)>,
+    account: UserGuard<Uuid>,
+    pool: web::Data<Pool<Postgres>>,
+    store: web::Data<dyn BlobStore>,
+    mailbox_key: Option<MailboxKey>,
+) -> Result<HttpResponse, RouteError> {
+    let (id, part) = path.into_inner();
+
+    let mut conn = pool.acquire().await?;
+    let message = Message::find(&mut conn, account.into(), id).await?;
+    let (attachment, content) = Attachment::load(
+        &mut conn,
+        store.as_ref(),
+        mailbox_key.as_ref(),
+        &message,
+        part,
+    )
+    .await?;
+
+    let inline = attachment.disposition == Disposition::Inline
+        && attachment.content_type.starts_with("image/")
+        && attachment.content_type != "image/svg+xml";
+
+    let mut parameters = Vec::new();
+    if let Some(filename) = attachment.filename {
+        parameters.push(DispositionParam::FilenameExt(ExtendedValue {
+            charset: Charset::Ext("UTF-8".into()),
+            language_tag: None,
+            value: filename.into_bytes(),
+        }));
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(attachment.content_type)
+        .insert_header(ContentDisposition {
+            disposition: match inline {
+                true => DispositionType::Inline,
+                false => DispositionType::Attachment,
+            },
+            parameters,
+        })
+        .insert_header(("X-Content-Type-Options", "nosniff"))
+        .insert_header(("Content-Security-Policy", "sandbox"))
+        .body(content))
+}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("The message does not exist.")]
+    NotFound,
+    #[error("The attachment does not exist.")]
+    AttachmentNotFound,
+    #[error("The message is encrypted and the mailbox key is not unlocked.")]
+    Locked,
+    #[error("The message could not be read from storage.")]
+    StorageError(BlobError),
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::NotFound => "notfound",
+            RouteError::AttachmentNotFound => "attachmentnotfound",
+            RouteError::Locked => "locked",
+            RouteError::StorageError(_) => "storageerror",
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[
+            RouteError::NotFound,
+            RouteError::AttachmentNotFound,
+            RouteError::Locked,
+            RouteError::StorageError(BlobError::DecryptError),
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::NotFound => StatusCode::NOT_FOUND,
+            RouteError::AttachmentNotFound => StatusCode::NOT_FOUND,
+            RouteError::Locked => StatusCode::FORBIDDEN,
+            RouteError::StorageError(_) => StatusCode::SERVICE_UNAVAILABLE,
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<message::FindError> for RouteError {
+    fn from(err: message::FindError) -> Self {
+        match err {
+            message::FindError::NotFound => RouteError::NotFound,
+            message::FindError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<LoadError> for RouteError {
+    fn from(err: LoadError) -> Self {
+        match err {
+            LoadError::NotFound => RouteError::AttachmentNotFound,
+            LoadError::ReadError(e) => e.into(),
+            LoadError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<ReadError> for RouteError {
+    fn from(err: ReadError) -> Self {
+        match err {
+            ReadError::Locked => RouteError::Locked,
+            ReadError::BlobError(BlobError::DatabaseError(e)) => RouteError::DatabaseError(e),
+            ReadError::BlobError(e) => RouteError::StorageError(e),
+        }
+    }
+}
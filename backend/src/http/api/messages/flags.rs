@@ -0,0 +1,112 @@
+use actix_web::{
+    http::StatusCode,
+    post,
+    web::{self, Json},
+    HttpResponse, ResponseError,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::{IntoResponses, ToSchema};
+use uuid::Uuid;
+
+use crate::http::{guarded_error_responses, ApiError, Responses, UserGuard};
+use crate::logic::message::{FlagChanges, FlagError, Message};
+
+/// Change the flags and keywords of messages of the current user, in bulk.
+/// Flags which are left out stay as they are, messages which do not exist are skipped.
+#[utoipa::path(
+    operation_id = "messages.flags",
+    tag = "messages",
+    request_body = inline(BodyData),
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError),
+    security(("session" = []))
+)]
+#[post("/flags")]
+async fn flags(
+    data: Json<BodyData>,
+    account: UserGuard<Uuid>,
+    pool: web::Data<Pool<Postgres>>,
+) -> Result<Json<Response>, RouteError> {
+    let data = data.into_inner();
+    let mut conn = pool.acquire().await?;
+    let updated =
+        Message::set_flags(&mut conn, account.into(), &data.messages, data.changes).await?;
+
+    Ok(Json(Response { updated }))
+}
+
+/// Requested data for this route.
+#[derive(Deserialize, ToSchema)]
+struct BodyData {
+    messages: Vec<Uuid>,
+    #[serde(flatten)]
+    changes: FlagChanges,
+}
+
+/// Success response of this route.
+#[derive(Serialize, ToSchema)]
+struct Response {
+    /// The messages which changed.
+    updated: Vec<Uuid>,
+}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("Too many messages are changed at once.")]
+    TooManyMessages,
+    #[error("A keyword is empty, too long or contains invalid characters.")]
+    InvalidKeyword,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::TooManyMessages => "toomanymessages",
+            RouteError::InvalidKeyword => "invalidkeyword",
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[
+            RouteError::TooManyMessages,
+            RouteError::InvalidKeyword,
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::TooManyMessages => StatusCode::BAD_REQUEST,
+            RouteError::InvalidKeyword => StatusCode::BAD_REQUEST,
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<FlagError> for RouteError {
+    fn from(err: FlagError) -> Self {
+        match err {
+            FlagError::TooManyMessages => RouteError::TooManyMessages,
+            FlagError::InvalidKeyword => RouteError::InvalidKeyword,
+            FlagError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
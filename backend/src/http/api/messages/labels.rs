@@ -0,0 +1,120 @@
+use actix_web::{
+    http::StatusCode,
+    post,
+    web::{self, Json},
+    HttpResponse, ResponseError,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::{IntoResponses, ToSchema};
+use uuid::Uuid;
+
+use crate::http::{guarded_error_responses, ApiError, Responses, UserGuard};
+use crate::logic::label::{ApplyError, Label};
+
+/// Add and remove labels of messages of the current user, in bulk.
+/// Messages which do not exist are skipped.
+#[utoipa::path(
+    operation_id = "messages.labels",
+    tag = "messages",
+    request_body = inline(BodyData),
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError),
+    security(("session" = []))
+)]
+#[post("/labels")]
+async fn labels(
+    data: Json<BodyData>,
+    account: UserGuard<Uuid>,
+    pool: web::Data<Pool<Postgres>>,
+) -> Result<Json<Response>, RouteError> {
+    let mut conn = pool.begin().await?;
+    let (added, removed) = Label::apply(
+        &mut conn,
+        account.into(),
+        &data.messages,
+        &data.add,
+        &data.remove,
+    )
+    .await?;
+    conn.commit().await?;
+
+    Ok(Json(Response { added, removed }))
+}
+
+/// Requested data for this route.
+#[derive(Deserialize, ToSchema)]
+struct BodyData {
+    messages: Vec<Uuid>,
+    #[serde(default)]
+    add: Vec<Uuid>,
+    #[serde(default)]
+    remove: Vec<Uuid>,
+}
+
+/// Success response of this route.
+#[derive(Serialize, ToSchema)]
+struct Response {
+    added: u64,
+    removed: u64,
+}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("Too many messages are labeled at once.")]
+    TooManyMessages,
+    #[error("A label does not exist.")]
+    LabelNotFound,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::TooManyMessages => "toomanymessages",
+            RouteError::LabelNotFound => "labelnotfound",
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[
+            RouteError::TooManyMessages,
+            RouteError::LabelNotFound,
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::TooManyMessages => StatusCode::BAD_REQUEST,
+            RouteError::LabelNotFound => StatusCode::BAD_REQUEST,
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<ApplyError> for RouteError {
+    fn from(err: ApplyError) -> Self {
+        match err {
+            ApplyError::TooManyMessages => RouteError::TooManyMessages,
+            ApplyError::LabelNotFound => RouteError::LabelNotFound,
+            ApplyError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
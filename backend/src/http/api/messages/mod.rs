@@ -0,0 +1,65 @@
+use actix_web::{web, Scope};
+use utoipa::OpenApi;
+
+use crate::logic::reply::Mode;
+
+mod attachments;
+mod body;
+mod bulk;
+mod bulk_job;
+mod delete;
+mod download;
+mod flags;
+mod get;
+mod labels;
+mod move_to;
+mod raw;
+mod remote_content;
+mod reply;
+mod search;
+mod send;
+
+/// Returns the routes of this scope.
+pub fn routes() -> Scope {
+    web::scope("/messages")
+        .service(search::search)
+        .service(send::send)
+        .service(bulk::apply)
+        .service(bulk_job::bulk_job)
+        .service(labels::labels)
+        .service(flags::flags)
+        .service(get::get)
+        .service(raw::raw)
+        .service(body::body)
+        .service(remote_content::remote_content)
+        .service(reply::draft)
+        .service(attachments::attachments)
+        .service(download::download)
+        .service(move_to::move_to)
+        .service(delete::delete)
+        .default_service(web::route().to(super::not_found))
+}
+
+/// The documentation of the routes of this scope.
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        search::search,
+        send::send,
+        bulk::apply,
+        bulk_job::bulk_job,
+        labels::labels,
+        flags::flags,
+        get::get,
+        raw::raw,
+        body::body,
+        remote_content::remote_content,
+        reply::draft,
+        attachments::attachments,
+        download::download,
+        move_to::move_to,
+        delete::delete
+    ),
+    components(schemas(Mode))
+)]
+pub struct Api;
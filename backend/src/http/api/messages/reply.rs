@@ -0,0 +1,168 @@
+use actix_web::{
+    get,
+    http::StatusCode,
+    web::{self, Json, Path, Query},
+    HttpResponse, ResponseError,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::{IntoParams, IntoResponses, ToSchema};
+use uuid::Uuid;
+
+use crate::http::{guarded_error_responses, ApiError, Responses, UserGuard};
+use crate::logic::{
+    account::{self, Account},
+    blob::BlobError,
+    compose::Compose,
+    mailbox_key::MailboxKey,
+    message::{self, Message, ReadError},
+    reply::{self, Mode, ReplyError},
+};
+use crate::storage::BlobStore;
+
+/// Prefill a draft replying to or forwarding a message, to be edited and sent by the client.
+/// It quotes the original and carries the threading headers, forwarded attachments are referenced.
+#[utoipa::path(
+    operation_id = "messages.reply",
+    tag = "messages",
+    params(Params),
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError),
+    security(("session" = []))
+)]
+#[get("/{id}/reply")]
+async fn draft(
+    id: Path<Uuid>,
+    params: Query<Params>,
+    account: UserGuard<Uuid>,
+    pool: web::Data<Pool<Postgres>>,
+    store: web::Data<dyn BlobStore>,
+    mailbox_key: Option<MailboxKey>,
+) -> Result<Json<Response>, RouteError> {
+    let mut conn = pool.acquire().await?;
+    let account = Account::find(&mut conn, &account.into()).await?;
+    let message = Message::find(&mut conn, account.id, *id).await?;
+    let draft = reply::draft(
+        &mut conn,
+        store.as_ref(),
+        mailbox_key.as_ref(),
+        &account,
+        &message,
+        params.mode,
+    )
+    .await?;
+
+    Ok(Json(Response { draft }))
+}
+
+/// Requested parameters for this route.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct Params {
+    mode: Mode,
+}
+
+/// Success response of this route.
+#[derive(Serialize, ToSchema)]
+struct Response {
+    draft: Compose,
+}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("The message does not exist.")]
+    NotFound,
+    #[error("The message could not be parsed.")]
+    Malformed,
+    #[error("The message is encrypted and the mailbox key is not unlocked.")]
+    Locked,
+    #[error("The message could not be read from storage.")]
+    StorageError(BlobError),
+    #[error("An internal server error occured.")]
+    InternalError,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::NotFound => "notfound",
+            RouteError::Malformed => "malformed",
+            RouteError::Locked => "locked",
+            RouteError::StorageError(_) => "storageerror",
+            RouteError::InternalError => "internalerror",
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[
+            RouteError::NotFound,
+            RouteError::Malformed,
+            RouteError::Locked,
+            RouteError::StorageError(BlobError::DecryptError),
+            RouteError::InternalError,
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::NotFound => StatusCode::NOT_FOUND,
+            RouteError::Malformed => StatusCode::UNPROCESSABLE_ENTITY,
+            RouteError::Locked => StatusCode::FORBIDDEN,
+            RouteError::StorageError(_) => StatusCode::SERVICE_UNAVAILABLE,
+            RouteError::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<account::FindError> for RouteError {
+    fn from(err: account::FindError) -> Self {
+        match err {
+            account::FindError::NotFound => RouteError::InternalError,
+            account::FindError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<message::FindError> for RouteError {
+    fn from(err: message::FindError) -> Self {
+        match err {
+            message::FindError::NotFound => RouteError::NotFound,
+            message::FindError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<ReplyError> for RouteError {
+    fn from(err: ReplyError) -> Self {
+        match err {
+            ReplyError::Malformed => RouteError::Malformed,
+            ReplyError::ReadError(ReadError::Locked) => RouteError::Locked,
+            ReplyError::ReadError(ReadError::BlobError(BlobError::DatabaseError(e))) => {
+                RouteError::DatabaseError(e)
+            }
+            ReplyError::ReadError(ReadError::BlobError(e)) => RouteError::StorageError(e),
+            ReplyError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
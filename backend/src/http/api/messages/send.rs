@@ -0,0 +1,196 @@
+use actix_web::{
+    http::StatusCode,
+    post,
+    web::{self, Json},
+    HttpResponse, ResponseError,
+};
+use serde::Serialize;
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::{IntoResponses, ToSchema};
+use uuid::Uuid;
+
+use crate::http::{guarded_error_responses, ApiError, Responses, UserGuard};
+use crate::logic::{
+    account::{self, Account},
+    blob::BlobError,
+    compose::{Compose, SendError, Sent},
+    mailbox_key::MailboxKey,
+    quota::QuotaConfig,
+};
+use crate::storage::BlobStore;
+
+/// Send a message from the current user, a copy is saved in their Sent mailbox.
+/// Local recipients receive it right away, it is queued for all others.
+/// Forwarding parts of encrypted messages requires the mailbox key to be unlocked.
+#[utoipa::path(
+    operation_id = "messages.send",
+    tag = "messages",
+    request_body = Compose,
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError),
+    security(("session" = []))
+)]
+#[post("/send")]
+async fn send(
+    data: Json<Compose>,
+    account: UserGuard<Uuid>,
+    pool: web::Data<Pool<Postgres>>,
+    store: web::Data<dyn BlobStore>,
+    quota: web::Data<QuotaConfig>,
+    mailbox_key: Option<MailboxKey>,
+) -> Result<Json<Response>, RouteError> {
+    let mut conn = pool.begin().await?;
+
+    let account = Account::find(&mut conn, &account.into()).await?;
+    let sent = data
+        .send(
+            &mut conn,
+            store.as_ref(),
+            mailbox_key.as_ref(),
+            &account,
+            &quota,
+        )
+        .await?;
+
+    conn.commit().await?;
+
+    Ok(Json(Response { sent }))
+}
+
+/// Success response of this route.
+#[derive(Serialize, ToSchema)]
+struct Response {
+    #[serde(flatten)]
+    sent: Sent,
+}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("The message has no recipients.")]
+    NoRecipients,
+    #[error("The message has too many recipients.")]
+    TooManyRecipients,
+    #[error("An address or display name is invalid.")]
+    InvalidAddress,
+    #[error("The subject is too long or contains invalid characters.")]
+    InvalidSubject,
+    #[error("A Message-ID of In-Reply-To or References is invalid, or there are too many.")]
+    InvalidReference,
+    #[error("A local recipient does not exist.")]
+    UnknownRecipient,
+    #[error("An attachment was not found, or has expired.")]
+    AttachmentNotFound,
+    #[error("The attachments are too large.")]
+    TooLarge,
+    #[error("A forwarded message is encrypted and the mailbox key is not unlocked.")]
+    Locked,
+    #[error("The mailbox is full.")]
+    QuotaExceeded,
+    #[error("The message could not be stored.")]
+    StorageError,
+    #[error("An internal server error occured.")]
+    InternalError,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::NoRecipients => "norecipients",
+            RouteError::TooManyRecipients => "toomanyrecipients",
+            RouteError::InvalidAddress => "invalidaddress",
+            RouteError::InvalidSubject => "invalidsubject",
+            RouteError::InvalidReference => "invalidreference",
+            RouteError::UnknownRecipient => "unknownrecipient",
+            RouteError::AttachmentNotFound => "attachmentnotfound",
+            RouteError::TooLarge => "toolarge",
+            RouteError::Locked => "locked",
+            RouteError::QuotaExceeded => "quotaexceeded",
+            RouteError::StorageError => "storageerror",
+            RouteError::InternalError => "internalerror",
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[
+            RouteError::NoRecipients,
+            RouteError::TooManyRecipients,
+            RouteError::InvalidAddress,
+            RouteError::InvalidSubject,
+            RouteError::InvalidReference,
+            RouteError::UnknownRecipient,
+            RouteError::AttachmentNotFound,
+            RouteError::TooLarge,
+            RouteError::Locked,
+            RouteError::QuotaExceeded,
+            RouteError::StorageError,
+            RouteError::InternalError,
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::NoRecipients => StatusCode::BAD_REQUEST,
+            RouteError::TooManyRecipients => StatusCode::BAD_REQUEST,
+            RouteError::InvalidAddress => StatusCode::BAD_REQUEST,
+            RouteError::InvalidSubject => StatusCode::BAD_REQUEST,
+            RouteError::InvalidReference => StatusCode::BAD_REQUEST,
+            RouteError::UnknownRecipient => StatusCode::UNPROCESSABLE_ENTITY,
+            RouteError::AttachmentNotFound => StatusCode::UNPROCESSABLE_ENTITY,
+            RouteError::TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            RouteError::Locked => StatusCode::FORBIDDEN,
+            RouteError::QuotaExceeded => StatusCode::INSUFFICIENT_STORAGE,
+            RouteError::StorageError => StatusCode::SERVICE_UNAVAILABLE,
+            RouteError::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<account::FindError> for RouteError {
+    fn from(err: account::FindError) -> Self {
+        match err {
+            account::FindError::NotFound => RouteError::InternalError,
+            account::FindError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<SendError> for RouteError {
+    fn from(err: SendError) -> Self {
+        match err {
+            SendError::NoRecipients => RouteError::NoRecipients,
+            SendError::TooManyRecipients => RouteError::TooManyRecipients,
+            SendError::InvalidAddress => RouteError::InvalidAddress,
+            SendError::InvalidSubject => RouteError::InvalidSubject,
+            SendError::InvalidReference => RouteError::InvalidReference,
+            SendError::UnknownRecipient => RouteError::UnknownRecipient,
+            SendError::AttachmentNotFound => RouteError::AttachmentNotFound,
+            SendError::TooLarge => RouteError::TooLarge,
+            SendError::Locked => RouteError::Locked,
+            SendError::QuotaExceeded => RouteError::QuotaExceeded,
+            SendError::BlobError(BlobError::DatabaseError(e)) => RouteError::DatabaseError(e),
+            SendError::BlobError(_) => RouteError::StorageError,
+            SendError::DeliverError(_) => RouteError::InternalError,
+            SendError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
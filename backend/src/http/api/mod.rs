@@ -1,14 +1,55 @@
 use actix_web::{web, Scope};
+use utoipa::OpenApi;
 
 use super::not_found;
 
 mod account;
+mod domain;
+mod drafts;
+mod events;
 mod health;
+mod labels;
+mod lists;
+mod mailboxes;
+mod messages;
+mod openapi;
+mod threads;
+mod uploads;
 
 /// Returns the routes of this scope.
 pub fn routes() -> Scope {
     web::scope("/api")
         .service(account::routes())
+        .service(domain::routes())
+        .service(drafts::routes())
+        .service(events::routes())
         .service(health::routes())
+        .service(labels::routes())
+        .service(lists::routes())
+        .service(mailboxes::routes())
+        .service(messages::routes())
+        .service(openapi::openapi)
+        .service(threads::routes())
+        .service(uploads::routes())
         .default_service(web::route().to(not_found))
 }
+
+/// The documentation of the routes of this scope, with the scopes nested in it.
+#[derive(OpenApi)]
+#[openapi(
+    paths(openapi::openapi),
+    nest(
+        (path = "/account", api = account::Api),
+        (path = "/domain", api = domain::Api),
+        (path = "/drafts", api = drafts::Api),
+        (path = "/events", api = events::Api),
+        (path = "/health", api = health::Api),
+        (path = "/labels", api = labels::Api),
+        (path = "/lists", api = lists::Api),
+        (path = "/mailboxes", api = mailboxes::Api),
+        (path = "/messages", api = messages::Api),
+        (path = "/threads", api = threads::Api),
+        (path = "/uploads", api = uploads::Api),
+    )
+)]
+pub struct Api;
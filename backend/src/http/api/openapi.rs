@@ -0,0 +1,195 @@
+use actix_web::{get, HttpResponse};
+use lazy_static::lazy_static;
+use utoipa::{
+    openapi::{
+        security::{ApiKey, ApiKeyValue, SecurityScheme},
+        InfoBuilder, OpenApi as Document, OpenApiBuilder,
+    },
+    OpenApi,
+};
+
+use super::Api;
+
+lazy_static! {
+    /// The OpenAPI document of the API, only generated once.
+    static ref DOCUMENT: String = document()
+        .to_pretty_json()
+        .expect("Serialization failed, which should not be impossible.");
+}
+
+/// Generate the OpenAPI document of the API, from the documentation of the routes of every scope.
+pub fn document() -> Document {
+    let mut document = OpenApiBuilder::new()
+        .info(
+            InfoBuilder::new()
+                .title("Nexium")
+                .version(env!("CARGO_PKG_VERSION")),
+        )
+        .build()
+        .nest("/api", Api::openapi());
+
+    document
+        .components
+        .get_or_insert_with(Default::default)
+        .add_security_scheme(
+            "session",
+            SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::with_description(
+                "nexium",
+                "The session cookie, set by logging in.",
+            ))),
+        );
+
+    document
+}
+
+/// Get the OpenAPI document describing every route of the API.
+#[utoipa::path(
+    operation_id = "openapi",
+    tag = "openapi",
+    responses((status = OK, description = "The OpenAPI document.", content_type = "application/json"))
+)]
+#[get("/openapi.json")]
+async fn openapi() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("application/json")
+        .body(DOCUMENT.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use actix_web::{
+        rt::System,
+        test::{call_service, init_service, read_body, TestRequest},
+        web::Data,
+        App,
+    };
+    use serde_json::Value;
+    use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
+    use uuid::Uuid;
+
+    use super::*;
+
+    const METHODS: [&str; 5] = ["GET", "POST", "PUT", "DELETE", "PATCH"];
+
+    /// List the routes of the API at the paths of the document, as methods and patterns served by a handler.
+    /// A route is registered when its path resolves to the same pattern,
+    /// and a request to it does not fall back to the default service of its scope.
+    async fn registered(document: &Value) -> BTreeSet<(String, String)> {
+        // The guards need a pool, but never connect before checking the session.
+        let pool: Pool<Postgres> = PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/nexium")
+            .unwrap();
+        let app = init_service(
+            App::new()
+                .app_data(Data::new(pool))
+                .service(super::super::routes()),
+        )
+        .await;
+
+        let mut routes = BTreeSet::new();
+
+        for pattern in document["paths"].as_object().unwrap().keys() {
+            // Fill the variables, so they can be told apart from the constant segments of other patterns.
+            let mut variables = Vec::new();
+            let uri = pattern
+                .split('/')
+                .map(|segment| match segment.strip_prefix('{') {
+                    Some(name) => {
+                        let value = Uuid::new_v4().to_string();
+                        variables.push((name.trim_end_matches('}').to_string(), value.clone()));
+                        value
+                    }
+                    None => segment.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("/");
+
+            for method in METHODS {
+                let req = TestRequest::default()
+                    .method(method.parse().unwrap())
+                    .uri(&uri)
+                    .to_request();
+                let res = call_service(&app, req).await;
+                let resolved = res.request().resource_map().match_pattern(&uri);
+                let matched = res
+                    .request()
+                    .match_info()
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), value.to_string()))
+                    .collect::<Vec<_>>();
+
+                if resolved.as_ref() == Some(pattern)
+                    && matched == variables
+                    && read_body(res).await != "404 Not Found"
+                {
+                    routes.insert((method.to_string(), pattern.clone()));
+                }
+            }
+        }
+
+        routes
+    }
+
+    /// List the routes of the OpenAPI document, as methods and paths.
+    fn documented(document: &Value) -> BTreeSet<(String, String)> {
+        let mut routes = BTreeSet::new();
+
+        for (path, item) in document["paths"].as_object().unwrap() {
+            for method in METHODS {
+                if item.get(method.to_lowercase()).is_some() {
+                    routes.insert((method.to_string(), path.clone()));
+                }
+            }
+        }
+
+        routes
+    }
+
+    #[test]
+    fn document_matches_routes() {
+        let document: Value = serde_json::from_str(&DOCUMENT).unwrap();
+        let registered = System::new().block_on(registered(&document));
+        let documented = documented(&document);
+
+        assert!(registered.contains(&("GET".to_string(), "/api/account/whoami".to_string())));
+        assert_eq!(
+            registered.difference(&documented).collect::<Vec<_>>(),
+            Vec::<&(String, String)>::new(),
+            "Methods served at the paths of the document are missing from it."
+        );
+        assert_eq!(
+            documented.difference(&registered).collect::<Vec<_>>(),
+            Vec::<&(String, String)>::new(),
+            "The document has routes which are not registered."
+        );
+    }
+
+    #[test]
+    fn document_is_consistent() {
+        let document: Value = serde_json::from_str(&DOCUMENT).unwrap();
+        let schemas = document["components"]["schemas"].as_object().unwrap();
+        let mut operations = BTreeSet::new();
+
+        for item in document["paths"].as_object().unwrap().values() {
+            for operation in item.as_object().unwrap().values() {
+                let id = operation["operationId"].as_str().unwrap();
+                assert!(operations.insert(id), "Operation {} is duplicated.", id);
+
+                // Routes behind the guards must document their errors.
+                if operation.get("security").is_some() {
+                    assert!(operation["responses"].get("401").is_some(), "{}", id);
+                }
+            }
+        }
+
+        // Every reference must point to a schema of the document.
+        let dump = document.to_string();
+        for reference in dump.split("\"$ref\":\"").skip(1) {
+            let name = reference.split('"').next().unwrap();
+            let name = name.trim_start_matches("#/components/schemas/");
+            assert!(schemas.contains_key(name), "Schema {} is missing.", name);
+        }
+    }
+}
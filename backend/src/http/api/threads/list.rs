@@ -0,0 +1,145 @@
+use actix_web::{
+    get,
+    http::StatusCode,
+    web::{self, Json},
+    HttpResponse, ResponseError,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::{IntoParams, IntoResponses, ToSchema};
+use uuid::Uuid;
+
+use crate::http::{guarded_error_responses, ApiError, Responses, UserGuard};
+use crate::logic::{
+    label::{self, Label},
+    mailbox::{self, Mailbox},
+    thread::Thread,
+};
+
+/// The maximum amount of threads in a single response.
+const MAX_LIMIT: i64 = 100;
+
+/// List the conversations of the current user, the one with the newest message first.
+/// With a mailbox or label, only conversations with a message in it or carrying it are listed.
+#[utoipa::path(
+    operation_id = "threads.list",
+    tag = "threads",
+    params(Params),
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError),
+    security(("session" = []))
+)]
+#[get("")]
+async fn list(
+    params: web::Query<Params>,
+    account: UserGuard<Uuid>,
+    pool: web::Data<Pool<Postgres>>,
+) -> Result<Json<Response>, RouteError> {
+    let account = account.into();
+    let limit = params.limit.unwrap_or(50).clamp(1, MAX_LIMIT);
+    let offset = params.offset.unwrap_or(0).max(0);
+
+    let mut conn = pool.acquire().await?;
+    if let Some(mailbox) = params.mailbox {
+        Mailbox::find(&mut conn, account, mailbox).await?;
+    }
+    if let Some(label) = params.label {
+        Label::find(&mut conn, account, label).await?;
+    }
+    let threads = Thread::list(
+        &mut conn,
+        account,
+        params.mailbox,
+        params.label,
+        limit,
+        offset,
+    )
+    .await?;
+
+    Ok(Json(Response { threads }))
+}
+
+/// Query parameters of this route.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct Params {
+    mailbox: Option<Uuid>,
+    label: Option<Uuid>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+/// Success response of this route.
+#[derive(Serialize, ToSchema)]
+struct Response {
+    threads: Vec<Thread>,
+}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("The mailbox does not exist.")]
+    MailboxNotFound,
+    #[error("The label does not exist.")]
+    LabelNotFound,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::MailboxNotFound => "mailboxnotfound",
+            RouteError::LabelNotFound => "labelnotfound",
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[
+            RouteError::MailboxNotFound,
+            RouteError::LabelNotFound,
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::MailboxNotFound => StatusCode::NOT_FOUND,
+            RouteError::LabelNotFound => StatusCode::NOT_FOUND,
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<mailbox::FindError> for RouteError {
+    fn from(err: mailbox::FindError) -> Self {
+        match err {
+            mailbox::FindError::NotFound => RouteError::MailboxNotFound,
+            mailbox::FindError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<label::FindError> for RouteError {
+    fn from(err: label::FindError) -> Self {
+        match err {
+            label::FindError::NotFound => RouteError::LabelNotFound,
+            label::FindError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
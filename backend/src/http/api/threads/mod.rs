@@ -0,0 +1,16 @@
+use actix_web::{web, Scope};
+use utoipa::OpenApi;
+
+mod list;
+
+/// Returns the routes of this scope.
+pub fn routes() -> Scope {
+    web::scope("/threads")
+        .service(list::list)
+        .default_service(web::route().to(super::not_found))
+}
+
+/// The documentation of the routes of this scope.
+#[derive(OpenApi)]
+#[openapi(paths(list::list))]
+pub struct Api;
@@ -0,0 +1,22 @@
+use actix_web::{web, Scope};
+use utoipa::OpenApi;
+
+use crate::logic::upload;
+
+mod delete;
+mod new;
+
+/// Returns the routes of this scope.
+/// Uploads are accepted up to their maximum size, the default limit of bodies is much smaller.
+pub fn routes() -> Scope {
+    web::scope("/uploads")
+        .app_data(web::PayloadConfig::new(upload::MAX_SIZE as usize))
+        .service(new::new_upload)
+        .service(delete::delete)
+        .default_service(web::route().to(super::not_found))
+}
+
+/// The documentation of the routes of this scope.
+#[derive(OpenApi)]
+#[openapi(paths(new::new_upload, delete::delete))]
+pub struct Api;
@@ -0,0 +1,144 @@
+use actix_web::{
+    http::{header, StatusCode},
+    post,
+    web::{self, Bytes, Json, Query},
+    HttpRequest, HttpResponse, ResponseError,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use utoipa::{IntoParams, IntoResponses, ToSchema};
+use uuid::Uuid;
+
+use crate::http::{guarded_error_responses, ApiError, Responses, UserGuard};
+use crate::logic::{
+    blob::BlobError,
+    upload::{CreateError, Upload},
+};
+use crate::storage::{BlobStore, Spool};
+
+/// Upload a file to attach to messages, the body is the content of the file.
+/// The content type is taken from the request, or guessed from the filename.
+#[utoipa::path(
+    operation_id = "uploads.new",
+    tag = "uploads",
+    params(Params),
+    request_body(content = Vec<u8>, description = "The content of the file."),
+    responses((status = OK, description = "Success.", body = inline(Response)), RouteError),
+    security(("session" = []))
+)]
+#[post("/new")]
+async fn new_upload(
+    request: HttpRequest,
+    params: Query<Params>,
+    body: Bytes,
+    account: UserGuard<Uuid>,
+    pool: web::Data<Pool<Postgres>>,
+    store: web::Data<dyn BlobStore>,
+) -> Result<Json<Response>, RouteError> {
+    let content_type = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok());
+
+    let spooled = async {
+        let mut spool = Spool::create().await?;
+        spool.write(&body).await?;
+        spool.finish().await
+    }
+    .await
+    .map_err(|_| RouteError::StorageError)?;
+
+    let mut conn = pool.acquire().await?;
+    let upload = Upload::create(
+        &mut conn,
+        store.as_ref(),
+        account.into(),
+        &params.filename,
+        content_type,
+        &spooled,
+    )
+    .await?;
+
+    Ok(Json(Response { upload }))
+}
+
+/// Requested parameters for this route.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct Params {
+    filename: String,
+}
+
+/// Success response of this route.
+#[derive(Serialize, ToSchema)]
+struct Response {
+    upload: Upload,
+}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("The filename is empty, too long or contains invalid characters.")]
+    InvalidFilename,
+    #[error("The file is too large.")]
+    TooLarge,
+    #[error("The file could not be stored.")]
+    StorageError,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::InvalidFilename => "invalidfilename",
+            RouteError::TooLarge => "toolarge",
+            RouteError::StorageError => "storageerror",
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl IntoResponses for RouteError {
+    /// Document the errors of this route.
+    fn responses() -> Responses {
+        guarded_error_responses(&[
+            RouteError::InvalidFilename,
+            RouteError::TooLarge,
+            RouteError::StorageError,
+            RouteError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ])
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::InvalidFilename => StatusCode::BAD_REQUEST,
+            RouteError::TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            RouteError::StorageError => StatusCode::SERVICE_UNAVAILABLE,
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<CreateError> for RouteError {
+    fn from(err: CreateError) -> Self {
+        match err {
+            CreateError::InvalidFilename => RouteError::InvalidFilename,
+            CreateError::TooLarge => RouteError::TooLarge,
+            CreateError::BlobError(BlobError::DatabaseError(e)) => RouteError::DatabaseError(e),
+            CreateError::BlobError(_) => RouteError::StorageError,
+            CreateError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
@@ -1,28 +1,37 @@
 use std::{future::Future, pin::Pin};
 
-use actix_session::UserSession;
+use actix_session::{Session, UserSession};
 use actix_web::{
     dev::Payload, http::StatusCode, web, FromRequest, HttpRequest, HttpResponse, ResponseError,
 };
-use sqlx::{Pool, Postgres};
+use sqlx::{PgConnection, Pool, Postgres};
 use thiserror::Error;
+use utoipa::IntoResponses;
 use uuid::Uuid;
 
-use crate::logic::account::{self, Account};
+use crate::logic::{
+    account::{self, Account},
+    auth::session::Generation,
+    mailbox_key::MailboxKey,
+};
 
-use super::ApiError;
+use super::{error_responses, ApiError, Responses, KEY_COOKIE};
 
 pub struct UserGuard<T>(T);
 
 /// Userguard with the UUID generic.
 /// This does not return the entire user object, but only the UUID.
-/// This saves an call to the database, as long as the user object is not required in the route.
+/// This saves loading the account, as long as the user object is not required in the route.
 impl FromRequest for UserGuard<Uuid> {
     type Error = GuardError;
     type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
 
     fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
         let session = req.get_session();
+        let pool = req
+            .app_data::<web::Data<Pool<Postgres>>>()
+            .expect("Database was not available in the guards!")
+            .clone();
 
         Box::pin(async move {
             // Get the user ID from the session.
@@ -37,6 +46,9 @@ impl FromRequest for UserGuard<Uuid> {
                 None => return Err(GuardError::NotAuthenticated),
             };
 
+            let mut conn = pool.acquire().await?;
+            check_generation(&mut conn, &session, user).await?;
+
             Ok(UserGuard(user))
         })
     }
@@ -77,6 +89,7 @@ impl FromRequest for UserGuard<Account> {
 
             // Fetch the current user object from the database.
             let mut conn = pool.acquire().await?;
+            check_generation(&mut conn, &session, user).await?;
             let account = Account::find(&mut conn, &user).await?;
 
             Ok(UserGuard(account))
@@ -90,22 +103,126 @@ impl From<UserGuard<Account>> for Account {
     }
 }
 
+/// Check that a session was not revoked, it has to be of the current generation of the account.
+/// Revoked sessions are cleared, so the client has to log in again.
+async fn check_generation(
+    conn: &mut PgConnection,
+    session: &Session,
+    account: Uuid,
+) -> Result<(), GuardError> {
+    // Sessions from before generations were introduced are of the first one.
+    let generation = match session.get::<i32>("generation") {
+        Ok(g) => g.unwrap_or(0),
+        Err(_) => return Err(GuardError::InternalError),
+    };
+
+    if generation != Generation::current(conn, account).await? {
+        session.clear();
+        return Err(GuardError::NotAuthenticated);
+    }
+
+    Ok(())
+}
+
+pub struct AdminGuard(Account);
+
+/// Guard which only allows administrators.
+/// This returns the entire user object of the administrator.
+impl FromRequest for AdminGuard {
+    type Error = GuardError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let user = UserGuard::<Account>::from_request(req, payload);
+
+        Box::pin(async move {
+            let account: Account = user.await?.into();
+
+            // Validate that the account is an administrator.
+            if !account.admin {
+                return Err(GuardError::NotAuthorized);
+            }
+
+            Ok(AdminGuard(account))
+        })
+    }
+}
+
+impl From<AdminGuard> for Account {
+    fn from(val: AdminGuard) -> Self {
+        val.0
+    }
+}
+
+/// The unlocked mailbox key, kept wrapped in the session since login and unwrapped with the cookie of the client.
+/// Sessions from before encryption have no key, so routes take it as an option.
+impl FromRequest for MailboxKey {
+    type Error = GuardError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let session = req.get_session();
+        let wrapping = req.cookie(KEY_COOKIE).map(|c| c.value().to_owned());
+
+        Box::pin(async move {
+            // Older sessions kept the key without wrapping, it is dropped until the next login.
+            if let Ok(Some(_)) = session.get::<String>("mailbox_key") {
+                session.remove("mailbox_key");
+            }
+
+            let sealed = match session.get::<String>("sealed_mailbox_key") {
+                Ok(k) => k,
+                Err(_) => return Err(GuardError::InternalError),
+            };
+
+            let (sealed, wrapping) = match (sealed, wrapping) {
+                (Some(s), Some(w)) => (s, w),
+                _ => return Err(GuardError::NotAuthenticated),
+            };
+
+            MailboxKey::from_session(&sealed, &wrapping).map_err(|_| GuardError::NotAuthenticated)
+        })
+    }
+}
+
 /// All possible error responses for this route.
 #[derive(Error, Debug)]
 pub enum GuardError {
     #[error("You are not logged in.")]
     NotAuthenticated,
+    #[error("You are not allowed to do this.")]
+    NotAuthorized,
     #[error("Internal server error.")]
     InternalError,
     #[error("Internal server error.")]
     DatabaseError(#[from] sqlx::Error),
 }
 
+impl GuardError {
+    /// An example of every guard error, to document the routes requiring a session.
+    pub fn examples() -> [GuardError; 4] {
+        [
+            GuardError::NotAuthenticated,
+            GuardError::NotAuthorized,
+            GuardError::InternalError,
+            GuardError::DatabaseError(sqlx::Error::PoolTimedOut),
+        ]
+    }
+}
+
+impl IntoResponses for GuardError {
+    /// Document the errors of the guards, for routes without errors of their own.
+    fn responses() -> Responses {
+        error_responses(&GuardError::examples())
+    }
+}
+
 impl<'a> ApiError<'a> for GuardError {
     /// Convert the enum variant to a machine-readable name for the client.
     fn error_code(&self) -> &'a str {
         match self {
             GuardError::NotAuthenticated => "notauthenticated",
+            GuardError::NotAuthorized => "notauthorized",
             GuardError::InternalError => "internalerror",
             GuardError::DatabaseError(_) => "databaseerror",
         }
@@ -117,6 +234,7 @@ impl ResponseError for GuardError {
     fn status_code(&self) -> StatusCode {
         match self {
             GuardError::NotAuthenticated => StatusCode::UNAUTHORIZED,
+            GuardError::NotAuthorized => StatusCode::FORBIDDEN,
             GuardError::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
             GuardError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
@@ -1,5 +1,26 @@
-use actix_web::{body::AnyBody, http::header, HttpResponse, ResponseError};
+use std::collections::BTreeMap;
+
+use actix_session::Session;
+use actix_web::{
+    body::AnyBody, cookie::Cookie, cookie::SameSite, http::header, HttpResponse, ResponseError,
+};
 use serde::Serialize;
+use time::Duration;
+use utoipa::openapi::{ContentBuilder, ObjectBuilder, RefOr, Response, ResponseBuilder, Type};
+use uuid::Uuid;
+
+use crate::logic::mailbox_key::MailboxKey;
+
+use super::GuardError;
+
+/// The cookie with the key that unwraps the mailbox key of the session.
+pub const KEY_COOKIE: &str = "nexium_key";
+
+/// Seconds a session is kept after it last changed, like by logging in.
+pub const SESSION_TTL: i64 = 7 * 24 * 60 * 60;
+
+/// The documented responses of a route, by their status.
+pub type Responses = BTreeMap<String, RefOr<Response>>;
 
 /// Not found route, used as a fallback when no route matches.
 pub fn not_found() -> HttpResponse {
@@ -44,3 +65,114 @@ pub trait ApiError<'a>: ResponseError {
         ))
     }
 }
+
+/// Document the error responses of a route, from an example of every error it returns.
+/// The errors are grouped by their status, which lists their codes and messages.
+pub fn error_responses<'a, E: ApiError<'a>>(errors: &[E]) -> Responses {
+    responses(describe(errors))
+}
+
+/// Document the error responses of a route which requires a logged in session.
+/// The errors of the guards are documented after the ones of the route.
+pub fn guarded_error_responses<'a, E: ApiError<'a>>(errors: &[E]) -> Responses {
+    let mut described = describe(errors);
+    described.extend(describe(&GuardError::examples()));
+
+    responses(described)
+}
+
+/// Get the status, code and message of errors.
+fn describe<'a, E: ApiError<'a>>(errors: &[E]) -> Vec<(u16, String, String)> {
+    errors
+        .iter()
+        .map(|e| {
+            (
+                e.status_code().as_u16(),
+                e.error_code().to_string(),
+                e.to_string(),
+            )
+        })
+        .collect()
+}
+
+/// Build a response for every status of the described errors, each code is listed once.
+fn responses(errors: Vec<(u16, String, String)>) -> Responses {
+    let mut statuses: BTreeMap<u16, Vec<(String, String)>> = BTreeMap::new();
+    for (status, code, message) in errors {
+        let codes = statuses.entry(status).or_default();
+        if !codes.iter().any(|(c, _)| *c == code) {
+            codes.push((code, message));
+        }
+    }
+
+    statuses
+        .into_iter()
+        .map(|(status, codes)| {
+            let description = codes
+                .iter()
+                .map(|(code, message)| format!("`{}`: {}", code, message))
+                .collect::<Vec<_>>()
+                .join("\n");
+            let schema = ObjectBuilder::new()
+                .property(
+                    "code",
+                    ObjectBuilder::new()
+                        .schema_type(Type::String)
+                        .enum_values(Some(codes.into_iter().map(|(code, _)| code))),
+                )
+                .required("code")
+                .property("message", ObjectBuilder::new().schema_type(Type::String))
+                .required("message");
+            let response = ResponseBuilder::new()
+                .description(description)
+                .content(
+                    "application/json",
+                    ContentBuilder::new().schema(Some(schema)).build(),
+                )
+                .build();
+
+            (status.to_string(), RefOr::T(response))
+        })
+        .collect()
+}
+
+/// The ID of a session, which tells its event stream apart from those of the other sessions.
+/// Sessions from before events were introduced get an ID now, so they can be revoked as well.
+pub fn session_id(session: &Session) -> Result<Uuid, actix_web::Error> {
+    match session.get::<Uuid>("session")? {
+        Some(id) => Ok(id),
+        None => {
+            let id = Uuid::new_v4();
+            session.insert("session", id)?;
+            Ok(id)
+        }
+    }
+}
+
+/// Keep the mailbox key in the session, wrapped with a key that is only given to the client.
+/// The returned cookie has to be set on the response, it lives as long as the session since it changed now.
+pub fn keep_mailbox_key(
+    session: &Session,
+    key: &MailboxKey,
+) -> Result<Cookie<'static>, actix_web::Error> {
+    let key = key.to_session();
+    session.insert("sealed_mailbox_key", key.sealed)?;
+
+    Ok(Cookie::build(KEY_COOKIE, key.wrapping)
+        .path("/")
+        .max_age(Duration::seconds(SESSION_TTL))
+        .secure(true)
+        .http_only(true)
+        .same_site(SameSite::Strict)
+        .finish())
+}
+
+/// The cookie that makes the client forget the key of the session, used on logout.
+pub fn forget_mailbox_key() -> Cookie<'static> {
+    let mut cookie = Cookie::build(KEY_COOKIE, "")
+        .path("/")
+        .secure(true)
+        .finish();
+    cookie.make_removal();
+    cookie
+}
@@ -1,45 +1,91 @@
+use std::{io, net::TcpListener, sync::Arc};
+
+use actix_http::{HttpService, Protocol};
 use actix_redis::{RedisSession, SameSite};
+use actix_service::{map_config, IntoServiceFactory, ServiceFactoryExt};
 use actix_web::{
+    dev::{fn_service, AppConfig, Server},
     middleware,
     web::{self, Data},
-    App, HttpServer,
+    App,
 };
 use sqlx::{Pool, Postgres};
 use time::Duration;
+use tokio::net::TcpStream;
 
-use crate::environment::Environment;
+use crate::{environment::Environment, logic::event::Events, storage::BlobStore};
 
 mod api;
 mod extractors;
 mod frontend;
 mod helpers;
+mod well_known;
 
 pub use extractors::*;
 pub use helpers::*;
 
+/// The address the HTTP server listens on, without TLS.
+const ADDRESS: &str = "0.0.0.0:8000";
+
 /// Start the http server.
-pub async fn start(conn: Pool<Postgres>, env: Environment) -> std::io::Result<()> {
+/// The server is built from its parts, so the client address can be taken from the PROXY header of trusted proxies.
+pub async fn start(
+    conn: Pool<Postgres>,
+    store: Arc<dyn BlobStore>,
+    env: Environment,
+    events: Events,
+) -> io::Result<()> {
     info!("Starting HTTP service");
 
-    HttpServer::new(move || {
-        App::new()
-            .app_data(Data::new(conn.clone()))
-            .app_data(Data::new(env.clone()))
-            .wrap(
-                RedisSession::new(env.redis_url.clone(), env.secret.as_bytes())
-                    .cookie_name("nexium")
-                    .ttl(7 * 24 * 60 * 60) // Keep a session active for one week.
-                    .cookie_max_age(Some(Duration::days(100 * 365))) // Let the client store the session for a long time, or 100 years, whatever comes first.
-                    .cookie_http_only(true)
-                    .cookie_same_site(SameSite::Strict),
-            )
-            .wrap(middleware::Compress::default())
-            .wrap(middleware::Logger::default())
-            .service(api::routes())
-            .service(web::resource("/{_:.*}").route(web::get().to(|p| frontend::dist(&p))))
-            .default_service(web::get().to(frontend::index))
-    })
-    .bind("0.0.0.0:8000")?
-    .run()
-    .await
+    // The application sees the address it is served on and the configured hostname, like behind HttpServer.
+    // This version of actix only exposes the constructor of the config for its test server.
+    let listener = TcpListener::bind(ADDRESS)?;
+    let config = AppConfig::__priv_test_new(false, env.hostname.clone(), listener.local_addr()?);
+
+    Server::build()
+        .listen("nexium-http", listener, move || {
+            let proxy = env.proxy.clone();
+            let config = config.clone();
+            let app = App::new()
+                .app_data(Data::new(conn.clone()))
+                .app_data(Data::from(store.clone()))
+                .app_data(Data::new(env.clone()))
+                .app_data(Data::new(env.quota))
+                .app_data(Data::new(events.clone()))
+                .wrap(
+                    RedisSession::new(env.redis_url.clone(), env.secret.as_bytes())
+                        .cookie_name("nexium")
+                        .ttl(SESSION_TTL as u32)
+                        .cookie_max_age(Some(Duration::days(100 * 365))) // Let the client store the session for a long time, or 100 years, whatever comes first.
+                        .cookie_http_only(true)
+                        .cookie_same_site(SameSite::Strict),
+                )
+                .wrap(middleware::Compress::default())
+                .wrap(middleware::Logger::default())
+                .service(api::routes())
+                .service(well_known::routes())
+                .service(web::resource("/{_:.*}").route(web::get().to(|p| frontend::dist(&p))))
+                .default_service(web::get().to(frontend::index))
+                .into_factory()
+                .map_err(|err| err.as_response_error().error_response());
+            let service = HttpService::build().finish(map_config(app, move |_| config.clone()));
+
+            fn_service(move |mut stream: TcpStream| {
+                let proxy = proxy.clone();
+
+                async move {
+                    let peer = stream.peer_addr()?;
+                    let client = proxy.accept(&mut stream, peer, true).await.map_err(|e| {
+                        warn!("Rejected HTTP connection from {}: {}", peer, e);
+                        io::Error::new(io::ErrorKind::ConnectionRefused, e)
+                    })?;
+
+                    // Without TLS there is no ALPN to agree on HTTP/2, like for plain listeners of HttpServer.
+                    Ok((stream, Protocol::Http1, Some(client)))
+                }
+            })
+            .and_then(service)
+        })?
+        .run()
+        .await
 }
@@ -0,0 +1,78 @@
+use actix_web::{
+    http::StatusCode,
+    web::{self, Data},
+    HttpRequest, HttpResponse, ResponseError, Scope,
+};
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+
+use crate::http::ApiError;
+use crate::logic::domain;
+
+/// Returns the routes of this scope.
+pub fn routes() -> Scope {
+    web::scope("/.well-known")
+        .route("/mta-sts.txt", web::get().to(mta_sts))
+        .default_service(web::route().to(super::not_found))
+}
+
+/// Serve the MTA-STS policy of a domain.
+/// The domain is taken from the host, which has the form `mta-sts.<domain>`.
+async fn mta_sts(req: HttpRequest, pool: Data<Pool<Postgres>>) -> Result<HttpResponse, RouteError> {
+    let host = req.connection_info().host().to_lowercase();
+
+    // Strip the port, and the required `mta-sts` label.
+    let host = host.split(':').next().unwrap_or_default();
+    let name = host.strip_prefix("mta-sts.").ok_or(RouteError::NotFound)?;
+
+    let mut conn = pool.acquire().await?;
+    let domain = domain::Domain::find(&mut conn, name).await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; charset=utf-8")
+        .body(domain.mta_sts_policy()))
+}
+
+/// All possible error responses for this route.
+#[derive(Error, Debug)]
+enum RouteError {
+    #[error("No policy found for this domain.")]
+    NotFound,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<'a> ApiError<'a> for RouteError {
+    /// Convert the enum variant to a machine-readable name for the client.
+    fn error_code(&self) -> &'a str {
+        match self {
+            RouteError::NotFound => "notfound",
+            RouteError::DatabaseError(_) => "databaseerror",
+        }
+    }
+}
+
+impl ResponseError for RouteError {
+    /// Translate a route error to a HTTP status.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RouteError::NotFound => StatusCode::NOT_FOUND,
+            RouteError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Generate the error response.
+    fn error_response(&self) -> HttpResponse {
+        self.json()
+    }
+}
+
+/// Convert the internal error to an route error.
+impl From<domain::FindError> for RouteError {
+    fn from(err: domain::FindError) -> Self {
+        match err {
+            domain::FindError::NotFound => RouteError::NotFound,
+            domain::FindError::DatabaseError(e) => RouteError::DatabaseError(e),
+        }
+    }
+}
@@ -3,17 +3,19 @@ use regex::Regex;
 use serde::Serialize;
 use sqlx::PgConnection;
 use thiserror::Error;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 use crate::database;
 
 /// Representing an account of an user.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Account {
     pub id: Uuid,
     pub username: String,
     pub full_name: String,
+    pub admin: bool,
 }
 
 impl Account {
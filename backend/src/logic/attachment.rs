@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use sqlx::PgConnection;
+use thiserror::Error;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    database,
+    logic::{
+        blob::Blob,
+        mailbox_key::MailboxKey,
+        message::{Message, ReadError},
+        mime::{self, Structure},
+    },
+    storage::BlobStore,
+};
+
+/// Represents a single MIME part of a message.
+/// Parts are numbered in depth-first order, only counting parts without subparts.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Attachment {
+    pub part: i32,
+    pub content_type: String,
+    pub filename: Option<String>,
+    /// The size of the decoded content.
+    pub size: i64,
+    /// The Content-ID, without the angle brackets.
+    pub content_id: Option<String>,
+    pub disposition: Disposition,
+    /// The location of the body in the raw message, unknown for parts registered before it was stored.
+    #[serde(skip)]
+    pub body_offset: Option<i64>,
+    #[serde(skip)]
+    pub body_length: Option<i64>,
+    #[serde(skip)]
+    pub transfer_encoding: Option<String>,
+}
+
+/// How a part is meant to be presented (RFC 2183).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "disposition", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Disposition {
+    /// Shown as part of the message, the default without a Content-Disposition.
+    Inline,
+    /// Kept separate, only shown on request.
+    Attachment,
+}
+
+impl Attachment {
+    /// List the parts of a message.
+    /// The parts of encrypted messages are found in the decrypted raw message.
+    pub async fn list(
+        conn: &mut PgConnection,
+        store: &dyn BlobStore,
+        mailbox_key: Option<&MailboxKey>,
+        message: &Message,
+    ) -> Result<Vec<Self>, ListError> {
+        if !message.is_encrypted() {
+            return Ok(database::attachment::list(conn, &message.blob).await?);
+        }
+
+        let raw = message.raw(conn, store, mailbox_key).await?;
+
+        Ok(Structure::parse(&raw).attachments())
+    }
+
+    /// Load a single part of a message, with its decoded content.
+    /// Only the body of a registered part is read from the blob, using its location in the raw message.
+    /// Encrypted messages and parts registered before their location was are read whole.
+    pub async fn load(
+        conn: &mut PgConnection,
+        store: &dyn BlobStore,
+        mailbox_key: Option<&MailboxKey>,
+        message: &Message,
+        part: i32,
+    ) -> Result<(Self, Vec<u8>), LoadError> {
+        if !message.is_encrypted() {
+            let attachment = database::attachment::find(conn, &message.blob, part)
+                .await?
+                .ok_or(LoadError::NotFound)?;
+
+            if let (Some(offset), Some(length)) = (attachment.body_offset, attachment.body_length) {
+                let body = Blob::load_range(
+                    conn,
+                    store,
+                    &message.blob,
+                    None,
+                    offset as u64,
+                    length as u64,
+                )
+                .await
+                .map_err(ReadError::BlobError)?;
+                let content = mime::decode(attachment.transfer_encoding.as_deref(), &body);
+
+                return Ok((attachment, content));
+            }
+        }
+
+        let raw = message.raw(conn, store, mailbox_key).await?;
+        let attachment = Structure::parse(&raw)
+            .attachments()
+            .into_iter()
+            .find(|a| a.part == part)
+            .ok_or(LoadError::NotFound)?;
+        let content = attachment.decode(&raw);
+
+        Ok((attachment, content))
+    }
+
+    /// Whether the part can be the readable body of the message, an inline text without a filename.
+    pub fn is_body(&self) -> bool {
+        self.disposition == Disposition::Inline
+            && self.filename.is_none()
+            && (self.content_type == "text/plain" || self.content_type == "text/html")
+    }
+
+    /// Decode the content of this part from the raw message it was found in.
+    pub fn decode(&self, raw: &[u8]) -> Vec<u8> {
+        let start = self.body_offset.unwrap_or(0) as usize;
+        let end = start.saturating_add(self.body_length.unwrap_or(0) as usize);
+        let body = raw.get(start..end.min(raw.len())).unwrap_or_default();
+
+        mime::decode(self.transfer_encoding.as_deref(), body)
+    }
+}
+
+/// Point the `cid:` references (RFC 2392) in HTML to the attachment URLs of a message.
+/// References to unknown parts are left as is.
+pub fn rewrite_cids(html: &str, message: Uuid, attachments: &[Attachment]) -> String {
+    let ids: HashMap<&str, i32> = attachments
+        .iter()
+        .filter_map(|a| a.content_id.as_deref().map(|id| (id, a.part)))
+        .collect();
+
+    // Lowercasing ASCII keeps the byte offsets the same.
+    let lower = html.to_ascii_lowercase();
+    let mut output = String::with_capacity(html.len());
+    let mut position = 0;
+
+    while let Some(start) = lower[position..].find("cid:").map(|i| position + i) {
+        let end = html[start + 4..]
+            .find(|c: char| c == '"' || c == '\'' || c == ')' || c == '>' || c.is_whitespace())
+            .map(|i| start + 4 + i)
+            .unwrap_or_else(|| html.len());
+
+        output.push_str(&html[position..start]);
+
+        match ids.get(percent_decode(&html[start + 4..end]).as_str()) {
+            Some(part) => {
+                output.push_str(&format!("/api/messages/{}/attachments/{}", message, part))
+            }
+            None => output.push_str(&html[start..end]),
+        }
+
+        position = end;
+    }
+
+    output.push_str(&html[position..]);
+    output
+}
+
+/// Decode the percent-encoding of a URL, keeping invalid sequences.
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut output = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let decoded = match bytes[i] {
+            b'%' if i + 2 < bytes.len() => std::str::from_utf8(&bytes[i + 1..i + 3])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+
+        match decoded {
+            Some(byte) => {
+                output.push(byte);
+                i += 3;
+            }
+            None => {
+                output.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&output).into_owned()
+}
+
+/// Possible errors with listing the parts of a message.
+#[derive(Error, Debug)]
+pub enum ListError {
+    #[error("{0}")]
+    ReadError(#[from] ReadError),
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Possible errors with loading a part.
+#[derive(Error, Debug)]
+pub enum LoadError {
+    #[error("The attachment was not found.")]
+    NotFound,
+    #[error("{0}")]
+    ReadError(#[from] ReadError),
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
@@ -8,7 +8,7 @@ use thiserror::Error;
 use uuid::Uuid;
 use zxcvbn::zxcvbn;
 
-use crate::{database, logic::account::Account};
+use crate::{crypto, database, logic::account::Account};
 
 /// Represents an optional password authentication method for an user account.
 #[derive(Debug, Serialize)]
@@ -53,6 +53,33 @@ impl AuthPassword {
         Ok(())
     }
 
+    /// Change the password of an account, after checking the current one.
+    pub async fn change(
+        conn: &mut PgConnection,
+        account: &Account,
+        current: &str,
+        new: &str,
+    ) -> Result<(), ChangeError> {
+        Self::authenticate(conn, account, current).await?;
+
+        if !Self::validate(new, &[account.username.as_str()]) {
+            return Err(ChangeError::PasswordComplexity);
+        }
+
+        let hash = Self::hash(new).map_err(|_| ChangeError::HashError)?;
+        database::auth_password::update(conn, account.id, hash).await?;
+
+        Ok(())
+    }
+
+    /// Derive a key from a password and salt, for encryption instead of storage.
+    pub fn derive_key(plaintext: &str, salt: &[u8]) -> Result<crypto::Key, argon2::Error> {
+        let mut key = [0; crypto::KEY_LENGTH];
+        Self::create_context().hash_password_into(plaintext.as_bytes(), salt, &mut key)?;
+
+        Ok(key)
+    }
+
     /// Compare an hashed password with an plaintext.
     /// Only returns Ok when the password is valid, error otherwise.
     fn compare(hash: &str, plaintext: &str) -> Result<(), password_hash::Error> {
@@ -118,3 +145,16 @@ pub enum AuthenticateError {
     #[error("An internal database error occured.")]
     DatabaseError(#[from] sqlx::Error),
 }
+
+/// Possible errors which occur when changing the password.
+#[derive(Error, Debug)]
+pub enum ChangeError {
+    #[error("{0}")]
+    AuthenticateError(#[from] AuthenticateError),
+    #[error("The password is not complex enough")]
+    PasswordComplexity,
+    #[error("The password could not be hashed")]
+    HashError,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
@@ -0,0 +1,24 @@
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::database;
+
+/// The generation of the sessions of an account, kept in each session when it logs in.
+/// Sessions of an older generation are revoked, they are no longer accepted by the guards.
+pub struct Generation;
+
+impl Generation {
+    /// Find the current generation of the sessions of an account.
+    /// Accounts start at zero, which is also assumed for sessions from before generations.
+    pub async fn current(conn: &mut PgConnection, account: Uuid) -> Result<i32, sqlx::Error> {
+        Ok(database::session_generation::find(conn, account)
+            .await?
+            .unwrap_or(0))
+    }
+
+    /// Revoke all sessions of an account, by moving on to the next generation.
+    /// The new generation is returned, for the session that stays logged in.
+    pub async fn revoke(conn: &mut PgConnection, account: Uuid) -> Result<i32, sqlx::Error> {
+        database::session_generation::increment(conn, account).await
+    }
+}
@@ -0,0 +1,361 @@
+use std::{
+    collections::HashMap,
+    io::Cursor,
+    sync::{Arc, Mutex},
+};
+
+use lazy_static::lazy_static;
+use sha2::{Digest, Sha256};
+use sqlx::{Connection, PgConnection, Pool, Postgres};
+use thiserror::Error;
+
+use crate::{
+    crypto::{self, Key},
+    database,
+    storage::{self, BlobStore, Compressed, Encrypted, Spooled, StoreError},
+};
+
+/// Seconds a blob stays around after its last reference is dropped.
+/// This covers deliveries which read the reference count just before it dropped to zero.
+const GC_GRACE: i32 = 60 * 60;
+
+/// Seconds content written to the store has to be registered in, before it is considered abandoned.
+/// This is far longer than any transaction storing a blob takes.
+const PENDING_GRACE: i32 = 24 * 60 * 60;
+
+/// The amount of blobs removed in a single transaction.
+const GC_BATCH: i64 = 100;
+
+/// The amount of existing blobs compressed in a single transaction.
+const COMPRESS_BATCH: i64 = 10;
+
+/// The amount of blobs a dictionary is trained from, training is skipped with less.
+const TRAINING_SAMPLES: i64 = 1000;
+const TRAINING_MIN_SAMPLES: usize = 100;
+
+/// Only the start of a blob is used for training, where the headers are.
+const TRAINING_SAMPLE_SIZE: usize = 16 * 1024;
+
+lazy_static! {
+    /// Dictionaries never change once trained, so they are kept in memory after the first use.
+    static ref DICTIONARIES: Mutex<HashMap<i64, Arc<Vec<u8>>>> = Mutex::new(HashMap::new());
+}
+
+/// Represents a reference to content in the blob store.
+#[derive(Debug)]
+pub struct Blob {
+    pub hash: String,
+    pub size: i64,
+}
+
+/// Where and how the content of a blob is kept in the store.
+#[derive(Debug)]
+pub struct Stored {
+    /// The key of the content, the hash unless it was compressed in the background.
+    pub location: String,
+    pub compressed: bool,
+    pub dictionary: Option<i64>,
+}
+
+impl Blob {
+    /// Store spooled content, taking a reference to it.
+    /// Identical content is only stored once, and shared by all references.
+    pub async fn store(
+        conn: &mut PgConnection,
+        store: &dyn BlobStore,
+        spooled: &Spooled,
+    ) -> Result<Self, BlobError> {
+        let blob = Blob {
+            hash: spooled.hash.clone(),
+            size: spooled.size as i64,
+        };
+
+        // The content is only written when it is new, compressed with the latest dictionary.
+        if !database::blob::acquire(conn, &blob.hash).await? {
+            let (id, dictionary) = latest_dictionary(conn).await?;
+            let compressed = spooled.compress(dictionary).await.map_err(StoreError::Io)?;
+
+            store.put(&blob.hash, compressed.path()).await?;
+            let stored_size = compressed.size as i64;
+            database::blob::create(conn, &blob.hash, blob.size, stored_size, id, false).await?;
+        }
+
+        Ok(blob)
+    }
+
+    /// Store spooled content encrypted with a new random content key.
+    /// Encrypted content is never shared, so the blob is always new.
+    pub async fn store_encrypted(
+        conn: &mut PgConnection,
+        store: &dyn BlobStore,
+        spooled: &Spooled,
+    ) -> Result<(Self, Key), BlobError> {
+        let (id, dictionary) = latest_dictionary(conn).await?;
+        let compressed = spooled.compress(dictionary).await.map_err(StoreError::Io)?;
+
+        let key = crypto::random_key();
+        let encrypted = Encrypted::create(&compressed, &key)
+            .await
+            .map_err(StoreError::Io)?;
+
+        let blob = Blob {
+            hash: encrypted.hash.clone(),
+            size: spooled.size as i64,
+        };
+
+        store.put(&blob.hash, encrypted.path()).await?;
+        let stored_size = encrypted.size as i64;
+        database::blob::create(conn, &blob.hash, blob.size, stored_size, id, true).await?;
+
+        Ok((blob, key))
+    }
+
+    /// Read the content of a blob, decrypting it with the content key and decompressing it when needed.
+    pub async fn load(
+        conn: &mut PgConnection,
+        store: &dyn BlobStore,
+        hash: &str,
+        key: Option<&Key>,
+    ) -> Result<Vec<u8>, BlobError> {
+        Self::load_range(conn, store, hash, key, 0, u64::MAX).await
+    }
+
+    /// Read a range of the content of a blob, like `load`.
+    /// The stored content is fetched whole, but only the range is decompressed into memory.
+    /// How the content is stored is read from the blob, content compressed in the background has a new key,
+    /// so readers which looked the blob up before see the uncompressed content until the grace period ends.
+    pub async fn load_range(
+        conn: &mut PgConnection,
+        store: &dyn BlobStore,
+        hash: &str,
+        key: Option<&Key>,
+        offset: u64,
+        length: u64,
+    ) -> Result<Vec<u8>, BlobError> {
+        let stored = database::blob::find(conn, hash)
+            .await?
+            .ok_or_else(|| BlobError::NotFound(hash.to_string()))?;
+        let mut data = store.get(&stored.location).await?;
+
+        if let Some(key) = key {
+            data = crypto::decrypt(key, &data).map_err(|_| BlobError::DecryptError)?;
+        }
+
+        if !stored.compressed {
+            let start = data.len().min(offset as usize);
+            let end = data.len().min(start.saturating_add(length as usize));
+            data.truncate(end);
+            data.drain(..start);
+
+            return Ok(data);
+        }
+
+        let dictionary = match stored.dictionary {
+            Some(id) => Some(dictionary(conn, id).await?),
+            None => None,
+        };
+
+        let data = tokio::task::spawn_blocking(move || {
+            storage::decompress(
+                &data,
+                dictionary.as_deref().map(Vec::as_slice),
+                offset,
+                length,
+            )
+        })
+        .await
+        .map_err(|e| StoreError::Io(std::io::Error::new(std::io::ErrorKind::Other, e)))?
+        .map_err(StoreError::Io)?;
+
+        Ok(data)
+    }
+
+    /// Drop a reference to a blob.
+    /// The content is removed by the garbage collection once no references are left.
+    pub async fn release(conn: &mut PgConnection, hash: &str) -> Result<(), sqlx::Error> {
+        database::blob::release(conn, hash).await
+    }
+
+    /// Remove all blobs without references from the store.
+    /// Returns the amount of removed blobs.
+    pub async fn collect_garbage(
+        db: &Pool<Postgres>,
+        store: &dyn BlobStore,
+    ) -> Result<usize, BlobError> {
+        let mut removed = 0;
+
+        loop {
+            // The rows stay locked while the content is removed, so no new references can be taken.
+            let mut tx = db.begin().await?;
+            let blobs = database::blob::lock_unreferenced(&mut tx, GC_GRACE, GC_BATCH).await?;
+
+            if blobs.is_empty() {
+                return Ok(removed);
+            }
+
+            for (hash, location) in &blobs {
+                store.delete(location).await?;
+                database::blob::delete(&mut tx, hash).await?;
+            }
+
+            tx.commit().await?;
+            removed += blobs.len();
+        }
+    }
+
+    /// Remove content written to the store whose transaction was rolled back, so it was never registered.
+    /// Returns the amount of removed blobs.
+    pub async fn collect_abandoned(
+        db: &Pool<Postgres>,
+        store: &dyn BlobStore,
+    ) -> Result<usize, BlobError> {
+        let mut conn = db.acquire().await?;
+        database::blob_pending::settle(&mut conn, PENDING_GRACE).await?;
+        drop(conn);
+
+        let mut removed = 0;
+
+        loop {
+            // The records stay locked while the content is removed, so the same content is not written meanwhile.
+            let mut tx = db.begin().await?;
+            let hashes =
+                database::blob_pending::lock_abandoned(&mut tx, PENDING_GRACE, GC_BATCH).await?;
+
+            if hashes.is_empty() {
+                return Ok(removed);
+            }
+
+            for hash in &hashes {
+                store.delete(hash).await?;
+                database::blob_pending::delete(&mut tx, hash).await?;
+            }
+
+            tx.commit().await?;
+            removed += hashes.len();
+        }
+    }
+
+    /// Compress all blobs which were stored before compression was introduced.
+    /// The compressed copy is written under a new key outside of any transaction, and the blob then points to it.
+    /// The uncompressed content is left to the garbage collection of abandoned content,
+    /// so readers which looked the blob up before can still read it during the grace period.
+    /// Returns the amount of compressed blobs.
+    pub async fn compress_existing(
+        db: &Pool<Postgres>,
+        store: &dyn BlobStore,
+    ) -> Result<usize, BlobError> {
+        let mut compressed = 0;
+        let mut conn = db.acquire().await?;
+
+        loop {
+            let hashes = database::blob::uncompressed(&mut conn, COMPRESS_BATCH).await?;
+
+            if hashes.is_empty() {
+                return Ok(compressed);
+            }
+
+            let (id, dictionary) = latest_dictionary(&mut conn).await?;
+
+            for hash in &hashes {
+                let data = store.get(hash).await?;
+                let file = Compressed::create(Cursor::new(data), dictionary.clone())
+                    .await
+                    .map_err(StoreError::Io)?;
+
+                let location = compressed_location(hash);
+                store.put(&location, file.path()).await?;
+
+                let mut tx = conn.begin().await?;
+                let size = file.size as i64;
+                if database::blob::set_compressed(&mut tx, hash, &location, size, id).await? {
+                    database::blob_pending::record(&mut tx, hash).await?;
+                    compressed += 1;
+                }
+                tx.commit().await?;
+            }
+        }
+    }
+
+    /// Train a dictionary from the start of random stored blobs, if there is none yet.
+    /// Returns the ID of the new dictionary.
+    pub async fn train_dictionary(
+        db: &Pool<Postgres>,
+        store: &dyn BlobStore,
+    ) -> Result<Option<i64>, BlobError> {
+        let mut conn = db.acquire().await?;
+
+        if database::blob_dictionary::latest(&mut conn)
+            .await?
+            .is_some()
+        {
+            return Ok(None);
+        }
+
+        let hashes = database::blob::sample(&mut conn, 1024 * 1024, TRAINING_SAMPLES).await?;
+        if hashes.len() < TRAINING_MIN_SAMPLES {
+            return Ok(None);
+        }
+
+        let mut samples = Vec::with_capacity(hashes.len());
+        for hash in &hashes {
+            let mut data = Blob::load(&mut conn, store, hash, None).await?;
+            data.truncate(TRAINING_SAMPLE_SIZE);
+            samples.push(data);
+        }
+
+        let (id, data) = tokio::task::spawn_blocking(move || storage::train(&samples))
+            .await
+            .map_err(|e| StoreError::Io(std::io::Error::new(std::io::ErrorKind::Other, e)))?
+            .map_err(StoreError::Io)?;
+
+        database::blob_dictionary::create(&mut conn, i64::from(id), &data).await?;
+
+        Ok(Some(i64::from(id)))
+    }
+}
+
+/// The key of the compressed copy of a blob which was stored uncompressed.
+fn compressed_location(hash: &str) -> String {
+    hex::encode(Sha256::digest(format!("zstd:{}", hash).as_bytes()))
+}
+
+/// Get a zstd dictionary by ID, from memory when it was used before.
+async fn dictionary(conn: &mut PgConnection, id: i64) -> Result<Arc<Vec<u8>>, BlobError> {
+    if let Some(dictionary) = DICTIONARIES.lock().unwrap().get(&id) {
+        return Ok(dictionary.clone());
+    }
+
+    let dictionary = match database::blob_dictionary::find(conn, id).await? {
+        Some(data) => Arc::new(data),
+        None => return Err(BlobError::DictionaryNotFound(id)),
+    };
+
+    DICTIONARIES.lock().unwrap().insert(id, dictionary.clone());
+
+    Ok(dictionary)
+}
+
+/// Get the dictionary to compress new blobs with, if one was trained.
+async fn latest_dictionary(
+    conn: &mut PgConnection,
+) -> Result<(Option<i64>, Option<Arc<Vec<u8>>>), BlobError> {
+    match database::blob_dictionary::latest(conn).await? {
+        Some(id) => Ok((Some(id), Some(dictionary(conn, id).await?))),
+        None => Ok((None, None)),
+    }
+}
+
+/// Possible errors with storing blobs.
+#[derive(Error, Debug)]
+pub enum BlobError {
+    #[error("{0}")]
+    StoreError(#[from] StoreError),
+    #[error("The compression dictionary {0} does not exist.")]
+    DictionaryNotFound(i64),
+    #[error("The blob {0} does not exist.")]
+    NotFound(String),
+    #[error("The blob could not be decrypted.")]
+    DecryptError,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
@@ -0,0 +1,448 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Json, PgConnection, Pool, Postgres};
+use thiserror::Error;
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    database,
+    logic::{
+        label::{self, Label},
+        mailbox::{self, Mailbox, Role},
+        message::{self, CopyError, FlagChanges, FlagError, Message},
+        quota::{QuotaConfig, QuotaError},
+        search::Query,
+    },
+};
+
+/// The most messages changed by a single bulk operation.
+pub const MAX_MESSAGES: usize = 10_000;
+
+/// The most messages changed within a request, more have to be changed in the background.
+pub const MAX_DIRECT: usize = 1000;
+
+/// The amount of messages changed in a single transaction, which keeps the locks short.
+const BATCH: usize = 100;
+
+/// Minutes without progress after which a running operation was interrupted, like by a restart, and is resumed.
+/// Progress is saved after every batch, which takes far less.
+const STALLED_MINUTES: i32 = 2;
+
+/// Interval of resuming interrupted operations.
+const RESUME_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Days after which completed operations are removed.
+const EXPIRY_DAYS: i32 = 7;
+
+/// A change applied to many messages of an account at once.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Action {
+    /// Move the messages into a mailbox.
+    Move { mailbox: Uuid },
+    /// Copy the messages into a mailbox, the copies count into the quota.
+    Copy { mailbox: Uuid },
+    /// Set or clear flags and keywords.
+    Flags(FlagChanges),
+    /// Add and remove labels.
+    Labels {
+        #[serde(default)]
+        add: Vec<Uuid>,
+        #[serde(default)]
+        remove: Vec<Uuid>,
+    },
+    /// Permanently delete the messages.
+    Delete,
+    /// Move the messages into the Junk mailbox, marked with the `$junk` keyword.
+    Spam,
+}
+
+/// What happened to a single message of a bulk operation.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Done,
+    /// The message does not exist, or was deleted in the meantime.
+    /// Also when it was deleted while the batch of a copy or delete ran.
+    NotFound,
+    /// The message could not be copied, as the mailbox is full.
+    QuotaExceeded,
+}
+
+/// The result of a bulk operation for a single message.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Outcome {
+    pub id: Uuid,
+    pub status: Status,
+}
+
+/// The states a bulk operation in the background can be in.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "bulk_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum BulkStatus {
+    Running,
+    Finished,
+    Failed,
+}
+
+/// Represents a bulk operation running in the background, with the results of the batches done so far.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkJob {
+    pub id: Uuid,
+    pub status: BulkStatus,
+    /// The amount of messages in the operation.
+    pub total: i32,
+    #[schema(value_type = Vec<Outcome>)]
+    pub results: Json<Vec<Outcome>>,
+    /// Why the operation failed, the batches before stay applied.
+    pub error: Option<String>,
+    #[serde(with = "time::serde::timestamp")]
+    pub created: OffsetDateTime,
+    #[serde(with = "time::serde::timestamp")]
+    pub updated: OffsetDateTime,
+}
+
+/// A running bulk operation taken over to resume it, with the messages it did not change yet.
+#[derive(Debug)]
+pub struct Interrupted {
+    pub id: Uuid,
+    pub account: Uuid,
+    pub action: Json<Action>,
+    pub remaining: Vec<Uuid>,
+}
+
+impl Action {
+    /// Check the mailbox, labels or keywords of the action, before any message is changed.
+    pub async fn validate(&self, conn: &mut PgConnection, account: Uuid) -> Result<(), BulkError> {
+        match self {
+            Action::Move { mailbox } | Action::Copy { mailbox } => {
+                Mailbox::find(conn, account, *mailbox).await?;
+            }
+            Action::Flags(changes) => {
+                let valid = changes
+                    .add_keywords
+                    .iter()
+                    .chain(&changes.remove_keywords)
+                    .all(|k| message::normalize_keyword(k).is_some());
+                if !valid {
+                    return Err(BulkError::InvalidKeyword);
+                }
+            }
+            Action::Labels { add, remove } => {
+                for id in add.iter().chain(remove) {
+                    Label::find(conn, account, *id).await?;
+                }
+            }
+            Action::Delete | Action::Spam => {}
+        }
+
+        Ok(())
+    }
+
+    /// Apply the action to messages of an account in batches, each in its own transaction.
+    /// The results of every batch are added to the job, when running in the background.
+    /// Batches which completed stay applied when a later one fails.
+    pub async fn run(
+        &self,
+        db: &Pool<Postgres>,
+        account: Uuid,
+        ids: &[Uuid],
+        job: Option<Uuid>,
+        quota: &QuotaConfig,
+    ) -> Result<Vec<Outcome>, BulkError> {
+        let mut results = Vec::with_capacity(ids.len());
+
+        for batch in ids.chunks(BATCH) {
+            let mut tx = db.begin().await?;
+            let outcomes = self.apply(&mut tx, account, batch, quota).await?;
+            if let Some(job) = job {
+                database::bulk_job::append(&mut tx, job, &outcomes).await?;
+            }
+            tx.commit().await?;
+
+            results.extend(outcomes);
+        }
+
+        Ok(results)
+    }
+
+    /// Start applying the action in the background.
+    /// Returns the job, which follows the progress.
+    /// The messages not changed yet are kept with the job, so it is resumed when interrupted.
+    pub async fn start(
+        self,
+        db: Pool<Postgres>,
+        account: Uuid,
+        ids: Vec<Uuid>,
+        quota: QuotaConfig,
+    ) -> Result<BulkJob, sqlx::Error> {
+        let mut conn = db.acquire().await?;
+        let id = database::bulk_job::create(&mut conn, account, &self, &ids).await?;
+        let job = database::bulk_job::find(&mut conn, account, id)
+            .await?
+            .ok_or(sqlx::Error::RowNotFound)?;
+        drop(conn);
+
+        self.spawn(db, account, id, ids, quota);
+
+        Ok(job)
+    }
+
+    /// Apply the action to the messages of a job in the background, and set its final status.
+    fn spawn(
+        self,
+        db: Pool<Postgres>,
+        account: Uuid,
+        job: Uuid,
+        ids: Vec<Uuid>,
+        quota: QuotaConfig,
+    ) {
+        tokio::spawn(async move {
+            let (status, error) = match self.run(&db, account, &ids, Some(job), &quota).await {
+                Ok(_) => (BulkStatus::Finished, None),
+                Err(e) => {
+                    warn!("Bulk operation {} failed: {}", job, e);
+                    (BulkStatus::Failed, Some(e.to_string()))
+                }
+            };
+
+            let res = async {
+                let mut conn = db.acquire().await?;
+                database::bulk_job::finish(&mut conn, job, status, error.as_deref()).await
+            }
+            .await;
+            if let Err(e) = res {
+                warn!("Failed to finish bulk operation {}: {}", job, e);
+            }
+        });
+    }
+
+    /// Apply the action to a batch of messages, within a transaction.
+    async fn apply(
+        &self,
+        conn: &mut PgConnection,
+        account: Uuid,
+        ids: &[Uuid],
+        quota: &QuotaConfig,
+    ) -> Result<Vec<Outcome>, BulkError> {
+        let existing = database::message::existing(conn, account, ids).await?;
+        let mut over_quota = Vec::new();
+        let mut missing = Vec::new();
+
+        match self {
+            Action::Move { mailbox } => {
+                let mailbox = Mailbox::find(conn, account, *mailbox).await?;
+                database::message::move_many(conn, &existing, mailbox.id).await?;
+            }
+            Action::Copy { mailbox } => {
+                let mailbox = Mailbox::find(conn, account, *mailbox).await?;
+                for id in &existing {
+                    let message = match Message::find(conn, account, *id).await {
+                        Ok(message) => message,
+                        Err(message::FindError::NotFound) => {
+                            missing.push(*id);
+                            continue;
+                        }
+                        Err(message::FindError::DatabaseError(e)) => return Err(e.into()),
+                    };
+
+                    match message.copy_to(conn, account, &mailbox, quota).await {
+                        Ok(_) => {}
+                        Err(CopyError::OverQuota(QuotaError::DatabaseError(e)))
+                        | Err(CopyError::DatabaseError(e)) => return Err(e.into()),
+                        Err(CopyError::OverQuota(_)) => over_quota.push(*id),
+                    }
+                }
+            }
+            Action::Flags(changes) => {
+                Message::set_flags(conn, account, &existing, changes.clone()).await?;
+            }
+            Action::Labels { add, remove } => {
+                Label::apply(conn, account, &existing, add, remove).await?;
+            }
+            Action::Delete => {
+                for id in &existing {
+                    match Message::find(conn, account, *id).await {
+                        Ok(message) => message.delete(conn, account, quota).await?,
+                        Err(message::FindError::NotFound) => missing.push(*id),
+                        Err(message::FindError::DatabaseError(e)) => return Err(e.into()),
+                    }
+                }
+            }
+            Action::Spam => {
+                let junk = Mailbox::role(conn, account, Role::Junk).await?;
+                database::message::move_many(conn, &existing, junk.id).await?;
+
+                let changes = FlagChanges {
+                    add_keywords: vec!["$junk".to_string()],
+                    remove_keywords: vec!["$notjunk".to_string()],
+                    ..FlagChanges::default()
+                };
+                Message::set_flags(conn, account, &existing, changes).await?;
+            }
+        }
+
+        Ok(ids
+            .iter()
+            .map(|id| {
+                let status = if over_quota.contains(id) {
+                    Status::QuotaExceeded
+                } else if existing.contains(id) && !missing.contains(id) {
+                    Status::Done
+                } else {
+                    Status::NotFound
+                };
+
+                Outcome { id: *id, status }
+            })
+            .collect())
+    }
+}
+
+impl BulkJob {
+    /// Find a bulk operation of an account by ID.
+    pub async fn find(conn: &mut PgConnection, account: Uuid, id: Uuid) -> Result<Self, FindError> {
+        let res = database::bulk_job::find(conn, account, id).await?;
+
+        match res {
+            Some(job) => Ok(job),
+            None => Err(FindError::NotFound),
+        }
+    }
+
+    /// Start resuming the operations interrupted by a restart, from startup on.
+    /// Old completed operations are removed along the way.
+    pub async fn start_worker(db: Pool<Postgres>, quota: QuotaConfig) {
+        info!("Starting bulk operations");
+
+        loop {
+            if let Err(e) = Self::resume(&db, &quota).await {
+                warn!("Failed to resume interrupted bulk operations: {}", e);
+            }
+
+            match Self::expire(&db).await {
+                Ok(0) => {}
+                Ok(count) => info!("Removed {} completed bulk operations.", count),
+                Err(e) => warn!("Failed to remove completed bulk operations: {}", e),
+            }
+
+            tokio::time::sleep(RESUME_INTERVAL).await;
+        }
+    }
+
+    /// Resume operations which stopped making progress, like after a restart.
+    /// Operations started before they could be resumed are marked as failed instead.
+    async fn resume(db: &Pool<Postgres>, quota: &QuotaConfig) -> Result<(), sqlx::Error> {
+        let mut conn = db.acquire().await?;
+
+        for job in database::bulk_job::claim_stalled(&mut conn, STALLED_MINUTES).await? {
+            info!(
+                "Resuming bulk operation {} with {} messages left.",
+                job.id,
+                job.remaining.len()
+            );
+            job.action
+                .0
+                .spawn(db.clone(), job.account, job.id, job.remaining, *quota);
+        }
+
+        let stalled = database::bulk_job::fail_stalled(&mut conn, STALLED_MINUTES).await?;
+        if stalled > 0 {
+            warn!("{} bulk operations were interrupted.", stalled);
+        }
+
+        Ok(())
+    }
+
+    /// Remove old completed operations.
+    /// Returns the amount of removed operations.
+    async fn expire(db: &Pool<Postgres>) -> Result<u64, sqlx::Error> {
+        let mut conn = db.acquire().await?;
+        database::bulk_job::delete_expired(&mut conn, EXPIRY_DAYS).await
+    }
+}
+
+/// Find the messages of an account matching a search query, for a bulk operation.
+pub async fn search(
+    conn: &mut PgConnection,
+    account: Uuid,
+    query: &Query,
+) -> Result<Vec<Uuid>, BulkError> {
+    let messages = Message::search(conn, account, query, MAX_MESSAGES as i64 + 1, 0).await?;
+    if messages.len() > MAX_MESSAGES {
+        return Err(BulkError::TooManyMessages);
+    }
+
+    Ok(messages.into_iter().map(|m| m.id).collect())
+}
+
+/// Possible errors with a bulk operation.
+#[derive(Error, Debug)]
+pub enum BulkError {
+    #[error("Too many messages are changed at once.")]
+    TooManyMessages,
+    #[error("The mailbox does not exist.")]
+    MailboxNotFound,
+    #[error("A label does not exist.")]
+    LabelNotFound,
+    #[error("A keyword is empty, too long or contains invalid characters.")]
+    InvalidKeyword,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Map the errors of the single steps, keeping database errors apart.
+impl From<mailbox::FindError> for BulkError {
+    fn from(err: mailbox::FindError) -> Self {
+        match err {
+            mailbox::FindError::NotFound => BulkError::MailboxNotFound,
+            mailbox::FindError::DatabaseError(e) => BulkError::DatabaseError(e),
+        }
+    }
+}
+
+/// Map the errors of the single steps, keeping database errors apart.
+impl From<label::FindError> for BulkError {
+    fn from(err: label::FindError) -> Self {
+        match err {
+            label::FindError::NotFound => BulkError::LabelNotFound,
+            label::FindError::DatabaseError(e) => BulkError::DatabaseError(e),
+        }
+    }
+}
+
+/// Map the errors of the single steps, keeping database errors apart.
+impl From<label::ApplyError> for BulkError {
+    fn from(err: label::ApplyError) -> Self {
+        match err {
+            label::ApplyError::TooManyMessages => BulkError::TooManyMessages,
+            label::ApplyError::LabelNotFound => BulkError::LabelNotFound,
+            label::ApplyError::DatabaseError(e) => BulkError::DatabaseError(e),
+        }
+    }
+}
+
+/// Map the errors of the single steps, keeping database errors apart.
+impl From<FlagError> for BulkError {
+    fn from(err: FlagError) -> Self {
+        match err {
+            FlagError::TooManyMessages => BulkError::TooManyMessages,
+            FlagError::InvalidKeyword => BulkError::InvalidKeyword,
+            FlagError::DatabaseError(e) => BulkError::DatabaseError(e),
+        }
+    }
+}
+
+/// Possible errors with finding a bulk operation.
+#[derive(Error, Debug)]
+pub enum FindError {
+    #[error("The bulk operation was not found.")]
+    NotFound,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
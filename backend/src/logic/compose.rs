@@ -0,0 +1,665 @@
+use serde::{Deserialize, Serialize};
+use sqlx::PgConnection;
+use thiserror::Error;
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    crypto,
+    logic::{
+        account::{self, Account},
+        attachment::{self, Attachment},
+        blob::BlobError,
+        domain::{Domain, DEFAULT_DOMAIN},
+        html,
+        mailbox::Role,
+        mailbox_key::MailboxKey,
+        mailing_list::MailingList,
+        message::{self, DeliverError, Message, ReadError},
+        mime::Structure,
+        outbound::Outbound,
+        quota::{QuotaConfig, QuotaError},
+        search::Document,
+        thread,
+        upload::{self, Upload},
+    },
+    storage::{BlobStore, Spool, Spooled, StoreError},
+};
+
+/// The maximum amount of recipients of a message, over To, Cc and Bcc together.
+pub const MAX_RECIPIENTS: usize = 100;
+
+/// The longest subject and display name, in characters.
+const MAX_SUBJECT: usize = 500;
+const MAX_NAME: usize = 200;
+
+/// The maximum length of encoded lines in the body (RFC 2045).
+const LINE_LENGTH: usize = 76;
+
+/// The amount of bytes encoded into a single encoded-word, which keeps it within 75 characters (RFC 2047).
+const WORD_BYTES: usize = 45;
+
+/// A mailbox a message is from or addressed to, with an optional display name.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Address {
+    #[serde(default)]
+    pub name: Option<String>,
+    pub address: String,
+}
+
+/// A message written by an account, as entered in the client.
+/// Attachments are uploads of the account, or parts of its messages when forwarding.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Compose {
+    #[serde(default)]
+    pub to: Vec<Address>,
+    #[serde(default)]
+    pub cc: Vec<Address>,
+    #[serde(default)]
+    pub bcc: Vec<Address>,
+    #[serde(default)]
+    pub subject: String,
+    pub text: Option<String>,
+    pub html: Option<String>,
+    #[serde(default)]
+    pub attachments: Vec<Uuid>,
+    #[serde(default)]
+    pub forwarded: Vec<Forwarded>,
+    /// The Message-ID of the message replied to, without angle brackets.
+    pub in_reply_to: Option<String>,
+    /// The Message-IDs of the earlier messages in the conversation, the oldest first.
+    #[serde(default)]
+    pub references: Vec<String>,
+}
+
+/// A part of a message of the account, attached to a new message by reference.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Forwarded {
+    pub message: Uuid,
+    pub part: i32,
+}
+
+/// The content of an attachment, to build a message with.
+pub struct Part {
+    pub filename: String,
+    pub content_type: String,
+    pub content: Vec<u8>,
+}
+
+/// The outcome of sending a message, per recipient.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Sent {
+    /// The copy in the Sent mailbox.
+    pub message: Uuid,
+    pub message_id: String,
+    /// Local recipients the message was delivered to.
+    pub delivered: Vec<String>,
+    /// Remote recipients, the message is queued for delivery to them.
+    pub queued: Vec<String>,
+    /// Local recipients which could not receive the message, as their mailbox is full.
+    pub rejected: Vec<String>,
+}
+
+impl Compose {
+    /// Send the message from an account, after saving a copy in its Sent mailbox.
+    /// Local recipients receive it directly, for the others it is queued.
+    /// Forwarding parts of encrypted messages requires the mailbox key.
+    /// Should run in a transaction, so the message is sent completely or not at all.
+    pub async fn send(
+        &self,
+        conn: &mut PgConnection,
+        store: &dyn BlobStore,
+        mailbox_key: Option<&MailboxKey>,
+        account: &Account,
+        quota: &QuotaConfig,
+    ) -> Result<Sent, SendError> {
+        self.validate()?;
+        if self.recipients().is_empty() {
+            return Err(SendError::NoRecipients);
+        }
+
+        // Local recipients have to exist, as nobody else could deliver to them.
+        // Mailing lists on hosted domains are queued as well, their delivery expands them.
+        let mut local = Vec::new();
+        let mut remote = Vec::new();
+        for recipient in self.recipients() {
+            let (username, domain) = match recipient.rsplit_once('@') {
+                Some(parts) => parts,
+                None => {
+                    remote.push(recipient);
+                    continue;
+                }
+            };
+            if !Domain::is_hosted(conn, domain).await? {
+                remote.push(recipient);
+                continue;
+            }
+
+            match Account::find_username(conn, username).await {
+                Ok(account) => local.push((recipient, account)),
+                Err(account::FindError::NotFound) => {
+                    match MailingList::resolve(conn, username, domain).await? {
+                        Some(_) => remote.push(recipient),
+                        None => return Err(SendError::UnknownRecipient),
+                    }
+                }
+                Err(account::FindError::DatabaseError(e)) => return Err(e.into()),
+            }
+        }
+
+        let parts = self.parts(conn, store, mailbox_key, account.id).await?;
+
+        let from = sender(account);
+        let message_id = format!("{}@{}", Uuid::new_v4(), DEFAULT_DOMAIN);
+        let date = OffsetDateTime::now_utc();
+
+        // Only the copy in Sent shows the Bcc recipients.
+        let copy = self.build(&from, &message_id, date, &parts, true);
+        let message = save(conn, store, account, Role::Sent, &copy, quota).await?;
+
+        let data = self.build(&from, &message_id, date, &parts, false);
+        let spooled = spool(&data).await?;
+        let structure = Structure::parse(&data);
+        let document = Document::extract(&structure);
+
+        let mut delivered = Vec::new();
+        let mut rejected = Vec::new();
+        for (recipient, account) in local {
+            let result = Message::deliver_protected(
+                conn, store, &account, &spooled, &structure, &document, quota,
+            )
+            .await;
+
+            match result {
+                Ok(_) => delivered.push(recipient),
+                Err(DeliverError::OverQuota(_)) => rejected.push(recipient),
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        if !remote.is_empty() {
+            Outbound::queue(conn, &from.address, &remote, &data).await?;
+        }
+
+        Ok(Sent {
+            message: message.id,
+            message_id,
+            delivered,
+            queued: remote,
+            rejected,
+        })
+    }
+
+    /// Load the content of the attachments, the uploads first and then the forwarded parts.
+    pub async fn parts(
+        &self,
+        conn: &mut PgConnection,
+        store: &dyn BlobStore,
+        mailbox_key: Option<&MailboxKey>,
+        account: Uuid,
+    ) -> Result<Vec<Part>, SendError> {
+        let uploads = Upload::find_many(conn, account, &self.attachments)
+            .await
+            .map_err(|e| match e {
+                upload::FindError::NotFound => SendError::AttachmentNotFound,
+                upload::FindError::DatabaseError(e) => SendError::DatabaseError(e),
+            })?;
+        if uploads.iter().map(|u| u.size).sum::<i64>() > upload::MAX_SIZE as i64 {
+            return Err(SendError::TooLarge);
+        }
+
+        let mut parts = Vec::with_capacity(uploads.len() + self.forwarded.len());
+        for upload in uploads {
+            parts.push(Part {
+                content: upload.load(conn, store).await?,
+                filename: upload.filename,
+                content_type: upload.content_type,
+            });
+        }
+        for forwarded in &self.forwarded {
+            parts.push(forwarded.load(conn, store, mailbox_key, account).await?);
+        }
+        if parts.iter().map(|p| p.content.len()).sum::<usize>() > upload::MAX_SIZE as usize {
+            return Err(SendError::TooLarge);
+        }
+
+        Ok(parts)
+    }
+
+    /// Check the addresses, names and subject, without requiring any recipients.
+    pub fn validate(&self) -> Result<(), SendError> {
+        let addresses = self.to.iter().chain(&self.cc).chain(&self.bcc);
+        if addresses.clone().count() > MAX_RECIPIENTS {
+            return Err(SendError::TooManyRecipients);
+        }
+
+        for address in addresses {
+            if !validate_address(address.address.trim()) {
+                return Err(SendError::InvalidAddress);
+            }
+            if let Some(name) = &address.name {
+                if name.chars().count() > MAX_NAME || name.chars().any(char::is_control) {
+                    return Err(SendError::InvalidAddress);
+                }
+            }
+        }
+
+        if self.subject.chars().count() > MAX_SUBJECT || self.subject.chars().any(char::is_control)
+        {
+            return Err(SendError::InvalidSubject);
+        }
+
+        if self.references.len() > thread::MAX_REFERENCES
+            || !self
+                .in_reply_to
+                .iter()
+                .chain(&self.references)
+                .all(|id| validate_message_id(id))
+        {
+            return Err(SendError::InvalidReference);
+        }
+
+        Ok(())
+    }
+
+    /// The addresses of all recipients, each once and in lowercase.
+    pub fn recipients(&self) -> Vec<String> {
+        let mut recipients: Vec<String> = Vec::new();
+
+        for address in self.to.iter().chain(&self.cc).chain(&self.bcc) {
+            let address = address.address.trim().to_lowercase();
+            if !recipients.contains(&address) {
+                recipients.push(address);
+            }
+        }
+
+        recipients
+    }
+
+    /// Build the raw message (RFC 5322), with its bodies and attachments in MIME parts.
+    /// Without a plain text body, it is generated from the HTML.
+    pub fn build(
+        &self,
+        from: &Address,
+        message_id: &str,
+        date: OffsetDateTime,
+        attachments: &[Part],
+        bcc: bool,
+    ) -> Vec<u8> {
+        let mut output = Vec::new();
+
+        header(
+            &mut output,
+            "From",
+            &format_addresses(std::slice::from_ref(from)),
+        );
+        for (name, addresses) in [("To", &self.to), ("Cc", &self.cc)] {
+            if !addresses.is_empty() {
+                header(&mut output, name, &format_addresses(addresses));
+            }
+        }
+        if bcc && !self.bcc.is_empty() {
+            header(&mut output, "Bcc", &format_addresses(&self.bcc));
+        }
+        header(&mut output, "Subject", &encode_words(self.subject.trim()));
+        header(
+            &mut output,
+            "Date",
+            &date.format("%a, %d %b %Y %H:%M:%S +0000"),
+        );
+        header(&mut output, "Message-ID", &format!("<{}>", message_id));
+        if let Some(id) = &self.in_reply_to {
+            header(&mut output, "In-Reply-To", &format!("<{}>", id));
+        }
+        if !self.references.is_empty() {
+            let references: Vec<String> = self
+                .references
+                .iter()
+                .map(|id| format!("<{}>", id))
+                .collect();
+            header(&mut output, "References", &references.join("\r\n "));
+        }
+        header(&mut output, "MIME-Version", "1.0");
+
+        let text = match (&self.text, &self.html) {
+            (Some(text), _) => text.clone(),
+            (None, Some(html)) => html::to_text(html),
+            (None, None) => String::new(),
+        };
+        let mut body = Entity::Text("plain", text);
+        if let Some(html) = &self.html {
+            body = Entity::Multipart(
+                "alternative",
+                vec![body, Entity::Text("html", html.clone())],
+            );
+        }
+        if !attachments.is_empty() {
+            let mut parts = vec![body];
+            parts.extend(attachments.iter().map(Entity::Attachment));
+            body = Entity::Multipart("mixed", parts);
+        }
+
+        body.write(&mut output);
+        output
+    }
+}
+
+impl Forwarded {
+    /// Load the content of the part, with the filename and type it has in the message.
+    async fn load(
+        &self,
+        conn: &mut PgConnection,
+        store: &dyn BlobStore,
+        mailbox_key: Option<&MailboxKey>,
+        account: Uuid,
+    ) -> Result<Part, SendError> {
+        let message = Message::find(conn, account, self.message)
+            .await
+            .map_err(|e| match e {
+                message::FindError::NotFound => SendError::AttachmentNotFound,
+                message::FindError::DatabaseError(e) => SendError::DatabaseError(e),
+            })?;
+        let (attachment, content) = Attachment::load(conn, store, mailbox_key, &message, self.part)
+            .await
+            .map_err(|e| match e {
+                attachment::LoadError::NotFound => SendError::AttachmentNotFound,
+                attachment::LoadError::ReadError(ReadError::Locked) => SendError::Locked,
+                attachment::LoadError::ReadError(ReadError::BlobError(e)) => {
+                    SendError::BlobError(e)
+                }
+                attachment::LoadError::DatabaseError(e) => SendError::DatabaseError(e),
+            })?;
+
+        Ok(Part {
+            filename: attachment
+                .filename
+                .unwrap_or_else(|| "attachment".to_string()),
+            content_type: attachment.content_type,
+            content,
+        })
+    }
+}
+
+/// A MIME entity of a message being built.
+enum Entity<'a> {
+    /// A text part with its subtype, in UTF-8.
+    Text(&'static str, String),
+    Attachment(&'a Part),
+    /// A multipart with its subtype, and the parts in it.
+    Multipart(&'static str, Vec<Entity<'a>>),
+}
+
+impl<'a> Entity<'a> {
+    /// Write the content headers and the body of the entity.
+    fn write(&self, output: &mut Vec<u8>) {
+        match self {
+            Entity::Text(subtype, text) => {
+                header(
+                    output,
+                    "Content-Type",
+                    &format!("text/{}; charset=utf-8", subtype),
+                );
+                header(output, "Content-Transfer-Encoding", "quoted-printable");
+                output.extend_from_slice(b"\r\n");
+
+                let text = text
+                    .replace("\r\n", "\n")
+                    .replace('\r', "\n")
+                    .replace('\n', "\r\n");
+                output.extend_from_slice(quoted_printable::encode_to_str(text).as_bytes());
+                output.extend_from_slice(b"\r\n");
+            }
+            Entity::Attachment(part) => {
+                header(
+                    output,
+                    "Content-Type",
+                    &format!(
+                        "{}; {}",
+                        part.content_type,
+                        parameter("name", &part.filename)
+                    ),
+                );
+                header(
+                    output,
+                    "Content-Disposition",
+                    &format!("attachment; {}", parameter("filename", &part.filename)),
+                );
+                header(output, "Content-Transfer-Encoding", "base64");
+                output.extend_from_slice(b"\r\n");
+
+                let encoded = base64::encode(&part.content);
+                for line in encoded.as_bytes().chunks(LINE_LENGTH) {
+                    output.extend_from_slice(line);
+                    output.extend_from_slice(b"\r\n");
+                }
+            }
+            Entity::Multipart(subtype, parts) => {
+                let boundary = hex::encode(crypto::random_bytes(16));
+                header(
+                    output,
+                    "Content-Type",
+                    &format!("multipart/{}; boundary=\"{}\"", subtype, boundary),
+                );
+                output.extend_from_slice(b"\r\n");
+
+                for part in parts {
+                    output.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+                    part.write(output);
+                }
+                output.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+            }
+        }
+    }
+}
+
+/// The address an account sends from, with its full name.
+pub fn sender(account: &Account) -> Address {
+    Address {
+        name: Some(account.full_name.clone()).filter(|n| !n.trim().is_empty()),
+        address: format!("{}@{}", account.username, DEFAULT_DOMAIN),
+    }
+}
+
+/// Save a raw message written by an account into its mailbox with a role, like Sent or Drafts.
+pub async fn save(
+    conn: &mut PgConnection,
+    store: &dyn BlobStore,
+    account: &Account,
+    role: Role,
+    data: &[u8],
+    quota: &QuotaConfig,
+) -> Result<Message, SendError> {
+    let spooled = spool(data).await?;
+    let structure = Structure::parse(data);
+    let document = Document::extract(&structure);
+
+    Message::save(
+        conn,
+        store,
+        account,
+        role,
+        &spooled,
+        &structure.attachments(),
+        &document,
+        quota,
+    )
+    .await
+    .map_err(|e| match e {
+        DeliverError::OverQuota(QuotaError::DatabaseError(e)) => SendError::DatabaseError(e),
+        DeliverError::OverQuota(_) => SendError::QuotaExceeded,
+        e => e.into(),
+    })
+}
+
+/// Write a raw message into a spool file.
+async fn spool(data: &[u8]) -> Result<Spooled, SendError> {
+    async {
+        let mut spool = Spool::create().await?;
+        spool.write(data).await?;
+        spool.finish().await
+    }
+    .await
+    .map_err(|e| SendError::BlobError(BlobError::StoreError(StoreError::Io(e))))
+}
+
+/// Check if an address is a valid mailbox, with a dot-atom local part and a domain name.
+/// Quoted local parts and address literals are not accepted.
+pub fn validate_address(address: &str) -> bool {
+    let (local, domain) = match address.rsplit_once('@') {
+        Some(parts) => parts,
+        None => return false,
+    };
+
+    let atext = |c: char| c.is_ascii_alphanumeric() || "!#$%&'*+-/=?^_`{|}~".contains(c);
+    let local_valid = !local.is_empty()
+        && local.len() <= 64
+        && local
+            .split('.')
+            .all(|a| !a.is_empty() && a.chars().all(atext));
+
+    let domain_valid = domain.len() <= 253
+        && domain.contains('.')
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        });
+
+    local_valid && domain_valid
+}
+
+/// Check if a Message-ID, without its angle brackets, can be written into a header as it is.
+pub fn validate_message_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 250
+        && id
+            .chars()
+            .all(|c| c.is_ascii_graphic() && c != '<' && c != '>')
+}
+
+/// Append a header field, the value has to be encoded already.
+fn header(output: &mut Vec<u8>, name: &str, value: &str) {
+    output.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+}
+
+/// Format addresses for a header, each on its own folded line.
+fn format_addresses(addresses: &[Address]) -> String {
+    addresses
+        .iter()
+        .map(|a| match a.name.as_deref().map(str::trim) {
+            Some(name) if !name.is_empty() => format!("{} <{}>", phrase(name), a.address.trim()),
+            _ => a.address.trim().to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(",\r\n ")
+}
+
+/// Encode a display name, as atoms, a quoted string or encoded-words.
+fn phrase(name: &str) -> String {
+    if !name.is_ascii() {
+        return encode_words(name);
+    }
+
+    let atom = |c: char| c.is_ascii_alphanumeric() || " !#$%&'*+-/=?^_`{|}~".contains(c);
+    match name.chars().all(atom) {
+        true => name.to_string(),
+        false => format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\"")),
+    }
+}
+
+/// Encode text for a header as encoded-words (RFC 2047) if it is not plain ASCII.
+/// The words are folded onto their own lines, splitting only between characters.
+pub fn encode_words(text: &str) -> String {
+    let plain = text.chars().all(|c| c == ' ' || c.is_ascii_graphic());
+    if plain && !text.contains("=?") {
+        return text.to_string();
+    }
+
+    let mut words = Vec::new();
+    let mut start = 0;
+    let mut end = 0;
+    for (i, c) in text.char_indices() {
+        if i + c.len_utf8() - start > WORD_BYTES {
+            words.push(&text[start..end]);
+            start = i;
+        }
+        end = i + c.len_utf8();
+    }
+    words.push(&text[start..end]);
+
+    words
+        .iter()
+        .map(|w| format!("=?utf-8?B?{}?=", base64::encode(w)))
+        .collect::<Vec<_>>()
+        .join("\r\n ")
+}
+
+/// Format a parameter of a content header, like the filename.
+/// Values which are not plain ASCII use the extended syntax (RFC 2231).
+fn parameter(name: &str, value: &str) -> String {
+    if value.chars().all(|c| c == ' ' || c.is_ascii_graphic()) {
+        return format!(
+            "{}=\"{}\"",
+            name,
+            value.replace('\\', "\\\\").replace('"', "\\\"")
+        );
+    }
+
+    let encoded: String = value
+        .bytes()
+        .map(
+            |b| match b.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&b) {
+                true => (b as char).to_string(),
+                false => format!("%{:02X}", b),
+            },
+        )
+        .collect();
+    format!("{}*=utf-8''{}", name, encoded)
+}
+
+/// Possible errors with sending a message.
+#[derive(Error, Debug)]
+pub enum SendError {
+    #[error("The message has no recipients.")]
+    NoRecipients,
+    #[error("The message has too many recipients.")]
+    TooManyRecipients,
+    #[error("An address or display name is invalid.")]
+    InvalidAddress,
+    #[error("The subject is too long or contains invalid characters.")]
+    InvalidSubject,
+    #[error("A Message-ID of In-Reply-To or References is invalid, or there are too many.")]
+    InvalidReference,
+    #[error("A local recipient does not exist.")]
+    UnknownRecipient,
+    #[error("An attachment was not found, or has expired.")]
+    AttachmentNotFound,
+    #[error("The attachments are too large.")]
+    TooLarge,
+    #[error("A forwarded message is encrypted and the mailbox key is not unlocked.")]
+    Locked,
+    #[error("The mailbox is full.")]
+    QuotaExceeded,
+    #[error("{0}")]
+    BlobError(#[from] BlobError),
+    #[error("{0}")]
+    DeliverError(DeliverError),
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Keep database errors apart, so they are reported the same everywhere.
+impl From<DeliverError> for SendError {
+    fn from(err: DeliverError) -> Self {
+        match err {
+            DeliverError::DatabaseError(e) => SendError::DatabaseError(e),
+            DeliverError::BlobError(e) => SendError::BlobError(e),
+            e => SendError::DeliverError(e),
+        }
+    }
+}
@@ -0,0 +1,239 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sqlx::PgConnection;
+use thiserror::Error;
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+
+use crate::database;
+
+/// The domain of the accounts, which is hosted without being configured.
+pub const DEFAULT_DOMAIN: &str = "nexium.app";
+
+/// The local part of the address receiving TLS-RPT reports on every domain.
+pub const TLSRPT_LOCAL: &str = "tlsrpt";
+
+/// The maximum MTA-STS policy lifetime allowed by RFC 8461, one year.
+const MAX_POLICY_AGE: i32 = 31_557_600;
+
+/// Represents a domain for which this server receives mail.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Domain {
+    pub name: String,
+    pub mta_sts_mode: MtaStsMode,
+    pub mta_sts_mx: Vec<String>,
+    pub mta_sts_max_age: i32,
+    pub mta_sts_id: String,
+}
+
+/// The mode of an MTA-STS policy.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "mta_sts_mode", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum MtaStsMode {
+    Enforce,
+    Testing,
+    None,
+}
+
+/// A DNS record which has to be published for a domain.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DnsRecord {
+    pub name: String,
+    pub kind: &'static str,
+    pub value: String,
+}
+
+impl MtaStsMode {
+    /// The textual representation used in the policy file.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MtaStsMode::Enforce => "enforce",
+            MtaStsMode::Testing => "testing",
+            MtaStsMode::None => "none",
+        }
+    }
+}
+
+impl Domain {
+    /// Create a new domain.
+    /// The MTA-STS policy starts in the `none` mode.
+    pub async fn create(conn: &mut PgConnection, name: &str) -> Result<Self, CreateError> {
+        let name = name.to_lowercase();
+
+        if !Self::validate_name(&name) {
+            return Err(CreateError::InvalidName(name));
+        }
+
+        if database::domain::find(conn, &name).await?.is_some() {
+            return Err(CreateError::DomainExists(name));
+        }
+
+        Ok(database::domain::create(conn, &name, &Self::generate_policy_id()).await?)
+    }
+
+    /// Create the default domain like the configured ones, so it has a policy and its TLS reports are kept.
+    /// Returns true if it did not exist yet.
+    pub async fn create_default(conn: &mut PgConnection) -> Result<bool, sqlx::Error> {
+        database::domain::create_missing(conn, DEFAULT_DOMAIN, &Self::generate_policy_id()).await
+    }
+
+    /// Find a domain by name.
+    pub async fn find(conn: &mut PgConnection, name: &str) -> Result<Self, FindError> {
+        let res = database::domain::find(conn, &name.to_lowercase()).await?;
+
+        match res {
+            Some(domain) => Ok(domain),
+            None => Err(FindError::NotFound),
+        }
+    }
+
+    /// Check if mail for a domain is received by this server.
+    /// These are the domain of the accounts, and every configured domain.
+    pub async fn is_hosted(conn: &mut PgConnection, name: &str) -> Result<bool, sqlx::Error> {
+        let name = name.to_lowercase();
+
+        if name == DEFAULT_DOMAIN {
+            return Ok(true);
+        }
+
+        Ok(database::domain::find(conn, &name).await?.is_some())
+    }
+
+    /// List all domains.
+    pub async fn list(conn: &mut PgConnection) -> Result<Vec<Self>, sqlx::Error> {
+        database::domain::list(conn).await
+    }
+
+    /// Update the MTA-STS policy of this domain.
+    /// A new policy ID is generated, so that senders refetch the policy.
+    pub async fn update_mta_sts(
+        &mut self,
+        conn: &mut PgConnection,
+        mode: MtaStsMode,
+        mx: Vec<String>,
+        max_age: i32,
+    ) -> Result<(), UpdateError> {
+        if !(0..=MAX_POLICY_AGE).contains(&max_age) {
+            return Err(UpdateError::InvalidMaxAge);
+        }
+
+        let mx: Vec<String> = mx.iter().map(|m| m.to_lowercase()).collect();
+        if let Some(invalid) = mx.iter().find(|m| !Self::validate_mx(m)) {
+            return Err(UpdateError::InvalidMx(invalid.clone()));
+        }
+
+        if mode != MtaStsMode::None && mx.is_empty() {
+            return Err(UpdateError::MissingMx);
+        }
+
+        let updated = database::domain::update_mta_sts(
+            conn,
+            &self.name,
+            mode,
+            &mx,
+            max_age,
+            &Self::generate_policy_id(),
+        )
+        .await?
+        .ok_or(UpdateError::NotFound)?;
+
+        *self = updated;
+
+        Ok(())
+    }
+
+    /// Render the MTA-STS policy file, served at `https://mta-sts.<domain>/.well-known/mta-sts.txt`.
+    pub fn mta_sts_policy(&self) -> String {
+        let mut policy = format!("version: STSv1\r\nmode: {}\r\n", self.mta_sts_mode.as_str());
+
+        for mx in &self.mta_sts_mx {
+            policy.push_str(&format!("mx: {}\r\n", mx));
+        }
+
+        policy.push_str(&format!("max_age: {}\r\n", self.mta_sts_max_age));
+
+        policy
+    }
+
+    /// The DNS records to publish for MTA-STS and TLS reporting.
+    /// The `_mta-sts` record carries the policy ID, and has to be updated after every policy change.
+    pub fn dns_records(&self) -> Vec<DnsRecord> {
+        vec![
+            DnsRecord {
+                name: format!("_mta-sts.{}", self.name),
+                kind: "TXT",
+                value: format!("v=STSv1; id={};", self.mta_sts_id),
+            },
+            DnsRecord {
+                name: format!("_smtp._tls.{}", self.name),
+                kind: "TXT",
+                value: format!("v=TLSRPTv1; rua=mailto:{};", self.tlsrpt_address()),
+            },
+        ]
+    }
+
+    /// The address on which TLS-RPT reports for this domain are received.
+    pub fn tlsrpt_address(&self) -> String {
+        format!("{}@{}", TLSRPT_LOCAL, self.name)
+    }
+
+    /// Generate a new MTA-STS policy ID.
+    /// It is based on the current time, so that it changes with every update.
+    fn generate_policy_id() -> String {
+        OffsetDateTime::now_utc().unix_timestamp_nanos().to_string()
+    }
+
+    /// Validate that a domain name consists of valid labels.
+    fn validate_name(name: &str) -> bool {
+        lazy_static! {
+            static ref REGEX: Regex =
+                Regex::new("^([a-z0-9]([a-z0-9-]{0,61}[a-z0-9])?\\.)+[a-z]{2,63}$").unwrap();
+        }
+
+        name.len() <= 253 && REGEX.is_match(name)
+    }
+
+    /// Validate an MX pattern of a policy, which may start with a wildcard label.
+    fn validate_mx(mx: &str) -> bool {
+        Self::validate_name(mx.strip_prefix("*.").unwrap_or(mx))
+    }
+}
+
+/// Possible errors with creating a new domain.
+#[derive(Error, Debug)]
+pub enum CreateError {
+    #[error("The domain name '{0}' is invalid.")]
+    InvalidName(String),
+    #[error("The domain '{0}' already exists.")]
+    DomainExists(String),
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Possible errors with finding a domain.
+#[derive(Error, Debug)]
+pub enum FindError {
+    #[error("The domain was not found.")]
+    NotFound,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Possible errors with updating the MTA-STS policy of a domain.
+#[derive(Error, Debug)]
+pub enum UpdateError {
+    #[error("The domain was not found.")]
+    NotFound,
+    #[error("The MX pattern '{0}' is invalid.")]
+    InvalidMx(String),
+    #[error("At least one MX pattern is required.")]
+    MissingMx,
+    #[error("The maximum age is out of range.")]
+    InvalidMaxAge,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
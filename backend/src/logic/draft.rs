@@ -0,0 +1,419 @@
+use serde::Serialize;
+use sqlx::{types::Json, Connection, PgConnection};
+use thiserror::Error;
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    crypto, database,
+    logic::{
+        account::Account,
+        attachment::Disposition,
+        compose::{self, Compose, Forwarded, SendError, Sent},
+        domain::DEFAULT_DOMAIN,
+        mailbox::Role,
+        mailbox_key::{KeyError, MailboxKey},
+        message::{self, Message},
+        mime::Structure,
+        quota::QuotaConfig,
+    },
+    storage::BlobStore,
+};
+
+/// The number of drafts sealed at once, when sealing those saved before drafts were sealed.
+const SEAL_BATCH: i64 = 100;
+
+/// Represents a message an account is still writing, saved so it can continue on any device.
+/// Each save stores the message in the Drafts mailbox, replacing the one of the previous version.
+/// The attachments are kept in that message, so they are referenced as forwarded parts of it.
+/// For accounts with a mailbox key the content is sealed to it, like the message itself.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Draft {
+    pub id: Uuid,
+    /// The message of the draft in the Drafts mailbox.
+    pub message: Uuid,
+    /// Increased on every save, a save based on an older version is rejected.
+    pub version: i64,
+    #[schema(value_type = Compose)]
+    pub content: Json<Compose>,
+    #[serde(with = "time::serde::timestamp")]
+    pub updated: OffsetDateTime,
+}
+
+/// A draft as stored, of which the content is sealed to the mailbox key of the account if it has one.
+#[derive(Debug)]
+pub struct Stored {
+    pub id: Uuid,
+    pub message: Uuid,
+    pub version: i64,
+    pub content: Option<Json<Compose>>,
+    pub sealed_content: Option<Vec<u8>>,
+    pub updated: OffsetDateTime,
+}
+
+impl Stored {
+    /// Open the content of the draft, sealed content needs the unlocked mailbox key.
+    fn open(self, mailbox_key: Option<&MailboxKey>) -> Result<Draft, FindError> {
+        let content = match (self.content, &self.sealed_content, mailbox_key) {
+            (Some(content), _, _) => content,
+            (None, Some(sealed), Some(key)) => {
+                let data = key.unseal(sealed).map_err(|_| FindError::Locked)?;
+                serde_json::from_slice(&data).map_err(|_| FindError::Locked)?
+            }
+            _ => return Err(FindError::Locked),
+        };
+
+        Ok(Draft {
+            id: self.id,
+            message: self.message,
+            version: self.version,
+            content,
+            updated: self.updated,
+        })
+    }
+
+    /// Turn the draft into the one with the content as it was saved.
+    fn with_content(self, content: Compose) -> Draft {
+        Draft {
+            id: self.id,
+            message: self.message,
+            version: self.version,
+            content: Json(content),
+            updated: self.updated,
+        }
+    }
+}
+
+impl Draft {
+    /// Save a new draft for an account.
+    /// Recipients are not required yet, but the addresses which are given have to be valid.
+    pub async fn create(
+        conn: &mut PgConnection,
+        store: &dyn BlobStore,
+        mailbox_key: Option<&MailboxKey>,
+        account: &Account,
+        content: &Compose,
+        quota: &QuotaConfig,
+    ) -> Result<Self, DraftError> {
+        let id = Uuid::new_v4();
+        let (message, content) =
+            write(conn, store, mailbox_key, account, id, content, quota).await?;
+        let sealed = seal(conn, account.id, &content).await?;
+
+        let stored = match &sealed {
+            Some(sealed) => {
+                database::draft::create(conn, id, account.id, message.id, None, Some(sealed))
+                    .await?
+            }
+            None => {
+                database::draft::create(conn, id, account.id, message.id, Some(&content), None)
+                    .await?
+            }
+        };
+
+        Ok(stored.with_content(content))
+    }
+
+    /// List the drafts of an account, the most recently changed first.
+    pub async fn list(
+        conn: &mut PgConnection,
+        mailbox_key: Option<&MailboxKey>,
+        account: Uuid,
+    ) -> Result<Vec<Self>, FindError> {
+        database::draft::list(conn, account)
+            .await?
+            .into_iter()
+            .map(|stored| stored.open(mailbox_key))
+            .collect()
+    }
+
+    /// Find a draft of an account by ID.
+    pub async fn find(
+        conn: &mut PgConnection,
+        mailbox_key: Option<&MailboxKey>,
+        account: Uuid,
+        id: Uuid,
+    ) -> Result<Self, FindError> {
+        let res = database::draft::find(conn, account, id).await?;
+
+        match res {
+            Some(stored) => stored.open(mailbox_key),
+            None => Err(FindError::NotFound),
+        }
+    }
+
+    /// Find a draft of an account by ID, locking it until the end of the transaction.
+    /// Saves of the draft from other devices wait, so they see the new version.
+    pub async fn lock(
+        conn: &mut PgConnection,
+        mailbox_key: Option<&MailboxKey>,
+        account: Uuid,
+        id: Uuid,
+    ) -> Result<Self, FindError> {
+        let res = database::draft::lock(conn, account, id).await?;
+
+        match res {
+            Some(stored) => stored.open(mailbox_key),
+            None => Err(FindError::NotFound),
+        }
+    }
+
+    /// Save new content as the next version of the draft.
+    /// Fails with a conflict when the draft was saved since the version the content is based on.
+    /// Should run in a transaction, after locking the draft.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn update(
+        self,
+        conn: &mut PgConnection,
+        store: &dyn BlobStore,
+        mailbox_key: Option<&MailboxKey>,
+        account: &Account,
+        version: i64,
+        content: &Compose,
+        quota: &QuotaConfig,
+    ) -> Result<Self, DraftError> {
+        if self.version != version {
+            return Err(DraftError::Conflict);
+        }
+
+        let (message, content) =
+            write(conn, store, mailbox_key, account, self.id, content, quota).await?;
+        let sealed = seal(conn, account.id, &content).await?;
+
+        let stored = match &sealed {
+            Some(sealed) => {
+                database::draft::update(conn, self.id, message.id, None, Some(sealed)).await?
+            }
+            None => {
+                database::draft::update(conn, self.id, message.id, Some(&content), None).await?
+            }
+        };
+        release(conn, account.id, self.message, quota).await?;
+
+        Ok(stored.with_content(content))
+    }
+
+    /// Add uploads as attachments to the latest version of the draft.
+    /// Nothing else changes, so no version has to be given.
+    /// Should run in a transaction, after locking the draft.
+    pub async fn attach(
+        self,
+        conn: &mut PgConnection,
+        store: &dyn BlobStore,
+        mailbox_key: Option<&MailboxKey>,
+        account: &Account,
+        uploads: &[Uuid],
+        quota: &QuotaConfig,
+    ) -> Result<Self, DraftError> {
+        let mut content = self.content.0.clone();
+        content.attachments.extend(uploads);
+        let version = self.version;
+
+        self.update(conn, store, mailbox_key, account, version, &content, quota)
+            .await
+    }
+
+    /// Send the draft, and delete it with its message.
+    /// Should run in a transaction after locking the draft, so it is not sent twice.
+    pub async fn send(
+        self,
+        conn: &mut PgConnection,
+        store: &dyn BlobStore,
+        mailbox_key: Option<&MailboxKey>,
+        account: &Account,
+        version: i64,
+        quota: &QuotaConfig,
+    ) -> Result<Sent, DraftError> {
+        if self.version != version {
+            return Err(DraftError::Conflict);
+        }
+
+        let sent = self
+            .content
+            .send(conn, store, mailbox_key, account, quota)
+            .await?;
+        discard(conn, account.id, self.id, self.message, quota).await?;
+
+        Ok(sent)
+    }
+
+    /// Delete a draft of an account by ID, with its message.
+    /// The content does not have to be readable for this.
+    /// Returns false when the account has no such draft. Should run in a transaction.
+    pub async fn delete(
+        conn: &mut PgConnection,
+        account: Uuid,
+        id: Uuid,
+        quota: &QuotaConfig,
+    ) -> Result<bool, sqlx::Error> {
+        let stored = match database::draft::lock(conn, account, id).await? {
+            Some(stored) => stored,
+            None => return Ok(false),
+        };
+        discard(conn, account, stored.id, stored.message, quota).await?;
+
+        Ok(true)
+    }
+
+    /// Seal the content of drafts saved before drafts were sealed.
+    /// Only the public mailbox key is needed, so this runs without the users.
+    pub async fn seal_existing(conn: &mut PgConnection) -> Result<usize, sqlx::Error> {
+        let mut sealed = 0;
+
+        loop {
+            let mut tx = conn.begin().await?;
+            let drafts = database::draft::lock_unsealed(&mut tx, SEAL_BATCH).await?;
+            if drafts.is_empty() {
+                return Ok(sealed);
+            }
+
+            for (id, content, public) in drafts {
+                // Keys which can not be used are left, their content stays as it is.
+                let public = match crypto::to_key(&public) {
+                    Ok(public) => public,
+                    Err(_) => continue,
+                };
+                let data = serde_json::to_vec(&content.0)
+                    .expect("Serialization failed, which should not be impossible.");
+
+                database::draft::seal(&mut tx, id, &crypto::seal(&public, &data)).await?;
+                sealed += 1;
+            }
+
+            tx.commit().await?;
+        }
+    }
+}
+
+/// Store the content of a draft as a message in the Drafts mailbox.
+/// Returns the content as it is saved, with the attachments referencing the parts of the message.
+async fn write(
+    conn: &mut PgConnection,
+    store: &dyn BlobStore,
+    mailbox_key: Option<&MailboxKey>,
+    account: &Account,
+    id: Uuid,
+    content: &Compose,
+    quota: &QuotaConfig,
+) -> Result<(Message, Compose), SendError> {
+    content.validate()?;
+    let parts = content.parts(conn, store, mailbox_key, account.id).await?;
+
+    // All versions of a draft have the same Message-ID, they are replaced by the sent message.
+    let message_id = format!("{}@{}", id, DEFAULT_DOMAIN);
+    let data = content.build(
+        &compose::sender(account),
+        &message_id,
+        OffsetDateTime::now_utc(),
+        &parts,
+        true,
+    );
+    let message = compose::save(conn, store, account, Role::Drafts, &data, quota).await?;
+
+    // The attachments are built in the order of the parts, after the bodies.
+    let forwarded = Structure::parse(&data)
+        .attachments()
+        .into_iter()
+        .filter(|a| a.disposition == Disposition::Attachment)
+        .map(|a| Forwarded {
+            message: message.id,
+            part: a.part,
+        })
+        .collect();
+
+    let content = Compose {
+        attachments: Vec::new(),
+        forwarded,
+        ..content.clone()
+    };
+
+    Ok((message, content))
+}
+
+/// Seal the content of a draft to the mailbox key of an account, None if the account has no key.
+async fn seal(
+    conn: &mut PgConnection,
+    account: Uuid,
+    content: &Compose,
+) -> Result<Option<Vec<u8>>, DraftError> {
+    let public = match MailboxKey::public(conn, account).await? {
+        Some(public) => public,
+        None => return Ok(None),
+    };
+    let data =
+        serde_json::to_vec(content).expect("Serialization failed, which should not be impossible.");
+
+    Ok(Some(crypto::seal(&public, &data)))
+}
+
+/// Delete a draft with its message.
+async fn discard(
+    conn: &mut PgConnection,
+    account: Uuid,
+    id: Uuid,
+    message: Uuid,
+    quota: &QuotaConfig,
+) -> Result<(), sqlx::Error> {
+    database::draft::delete(conn, id).await?;
+    release(conn, account, message, quota).await
+}
+
+/// Delete the message of a previous version of a draft, unless it was deleted already.
+async fn release(
+    conn: &mut PgConnection,
+    account: Uuid,
+    message: Uuid,
+    quota: &QuotaConfig,
+) -> Result<(), sqlx::Error> {
+    match Message::find(conn, account, message).await {
+        Ok(message) => message.delete(conn, account, quota).await,
+        Err(message::FindError::NotFound) => Ok(()),
+        Err(message::FindError::DatabaseError(e)) => Err(e),
+    }
+}
+
+/// Possible errors with saving or sending a draft.
+#[derive(Error, Debug)]
+pub enum DraftError {
+    #[error("The draft was saved since the version the changes are based on.")]
+    Conflict,
+    #[error("{0}")]
+    SendError(SendError),
+    #[error("{0}")]
+    MailboxKey(KeyError),
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Keep database errors apart, so they are reported the same everywhere.
+impl From<SendError> for DraftError {
+    fn from(err: SendError) -> Self {
+        match err {
+            SendError::DatabaseError(e) => DraftError::DatabaseError(e),
+            e => DraftError::SendError(e),
+        }
+    }
+}
+
+/// Keep database errors apart, so they are reported the same everywhere.
+impl From<KeyError> for DraftError {
+    fn from(err: KeyError) -> Self {
+        match err {
+            KeyError::DatabaseError(e) => DraftError::DatabaseError(e),
+            e => DraftError::MailboxKey(e),
+        }
+    }
+}
+
+/// Possible errors with finding a draft.
+#[derive(Error, Debug)]
+pub enum FindError {
+    #[error("The draft was not found.")]
+    NotFound,
+    #[error("The draft is sealed and the mailbox key is not unlocked.")]
+    Locked,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
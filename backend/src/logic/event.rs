@@ -0,0 +1,142 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgListener, PgConnection, Pool, Postgres};
+use tokio::sync::broadcast;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::database;
+
+/// The Postgres channel the events are published on, also used by the triggers on messages and mailboxes.
+pub const CHANNEL: &str = "nexium_events";
+
+/// The amount of events of an account kept for its subscribers which fall behind.
+const CAPACITY: usize = 256;
+
+/// Time to wait before listening again, after the connection to the database was lost.
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A change clients of an account are told about, as it happens.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Event {
+    MessageCreated {
+        message: Uuid,
+        mailbox: Uuid,
+    },
+    /// The flags, keywords, labels or mailbox of the message changed.
+    MessageUpdated {
+        message: Uuid,
+        mailbox: Uuid,
+    },
+    MessageDeleted {
+        message: Uuid,
+        mailbox: Uuid,
+    },
+    /// The counters of a mailbox changed, with the new ones.
+    MailboxChanged {
+        mailbox: Uuid,
+        total: i64,
+        unread: i64,
+    },
+    /// The session logged out, only its own event stream is told.
+    SessionRevoked {
+        session: Uuid,
+    },
+    /// The password changed in the session, which revoked the other sessions of the account.
+    /// Their event streams are told, so they end.
+    OtherSessionsRevoked {
+        session: Uuid,
+    },
+}
+
+/// An event for an account, as it is published on the channel.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    pub account: Uuid,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+/// Distributes the events published by every server to the event streams of this one.
+/// Changes to messages and mailboxes are published by the database itself, within the transaction making them.
+/// Every account has its own channel, so a busy account does not make the streams of others fall behind.
+#[derive(Clone, Default)]
+pub struct Events {
+    senders: Arc<Mutex<HashMap<Uuid, broadcast::Sender<Event>>>>,
+}
+
+impl Events {
+    /// Receive the events of an account published from now on.
+    pub fn subscribe(&self, account: Uuid) -> broadcast::Receiver<Event> {
+        let mut senders = self.senders.lock().unwrap();
+
+        senders
+            .entry(account)
+            .or_insert_with(|| broadcast::channel(CAPACITY).0)
+            .subscribe()
+    }
+
+    /// Pass an event on to the subscribers of its account.
+    /// The channel of an account is dropped once its last subscriber is gone.
+    fn send(&self, notification: Notification) {
+        let mut senders = self.senders.lock().unwrap();
+
+        if let Some(sender) = senders.get(&notification.account) {
+            // Sending only fails without subscribers.
+            if sender.send(notification.event).is_err() {
+                senders.remove(&notification.account);
+            }
+        }
+    }
+
+    /// Listen on the channel, and pass the events on to the subscribers.
+    /// Events published while the connection to the database is lost are missed.
+    pub async fn listen(self, db: Pool<Postgres>) {
+        info!("Starting event distribution");
+
+        loop {
+            match self.receive(&db).await {
+                Ok(()) => warn!("Lost the connection while listening for events."),
+                Err(e) => warn!("Failed to listen for events: {}", e),
+            }
+
+            tokio::time::sleep(RETRY_INTERVAL).await;
+        }
+    }
+
+    /// Receive the events of the channel, until the connection is lost.
+    async fn receive(&self, db: &Pool<Postgres>) -> Result<(), sqlx::Error> {
+        let mut listener = PgListener::connect_with(db).await?;
+        listener.listen(CHANNEL).await?;
+
+        while let Some(notification) = listener.try_recv().await? {
+            match serde_json::from_str(notification.payload()) {
+                Ok(notification) => self.send(notification),
+                Err(e) => warn!("Received a malformed event: {}", e),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Notification {
+    /// Publish an event for an account to the event streams of every server.
+    /// Within a transaction, it is only published once the transaction commits.
+    pub async fn publish(
+        conn: &mut PgConnection,
+        account: Uuid,
+        event: Event,
+    ) -> Result<(), sqlx::Error> {
+        let payload = serde_json::to_string(&Notification { account, event })
+            .expect("Serialization failed, which should not be impossible.");
+
+        database::event::notify(conn, CHANNEL, &payload).await
+    }
+}
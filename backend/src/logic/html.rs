@@ -0,0 +1,894 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+/// Elements which are kept when sanitizing, everything else is removed with its attributes.
+const ALLOWED_ELEMENTS: [&str; 69] = [
+    "a",
+    "abbr",
+    "address",
+    "article",
+    "aside",
+    "b",
+    "bdi",
+    "bdo",
+    "big",
+    "blockquote",
+    "br",
+    "caption",
+    "center",
+    "cite",
+    "code",
+    "col",
+    "colgroup",
+    "dd",
+    "del",
+    "details",
+    "dfn",
+    "div",
+    "dl",
+    "dt",
+    "em",
+    "figcaption",
+    "figure",
+    "font",
+    "footer",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "i",
+    "img",
+    "ins",
+    "kbd",
+    "li",
+    "mark",
+    "ol",
+    "p",
+    "pre",
+    "q",
+    "s",
+    "section",
+    "small",
+    "span",
+    "strike",
+    "strong",
+    "sub",
+    "summary",
+    "sup",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "time",
+    "tr",
+    "tt",
+    "u",
+    "ul",
+    "var",
+    "wbr",
+];
+
+/// Elements which never have content or an end tag.
+const VOID_ELEMENTS: [&str; 5] = ["br", "col", "hr", "img", "wbr"];
+
+/// Elements which are removed together with everything in them.
+const DROPPED_ELEMENTS: [&str; 7] = [
+    "applet", "frameset", "math", "object", "select", "svg", "template",
+];
+
+/// Elements of which the content is not HTML, it is skipped up to their end tag.
+/// Their content is never kept.
+const RAW_TEXT_ELEMENTS: [&str; 10] = [
+    "iframe",
+    "noembed",
+    "noframes",
+    "noscript",
+    "plaintext",
+    "script",
+    "style",
+    "textarea",
+    "title",
+    "xmp",
+];
+
+/// Attributes which are kept on all allowed elements.
+/// Links and images have their URLs checked separately, styles are checked as well.
+const ALLOWED_ATTRIBUTES: [&str; 27] = [
+    "abbr",
+    "align",
+    "alt",
+    "bgcolor",
+    "border",
+    "cellpadding",
+    "cellspacing",
+    "color",
+    "colspan",
+    "datetime",
+    "dir",
+    "face",
+    "headers",
+    "height",
+    "lang",
+    "nowrap",
+    "open",
+    "reversed",
+    "rowspan",
+    "scope",
+    "size",
+    "span",
+    "start",
+    "style",
+    "title",
+    "valign",
+    "width",
+];
+
+/// Elements after which text continues on a new line.
+const BLOCK_ELEMENTS: [&str; 26] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "br",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "footer",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "tr",
+    "ul",
+];
+
+/// Patterns in styles which can run script, load content or escape the checks.
+const FORBIDDEN_STYLE: [&str; 8] = [
+    "\\",
+    "/*",
+    "url(",
+    "image-set(",
+    "expression",
+    "javascript:",
+    "@import",
+    "binding",
+];
+
+/// A transparent image shown instead of a blocked remote image.
+const PLACEHOLDER: &str =
+    "data:image/gif;base64,R0lGODlhAQABAIAAAAAAAP///yH5BAEAAAAALAAAAAABAAEAAAIBRAA7";
+
+/// The image types kept as data URLs.
+const DATA_IMAGES: [&str; 4] = [
+    "data:image/png;base64,",
+    "data:image/gif;base64,",
+    "data:image/jpeg;base64,",
+    "data:image/webp;base64,",
+];
+
+/// HTML which is safe to show in the client, and how many remote images were blocked in it.
+#[derive(Debug, PartialEq)]
+pub struct Sanitized {
+    pub html: String,
+    pub blocked: usize,
+}
+
+/// Sanitize HTML against an allowlist of elements and attributes.
+/// Scripts, forms, frames, event handlers and URLs other than web, mail and phone links are removed.
+/// Remote images are replaced by a placeholder unless allowed, their URL is kept in `data-blocked-src`.
+/// The result is well-formed, all elements are closed in order.
+pub fn sanitize(html: &str, remote: bool) -> Sanitized {
+    let mut output = String::with_capacity(html.len());
+    let mut open: Vec<String> = Vec::new();
+    let mut blocked = 0;
+    // The element being dropped with its content, and how deep it is nested in itself.
+    let mut dropping: Option<(String, usize)> = None;
+
+    for token in Tokenizer::new(html) {
+        let tag = match token {
+            Token::Text(text) => {
+                if dropping.is_none() {
+                    output.push_str(&escape(&decode_entities(text)));
+                }
+                continue;
+            }
+            Token::Tag(tag) => tag,
+        };
+
+        if let Some((name, depth)) = &mut dropping {
+            if *name == tag.name {
+                match tag.closing {
+                    true => *depth -= 1,
+                    false => *depth += 1,
+                }
+            }
+            if *depth == 0 {
+                dropping = None;
+            }
+            continue;
+        }
+
+        if DROPPED_ELEMENTS.contains(&tag.name.as_str()) {
+            if !tag.closing {
+                dropping = Some((tag.name, 1));
+            }
+            continue;
+        }
+
+        if !ALLOWED_ELEMENTS.contains(&tag.name.as_str()) {
+            continue;
+        }
+
+        if tag.closing {
+            // End tags without a matching start tag are left out.
+            if let Some(index) = open.iter().rposition(|name| *name == tag.name) {
+                for name in open.drain(index..).rev() {
+                    output.push_str(&format!("</{}>", name));
+                }
+            }
+            continue;
+        }
+
+        output.push('<');
+        output.push_str(&tag.name);
+        let mut seen: Vec<&str> = Vec::new();
+        for (attribute, value) in &tag.attributes {
+            // Browsers use the first of duplicate attributes.
+            if seen.contains(&attribute.as_str()) {
+                continue;
+            }
+            seen.push(attribute);
+
+            let value = decode_entities(value);
+            let value = match (tag.name.as_str(), attribute.as_str()) {
+                ("a", "href") => safe_link(&value),
+                ("img", "src") => match image(&value) {
+                    Image::Remote(url) if !remote => {
+                        blocked += 1;
+                        output.push_str(&format!(" data-blocked-src=\"{}\"", escape(&url)));
+                        Some(PLACEHOLDER.to_string())
+                    }
+                    Image::Remote(url) | Image::Local(url) => Some(url),
+                    Image::Invalid => None,
+                },
+                (_, "style") => safe_style(&value),
+                (_, attribute) if ALLOWED_ATTRIBUTES.contains(&attribute) => Some(value),
+                _ => None,
+            };
+
+            if let Some(value) = value {
+                output.push_str(&format!(" {}=\"{}\"", attribute, escape(&value)));
+            }
+        }
+
+        // Links open outside of the client, without access to it.
+        if tag.name == "a" {
+            output.push_str(" target=\"_blank\" rel=\"noopener noreferrer\"");
+        }
+        output.push('>');
+
+        if !VOID_ELEMENTS.contains(&tag.name.as_str()) {
+            open.push(tag.name);
+        }
+    }
+
+    for name in open.iter().rev() {
+        output.push_str(&format!("</{}>", name));
+    }
+
+    Sanitized {
+        html: output,
+        blocked,
+    }
+}
+
+/// Convert HTML to plain text, with the blocks on their own lines.
+/// The content of scripts and styles is dropped.
+pub fn to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+
+    for token in Tokenizer::new(html) {
+        match token {
+            Token::Text(value) => {
+                let value = decode_entities(value);
+                let mut words = value.split_whitespace().peekable();
+
+                if value.starts_with(char::is_whitespace) && words.peek().is_some() {
+                    text.push(' ');
+                }
+                while let Some(word) = words.next() {
+                    text.push_str(word);
+                    if words.peek().is_some() || value.ends_with(char::is_whitespace) {
+                        text.push(' ');
+                    }
+                }
+            }
+            Token::Tag(tag) => match tag.name.as_str() {
+                "td" | "th" if !tag.closing => text.push(' '),
+                name if BLOCK_ELEMENTS.contains(&name) => text.push('\n'),
+                _ => {}
+            },
+        }
+    }
+
+    // Trim the lines, and keep at most one empty line between paragraphs.
+    let mut output = String::with_capacity(text.len());
+    let mut empty = 0;
+    for line in text.lines().map(str::trim) {
+        match line.is_empty() {
+            true => empty += 1,
+            false => {
+                if !output.is_empty() {
+                    output.push_str(if empty > 1 { "\n\n" } else { "\n" });
+                }
+                output.push_str(line);
+                empty = 0;
+            }
+        }
+    }
+
+    output
+}
+
+/// Decode the numeric and most common named character references in HTML text.
+/// Unknown references are kept as is.
+pub fn decode_entities(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let decoded = rest[1..]
+            .find(';')
+            .filter(|end| *end <= 10)
+            .and_then(|end| {
+                let name = &rest[1..end + 1];
+                let c = match name {
+                    "amp" => Some('&'),
+                    "lt" => Some('<'),
+                    "gt" => Some('>'),
+                    "quot" => Some('"'),
+                    "apos" => Some('\''),
+                    "nbsp" => Some(' '),
+                    _ => match name.strip_prefix('#') {
+                        Some(n) => match n.strip_prefix(|c| c == 'x' || c == 'X') {
+                            Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                            None => n.parse().ok(),
+                        }
+                        .and_then(std::char::from_u32),
+                        None => None,
+                    },
+                };
+                c.map(|c| (c, end + 2))
+            });
+
+        match decoded {
+            Some((c, length)) => {
+                output.push(c);
+                rest = &rest[length..];
+            }
+            None => {
+                output.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    output.push_str(rest);
+    output
+}
+
+/// Escape text for HTML, inside elements and quoted attributes.
+pub fn escape(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&#39;"),
+            // Control characters besides whitespace have no use in mail, and confuse parsers.
+            c if c.is_control() && !c.is_whitespace() => {}
+            c => output.push(c),
+        }
+    }
+
+    output
+}
+
+/// Remove the whitespace and control characters browsers ignore in URLs and styles, in lowercase.
+fn compact(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// Keep a link if it goes to the web, a mail address or a phone number.
+fn safe_link(url: &str) -> Option<String> {
+    let scheme = compact(url);
+
+    match ["http://", "https://", "mailto:", "tel:"]
+        .iter()
+        .any(|s| scheme.starts_with(s))
+    {
+        true => Some(url.trim().to_string()),
+        false => None,
+    }
+}
+
+/// Where the source of an image points to.
+#[derive(Debug, PartialEq)]
+enum Image {
+    /// An attachment of the message, or a data URL.
+    Local(String),
+    /// An URL on another server, which tells it the message was read.
+    Remote(String),
+    Invalid,
+}
+
+/// Find out where the source of an image points to.
+/// Only the exact URLs of attachments are local, so paths can not lead to other routes.
+fn image(url: &str) -> Image {
+    lazy_static! {
+        static ref ATTACHMENT: Regex = Regex::new(
+            "^/api/messages/[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}/attachments/[0-9]{1,9}$"
+        )
+        .unwrap();
+    }
+
+    let compacted = compact(url);
+    let url = url.trim();
+
+    if ATTACHMENT.is_match(url) || DATA_IMAGES.iter().any(|d| compacted.starts_with(d)) {
+        Image::Local(url.to_string())
+    } else if compacted.starts_with("http://") || compacted.starts_with("https://") {
+        Image::Remote(url.to_string())
+    } else if compacted.starts_with("//") {
+        Image::Remote(format!("https:{}", url))
+    } else {
+        Image::Invalid
+    }
+}
+
+/// Keep a style if it can not load anything or run script.
+/// Positioning is removed, so messages can not cover the client.
+fn safe_style(style: &str) -> Option<String> {
+    let compacted = compact(style);
+    if FORBIDDEN_STYLE.iter().any(|f| compacted.contains(f)) {
+        return None;
+    }
+
+    let declarations: Vec<&str> = style
+        .split(';')
+        .map(str::trim)
+        .filter(|d| !d.is_empty())
+        .filter(|d| {
+            let property = d.split(':').next().unwrap_or_default();
+            compact(property) != "position"
+        })
+        .collect();
+
+    match declarations.is_empty() {
+        true => None,
+        false => Some(declarations.join("; ")),
+    }
+}
+
+/// A start or end tag, with lowercase names.
+#[derive(Debug)]
+struct Tag {
+    name: String,
+    closing: bool,
+    attributes: Vec<(String, String)>,
+}
+
+/// A part of HTML, text is not decoded yet.
+#[derive(Debug)]
+enum Token<'a> {
+    Text(&'a str),
+    Tag(Tag),
+}
+
+/// Splits HTML into text and tags, like browsers do for malformed HTML as well.
+/// Comments, doctypes and processing instructions are skipped, as is the content of raw text elements.
+struct Tokenizer<'a> {
+    html: &'a str,
+    /// The HTML in lowercase, lowercasing ASCII keeps the byte offsets the same.
+    lower: String,
+    position: usize,
+    /// The raw text element of which the content is skipped next.
+    raw: Option<String>,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn new(html: &'a str) -> Self {
+        Tokenizer {
+            html,
+            lower: html.to_ascii_lowercase(),
+            position: 0,
+            raw: None,
+        }
+    }
+
+    /// Find the end of the first match of a pattern after an offset, or the end of the HTML.
+    fn skip_past(&self, from: usize, pattern: &str) -> usize {
+        self.lower[from..]
+            .find(pattern)
+            .map(|i| from + i + pattern.len())
+            .unwrap_or_else(|| self.html.len())
+    }
+
+    /// Parse the tag at the current position, with the amount of bytes it spans.
+    /// The tag is None when it does not end, it spans the rest of the HTML then.
+    /// None if the `<` does not start a tag, and is text.
+    fn tag(&self) -> Option<(Option<Tag>, usize)> {
+        let rest = &self.html[self.position..];
+        let bytes = rest.as_bytes();
+        let closing = bytes.get(1) == Some(&b'/');
+        let mut i = if closing { 2 } else { 1 };
+
+        if !bytes.get(i).map_or(false, u8::is_ascii_alphabetic) {
+            return None;
+        }
+
+        let name_end = rest[i..]
+            .find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+            .map_or(rest.len(), |e| i + e);
+        let name = rest[i..name_end].to_lowercase();
+        i = name_end;
+
+        let mut attributes = Vec::new();
+        loop {
+            // Tags which do not end are dropped with the rest of the HTML, like browsers do.
+            let c = match rest[i..].chars().next() {
+                Some(c) => c,
+                None => return Some((None, rest.len())),
+            };
+
+            if c == '>' {
+                i += 1;
+                break;
+            }
+            if c.is_whitespace() || c == '/' {
+                i += c.len_utf8();
+                continue;
+            }
+
+            // The first character of a name can be anything, even `=`.
+            let start = i;
+            i += c.len_utf8();
+            i = rest[i..]
+                .find(|c: char| c.is_whitespace() || c == '/' || c == '>' || c == '=')
+                .map_or(rest.len(), |e| i + e);
+            let attribute = rest[start..i].to_lowercase();
+
+            let after = rest[i..].trim_start();
+            let mut value = "";
+            if let Some(after) = after.strip_prefix('=') {
+                let after = after.trim_start();
+                i = rest.len() - after.len();
+
+                match after.chars().next() {
+                    Some(quote) if quote == '"' || quote == '\'' => match after[1..].find(quote) {
+                        Some(end) => {
+                            value = &after[1..end + 1];
+                            i += end + 2;
+                        }
+                        None => return Some((None, rest.len())),
+                    },
+                    _ => {
+                        let end = after
+                            .find(|c: char| c.is_whitespace() || c == '>')
+                            .unwrap_or(after.len());
+                        value = &after[..end];
+                        i += end;
+                    }
+                }
+            }
+
+            attributes.push((attribute, value.to_string()));
+        }
+
+        Some((
+            Some(Tag {
+                name,
+                closing,
+                attributes,
+            }),
+            i,
+        ))
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(name) = self.raw.take() {
+            let end = self.lower[self.position..]
+                .find(&format!("</{}", name))
+                .map_or(self.html.len(), |i| self.position + i);
+            self.position = end;
+        }
+
+        loop {
+            if self.position >= self.html.len() {
+                return None;
+            }
+
+            let rest = &self.html[self.position..];
+            if !rest.starts_with('<') {
+                let end = rest
+                    .find('<')
+                    .map_or(self.html.len(), |i| self.position + i);
+                let text = &self.html[self.position..end];
+                self.position = end;
+                return Some(Token::Text(text));
+            }
+
+            if rest.starts_with("<!--") {
+                self.position = self.skip_past(self.position + 4, "-->");
+                continue;
+            }
+            if rest.starts_with("<!") || rest.starts_with("<?") || rest.starts_with("</>") {
+                self.position = self.skip_past(self.position + 2, ">");
+                continue;
+            }
+
+            return match self.tag() {
+                Some((Some(tag), length)) => {
+                    self.position += length;
+                    if !tag.closing && RAW_TEXT_ELEMENTS.contains(&tag.name.as_str()) {
+                        self.raw = Some(tag.name.clone());
+                    }
+                    Some(Token::Tag(tag))
+                }
+                Some((None, length)) => {
+                    self.position += length;
+                    continue;
+                }
+                None => {
+                    self.position += 1;
+                    Some(Token::Text("<"))
+                }
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Attacks and what is left of them, from the common XSS filter evasion lists.
+    const CORPUS: &[(&str, &str)] = &[
+        // Scripts
+        ("<script>alert(1)</script>hi", "hi"),
+        ("<SCRIPT SRC=//evil.example/x.js></SCRIPT>", ""),
+        ("<script>document.write('<p>')</script>", ""),
+        ("<scr<script>ipt>alert(1)</script>", "ipt&gt;alert(1)"),
+        ("<textarea><script>alert(1)</script></textarea>", ""),
+        ("<iframe src=javascript:alert(1)></iframe>", ""),
+        (
+            "<xmp><p title=\"</xmp><img src=x onerror=alert(1)>\">",
+            "<img>&quot;&gt;",
+        ),
+        // Event handlers
+        ("<img src=x onerror=alert(1)>", "<img>"),
+        ("<body onload=alert(1)>text", "text"),
+        ("<p ONCLICK=\"alert(1)\" title=t>x</p>", "<p title=\"t\">x</p>"),
+        ("<b/onmouseover=alert(1)>x", "<b>x</b>"),
+        (
+            "<p title=\"&quot;><script>alert(1)</script>\">x</p>",
+            "<p title=\"&quot;&gt;&lt;script&gt;alert(1)&lt;/script&gt;\">x</p>",
+        ),
+        ("<img \"\"\"><script>alert(1)</script>\">", "<img>&quot;&gt;"),
+        // Links
+        (
+            "<a href=\"javascript:alert(1)\">x</a>",
+            "<a target=\"_blank\" rel=\"noopener noreferrer\">x</a>",
+        ),
+        (
+            "<a href=\" JaVaScRiPt:alert(1)\">x</a>",
+            "<a target=\"_blank\" rel=\"noopener noreferrer\">x</a>",
+        ),
+        (
+            "<a href=\"vbscript:msgbox(1)\">x</a>",
+            "<a target=\"_blank\" rel=\"noopener noreferrer\">x</a>",
+        ),
+        (
+            "<a href=\"&#106;&#97;&#118;&#97;&#115;&#99;&#114;&#105;&#112;&#116;&#58;alert(1)\">x</a>",
+            "<a target=\"_blank\" rel=\"noopener noreferrer\">x</a>",
+        ),
+        (
+            "<a href=\"&#x6A;&#x61;&#x76;&#x61;script&colon;alert(1)\">x</a>",
+            "<a target=\"_blank\" rel=\"noopener noreferrer\">x</a>",
+        ),
+        (
+            "<a href=\"jav&#x09;ascript:alert(1)\">x</a>",
+            "<a target=\"_blank\" rel=\"noopener noreferrer\">x</a>",
+        ),
+        (
+            "<a href=\"java\0script:alert(1)\">x</a>",
+            "<a target=\"_blank\" rel=\"noopener noreferrer\">x</a>",
+        ),
+        (
+            "<a/href=\"javascript:alert(1)\">x</a>",
+            "<a target=\"_blank\" rel=\"noopener noreferrer\">x</a>",
+        ),
+        (
+            "<a href=\"data:text/html,<script>alert(1)</script>\">x</a>",
+            "<a target=\"_blank\" rel=\"noopener noreferrer\">x</a>",
+        ),
+        (
+            "<a href=\"https://example.com/?a=1&amp;b=2\">x</a>",
+            "<a href=\"https://example.com/?a=1&amp;b=2\" target=\"_blank\" rel=\"noopener noreferrer\">x</a>",
+        ),
+        ("<form action=javascript:alert(1)><button>x</button></form>", "x"),
+        // Namespace confusion
+        ("<svg><script>alert(1)</script></svg>after", "after"),
+        ("<svg onload=alert(1)>", ""),
+        ("<svg><style><img src=x onerror=alert(1)></style></svg>", ""),
+        ("<svg><svg></svg><script>alert(1)</script></svg>x", "x"),
+        ("<math><mi xlink:href=\"javascript:alert(1)\">x</mi></math>", ""),
+        (
+            "<math><mtext><table><mglyph><style><img src=x onerror=alert(1)>",
+            "",
+        ),
+        // Styles
+        (
+            "<div style=\"width: expression(alert(1))\">x</div>",
+            "<div>x</div>",
+        ),
+        (
+            "<div style=\"background:url(javascript:alert(1))\">x</div>",
+            "<div>x</div>",
+        ),
+        (
+            "<div style=\"background: URL ( 'http://evil.example/' )\">x</div>",
+            "<div>x</div>",
+        ),
+        ("<div style=\"background:u\\rl(x)\">x</div>", "<div>x</div>"),
+        ("<div style=\"width:exp/**/ression(alert(1))\">x</div>", "<div>x</div>"),
+        (
+            "<div style=\"width:&#101;xpression(alert(1))\">x</div>",
+            "<div>x</div>",
+        ),
+        (
+            "<div style=\"background-image: image-set('x.png' 1x)\">x</div>",
+            "<div>x</div>",
+        ),
+        ("<div style=\"-moz-binding: url(x.xml)\">x</div>", "<div>x</div>"),
+        (
+            "<div style=\"color: red; position: fixed\">x</div>",
+            "<div style=\"color: red\">x</div>",
+        ),
+        ("<style>@import 'http://evil.example/';</style>x", "x"),
+        // Unclosed tags and comments
+        ("<img src=x onerror=alert(1)//", ""),
+        ("a<a href=\"http://example.com\" onclick=\"alert(1)", "a"),
+        ("<script>alert(1)", ""),
+        ("<!-- <script>alert(1)</script> -->text", "text"),
+        ("<!-- unclosed <script>alert(1)</script>", ""),
+        ("<!--><script>alert(1)</script>-->x", "x"),
+        ("<![CDATA[<script>alert(1)</script>]]>x", "alert(1)]]&gt;x"),
+        ("<?xml <script>alert(1)</script>?>", "alert(1)?&gt;"),
+        ("x < y <3", "x &lt; y &lt;3"),
+        // Documents
+        ("<base href=\"http://evil.example/\">x", "x"),
+        (
+            "<meta http-equiv=\"refresh\" content=\"0;url=javascript:alert(1)\">x",
+            "x",
+        ),
+        ("<link rel=stylesheet href=http://evil.example/x.css>x", "x"),
+        // Images
+        (
+            "<img srcset=\"http://evil.example/x 1x\" src=\"http://example.com/i.png\">",
+            "<img data-blocked-src=\"http://example.com/i.png\" src=\"data:image/gif;base64,R0lGODlhAQABAIAAAAAAAP///yH5BAEAAAAALAAAAAABAAEAAAIBRAA7\">",
+        ),
+        (
+            "<img src=\"data:image/png;base64,iVBORw0KGgo=\">",
+            "<img src=\"data:image/png;base64,iVBORw0KGgo=\">",
+        ),
+        ("<img src=\"data:image/svg+xml;base64,PHN2Zz4=\">", "<img>"),
+        ("<img src=\"data:text/html;base64,PHNjcmlwdD4=\">", "<img>"),
+        ("<img src=\"javascript:alert(1)\">", "<img>"),
+        ("<img src=\"/api/messages//evil.example/x\">", "<img>"),
+        (
+            "<img src=\"/api/messages/6f0b5d0e-8c1a-4d8e-9b1e-2f6a3c4d5e6f/attachments/2\">",
+            "<img src=\"/api/messages/6f0b5d0e-8c1a-4d8e-9b1e-2f6a3c4d5e6f/attachments/2\">",
+        ),
+        ("<img src=\"/api/messages/../account/export\">", "<img>"),
+        ("<img src=\"/api/messages/%2e%2e/account/export\">", "<img>"),
+        (
+            "<img src=\"/api/messages/6f0b5d0e-8c1a-4d8e-9b1e-2f6a3c4d5e6f/attachments/1/../../../account\">",
+            "<img>",
+        ),
+        (
+            "<img src=\"/api/messages/6f0b5d0e-8c1a-4d8e-9b1e-2f6a3c4d5e6f/attachments/%2E%2E\">",
+            "<img>",
+        ),
+        (
+            "<img src=\"/api/messages/6f0b5d0e-8c1a-4d8e-9b1e-2f6a3c4d5e6f/attachments/1?x=1\">",
+            "<img>",
+        ),
+        ("<input type=image src=x onerror=alert(1)>", ""),
+        ("<video><source onerror=alert(1)></video>", ""),
+    ];
+
+    /// Check that sanitized HTML only has allowed elements, attributes, links, images and styles.
+    fn assert_safe(html: &str) {
+        for token in Tokenizer::new(html) {
+            let tag = match token {
+                Token::Tag(tag) => tag,
+                Token::Text(text) => {
+                    assert!(!text.contains('>'), "unescaped text in {}", html);
+                    continue;
+                }
+            };
+            assert!(
+                ALLOWED_ELEMENTS.contains(&tag.name.as_str()),
+                "{} in {}",
+                tag.name,
+                html
+            );
+
+            for (attribute, value) in &tag.attributes {
+                let value = decode_entities(value);
+                let safe = match (tag.name.as_str(), attribute.as_str()) {
+                    ("a", "href") => safe_link(&value).is_some(),
+                    ("a", "target") => value == "_blank",
+                    ("a", "rel") => value == "noopener noreferrer",
+                    ("img", "src") => image(&value) != Image::Invalid,
+                    ("img", "data-blocked-src") => true,
+                    (_, "style") => safe_style(&value).as_deref() == Some(value.as_str()),
+                    (_, attribute) => ALLOWED_ATTRIBUTES.contains(&attribute),
+                };
+                assert!(safe, "{}=\"{}\" in {}", attribute, value, html);
+            }
+        }
+    }
+
+    #[test]
+    fn attacks_are_removed() {
+        for (input, expected) in CORPUS {
+            let sanitized = sanitize(input, false);
+
+            assert_eq!(sanitized.html, *expected, "{}", input);
+            assert_safe(&sanitized.html);
+        }
+    }
+
+    #[test]
+    fn sanitizing_is_stable() {
+        for (input, _) in CORPUS {
+            let once = sanitize(input, true).html;
+
+            assert_safe(&once);
+            assert_eq!(sanitize(&once, true).html, once, "{}", input);
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use serde::Serialize;
+use sqlx::PgConnection;
+use thiserror::Error;
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::database;
+
+/// The longest name of a label, in characters.
+const MAX_NAME: usize = 100;
+
+/// The maximum amount of messages labeled at once.
+pub const MAX_MESSAGES: usize = 1000;
+
+/// A label of an account, which messages can carry any amount of.
+/// Unlike mailboxes, a message can have several labels.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Label {
+    pub id: Uuid,
+    #[serde(skip)]
+    pub account: Uuid,
+    pub name: String,
+    /// The color as `#rrggbb`.
+    pub color: String,
+    /// Labels are shown from the lowest position to the highest.
+    pub position: i32,
+    /// The amount of messages with the label, and how many of those are unread.
+    pub messages: i64,
+    pub unread: i64,
+    #[serde(with = "time::serde::timestamp")]
+    pub created: OffsetDateTime,
+}
+
+impl Label {
+    /// Create a label, after the other labels of the account.
+    pub async fn create(
+        conn: &mut PgConnection,
+        account: Uuid,
+        name: &str,
+        color: &str,
+    ) -> Result<Self, CreateError> {
+        let name = name.trim();
+        if !Self::validate_name(name) {
+            return Err(CreateError::InvalidName);
+        }
+        let color = Self::parse_color(color).ok_or(CreateError::InvalidColor)?;
+
+        if database::label::find_name(conn, account, name)
+            .await?
+            .is_some()
+        {
+            return Err(CreateError::NameExists);
+        }
+
+        Ok(database::label::create(conn, account, name, &color).await?)
+    }
+
+    /// List the labels of an account in their order.
+    pub async fn list(conn: &mut PgConnection, account: Uuid) -> Result<Vec<Self>, sqlx::Error> {
+        database::label::list(conn, account).await
+    }
+
+    /// Find a label of an account by ID.
+    pub async fn find(conn: &mut PgConnection, account: Uuid, id: Uuid) -> Result<Self, FindError> {
+        let res = database::label::find(conn, account, id).await?;
+
+        match res {
+            Some(label) => Ok(label),
+            None => Err(FindError::NotFound),
+        }
+    }
+
+    /// Change the name and color of the label.
+    pub async fn update(
+        &mut self,
+        conn: &mut PgConnection,
+        name: &str,
+        color: &str,
+    ) -> Result<(), CreateError> {
+        let name = name.trim();
+        if !Self::validate_name(name) {
+            return Err(CreateError::InvalidName);
+        }
+        let color = Self::parse_color(color).ok_or(CreateError::InvalidColor)?;
+
+        if let Some(other) = database::label::find_name(conn, self.account, name).await? {
+            if other != self.id {
+                return Err(CreateError::NameExists);
+            }
+        }
+
+        database::label::update(conn, self.id, name, &color).await?;
+        self.name = name.to_string();
+        self.color = color;
+
+        Ok(())
+    }
+
+    /// Order the labels of an account as in a list, which has to contain each of them once.
+    pub async fn reorder(
+        conn: &mut PgConnection,
+        account: Uuid,
+        ids: &[Uuid],
+    ) -> Result<Vec<Self>, OrderError> {
+        let mut existing = database::label::list_ids(conn, account).await?;
+        let mut requested = ids.to_vec();
+        existing.sort();
+        requested.sort();
+
+        if existing != requested {
+            return Err(OrderError::Incomplete);
+        }
+
+        database::label::reorder(conn, account, ids).await?;
+        Ok(database::label::list(conn, account).await?)
+    }
+
+    /// Delete the label, which removes it from its messages.
+    /// Should run in a transaction.
+    pub async fn delete(self, conn: &mut PgConnection) -> Result<(), sqlx::Error> {
+        let messages = database::message_label::messages(conn, self.id).await?;
+        database::message::touch(conn, &messages).await?;
+        database::label::delete(conn, self.id).await
+    }
+
+    /// Add and remove labels of messages of an account, in bulk.
+    /// Messages which do not exist are skipped, all labels have to exist.
+    /// Should run in a transaction.
+    /// Returns the amount of labels added and removed.
+    pub async fn apply(
+        conn: &mut PgConnection,
+        account: Uuid,
+        messages: &[Uuid],
+        add: &[Uuid],
+        remove: &[Uuid],
+    ) -> Result<(u64, u64), ApplyError> {
+        if messages.len() > MAX_MESSAGES {
+            return Err(ApplyError::TooManyMessages);
+        }
+
+        let existing = database::label::list_ids(conn, account).await?;
+        if !add.iter().chain(remove).all(|id| existing.contains(id)) {
+            return Err(ApplyError::LabelNotFound);
+        }
+
+        let added = database::message_label::add(conn, account, messages, add).await?;
+        let removed = database::message_label::remove(conn, account, messages, remove).await?;
+
+        // The labels are part of the messages for clients which sync them.
+        let mut changed: Vec<Uuid> = added.iter().chain(&removed).copied().collect();
+        changed.sort();
+        changed.dedup();
+        database::message::touch(conn, &changed).await?;
+
+        Ok((added.len() as u64, removed.len() as u64))
+    }
+
+    /// Check if a name is valid for a label.
+    pub fn validate_name(name: &str) -> bool {
+        !name.is_empty() && name.chars().count() <= MAX_NAME && !name.chars().any(char::is_control)
+    }
+
+    /// Parse a color as `#rrggbb`, in lowercase.
+    pub fn parse_color(color: &str) -> Option<String> {
+        let hex = color.trim().strip_prefix('#')?;
+
+        match hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            true => Some(format!("#{}", hex.to_ascii_lowercase())),
+            false => None,
+        }
+    }
+}
+
+/// Possible errors with creating or changing a label.
+#[derive(Error, Debug)]
+pub enum CreateError {
+    #[error("The name is empty, too long or contains invalid characters.")]
+    InvalidName,
+    #[error("The color is not formatted as #rrggbb.")]
+    InvalidColor,
+    #[error("A label with the name already exists.")]
+    NameExists,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Possible errors with finding a label.
+#[derive(Error, Debug)]
+pub enum FindError {
+    #[error("The label was not found.")]
+    NotFound,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Possible errors with ordering the labels.
+#[derive(Error, Debug)]
+pub enum OrderError {
+    #[error("The order has to contain every label once.")]
+    Incomplete,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Possible errors with labeling messages.
+#[derive(Error, Debug)]
+pub enum ApplyError {
+    #[error("Too many messages are labeled at once.")]
+    TooManyMessages,
+    #[error("A label was not found.")]
+    LabelNotFound,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
@@ -0,0 +1,398 @@
+use serde::{Deserialize, Serialize};
+use sqlx::PgConnection;
+use thiserror::Error;
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::database;
+
+/// The SQLSTATE of a unique violation, when a name is taken concurrently.
+const UNIQUE_VIOLATION: &str = "23505";
+
+/// The special-use role of a mailbox (RFC 6154).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "mailbox_role", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Inbox,
+    Sent,
+    Drafts,
+    Archive,
+    Junk,
+    Trash,
+}
+
+impl Role {
+    /// All roles, every account has a mailbox for each.
+    pub const ALL: [Role; 6] = [
+        Role::Inbox,
+        Role::Sent,
+        Role::Drafts,
+        Role::Archive,
+        Role::Junk,
+        Role::Trash,
+    ];
+
+    /// The name of the mailbox created for the role.
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::Inbox => "Inbox",
+            Role::Sent => "Sent",
+            Role::Drafts => "Drafts",
+            Role::Archive => "Archive",
+            Role::Junk => "Spam",
+            Role::Trash => "Trash",
+        }
+    }
+
+    /// Parse the role from its name in search queries, like `in:spam`.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "inbox" => Some(Role::Inbox),
+            "sent" => Some(Role::Sent),
+            "drafts" | "draft" => Some(Role::Drafts),
+            "archive" => Some(Role::Archive),
+            "junk" | "spam" => Some(Role::Junk),
+            "trash" => Some(Role::Trash),
+            _ => None,
+        }
+    }
+}
+
+/// What a retention policy does with messages once they are old enough.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "retention_action", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum RetentionAction {
+    /// Keep messages forever, overriding the system default.
+    Keep,
+    /// Delete messages permanently.
+    Delete,
+    /// Move messages into the archive.
+    Archive,
+}
+
+/// The longest name of a mailbox, in characters.
+const MAX_NAME: usize = 100;
+
+/// The maximum amount of each kind of change returned at once.
+const MAX_CHANGES: usize = 5000;
+
+/// Represents a mailbox of an account, containing messages.
+/// Mailboxes with a role are created with the account and can not be deleted.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Mailbox {
+    pub id: Uuid,
+    #[serde(skip)]
+    pub account: Uuid,
+    /// The mailbox this one is nested in, None at the top.
+    pub parent: Option<Uuid>,
+    pub name: String,
+    pub role: Option<Role>,
+    /// The retention policy of the mailbox, None uses the system default of its role.
+    pub retention_action: Option<RetentionAction>,
+    pub retention_days: Option<i32>,
+    /// The modification sequence, increased by every change to the messages in the mailbox.
+    pub modseq: i64,
+    #[serde(with = "time::serde::timestamp")]
+    pub created: OffsetDateTime,
+}
+
+/// The messages of a mailbox which changed after a modification sequence.
+/// Messages moved into the mailbox are created, moved out of it destroyed.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Changes {
+    /// The modification sequence the changes lead up to, to ask for the next changes with.
+    pub modseq: i64,
+    pub created: Vec<Uuid>,
+    pub updated: Vec<Uuid>,
+    pub destroyed: Vec<Uuid>,
+}
+
+impl Mailbox {
+    /// Create the mailboxes with a role for a new account.
+    /// Should run in the transaction which creates the account.
+    pub async fn create_system(conn: &mut PgConnection, account: Uuid) -> Result<(), sqlx::Error> {
+        database::mailbox::create_system(conn, account).await
+    }
+
+    /// Create a mailbox, nested in a parent mailbox of the account or at the top.
+    pub async fn create(
+        conn: &mut PgConnection,
+        account: Uuid,
+        parent: Option<Uuid>,
+        name: &str,
+    ) -> Result<Self, CreateError> {
+        let name = name.trim();
+        if !Self::validate_name(name) {
+            return Err(CreateError::InvalidName);
+        }
+
+        if let Some(parent) = parent {
+            database::mailbox::find(conn, account, parent)
+                .await?
+                .ok_or(CreateError::ParentNotFound)?;
+        }
+
+        if database::mailbox::find_name(conn, account, parent, name)
+            .await?
+            .is_some()
+        {
+            return Err(CreateError::NameExists);
+        }
+
+        Ok(database::mailbox::create(conn, account, parent, name).await?)
+    }
+
+    /// List the mailboxes of an account.
+    pub async fn list(conn: &mut PgConnection, account: Uuid) -> Result<Vec<Self>, sqlx::Error> {
+        database::mailbox::list(conn, account).await
+    }
+
+    /// Find a mailbox of an account by ID.
+    pub async fn find(conn: &mut PgConnection, account: Uuid, id: Uuid) -> Result<Self, FindError> {
+        let res = database::mailbox::find(conn, account, id).await?;
+
+        match res {
+            Some(mailbox) => Ok(mailbox),
+            None => Err(FindError::NotFound),
+        }
+    }
+
+    /// Get the mailbox of an account with a role.
+    pub async fn role(
+        conn: &mut PgConnection,
+        account: Uuid,
+        role: Role,
+    ) -> Result<Self, sqlx::Error> {
+        database::mailbox::find_role(conn, account, role)
+            .await?
+            .ok_or(sqlx::Error::RowNotFound)
+    }
+
+    /// Rename the mailbox, the name has to be unique within its parent.
+    pub async fn rename(&mut self, conn: &mut PgConnection, name: &str) -> Result<(), RenameError> {
+        let name = name.trim();
+        if !Self::validate_name(name) {
+            return Err(RenameError::InvalidName);
+        }
+
+        if let Some(other) =
+            database::mailbox::find_name(conn, self.account, self.parent, name).await?
+        {
+            if other.id != self.id {
+                return Err(RenameError::NameExists);
+            }
+        }
+
+        database::mailbox::rename(conn, self.id, name).await?;
+        self.name = name.to_string();
+
+        Ok(())
+    }
+
+    /// Move the mailbox into another parent, or to the top with None.
+    /// Mailboxes with a role stay at the top.
+    /// Should run in a transaction.
+    pub async fn move_to(
+        &mut self,
+        conn: &mut PgConnection,
+        parent: Option<Uuid>,
+    ) -> Result<(), MoveError> {
+        if self.role.is_some() {
+            return Err(MoveError::SystemMailbox);
+        }
+
+        // The hierarchy of the account is locked, so a concurrent move can not make a cycle either.
+        database::mailbox::lock(conn, self.account).await?;
+
+        // The parent can not be the mailbox itself or nested in it, which would make a cycle.
+        if let Some(parent) = parent {
+            database::mailbox::find(conn, self.account, parent)
+                .await?
+                .ok_or(MoveError::InvalidParent)?;
+
+            if database::mailbox::is_ancestor(conn, self.id, parent).await? {
+                return Err(MoveError::InvalidParent);
+            }
+        }
+
+        if let Some(other) =
+            database::mailbox::find_name(conn, self.account, parent, &self.name).await?
+        {
+            if other.id != self.id {
+                return Err(MoveError::NameExists);
+            }
+        }
+
+        // A mailbox renamed concurrently can still take the name.
+        match database::mailbox::set_parent(conn, self.id, parent).await {
+            Ok(()) => {}
+            Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some(UNIQUE_VIOLATION) => {
+                return Err(MoveError::NameExists)
+            }
+            Err(e) => return Err(e.into()),
+        }
+        self.parent = parent;
+
+        Ok(())
+    }
+
+    /// Delete the mailbox, its messages are moved into the trash.
+    /// Mailboxes with a role, or with nested mailboxes, can not be deleted.
+    /// Should run in a transaction.
+    pub async fn delete(self, conn: &mut PgConnection) -> Result<(), DeleteError> {
+        if self.role.is_some() {
+            return Err(DeleteError::SystemMailbox);
+        }
+
+        if database::mailbox::has_children(conn, self.id).await? {
+            return Err(DeleteError::HasChildren);
+        }
+
+        let trash = Mailbox::role(conn, self.account, Role::Trash).await?;
+        database::message::move_all(conn, self.id, trash.id).await?;
+        database::mailbox::delete(conn, self.id).await?;
+
+        Ok(())
+    }
+
+    /// Set the retention policy, None uses the system default of the role again.
+    /// Deleting and archiving happen after an amount of days, keeping has none.
+    pub async fn set_retention(
+        &mut self,
+        conn: &mut PgConnection,
+        action: Option<RetentionAction>,
+        days: Option<i32>,
+    ) -> Result<(), RetentionError> {
+        match (action, days) {
+            (None, None) | (Some(RetentionAction::Keep), None) => {}
+            (Some(RetentionAction::Delete), Some(d))
+            | (Some(RetentionAction::Archive), Some(d))
+                if d > 0 => {}
+            _ => return Err(RetentionError::InvalidDays),
+        }
+
+        if action == Some(RetentionAction::Archive) && self.role == Some(Role::Archive) {
+            return Err(RetentionError::ArchiveIntoItself);
+        }
+
+        database::mailbox::set_retention(conn, self.id, action, days).await?;
+        self.retention_action = action;
+        self.retention_days = days;
+
+        Ok(())
+    }
+
+    /// Get the changes to the messages of the mailbox after a modification sequence.
+    /// Clients which are too far behind have to fetch the mailbox again.
+    pub async fn changes(
+        &self,
+        conn: &mut PgConnection,
+        since: i64,
+    ) -> Result<Changes, ChangesError> {
+        if since < 0 || since > self.modseq {
+            return Err(ChangesError::InvalidSince);
+        }
+
+        let changes =
+            database::mailbox::changes(conn, self.id, since, MAX_CHANGES as i64 + 1).await?;
+        if [&changes.created, &changes.updated, &changes.destroyed]
+            .iter()
+            .any(|ids| ids.len() > MAX_CHANGES)
+        {
+            return Err(ChangesError::TooManyChanges);
+        }
+
+        Ok(changes)
+    }
+
+    /// Check if a name is valid for a mailbox.
+    /// The slash separates mailboxes in the paths of clients, so it can not be part of a name.
+    pub fn validate_name(name: &str) -> bool {
+        !name.is_empty()
+            && name.chars().count() <= MAX_NAME
+            && !name.chars().any(|c| c == '/' || c.is_control())
+    }
+}
+
+/// Possible errors with creating a mailbox.
+#[derive(Error, Debug)]
+pub enum CreateError {
+    #[error("The name is empty, too long or contains invalid characters.")]
+    InvalidName,
+    #[error("A mailbox with the name already exists within the parent.")]
+    NameExists,
+    #[error("The parent mailbox was not found.")]
+    ParentNotFound,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Possible errors with finding a mailbox.
+#[derive(Error, Debug)]
+pub enum FindError {
+    #[error("The mailbox was not found.")]
+    NotFound,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Possible errors with setting a retention policy.
+#[derive(Error, Debug)]
+pub enum RetentionError {
+    #[error("Deleting and archiving need a positive amount of days, keeping none.")]
+    InvalidDays,
+    #[error("The archive can not be archived into itself.")]
+    ArchiveIntoItself,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Possible errors with renaming a mailbox.
+#[derive(Error, Debug)]
+pub enum RenameError {
+    #[error("The name is empty, too long or contains invalid characters.")]
+    InvalidName,
+    #[error("A mailbox with the name already exists within the parent.")]
+    NameExists,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Possible errors with moving a mailbox.
+#[derive(Error, Debug)]
+pub enum MoveError {
+    #[error("Mailboxes with a role can not be moved.")]
+    SystemMailbox,
+    #[error("The parent was not found, or is nested in the mailbox.")]
+    InvalidParent,
+    #[error("A mailbox with the name already exists within the parent.")]
+    NameExists,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Possible errors with deleting a mailbox.
+#[derive(Error, Debug)]
+pub enum DeleteError {
+    #[error("Mailboxes with a role can not be deleted.")]
+    SystemMailbox,
+    #[error("The mailbox still has nested mailboxes.")]
+    HasChildren,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Possible errors with getting the changes of a mailbox.
+#[derive(Error, Debug)]
+pub enum ChangesError {
+    #[error("The modification sequence is not one of the mailbox.")]
+    InvalidSince,
+    #[error("Too many messages changed, the mailbox has to be fetched again.")]
+    TooManyChanges,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
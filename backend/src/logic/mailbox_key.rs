@@ -0,0 +1,157 @@
+use sqlx::PgConnection;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{
+    crypto::{self, CryptoError, Key},
+    database,
+    logic::auth::password::AuthPassword,
+};
+
+/// The length of the salt used to derive the wrapping key.
+const SALT_LENGTH: usize = 16;
+
+/// The unwrapped mailbox key of an account, an X25519 secret key.
+/// Every encrypted message has its own content key, sealed to the public mailbox key.
+/// The database only has the key wrapped with the password, a session only has it wrapped with a key kept by the client.
+///
+/// This protects the stored raw messages and drafts from someone with only the database and the blob store.
+/// It does not keep mail from the server itself, nor from whoever runs it:
+/// - The subject, the sender and the recipients, the search index and the snippet stay readable,
+///   so messages can be searched and listed.
+/// - Mail stored before the account had a key stays unencrypted, it is not migrated.
+/// - Mail is readable while it is received, and while a session has the key unlocked.
+pub struct MailboxKey {
+    secret: Key,
+}
+
+/// The mailbox key of a session, wrapped with a random key.
+/// The wrapped key is kept in the session store, the wrapping key only in a cookie of the client,
+/// so neither of them alone gives the mailbox key.
+pub struct SessionKey {
+    pub sealed: String,
+    pub wrapping: String,
+}
+
+/// The mailbox key as stored, wrapped with a key derived from the password.
+pub struct WrappedKey {
+    pub public_key: Vec<u8>,
+    pub wrapped_key: Vec<u8>,
+    pub salt: Vec<u8>,
+}
+
+impl MailboxKey {
+    /// Create a new mailbox key for an account, wrapped with the password.
+    pub async fn create(
+        conn: &mut PgConnection,
+        account: Uuid,
+        password: &str,
+    ) -> Result<Self, KeyError> {
+        let (secret, public) = crypto::generate_keypair();
+        let (wrapped_key, salt) = wrap(&secret, password)?;
+
+        let key = WrappedKey {
+            public_key: public.to_vec(),
+            wrapped_key,
+            salt,
+        };
+        database::mailbox_key::create(conn, account, &key).await?;
+
+        Ok(MailboxKey { secret })
+    }
+
+    /// Unwrap the mailbox key of an account with the password.
+    /// Accounts created before encryption get their key now, their older mail stays unencrypted.
+    pub async fn unlock(
+        conn: &mut PgConnection,
+        account: Uuid,
+        password: &str,
+    ) -> Result<Self, KeyError> {
+        let key = match database::mailbox_key::find(conn, account).await? {
+            Some(key) => key,
+            None => return Self::create(conn, account, password).await,
+        };
+
+        let wrapping =
+            AuthPassword::derive_key(password, &key.salt).map_err(|_| KeyError::Derivation)?;
+        let secret = crypto::decrypt(&wrapping, &key.wrapped_key)?;
+
+        Ok(MailboxKey {
+            secret: crypto::to_key(&secret)?,
+        })
+    }
+
+    /// Wrap the mailbox key of an account with a new password.
+    /// The key itself stays the same, so no message has to be encrypted again.
+    pub async fn rewrap(
+        conn: &mut PgConnection,
+        account: Uuid,
+        current: &str,
+        new: &str,
+    ) -> Result<Self, KeyError> {
+        let key = Self::unlock(conn, account, current).await?;
+        let (wrapped_key, salt) = wrap(&key.secret, new)?;
+
+        database::mailbox_key::update_wrapping(conn, account, &wrapped_key, &salt).await?;
+
+        Ok(key)
+    }
+
+    /// Get the public mailbox key of an account, if it has one.
+    pub async fn public(conn: &mut PgConnection, account: Uuid) -> Result<Option<Key>, KeyError> {
+        match database::mailbox_key::public_key(conn, account).await? {
+            Some(key) => Ok(Some(crypto::to_key(&key)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Open a content key sealed to this mailbox key.
+    pub fn open(&self, sealed: &[u8]) -> Result<Key, CryptoError> {
+        crypto::to_key(&self.unseal(sealed)?)
+    }
+
+    /// Open data sealed to this mailbox key, like the content of a draft.
+    pub fn unseal(&self, sealed: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        crypto::open(&self.secret, sealed)
+    }
+
+    /// Wrap the key with a new random key, to keep it in a session.
+    pub fn to_session(&self) -> SessionKey {
+        let wrapping = crypto::random_key();
+
+        SessionKey {
+            sealed: hex::encode(crypto::encrypt(&wrapping, &self.secret)),
+            wrapping: hex::encode(wrapping),
+        }
+    }
+
+    /// Unwrap a key kept in a session with the wrapping key of the client.
+    pub fn from_session(sealed: &str, wrapping: &str) -> Result<Self, CryptoError> {
+        let sealed = hex::decode(sealed).map_err(|_| CryptoError::Invalid)?;
+        let wrapping = hex::decode(wrapping).map_err(|_| CryptoError::Invalid)?;
+        let secret = crypto::decrypt(&crypto::to_key(&wrapping)?, &sealed)?;
+
+        Ok(MailboxKey {
+            secret: crypto::to_key(&secret)?,
+        })
+    }
+}
+
+/// Wrap a secret key with a key derived from the password, using a new salt.
+fn wrap(secret: &Key, password: &str) -> Result<(Vec<u8>, Vec<u8>), KeyError> {
+    let salt = crypto::random_bytes(SALT_LENGTH);
+    let wrapping = AuthPassword::derive_key(password, &salt).map_err(|_| KeyError::Derivation)?;
+
+    Ok((crypto::encrypt(&wrapping, secret), salt))
+}
+
+/// Possible errors with mailbox keys.
+#[derive(Error, Debug)]
+pub enum KeyError {
+    #[error("The key could not be derived from the password.")]
+    Derivation,
+    #[error("The mailbox key could not be unwrapped.")]
+    CryptoError(#[from] CryptoError),
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
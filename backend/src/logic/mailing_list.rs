@@ -0,0 +1,881 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sqlx::{Connection, PgConnection};
+use thiserror::Error;
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    database,
+    logic::{domain::Domain, outbound::Outbound},
+};
+
+/// The amount of bounces after which a subscriber is disabled.
+const BOUNCE_THRESHOLD: i32 = 5;
+
+/// Represents a mailing list, reachable at `<local_part>@<domain>`.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MailingList {
+    pub id: Uuid,
+    pub domain: String,
+    pub local_part: String,
+    pub name: String,
+    pub description: String,
+    pub policy: ListPolicy,
+    #[serde(with = "time::serde::timestamp")]
+    pub last_digest: OffsetDateTime,
+}
+
+/// Who is allowed to post to a mailing list.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "list_policy", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ListPolicy {
+    /// Only members can post.
+    Members,
+    /// Everyone can post, but posts have to be approved by a moderator.
+    Moderated,
+    /// Everyone can post.
+    Open,
+}
+
+/// The role of a subscriber.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "list_role", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ListRole {
+    Member,
+    Moderator,
+}
+
+/// Represents a subscriber of a mailing list.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ListMember {
+    pub address: String,
+    pub role: ListRole,
+    pub digest: bool,
+    pub disabled: bool,
+    pub bounces: i32,
+}
+
+/// Represents a post waiting for approval of a moderator.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ModeratedPost {
+    pub id: Uuid,
+    pub sender: String,
+    #[serde(skip)]
+    pub data: Vec<u8>,
+    #[serde(with = "time::serde::timestamp")]
+    pub created: OffsetDateTime,
+}
+
+/// A subscription change requested by mail, applied once the address confirms it.
+#[derive(Debug)]
+pub struct ListConfirmation {
+    pub address: String,
+    pub subscribe: bool,
+    pub digest: bool,
+}
+
+/// The different addresses a mailing list receives mail on.
+#[derive(Debug, PartialEq)]
+pub enum ListAddress {
+    /// Posts to the list itself.
+    Post,
+    /// Commands, sent to `<list>-request`.
+    Request,
+    /// Bounces for a subscriber, sent to the VERP address `<list>-bounces+<user>=<domain>`.
+    Bounce(String),
+}
+
+/// The outcome of a post to a mailing list.
+#[derive(Debug)]
+pub enum PostResult {
+    /// The post was sent to the given amount of subscribers.
+    Distributed(usize),
+    /// The post is held for moderation.
+    Moderated(Uuid),
+    /// The sender is not allowed to post.
+    Rejected,
+}
+
+impl MailingList {
+    /// Create a new mailing list on a domain.
+    pub async fn create(
+        conn: &mut PgConnection,
+        domain: &Domain,
+        local_part: &str,
+        name: &str,
+        description: &str,
+        policy: ListPolicy,
+    ) -> Result<Self, CreateError> {
+        let local_part = local_part.to_lowercase();
+
+        if !Self::validate_local_part(&local_part) {
+            return Err(CreateError::InvalidAddress(local_part));
+        }
+
+        if database::mailing_list::find_address(conn, &local_part, &domain.name)
+            .await?
+            .is_some()
+        {
+            return Err(CreateError::ListExists(local_part));
+        }
+
+        Ok(database::mailing_list::create(
+            conn,
+            &domain.name,
+            &local_part,
+            name,
+            description,
+            policy,
+        )
+        .await?)
+    }
+
+    /// Find a mailing list by ID.
+    pub async fn find(conn: &mut PgConnection, id: Uuid) -> Result<Self, FindError> {
+        let res = database::mailing_list::find(conn, id).await?;
+
+        match res {
+            Some(list) => Ok(list),
+            None => Err(FindError::NotFound),
+        }
+    }
+
+    /// List all mailing lists.
+    pub async fn list(conn: &mut PgConnection) -> Result<Vec<Self>, sqlx::Error> {
+        database::mailing_list::list(conn).await
+    }
+
+    /// Resolve a recipient to a mailing list address.
+    /// Returns None if the recipient does not belong to a mailing list.
+    pub async fn resolve(
+        conn: &mut PgConnection,
+        local_part: &str,
+        domain: &str,
+    ) -> Result<Option<(Self, ListAddress)>, sqlx::Error> {
+        let local_part = local_part.to_lowercase();
+        let domain = domain.to_lowercase();
+
+        let (list, kind) = if let Some(list) = local_part.strip_suffix("-request") {
+            (list, ListAddress::Request)
+        } else if let Some((list, verp)) = local_part.split_once("-bounces+") {
+            match verp.rsplit_once('=') {
+                Some((user, host)) => (list, ListAddress::Bounce(format!("{}@{}", user, host))),
+                None => return Ok(None),
+            }
+        } else {
+            (local_part.as_str(), ListAddress::Post)
+        };
+
+        Ok(database::mailing_list::find_address(conn, list, &domain)
+            .await?
+            .map(|list| (list, kind)))
+    }
+
+    /// The address to post to.
+    pub fn address(&self) -> String {
+        format!("{}@{}", self.local_part, self.domain)
+    }
+
+    /// The address to send commands to.
+    pub fn request_address(&self) -> String {
+        format!("{}-request@{}", self.local_part, self.domain)
+    }
+
+    /// The VERP envelope sender used for a subscriber, so bounces identify the subscriber.
+    pub fn bounce_address(&self, subscriber: &str) -> String {
+        format!(
+            "{}-bounces+{}@{}",
+            self.local_part,
+            subscriber.replacen('@', "=", 1),
+            self.domain
+        )
+    }
+
+    /// List all members of this mailing list.
+    pub async fn members(&self, conn: &mut PgConnection) -> Result<Vec<ListMember>, sqlx::Error> {
+        database::list_member::list(conn, self.id).await
+    }
+
+    /// Subscribe an address, or update an existing subscription.
+    pub async fn subscribe(
+        &self,
+        conn: &mut PgConnection,
+        address: &str,
+        role: ListRole,
+        digest: bool,
+    ) -> Result<ListMember, SubscribeError> {
+        let address = address.trim().to_lowercase();
+
+        if !Self::validate_address(&address) {
+            return Err(SubscribeError::InvalidAddress(address));
+        }
+
+        Ok(database::list_member::upsert(conn, self.id, &address, role, digest).await?)
+    }
+
+    /// Remove a subscription.
+    pub async fn unsubscribe(
+        &self,
+        conn: &mut PgConnection,
+        address: &str,
+    ) -> Result<(), SubscribeError> {
+        let address = address.trim().to_lowercase();
+
+        match database::list_member::delete(conn, self.id, &address).await? {
+            true => Ok(()),
+            false => Err(SubscribeError::NotSubscribed(address)),
+        }
+    }
+
+    /// Handle a post to the list, according to the posting policy.
+    pub async fn post(
+        &self,
+        conn: &mut PgConnection,
+        sender: &str,
+        data: &[u8],
+    ) -> Result<PostResult, sqlx::Error> {
+        let sender = sender.to_lowercase();
+        let member = database::list_member::find(conn, self.id, &sender).await?;
+        let is_member = member.map(|m| !m.disabled).unwrap_or(false);
+
+        match self.policy {
+            ListPolicy::Members if !is_member => Ok(PostResult::Rejected),
+            ListPolicy::Moderated if !is_member => {
+                let post = database::list_moderation::create(conn, self.id, &sender, data).await?;
+
+                self.notify_moderators(conn, &post).await?;
+
+                Ok(PostResult::Moderated(post.id))
+            }
+            _ => Ok(PostResult::Distributed(self.distribute(conn, data).await?)),
+        }
+    }
+
+    /// List the posts waiting for moderation.
+    pub async fn moderation_queue(
+        &self,
+        conn: &mut PgConnection,
+    ) -> Result<Vec<ModeratedPost>, sqlx::Error> {
+        database::list_moderation::list(conn, self.id).await
+    }
+
+    /// Approve or reject a post waiting for moderation.
+    pub async fn moderate(
+        &self,
+        conn: &mut PgConnection,
+        post: Uuid,
+        approve: bool,
+    ) -> Result<(), ModerateError> {
+        let post = database::list_moderation::take(conn, self.id, post)
+            .await?
+            .ok_or(ModerateError::NotFound)?;
+
+        if approve {
+            self.distribute(conn, &post.data).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Handle a command sent to the request address.
+    /// The command is taken from the subject, or the first line of the body.
+    pub async fn command(
+        &self,
+        conn: &mut PgConnection,
+        sender: &str,
+        mail: &mailparse::ParsedMail<'_>,
+    ) -> Result<(), sqlx::Error> {
+        let subject = mail
+            .headers
+            .iter()
+            .find(|h| h.get_key_ref().eq_ignore_ascii_case("subject"))
+            .map(|h| h.get_value())
+            .unwrap_or_default();
+        let body = mail.get_body().unwrap_or_default();
+        let first_line = body
+            .lines()
+            .map(|l| l.trim())
+            .find(|l| !l.is_empty())
+            .unwrap_or_default()
+            .to_string();
+
+        let sender = sender.to_lowercase();
+        let member = database::list_member::find(conn, self.id, &sender).await?;
+        let is_moderator = member
+            .as_ref()
+            .map(|m| m.role == ListRole::Moderator)
+            .unwrap_or(false);
+
+        let reply = match Self::parse_command(&subject).or_else(|| Self::parse_command(&first_line))
+        {
+            // Anyone can claim to be the sender, so the change is only made after the address confirms it.
+            Some(ListCommand::Subscribe { digest }) => {
+                if !Self::validate_address(&sender) {
+                    return Ok(());
+                }
+
+                return self.request_confirmation(conn, &sender, true, digest).await;
+            }
+            Some(ListCommand::Unsubscribe) if member.is_some() => {
+                return self.request_confirmation(conn, &sender, false, false).await;
+            }
+            Some(ListCommand::Unsubscribe) => {
+                SubscribeError::NotSubscribed(sender.clone()).to_string()
+            }
+            Some(ListCommand::Confirm { token }) => {
+                return self.confirm(conn, token).await;
+            }
+            Some(ListCommand::Moderate { post, approve }) if is_moderator => {
+                match self.moderate(conn, post, approve).await {
+                    Ok(_) if approve => format!("Post {} was approved.", post),
+                    Ok(_) => format!("Post {} was rejected.", post),
+                    Err(ModerateError::DatabaseError(e)) => return Err(e),
+                    Err(e) => e.to_string(),
+                }
+            }
+            _ => format!(
+                "The following commands are available for {}:\r\n\r\n\
+                subscribe - Subscribe to this list.\r\n\
+                subscribe digest - Subscribe to the daily digest of this list.\r\n\
+                unsubscribe - Unsubscribe from this list.\r\n",
+                self.address()
+            ),
+        };
+
+        self.notify(conn, &sender, &format!("Re: {}", self.name), &reply)
+            .await
+    }
+
+    /// Ask an address to confirm a subscription change.
+    /// The notice carries the token as its subject, so replying to it is enough.
+    async fn request_confirmation(
+        &self,
+        conn: &mut PgConnection,
+        address: &str,
+        subscribe: bool,
+        digest: bool,
+    ) -> Result<(), sqlx::Error> {
+        let token =
+            database::list_confirmation::create(conn, self.id, address, subscribe, digest).await?;
+
+        let change = match subscribe {
+            true => format!("subscribe {} to", address),
+            false => format!("unsubscribe {} from", address),
+        };
+        let text = format!(
+            "Someone asked to {} {}.\r\n\r\n\
+            Reply to this message to confirm, or ignore it if the request was not yours.\r\n",
+            change,
+            self.address()
+        );
+
+        self.notify(conn, address, &format!("confirm {}", token), &text)
+            .await
+    }
+
+    /// Apply a confirmed subscription change, and tell the address about it.
+    /// The change applies to the address the token was sent to, whoever replied.
+    async fn confirm(&self, conn: &mut PgConnection, token: Uuid) -> Result<(), sqlx::Error> {
+        let confirmation = match database::list_confirmation::take(conn, self.id, token).await? {
+            Some(confirmation) => confirmation,
+            None => {
+                info!("Ignoring unknown confirmation for {}.", self.address());
+                return Ok(());
+            }
+        };
+        let address = confirmation.address;
+
+        let reply = if confirmation.subscribe {
+            let role = database::list_member::find(conn, self.id, &address)
+                .await?
+                .map(|m| m.role)
+                .unwrap_or(ListRole::Member);
+
+            match self
+                .subscribe(conn, &address, role, confirmation.digest)
+                .await
+            {
+                Ok(_) => format!("You are now subscribed to {}.", self.address()),
+                Err(SubscribeError::DatabaseError(e)) => return Err(e),
+                Err(e) => e.to_string(),
+            }
+        } else {
+            match self.unsubscribe(conn, &address).await {
+                Ok(_) => format!("You are now unsubscribed from {}.", self.address()),
+                Err(SubscribeError::DatabaseError(e)) => return Err(e),
+                Err(e) => e.to_string(),
+            }
+        };
+
+        self.notify(conn, &address, &format!("Re: {}", self.name), &reply)
+            .await
+    }
+
+    /// Count a bounce for a subscriber, identified by the VERP address.
+    /// Only delivery status notifications (RFC 3464) from the null sender count, and only when they
+    /// report a failed delivery to the subscriber, so others can't disable subscribers.
+    pub async fn bounce(
+        &self,
+        conn: &mut PgConnection,
+        sender: Option<&str>,
+        subscriber: &str,
+        mail: &mailparse::ParsedMail<'_>,
+    ) -> Result<(), sqlx::Error> {
+        let subscriber = subscriber.to_lowercase();
+
+        if sender.is_some() || !Self::failed_recipients(mail).contains(&subscriber) {
+            info!(
+                "Ignoring bounce for {} on list {} without matching report.",
+                subscriber,
+                self.address()
+            );
+            return Ok(());
+        }
+
+        let member =
+            database::list_member::bounce(conn, self.id, &subscriber, BOUNCE_THRESHOLD).await?;
+
+        if let Some(member) = member {
+            if member.disabled {
+                info!(
+                    "Disabled {} on list {} after {} bounces.",
+                    member.address,
+                    self.address(),
+                    member.bounces
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Find the lists which have a digest due.
+    pub async fn due_digests(conn: &mut PgConnection) -> Result<Vec<Self>, sqlx::Error> {
+        database::mailing_list::due_digests(conn).await
+    }
+
+    /// Send the collected posts as a MIME digest (RFC 2046, section 5.1.5) to the digest subscribers.
+    /// The posts are only removed once the digest is queued, within one transaction.
+    /// Returns the amount of posts in the digest.
+    pub async fn send_digest(&self, conn: &mut PgConnection) -> Result<usize, sqlx::Error> {
+        let mut tx = conn.begin().await?;
+        let posts = database::list_digest::take(&mut tx, self.id).await?;
+        database::mailing_list::digest_sent(&mut tx, self.id).await?;
+
+        if posts.is_empty() {
+            tx.commit().await?;
+            return Ok(0);
+        }
+
+        let boundary = format!("digest-{}", Uuid::new_v4().to_simple());
+        let mut body = format!(
+            "{}\
+            Subject: {} digest\r\n\
+            MIME-Version: 1.0\r\n\
+            Content-Type: multipart/digest; boundary=\"{}\"\r\n\
+            \r\n\
+            This digest contains {} messages.\r\n",
+            self.notice_headers(&self.address()),
+            self.name,
+            boundary,
+            posts.len()
+        )
+        .into_bytes();
+
+        for post in &posts {
+            body.extend_from_slice(format!("\r\n--{}\r\n\r\n", boundary).as_bytes());
+            body.extend_from_slice(post);
+        }
+        body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+
+        let body = self.with_list_headers(&body);
+
+        for member in self.members(&mut tx).await? {
+            if member.digest && !member.disabled {
+                let sender = self.bounce_address(&member.address);
+                Outbound::queue(&mut tx, &sender, &[member.address], &body).await?;
+            }
+        }
+
+        tx.commit().await?;
+
+        Ok(posts.len())
+    }
+
+    /// Send a post to all subscribers.
+    /// Every subscriber gets its own envelope sender, so that bounces can be attributed.
+    /// Digest subscribers receive the post with the next digest.
+    async fn distribute(&self, conn: &mut PgConnection, data: &[u8]) -> Result<usize, sqlx::Error> {
+        let data = self.with_list_headers(data);
+        let mut sent = 0;
+        let mut digest = false;
+
+        for member in self.members(conn).await? {
+            if member.disabled {
+                continue;
+            }
+
+            if member.digest {
+                digest = true;
+                continue;
+            }
+
+            let sender = self.bounce_address(&member.address);
+            Outbound::queue(conn, &sender, &[member.address], &data).await?;
+            sent += 1;
+        }
+
+        if digest {
+            database::list_digest::create(conn, self.id, &data).await?;
+        }
+
+        Ok(sent)
+    }
+
+    /// Ask the moderators to review a post.
+    async fn notify_moderators(
+        &self,
+        conn: &mut PgConnection,
+        post: &ModeratedPost,
+    ) -> Result<(), sqlx::Error> {
+        let text = format!(
+            "A post from {} to {} is waiting for moderation.\r\n\r\n\
+            Reply with 'approve {}' or 'reject {}' to the request address.\r\n",
+            post.sender,
+            self.address(),
+            post.id,
+            post.id
+        );
+
+        for member in self.members(conn).await? {
+            if member.role == ListRole::Moderator && !member.disabled {
+                self.notify(
+                    conn,
+                    &member.address,
+                    &format!("Moderation required for {}", self.name),
+                    &text,
+                )
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Send a plain text notice from the request address.
+    async fn notify(
+        &self,
+        conn: &mut PgConnection,
+        to: &str,
+        subject: &str,
+        text: &str,
+    ) -> Result<(), sqlx::Error> {
+        let message = format!(
+            "{}Subject: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{}",
+            self.notice_headers(to),
+            subject,
+            text
+        );
+
+        Outbound::queue(
+            conn,
+            &self.bounce_address(to),
+            &[to.to_string()],
+            message.as_bytes(),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// The common headers of messages generated by the list itself.
+    fn notice_headers(&self, to: &str) -> String {
+        format!(
+            "From: {}\r\nTo: {}\r\nDate: {}\r\nMessage-ID: <{}@{}>\r\nAuto-Submitted: auto-generated\r\n",
+            self.request_address(),
+            to,
+            OffsetDateTime::now_utc().format("%a, %d %b %Y %H:%M:%S +0000"),
+            Uuid::new_v4(),
+            self.domain
+        )
+    }
+
+    /// Add the list headers of RFC 2369 and RFC 2919 to a message.
+    /// Existing list headers are removed, as they belong to another list.
+    fn with_list_headers(&self, data: &[u8]) -> Vec<u8> {
+        let request = self.request_address();
+        let mut message = format!(
+            "List-Id: \"{}\" <{}.{}>\r\n\
+            List-Post: <mailto:{}>\r\n\
+            List-Help: <mailto:{}?subject=help>\r\n\
+            List-Subscribe: <mailto:{}?subject=subscribe>\r\n\
+            List-Unsubscribe: <mailto:{}?subject=unsubscribe>\r\n\
+            Precedence: list\r\n",
+            self.name.replace('"', "'"),
+            self.local_part,
+            self.domain,
+            self.address(),
+            request,
+            request,
+            request
+        )
+        .into_bytes();
+
+        let mut in_headers = true;
+        let mut skipping = false;
+
+        for line in data.split_inclusive(|b| *b == b'\n') {
+            if in_headers {
+                if line == b"\r\n" || line == b"\n" {
+                    in_headers = false;
+                } else if line.starts_with(b" ") || line.starts_with(b"\t") {
+                    // Folded lines belong to the previous header.
+                    if skipping {
+                        continue;
+                    }
+                } else {
+                    skipping = line.len() >= 5 && line[..5].eq_ignore_ascii_case(b"list-")
+                        || line.len() >= 11 && line[..11].eq_ignore_ascii_case(b"precedence:");
+
+                    if skipping {
+                        continue;
+                    }
+                }
+            }
+
+            message.extend_from_slice(line);
+        }
+
+        message
+    }
+
+    /// The recipients a delivery status notification reports as failed.
+    /// Only `multipart/report` messages of the `delivery-status` type are notifications.
+    fn failed_recipients(mail: &mailparse::ParsedMail<'_>) -> Vec<String> {
+        let is_report = mail.ctype.mimetype.eq_ignore_ascii_case("multipart/report")
+            && mail
+                .ctype
+                .params
+                .get("report-type")
+                .map(|t| t.eq_ignore_ascii_case("delivery-status"))
+                .unwrap_or(false);
+        if !is_report {
+            return Vec::new();
+        }
+
+        let status = match mail
+            .subparts
+            .iter()
+            .find(|p| {
+                p.ctype
+                    .mimetype
+                    .eq_ignore_ascii_case("message/delivery-status")
+            })
+            .and_then(|p| p.get_body().ok())
+        {
+            Some(status) => status.replace("\r\n", "\n"),
+            None => return Vec::new(),
+        };
+
+        // The fields for the message come first, followed by a group of fields per recipient.
+        let mut failed = Vec::new();
+        for group in status.split("\n\n").skip(1) {
+            let mut recipient = None;
+            let mut action = None;
+
+            for line in group.lines() {
+                let (name, value) = match line.split_once(':') {
+                    Some((name, value)) => (name.trim().to_lowercase(), value.trim()),
+                    None => continue,
+                };
+
+                match name.as_str() {
+                    "final-recipient" => {
+                        recipient = match value.split_once(';') {
+                            Some((kind, address)) if kind.trim().eq_ignore_ascii_case("rfc822") => {
+                                Some(address.trim().trim_matches(|c| c == '<' || c == '>'))
+                            }
+                            _ => None,
+                        }
+                    }
+                    "action" => action = Some(value.to_lowercase()),
+                    _ => {}
+                }
+            }
+
+            if let (Some(recipient), Some("failed")) = (recipient, action.as_deref()) {
+                failed.push(recipient.to_lowercase());
+            }
+        }
+
+        failed
+    }
+
+    /// Parse a command line.
+    fn parse_command(line: &str) -> Option<ListCommand> {
+        let line = line.trim().to_lowercase();
+        let line = line.strip_prefix("re:").unwrap_or(&line).trim();
+        let mut words = line.split_whitespace();
+
+        match (words.next(), words.next()) {
+            (Some("subscribe"), Some("digest")) => Some(ListCommand::Subscribe { digest: true }),
+            (Some("subscribe"), None) => Some(ListCommand::Subscribe { digest: false }),
+            (Some("unsubscribe"), _) => Some(ListCommand::Unsubscribe),
+            (Some("confirm"), Some(token)) => Uuid::parse_str(token)
+                .ok()
+                .map(|token| ListCommand::Confirm { token }),
+            (Some("approve"), Some(id)) => {
+                Uuid::parse_str(id).ok().map(|post| ListCommand::Moderate {
+                    post,
+                    approve: true,
+                })
+            }
+            (Some("reject"), Some(id)) => {
+                Uuid::parse_str(id).ok().map(|post| ListCommand::Moderate {
+                    post,
+                    approve: false,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Validate the local part of a list address.
+    /// The suffixes used for the request and bounce addresses are not allowed.
+    fn validate_local_part(local_part: &str) -> bool {
+        lazy_static! {
+            static ref REGEX: Regex = Regex::new("^[a-z0-9]+([.-][a-z0-9]+)*$").unwrap();
+        }
+
+        local_part.len() <= 50
+            && REGEX.is_match(local_part)
+            && !local_part.ends_with("-request")
+            && !local_part.contains("-bounces")
+    }
+
+    /// Validate that an address has a local part and a domain.
+    fn validate_address(address: &str) -> bool {
+        lazy_static! {
+            static ref REGEX: Regex = Regex::new("^[^@\\s<>]+@[a-z0-9.-]+\\.[a-z]{2,63}$").unwrap();
+        }
+
+        address.len() <= 254 && REGEX.is_match(address)
+    }
+}
+
+/// Commands which can be sent to the request address.
+enum ListCommand {
+    Subscribe { digest: bool },
+    Unsubscribe,
+    Confirm { token: Uuid },
+    Moderate { post: Uuid, approve: bool },
+}
+
+/// Possible errors with creating a new mailing list.
+#[derive(Error, Debug)]
+pub enum CreateError {
+    #[error("The list address '{0}' is invalid.")]
+    InvalidAddress(String),
+    #[error("The list '{0}' already exists.")]
+    ListExists(String),
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Possible errors with finding a mailing list.
+#[derive(Error, Debug)]
+pub enum FindError {
+    #[error("The mailing list was not found.")]
+    NotFound,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Possible errors with changing subscriptions.
+#[derive(Error, Debug)]
+pub enum SubscribeError {
+    #[error("The address '{0}' is invalid.")]
+    InvalidAddress(String),
+    #[error("The address '{0}' is not subscribed.")]
+    NotSubscribed(String),
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Possible errors with moderating a post.
+#[derive(Error, Debug)]
+pub enum ModerateError {
+    #[error("The post is not waiting for moderation.")]
+    NotFound,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(report_type: &str, recipient: &str, action: &str) -> Vec<u8> {
+        format!(
+            "From: MAILER-DAEMON@example.com\r\n\
+            Content-Type: multipart/report; report-type={}; boundary=\"b\"\r\n\
+            \r\n\
+            --b\r\n\
+            Content-Type: text/plain\r\n\
+            \r\n\
+            Delivery failed.\r\n\
+            --b\r\n\
+            Content-Type: message/delivery-status\r\n\
+            \r\n\
+            Reporting-MTA: dns; mx.example.com\r\n\
+            \r\n\
+            Final-Recipient: rfc822; {}\r\n\
+            Action: {}\r\n\
+            Status: 5.1.1\r\n\
+            --b--\r\n",
+            report_type, recipient, action
+        )
+        .into_bytes()
+    }
+
+    fn failed(data: &[u8]) -> Vec<String> {
+        MailingList::failed_recipients(&mailparse::parse_mail(data).unwrap())
+    }
+
+    #[test]
+    fn failed_delivery_is_reported() {
+        let data = report("delivery-status", "<Alice@Example.com>", "failed");
+        assert_eq!(failed(&data), vec!["alice@example.com"]);
+    }
+
+    #[test]
+    fn other_reports_are_ignored() {
+        let delayed = report("delivery-status", "alice@example.com", "delayed");
+        assert!(failed(&delayed).is_empty());
+
+        let disposition = report("disposition-notification", "alice@example.com", "failed");
+        assert!(failed(&disposition).is_empty());
+
+        let plain =
+            b"From: alice@example.com\r\n\r\nFinal-Recipient: rfc822; alice@example.com\r\n";
+        assert!(failed(plain).is_empty());
+    }
+
+    #[test]
+    fn confirmations_are_parsed() {
+        let token = Uuid::new_v4();
+
+        let command = MailingList::parse_command(&format!("Re: confirm {}", token));
+        assert!(matches!(command, Some(ListCommand::Confirm { token: t }) if t == token));
+
+        assert!(MailingList::parse_command("confirm yes").is_none());
+    }
+}
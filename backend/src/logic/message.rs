@@ -0,0 +1,801 @@
+use mailparse::MailHeaderMap;
+use serde::{Deserialize, Serialize};
+use sqlx::{Connection, PgConnection};
+use thiserror::Error;
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    crypto, database,
+    logic::{
+        account::Account,
+        attachment::{self, Attachment},
+        blob::{Blob, BlobError},
+        html,
+        mailbox::{Mailbox, Role},
+        mailbox_key::{KeyError, MailboxKey},
+        mime::{self, Structure},
+        pgp_key::{self, EncryptError, PgpKey},
+        quota::{Quota, QuotaConfig, QuotaError},
+        search::{Document, Query},
+        thread::{self, Thread},
+    },
+    storage::{BlobStore, Spool, Spooled, StoreError},
+};
+
+/// The maximum amount of messages of which the flags are changed at once.
+pub const MAX_FLAGGED: usize = 1000;
+
+/// The longest keyword, in bytes.
+const MAX_KEYWORD: usize = 64;
+
+/// Represents a message in an account.
+/// The raw message is kept in the blob store, shared by all recipients.
+/// For accounts with a mailbox key it is encrypted instead, and only readable with the unlocked key.
+/// The metadata stays readable, so messages can be listed and searched without the key.
+/// That is the subject, the sender and the recipients, the search index and the snippet,
+/// which the server can read as well.
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Message {
+    pub id: Uuid,
+    pub mailbox: Uuid,
+    pub thread: Uuid,
+    #[serde(skip)]
+    pub blob: String,
+    pub size: i64,
+    pub sender: String,
+    pub recipients: String,
+    pub subject: String,
+    pub message_id: Option<String>,
+    #[serde(with = "time::serde::timestamp::option")]
+    pub sent: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::timestamp")]
+    pub received: OffsetDateTime,
+    /// The content key of an encrypted message, sealed to the mailbox key.
+    #[serde(skip)]
+    pub sealed_key: Option<Vec<u8>>,
+    pub seen: bool,
+    pub flagged: bool,
+    pub answered: bool,
+    pub draft: bool,
+    /// Marked for deletion, as by IMAP clients before expunging.
+    pub deleted: bool,
+    /// Custom keywords, in lowercase.
+    pub keywords: Vec<String>,
+    /// The IDs of the labels of the message.
+    pub labels: Vec<Uuid>,
+    /// The modification sequence of the last change, within the mailbox.
+    pub modseq: i64,
+}
+
+/// Changes to the flags of messages, None leaves a flag as it is.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct FlagChanges {
+    pub seen: Option<bool>,
+    pub flagged: Option<bool>,
+    pub answered: Option<bool>,
+    pub draft: Option<bool>,
+    pub deleted: Option<bool>,
+    #[serde(default)]
+    pub add_keywords: Vec<String>,
+    #[serde(default)]
+    pub remove_keywords: Vec<String>,
+}
+
+/// The metadata of a message, taken from its headers.
+#[derive(Debug, Default)]
+pub struct Metadata {
+    pub sender: String,
+    pub recipients: String,
+    pub subject: String,
+    pub message_id: Option<String>,
+    pub sent: Option<OffsetDateTime>,
+    /// The Message-IDs of In-Reply-To and References, without angle brackets.
+    pub references: Vec<String>,
+}
+
+impl Metadata {
+    /// Parse the metadata from the header section of a raw message.
+    /// Missing or malformed headers are left empty.
+    pub fn parse(data: &[u8]) -> Self {
+        let headers = match mailparse::parse_headers(data) {
+            Ok((headers, _)) => headers,
+            Err(_) => return Metadata::default(),
+        };
+
+        let recipients: Vec<String> = ["To", "Cc"]
+            .iter()
+            .flat_map(|key| headers.get_all_values(key))
+            .collect();
+
+        // Dates beyond the year 9999 are not supported by the database.
+        let sent = headers
+            .get_first_value("Date")
+            .and_then(|d| mailparse::dateparse(&d).ok())
+            .filter(|t| (0..=253_402_300_799).contains(t))
+            .map(OffsetDateTime::from_unix_timestamp);
+
+        let mut references: Vec<String> = ["References", "In-Reply-To"]
+            .iter()
+            .flat_map(|key| headers.get_all_values(key))
+            .flat_map(|value| thread::message_ids(&value))
+            .collect();
+        references.dedup();
+
+        Metadata {
+            sender: headers.get_first_value("From").unwrap_or_default(),
+            recipients: recipients.join(", "),
+            subject: headers.get_first_value("Subject").unwrap_or_default(),
+            message_id: headers.get_first_value("Message-ID"),
+            sent,
+            references,
+        }
+    }
+}
+
+/// The storage used by the messages of an account, in bytes.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Usage {
+    pub messages: i64,
+    /// The size of the messages as received.
+    pub size: i64,
+    /// The size of the messages in the blob store, after compression.
+    pub stored: i64,
+}
+
+/// The readable content of a message.
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct Body {
+    /// The plain text part, or the text of the HTML when there is none.
+    pub text: Option<String>,
+    /// The sanitized HTML, with `cid:` references pointing to the attachment URLs.
+    pub html: Option<String>,
+    /// The amount of remote images replaced by a placeholder.
+    pub blocked: usize,
+}
+
+impl Message {
+    /// Deliver a spooled raw message into an account, with the parts and search document extracted from it.
+    /// The parts of encrypted messages are not recorded, they are extracted when read.
+    /// Their content is not searchable either.
+    /// The account is warned once its usage crosses the warning threshold of its quota.
+    pub async fn deliver(
+        conn: &mut PgConnection,
+        store: &dyn BlobStore,
+        account: &Account,
+        spooled: &Spooled,
+        attachments: &[Attachment],
+        document: &Document,
+        quota: &QuotaConfig,
+    ) -> Result<Self, DeliverError> {
+        Self::save(
+            conn,
+            store,
+            account,
+            Role::Inbox,
+            spooled,
+            attachments,
+            document,
+            quota,
+        )
+        .await
+    }
+
+    /// Save a spooled raw message into the mailbox of an account with a role, like delivering it.
+    /// Used for the messages written by the account itself, which are stored in Sent or Drafts.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn save(
+        conn: &mut PgConnection,
+        store: &dyn BlobStore,
+        account: &Account,
+        role: Role,
+        spooled: &Spooled,
+        attachments: &[Attachment],
+        document: &Document,
+        quota: &QuotaConfig,
+    ) -> Result<Self, DeliverError> {
+        let (message, warn) = Self::store(
+            conn,
+            store,
+            account,
+            role,
+            spooled,
+            attachments,
+            document,
+            quota,
+            true,
+        )
+        .await?;
+
+        if warn {
+            if let Err(e) = Self::deliver_warning(conn, store, account, quota).await {
+                warn!("Failed to warn {} about its quota: {}", account.username, e);
+            }
+        }
+
+        Ok(message)
+    }
+
+    /// Deliver a spooled message into an account, wrapped in PGP/MIME when the account has an OpenPGP key.
+    /// Messages which are already encrypted or signed are stored as is.
+    /// Only the metadata of messages encrypted with PGP can be searched.
+    pub async fn deliver_protected(
+        conn: &mut PgConnection,
+        store: &dyn BlobStore,
+        account: &Account,
+        spooled: &Spooled,
+        structure: &Structure,
+        document: &Document,
+        quota: &QuotaConfig,
+    ) -> Result<Self, DeliverError> {
+        let encrypted = match pgp_key::is_protected(structure) {
+            true => None,
+            false => Self::encrypt_pgp(conn, store, account, spooled, quota).await?,
+        };
+
+        match &encrypted {
+            Some((spooled, attachments)) => {
+                let document = document.redact();
+                Self::deliver(conn, store, account, spooled, attachments, &document, quota).await
+            }
+            None => {
+                let attachments = structure.attachments();
+                Self::deliver(conn, store, account, spooled, &attachments, document, quota).await
+            }
+        }
+    }
+
+    /// Wrap a spooled message in PGP/MIME for an account with an OpenPGP key, with the parts of the result.
+    /// The encryption needs the whole message in memory, so it is only read for accounts with a key.
+    /// Returns None when the account has no key, and the message is stored as is.
+    /// A key which is no longer usable falls back to the mailbox key, the account is told once.
+    /// Without a mailbox key either the message is rejected, it is never stored readable.
+    async fn encrypt_pgp(
+        conn: &mut PgConnection,
+        store: &dyn BlobStore,
+        account: &Account,
+        spooled: &Spooled,
+        quota: &QuotaConfig,
+    ) -> Result<Option<(Spooled, Vec<Attachment>)>, DeliverError> {
+        let key = match PgpKey::find(conn, account.id).await {
+            Ok(key) => key,
+            Err(pgp_key::FindError::NotFound) => return Ok(None),
+            Err(pgp_key::FindError::DatabaseError(e)) => return Err(e.into()),
+        };
+
+        let data = spooled
+            .read()
+            .await
+            .map_err(|e| BlobError::StoreError(StoreError::Io(e)))?;
+        let (key, encrypted) = tokio::task::spawn_blocking(move || {
+            let encrypted = key.encrypt(&data);
+            (key, encrypted)
+        })
+        .await
+        .map_err(|_| EncryptError::EncryptionFailed)?;
+
+        let encrypted = match encrypted {
+            Ok(encrypted) => encrypted,
+            Err(EncryptError::Unusable) => {
+                if MailboxKey::public(conn, account.id).await?.is_none() {
+                    return Err(EncryptError::Unusable.into());
+                }
+
+                warn!(
+                    "The OpenPGP key of {} is no longer usable, storing under the mailbox key.",
+                    account.username
+                );
+                if let Err(e) = Self::deliver_key_warning(conn, store, account, &key, quota).await {
+                    warn!("Failed to warn {} about its key: {}", account.username, e);
+                }
+                return Ok(None);
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let spooled = async {
+            let mut spool = Spool::create().await?;
+            spool.write(&encrypted).await?;
+            spool.finish().await
+        }
+        .await
+        .map_err(|e| BlobError::StoreError(StoreError::Io(e)))?;
+
+        Ok(Some((spooled, Structure::parse(&encrypted).attachments())))
+    }
+
+    /// Store a message in the mailbox of an account with a role, counting it into the quota.
+    /// Messages stored outside of the inbox are written by the account itself, so they are seen already.
+    /// Those in Drafts are marked as drafts.
+    /// Returns whether the account should be warned about its usage.
+    #[allow(clippy::too_many_arguments)]
+    async fn store(
+        conn: &mut PgConnection,
+        store: &dyn BlobStore,
+        account: &Account,
+        role: Role,
+        spooled: &Spooled,
+        attachments: &[Attachment],
+        document: &Document,
+        quota: &QuotaConfig,
+        enforce: bool,
+    ) -> Result<(Self, bool), DeliverError> {
+        let metadata = Metadata::parse(&spooled.headers);
+        let size = spooled.size as i64;
+
+        // The quota is counted first, so nothing is stored when the message does not fit.
+        let mut tx = conn.begin().await?;
+        let (counted, warn) = Quota::count(&mut tx, account.id, size, quota).await?;
+        if enforce {
+            counted.within(size)?;
+        }
+        let mailbox = Mailbox::role(&mut tx, account.id, role).await?;
+        let thread = Thread::assign(&mut tx, account.id, &metadata).await?;
+
+        let (blob, sealed_key) = match MailboxKey::public(&mut tx, account.id).await? {
+            Some(public) => {
+                let (blob, key) = Blob::store_encrypted(&mut tx, store, spooled).await?;
+                (blob, Some(crypto::seal(&public, &key)))
+            }
+            None => {
+                let blob = Blob::store(&mut tx, store, spooled).await?;
+                for attachment in attachments {
+                    database::attachment::create(&mut tx, &blob.hash, attachment).await?;
+                }
+                (blob, None)
+            }
+        };
+        let redacted;
+        let document = match sealed_key {
+            Some(_) => {
+                redacted = document.redact();
+                &redacted
+            }
+            None => document,
+        };
+        let message = database::message::create(
+            &mut tx,
+            account.id,
+            mailbox.id,
+            thread,
+            &blob.hash,
+            blob.size,
+            &metadata,
+            document,
+            sealed_key.as_deref(),
+            role != Role::Inbox,
+            role == Role::Drafts,
+        )
+        .await?;
+        tx.commit().await?;
+
+        Ok((message, warn))
+    }
+
+    /// Deliver the warning that the storage of an account is almost full.
+    async fn deliver_warning(
+        conn: &mut PgConnection,
+        store: &dyn BlobStore,
+        account: &Account,
+        quota: &QuotaConfig,
+    ) -> Result<(), DeliverError> {
+        let warning = Quota::find(conn, account.id, quota).await?.warning(account);
+
+        let spooled = async {
+            let mut spool = Spool::create().await?;
+            spool.write(warning.as_bytes()).await?;
+            spool.finish().await
+        }
+        .await
+        .map_err(|e| BlobError::StoreError(StoreError::Io(e)))?;
+
+        let structure = Structure::parse(warning.as_bytes());
+        let document = Document::extract(&structure);
+
+        // The warning is stored even when it does not fit, the usage can jump past the threshold.
+        Self::store(
+            conn,
+            store,
+            account,
+            Role::Inbox,
+            &spooled,
+            &structure.attachments(),
+            &document,
+            quota,
+            false,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Deliver the warning that the OpenPGP key of an account is no longer usable, once for the key.
+    async fn deliver_key_warning(
+        conn: &mut PgConnection,
+        store: &dyn BlobStore,
+        account: &Account,
+        key: &PgpKey,
+        quota: &QuotaConfig,
+    ) -> Result<(), DeliverError> {
+        if !PgpKey::mark_unusable(conn, account.id).await? {
+            return Ok(());
+        }
+
+        let warning = key.warning(account);
+        let spooled = async {
+            let mut spool = Spool::create().await?;
+            spool.write(warning.as_bytes()).await?;
+            spool.finish().await
+        }
+        .await
+        .map_err(|e| BlobError::StoreError(StoreError::Io(e)))?;
+
+        let structure = Structure::parse(warning.as_bytes());
+        let document = Document::extract(&structure);
+
+        // Like the quota warning, it is stored even when it does not fit.
+        Self::store(
+            conn,
+            store,
+            account,
+            Role::Inbox,
+            &spooled,
+            &structure.attachments(),
+            &document,
+            quota,
+            false,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Find a message of an account by ID.
+    pub async fn find(conn: &mut PgConnection, account: Uuid, id: Uuid) -> Result<Self, FindError> {
+        let res = database::message::find(conn, account, id).await?;
+
+        match res {
+            Some(message) => Ok(message),
+            None => Err(FindError::NotFound),
+        }
+    }
+
+    /// Read the raw message from the blob store, decrypting it with the mailbox key when needed.
+    pub async fn raw(
+        &self,
+        conn: &mut PgConnection,
+        store: &dyn BlobStore,
+        mailbox_key: Option<&MailboxKey>,
+    ) -> Result<Vec<u8>, ReadError> {
+        let key = match (&self.sealed_key, mailbox_key) {
+            (None, _) => None,
+            (Some(sealed), Some(mailbox_key)) => {
+                Some(mailbox_key.open(sealed).map_err(|_| ReadError::Locked)?)
+            }
+            (Some(_), None) => return Err(ReadError::Locked),
+        };
+
+        Ok(Blob::load(conn, store, &self.blob, key.as_ref()).await?)
+    }
+
+    /// List the IDs of all messages of an account, oldest first, with whether they are encrypted.
+    pub async fn ids(
+        conn: &mut PgConnection,
+        account: Uuid,
+    ) -> Result<Vec<(Uuid, bool)>, sqlx::Error> {
+        database::message::ids(conn, account).await
+    }
+
+    /// Write the raw message as an entry of an mbox file.
+    /// Lines looking like the separator of the next entry are quoted with another `>` (mboxrd).
+    pub fn to_mbox(&self, raw: &[u8]) -> Vec<u8> {
+        let sender = self
+            .sender_address()
+            .filter(|a| !a.contains(char::is_whitespace))
+            .unwrap_or_else(|| "MAILER-DAEMON".to_string());
+        let date = self.received.format("%a %b %d %H:%M:%S %Y");
+
+        let mut output = Vec::with_capacity(raw.len() + 128);
+        output.extend_from_slice(format!("From {} {}\n", sender, date).as_bytes());
+
+        for line in raw.split_inclusive(|b| *b == b'\n') {
+            let quoted = line.iter().position(|b| *b != b'>').unwrap_or(line.len());
+            if line[quoted..].starts_with(b"From ") {
+                output.push(b'>');
+            }
+            output.extend_from_slice(line);
+        }
+
+        if !output.ends_with(b"\n") {
+            output.push(b'\n');
+        }
+        output.push(b'\n');
+
+        output
+    }
+
+    /// Whether the raw message is encrypted.
+    pub fn is_encrypted(&self) -> bool {
+        self.sealed_key.is_some()
+    }
+
+    /// Search the messages of an account, ranked by relevance and then by date.
+    pub async fn search(
+        conn: &mut PgConnection,
+        account: Uuid,
+        query: &Query,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        database::message::search(conn, account, &query.to_sql(3), limit, offset).await
+    }
+
+    /// Get the storage used by the messages of an account.
+    pub async fn usage(conn: &mut PgConnection, account: Uuid) -> Result<Usage, sqlx::Error> {
+        database::message::usage(conn, account).await
+    }
+
+    /// Get the first plain text and HTML parts, which are not attachments.
+    /// The HTML is sanitized, with remote images blocked unless allowed.
+    pub async fn body(
+        &self,
+        conn: &mut PgConnection,
+        store: &dyn BlobStore,
+        mailbox_key: Option<&MailboxKey>,
+        remote: bool,
+    ) -> Result<Body, BodyError> {
+        let raw = self.raw(conn, store, mailbox_key).await?;
+        let structure = Structure::parse(&raw);
+        let attachments = structure.attachments();
+
+        let (text, html) = Self::readable(&raw, &structure);
+        let mut body = Body {
+            text,
+            html,
+            blocked: 0,
+        };
+
+        if let Some(html) = body.html {
+            if body.text.is_none() {
+                body.text = Some(html::to_text(&html));
+            }
+
+            let sanitized = html::sanitize(
+                &attachment::rewrite_cids(&html, self.id, &attachments),
+                remote,
+            );
+            body.html = Some(sanitized.html);
+            body.blocked = sanitized.blocked;
+        }
+
+        Ok(body)
+    }
+
+    /// Get the first plain text and HTML parts of a scanned raw message as they are, skipping attachments.
+    pub fn readable(raw: &[u8], structure: &Structure) -> (Option<String>, Option<String>) {
+        let mut text = None;
+        let mut html = None;
+
+        for part in &structure.parts {
+            let attachment = &part.attachment;
+            if !attachment.is_body() {
+                continue;
+            }
+
+            let target = match attachment.content_type.as_str() {
+                "text/plain" => &mut text,
+                "text/html" => &mut html,
+                _ => continue,
+            };
+
+            if target.is_none() {
+                *target = Some(mime::decode_text(&attachment.decode(raw), &part.charset));
+            }
+        }
+
+        (text, html)
+    }
+
+    /// Check if an account loads remote images in messages from the sender of the message.
+    pub async fn remote_content(
+        &self,
+        conn: &mut PgConnection,
+        account: Uuid,
+    ) -> Result<bool, sqlx::Error> {
+        match self.sender_address() {
+            Some(address) => {
+                database::remote_content_sender::allowed(conn, account, &address).await
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Load or block remote images in all messages from the sender of the message.
+    pub async fn set_remote_content(
+        &self,
+        conn: &mut PgConnection,
+        account: Uuid,
+        allow: bool,
+    ) -> Result<(), RemoteContentError> {
+        let address = self.sender_address().ok_or(RemoteContentError::NoSender)?;
+
+        match allow {
+            true => database::remote_content_sender::add(conn, account, &address).await?,
+            false => database::remote_content_sender::remove(conn, account, &address).await?,
+        }
+
+        Ok(())
+    }
+
+    /// The address of the sender in lowercase, None if the From header has no single address.
+    pub fn sender_address(&self) -> Option<String> {
+        let addresses = mailparse::addrparse(&self.sender).ok()?;
+
+        match addresses.iter().next()? {
+            mailparse::MailAddr::Single(info) => Some(info.addr.to_lowercase()),
+            mailparse::MailAddr::Group(_) => None,
+        }
+    }
+
+    /// Delete the message, releasing its blob and its part of the quota.
+    pub async fn delete(
+        self,
+        conn: &mut PgConnection,
+        account: Uuid,
+        quota: &QuotaConfig,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = conn.begin().await?;
+        database::message::delete(&mut tx, self.id).await?;
+        Blob::release(&mut tx, &self.blob).await?;
+        Quota::release(&mut tx, account, self.size, 1, quota).await?;
+        tx.commit().await
+    }
+
+    /// Change the flags and keywords of messages of an account, in bulk.
+    /// Messages which do not exist are skipped.
+    /// Returns the IDs of the messages which changed.
+    pub async fn set_flags(
+        conn: &mut PgConnection,
+        account: Uuid,
+        ids: &[Uuid],
+        changes: FlagChanges,
+    ) -> Result<Vec<Uuid>, FlagError> {
+        if ids.len() > MAX_FLAGGED {
+            return Err(FlagError::TooManyMessages);
+        }
+
+        let normalize = |keywords: Vec<String>| {
+            keywords
+                .iter()
+                .map(|k| normalize_keyword(k).ok_or(FlagError::InvalidKeyword))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let changes = FlagChanges {
+            add_keywords: normalize(changes.add_keywords)?,
+            remove_keywords: normalize(changes.remove_keywords)?,
+            ..changes
+        };
+
+        Ok(database::message::set_flags(conn, account, ids, &changes).await?)
+    }
+
+    /// Copy the message into a mailbox of its account, with its flags and labels.
+    /// The copy shares the blob, but counts into the quota like any other message.
+    pub async fn copy_to(
+        &self,
+        conn: &mut PgConnection,
+        account: Uuid,
+        mailbox: &Mailbox,
+        quota: &QuotaConfig,
+    ) -> Result<Self, CopyError> {
+        let mut tx = conn.begin().await?;
+        let (counted, _) = Quota::count(&mut tx, account, self.size, quota).await?;
+        counted.within(self.size)?;
+
+        database::blob::acquire(&mut tx, &self.blob).await?;
+        let message = database::message::copy(&mut tx, self.id, mailbox.id).await?;
+        tx.commit().await?;
+
+        Ok(message)
+    }
+
+    /// Move the message into another mailbox of its account.
+    pub async fn move_to(
+        &mut self,
+        conn: &mut PgConnection,
+        mailbox: &Mailbox,
+    ) -> Result<(), sqlx::Error> {
+        database::message::move_many(conn, &[self.id], mailbox.id).await?;
+        self.mailbox = mailbox.id;
+
+        Ok(())
+    }
+}
+
+/// Normalize a keyword to lowercase, None if it is not valid.
+/// Keywords have to be valid IMAP atoms, which JMAP keywords are as well.
+pub fn normalize_keyword(keyword: &str) -> Option<String> {
+    let valid = !keyword.is_empty()
+        && keyword.len() <= MAX_KEYWORD
+        && keyword
+            .chars()
+            .all(|c| c.is_ascii_graphic() && !"(){%*\"\\]".contains(c));
+
+    match valid {
+        true => Some(keyword.to_ascii_lowercase()),
+        false => None,
+    }
+}
+
+/// Possible errors with delivering a message.
+#[derive(Error, Debug)]
+pub enum DeliverError {
+    #[error("{0}")]
+    BlobError(#[from] BlobError),
+    #[error("{0}")]
+    MailboxKey(#[from] KeyError),
+    #[error("{0}")]
+    OverQuota(#[from] QuotaError),
+    #[error("{0}")]
+    Encryption(#[from] EncryptError),
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Possible errors with reading a raw message.
+#[derive(Error, Debug)]
+pub enum ReadError {
+    #[error("The message is encrypted and the mailbox key is not unlocked.")]
+    Locked,
+    #[error("{0}")]
+    BlobError(#[from] BlobError),
+}
+
+/// Possible errors with reading the body of a message.
+#[derive(Error, Debug)]
+pub enum BodyError {
+    #[error("{0}")]
+    ReadError(#[from] ReadError),
+}
+
+/// Possible errors with loading remote images from the sender of a message.
+#[derive(Error, Debug)]
+pub enum RemoteContentError {
+    #[error("The message has no sender address.")]
+    NoSender,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Possible errors with changing the flags of messages.
+#[derive(Error, Debug)]
+pub enum FlagError {
+    #[error("Too many messages are changed at once.")]
+    TooManyMessages,
+    #[error("A keyword is empty, too long or contains invalid characters.")]
+    InvalidKeyword,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Possible errors with copying a message.
+#[derive(Error, Debug)]
+pub enum CopyError {
+    #[error("{0}")]
+    OverQuota(#[from] QuotaError),
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Possible errors with finding a message.
+#[derive(Error, Debug)]
+pub enum FindError {
+    #[error("The message was not found.")]
+    NotFound,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
@@ -0,0 +1,639 @@
+use std::io::{self, BufRead, BufReader};
+
+use charset::Charset;
+use mailparse::{DispositionType, MailHeaderMap};
+
+use crate::{
+    logic::attachment::{Attachment, Disposition},
+    storage::Spooled,
+};
+
+/// The maximum amount of decoded content kept of a part, the rest is only counted.
+const MAX_KEPT: usize = 1024 * 1024;
+
+/// The maximum size of the header section of a part which is parsed, the rest is skipped.
+const MAX_HEADER_SIZE: usize = 256 * 1024;
+
+/// The maximum amount of bytes read at once, longer lines are read in pieces.
+const MAX_LINE: usize = 8 * 1024;
+
+/// The media types of the parts whose content is kept, as it is read while receiving a message.
+const KEPT_TYPES: [&str; 4] = [
+    "text/plain",
+    "text/html",
+    "application/tlsrpt+json",
+    "application/tlsrpt+gzip",
+];
+
+/// The MIME structure of a raw message, found in a single pass over it.
+#[derive(Debug)]
+pub struct Structure {
+    /// The media type of the message itself, in lowercase.
+    pub content_type: String,
+    /// The Content-Language of the message.
+    pub language: Option<String>,
+    /// The parts without subparts, in depth-first order.
+    pub parts: Vec<Part>,
+}
+
+/// A part without subparts.
+#[derive(Debug)]
+pub struct Part {
+    /// The description of the part, with the location of its body in the raw message.
+    pub attachment: Attachment,
+    pub charset: String,
+    /// The decoded content of text parts and TLS reports, cut off after 1 MiB.
+    pub content: Option<Vec<u8>>,
+}
+
+impl Structure {
+    /// Scan a spooled message from its file, on the blocking thread pool.
+    /// Only the header sections and the kept content of parts are held in memory.
+    pub async fn scan_spooled(spooled: &Spooled) -> io::Result<Self> {
+        let file = spooled.open().await?;
+
+        tokio::task::spawn_blocking(move || Self::scan(BufReader::new(file)))
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?
+    }
+
+    /// Scan a raw message which is already in memory.
+    pub fn parse(raw: &[u8]) -> Self {
+        Self::scan(raw).expect("Reading from memory failed, which should be impossible.")
+    }
+
+    /// Scan a raw message from a reader.
+    /// Malformed messages are scanned as far as they can be, reading only fails on I/O errors.
+    pub fn scan<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut scanner = Scanner {
+            reader,
+            offset: 0,
+            line: Vec::new(),
+            line_start: true,
+            boundaries: Vec::new(),
+            top: None,
+            parts: Vec::new(),
+        };
+        scanner.entity()?;
+
+        let (content_type, language) = scanner.top.unwrap_or_default();
+
+        Ok(Structure {
+            content_type,
+            language,
+            parts: scanner.parts,
+        })
+    }
+
+    /// Describe all parts.
+    pub fn attachments(&self) -> Vec<Attachment> {
+        self.parts.iter().map(|p| p.attachment.clone()).collect()
+    }
+}
+
+impl Part {
+    /// The kept content as text, decoded with the charset of the part.
+    pub fn text(&self) -> Option<String> {
+        self.content
+            .as_deref()
+            .map(|content| decode_text(content, &self.charset))
+    }
+}
+
+/// Decode the transfer encoding of the body of a part.
+pub fn decode(encoding: Option<&str>, body: &[u8]) -> Vec<u8> {
+    let mut decoder = Decoder::new(encoding, usize::MAX);
+
+    for line in body.split_inclusive(|b| *b == b'\n') {
+        decoder.feed(line);
+    }
+
+    decoder.content
+}
+
+/// Decode text in a charset, invalid or unknown charsets are read as UTF-8.
+pub fn decode_text(content: &[u8], charset: &str) -> String {
+    match Charset::for_label(charset.as_bytes()) {
+        Some(charset) => charset.decode_without_bom_handling(content).0.into_owned(),
+        None => String::from_utf8_lossy(content).into_owned(),
+    }
+}
+
+/// Reads a raw message line by line, and collects its parts.
+struct Scanner<R> {
+    reader: R,
+    /// The position of the current line in the raw message.
+    offset: u64,
+    /// The current line, or a piece of it if it is too long.
+    line: Vec<u8>,
+    /// Whether the current line is not the continuation of a long line.
+    line_start: bool,
+    /// The boundaries of the enclosing multiparts, innermost last.
+    boundaries: Vec<Vec<u8>>,
+    /// The media type and language of the message itself.
+    top: Option<(String, Option<String>)>,
+    parts: Vec<Part>,
+}
+
+/// How an entity ended.
+enum End {
+    Eof,
+    /// A delimiter line, with the depth of its multipart and whether it is the closing one.
+    Delimiter(usize, bool),
+}
+
+impl<R: BufRead> Scanner<R> {
+    /// Read the next line, returns false at the end of the message.
+    fn next(&mut self) -> io::Result<bool> {
+        self.line_start = self.line.is_empty() || self.line.ends_with(b"\n");
+        self.offset += self.line.len() as u64;
+        self.line.clear();
+
+        loop {
+            let buffer = self.reader.fill_buf()?;
+            if buffer.is_empty() {
+                break;
+            }
+
+            let available = &buffer[..buffer.len().min(MAX_LINE - self.line.len())];
+            let (used, complete) = match available.iter().position(|b| *b == b'\n') {
+                Some(i) => (i + 1, true),
+                None => (
+                    available.len(),
+                    self.line.len() + available.len() == MAX_LINE,
+                ),
+            };
+
+            self.line.extend_from_slice(&available[..used]);
+            self.reader.consume(used);
+
+            if complete {
+                break;
+            }
+        }
+
+        Ok(!self.line.is_empty())
+    }
+
+    /// Check if the current line is a delimiter of one of the enclosing multiparts (RFC 2046).
+    fn delimiter(&self) -> Option<(usize, bool)> {
+        if !self.line_start || !self.line.starts_with(b"--") {
+            return None;
+        }
+
+        let end = self.line[2..]
+            .iter()
+            .rposition(|b| !b.is_ascii_whitespace())
+            .map(|i| i + 3)
+            .unwrap_or(2);
+        let line = &self.line[2..end];
+
+        self.boundaries
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(
+                |(depth, boundary)| match line.strip_prefix(boundary.as_slice())? {
+                    b"" => Some((depth, false)),
+                    b"--" => Some((depth, true)),
+                    _ => None,
+                },
+            )
+    }
+
+    /// Skip lines until a delimiter or the end of the message.
+    fn skip(&mut self) -> io::Result<End> {
+        while self.next()? {
+            if let Some((depth, closing)) = self.delimiter() {
+                return Ok(End::Delimiter(depth, closing));
+            }
+        }
+
+        Ok(End::Eof)
+    }
+
+    /// Scan an entity, its header section and its body.
+    fn entity(&mut self) -> io::Result<End> {
+        let mut header = Vec::new();
+        let ended = loop {
+            if !self.next()? {
+                break Some(End::Eof);
+            }
+            if let Some((depth, closing)) = self.delimiter() {
+                break Some(End::Delimiter(depth, closing));
+            }
+            if self.line_start && (self.line == b"\r\n" || self.line == b"\n") {
+                break None;
+            }
+            if header.len() + self.line.len() <= MAX_HEADER_SIZE {
+                header.extend_from_slice(&self.line);
+            }
+        };
+
+        let headers = match mailparse::parse_headers(&header) {
+            Ok((headers, _)) => headers,
+            Err(_) => Vec::new(),
+        };
+        let ctype = headers
+            .get_first_value("Content-Type")
+            .map(|c| mailparse::parse_content_type(&c))
+            .unwrap_or_default();
+        let content_type = ctype.mimetype.to_lowercase();
+
+        if self.top.is_none() {
+            let language = headers.get_first_value("Content-Language");
+            self.top = Some((content_type.clone(), language));
+        }
+
+        let boundary = ctype
+            .params
+            .get("boundary")
+            .filter(|b| !b.is_empty() && content_type.starts_with("multipart/"));
+        if let Some(boundary) = boundary {
+            if let Some(end) = ended {
+                return Ok(end);
+            }
+
+            self.boundaries.push(boundary.as_bytes().to_vec());
+            let depth = self.boundaries.len() - 1;
+            let end = self.multipart(depth);
+            self.boundaries.truncate(depth);
+
+            return end;
+        }
+
+        let disposition = headers
+            .get_first_value("Content-Disposition")
+            .map(|d| mailparse::parse_content_disposition(&d))
+            .unwrap_or_default();
+
+        // Parameters using RFC 2231 are decoded by the parser, encoded-words are a common violation.
+        let filename = disposition
+            .params
+            .get("filename")
+            .or_else(|| ctype.params.get("name"))
+            .map(|name| decode_words(name));
+
+        let content_id = headers
+            .get_first_value("Content-ID")
+            .map(|id| {
+                id.trim()
+                    .trim_start_matches('<')
+                    .trim_end_matches('>')
+                    .to_string()
+            })
+            .filter(|id| !id.is_empty());
+
+        let transfer_encoding = headers
+            .get_first_value("Content-Transfer-Encoding")
+            .map(|e| e.trim().to_lowercase())
+            .filter(|e| !e.is_empty());
+
+        let limit = match KEPT_TYPES.contains(&content_type.as_str()) {
+            true => MAX_KEPT,
+            false => 0,
+        };
+        let mut decoder = Decoder::new(transfer_encoding.as_deref(), limit);
+
+        // The line break before a delimiter belongs to the delimiter, so each line is decoded with the next.
+        let start = self.offset + self.line.len() as u64;
+        let (end, length) = match ended {
+            Some(end) => (end, 0),
+            None => {
+                let mut previous = Vec::new();
+
+                loop {
+                    if !self.next()? {
+                        decoder.feed(&previous);
+                        break (End::Eof, self.offset - start);
+                    }
+
+                    if let Some((depth, closing)) = self.delimiter() {
+                        let body = strip_break(&previous);
+                        decoder.feed(body);
+                        let length = self.offset - (previous.len() - body.len()) as u64;
+                        break (End::Delimiter(depth, closing), length.saturating_sub(start));
+                    }
+
+                    decoder.feed(&previous);
+                    previous.clear();
+                    previous.extend_from_slice(&self.line);
+                }
+            }
+        };
+
+        // Unknown dispositions are treated as attachments.
+        let disposition = match disposition.disposition {
+            DispositionType::Inline => Disposition::Inline,
+            _ => Disposition::Attachment,
+        };
+
+        self.parts.push(Part {
+            attachment: Attachment {
+                part: self.parts.len() as i32,
+                content_type,
+                filename,
+                size: decoder.size as i64,
+                content_id,
+                disposition,
+                body_offset: Some(start as i64),
+                body_length: Some(length as i64),
+                transfer_encoding,
+            },
+            charset: ctype.charset,
+            content: match limit {
+                0 => None,
+                _ => Some(decoder.content),
+            },
+        });
+
+        Ok(end)
+    }
+
+    /// Scan the parts of a multipart, whose boundary is at the given depth.
+    fn multipart(&mut self, depth: usize) -> io::Result<End> {
+        // The preamble is skipped.
+        let mut end = self.skip()?;
+
+        loop {
+            end = match end {
+                End::Delimiter(d, false) if d == depth => self.entity()?,
+                End::Delimiter(d, true) if d == depth => break,
+                end => return Ok(end),
+            };
+        }
+
+        // The epilogue is skipped, up to a delimiter of an enclosing multipart.
+        self.boundaries.truncate(depth);
+        self.skip()
+    }
+}
+
+/// Decodes the body of a part piece by piece, counting the decoded size.
+struct Decoder {
+    encoding: Encoding,
+    /// The bits of base64 not yet forming a byte, with their amount.
+    bits: u32,
+    count: u32,
+    size: u64,
+    content: Vec<u8>,
+    /// The amount of decoded content kept.
+    limit: usize,
+}
+
+/// The content transfer encodings (RFC 2045), others leave the content as is.
+enum Encoding {
+    Identity,
+    Base64,
+    QuotedPrintable,
+}
+
+impl Decoder {
+    fn new(encoding: Option<&str>, limit: usize) -> Self {
+        let encoding = match encoding {
+            Some("base64") => Encoding::Base64,
+            Some("quoted-printable") => Encoding::QuotedPrintable,
+            _ => Encoding::Identity,
+        };
+
+        Decoder {
+            encoding,
+            bits: 0,
+            count: 0,
+            size: 0,
+            content: Vec::new(),
+            limit,
+        }
+    }
+
+    /// Decode the next line of the body, the last one without its line break.
+    fn feed(&mut self, line: &[u8]) {
+        match self.encoding {
+            Encoding::Identity => self.emit(line),
+            Encoding::Base64 => {
+                let mut output = Vec::with_capacity(line.len() / 4 * 3 + 1);
+
+                // Characters outside of the alphabet are ignored, the padding as well.
+                for value in line.iter().filter_map(|b| base64_value(*b)) {
+                    self.bits = (self.bits << 6) | u32::from(value);
+                    self.count += 6;
+
+                    if self.count >= 8 {
+                        self.count -= 8;
+                        output.push((self.bits >> self.count) as u8);
+                        self.bits &= (1 << self.count) - 1;
+                    }
+                }
+
+                self.emit(&output);
+            }
+            Encoding::QuotedPrintable => {
+                let content = strip_break(line);
+                let hard_break = content.len() < line.len();
+                let end = content
+                    .iter()
+                    .rposition(|b| *b != b' ' && *b != b'\t')
+                    .map_or(0, |i| i + 1);
+                let content = &content[..end];
+
+                // A trailing equal sign is a soft line break.
+                let (content, soft) = match content.split_last() {
+                    Some((b'=', rest)) => (rest, true),
+                    _ => (content, false),
+                };
+
+                let mut output = Vec::with_capacity(content.len() + 2);
+                let mut i = 0;
+                while i < content.len() {
+                    let escaped = match content[i] {
+                        b'=' => content
+                            .get(i + 1..i + 3)
+                            .and_then(|hex| std::str::from_utf8(hex).ok())
+                            .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+                        _ => None,
+                    };
+
+                    match escaped {
+                        Some(byte) => {
+                            output.push(byte);
+                            i += 3;
+                        }
+                        None => {
+                            output.push(content[i]);
+                            i += 1;
+                        }
+                    }
+                }
+                if hard_break && !soft {
+                    output.extend_from_slice(b"\r\n");
+                }
+
+                self.emit(&output);
+            }
+        }
+    }
+
+    /// Count decoded content, and keep it up to the limit.
+    fn emit(&mut self, data: &[u8]) {
+        self.size += data.len() as u64;
+
+        let available = self.limit.saturating_sub(self.content.len());
+        self.content
+            .extend_from_slice(&data[..data.len().min(available)]);
+    }
+}
+
+/// The value of a character of the base64 alphabet.
+fn base64_value(byte: u8) -> Option<u8> {
+    match byte {
+        b'A'..=b'Z' => Some(byte - b'A'),
+        b'a'..=b'z' => Some(byte - b'a' + 26),
+        b'0'..=b'9' => Some(byte - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+/// Remove the line break at the end of a line.
+fn strip_break(line: &[u8]) -> &[u8] {
+    match line.strip_suffix(b"\n") {
+        Some(line) => line.strip_suffix(b"\r").unwrap_or(line),
+        None => line,
+    }
+}
+
+/// Decode the encoded-words (RFC 2047) in a parameter value.
+fn decode_words(value: &str) -> String {
+    if !value.contains("=?") {
+        return value.to_string();
+    }
+
+    match mailparse::parse_header(format!("X: {}", value).as_bytes()) {
+        Ok((header, _)) => header.get_value(),
+        Err(_) => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Nested multiparts with every transfer encoding.
+    const NESTED: &[u8] = b"From: a@example.com\r\n\
+Content-Language: de\r\n\
+Content-Type: multipart/mixed; boundary=outer\r\n\
+\r\n\
+preamble\r\n\
+--outer\r\n\
+Content-Type: multipart/alternative; boundary=\"inner\"\r\n\
+\r\n\
+--inner\r\n\
+Content-Type: text/plain; charset=iso-8859-1\r\n\
+Content-Transfer-Encoding: quoted-printable\r\n\
+\r\n\
+Gr=FC=DFe, a long line which is wrapped =\r\n\
+ softly.\r\n\
+--inner\r\n\
+Content-Type: text/html\r\n\
+\r\n\
+<p>Hello</p>\r\n\
+--inner--\r\n\
+--outer\r\n\
+Content-Type: application/octet-stream; name=\"=?utf-8?q?caf=C3=A9.bin?=\"\r\n\
+Content-Disposition: attachment\r\n\
+Content-Transfer-Encoding: base64\r\n\
+Content-ID: <part@example.com>\r\n\
+\r\n\
+AAECAwQF\r\n\
+BgcICQ==\r\n\
+--outer--\r\n\
+epilogue\r\n";
+
+    /// Compare the parts with those found by mailparse, which parses the whole message.
+    fn compare(raw: &[u8]) -> Structure {
+        let structure = Structure::parse(raw);
+        let mail = mailparse::parse_mail(raw).unwrap();
+
+        let mut expected = Vec::new();
+        let mut stack = vec![&mail];
+        while let Some(part) = stack.pop() {
+            if part.subparts.is_empty() {
+                expected.push(part);
+            }
+            stack.extend(part.subparts.iter().rev());
+        }
+
+        assert_eq!(structure.parts.len(), expected.len());
+        for (part, mail) in structure.parts.iter().zip(expected) {
+            // The line break before a delimiter belongs to the body for mailparse.
+            let body = mail.get_body_raw().unwrap();
+            let body = match structure.content_type.starts_with("multipart/") {
+                true => strip_break(&body).to_vec(),
+                false => body,
+            };
+            assert_eq!(part.attachment.content_type, mail.ctype.mimetype);
+            assert_eq!(part.attachment.size, body.len() as i64);
+            assert_eq!(part.attachment.decode(raw), body);
+        }
+
+        structure
+    }
+
+    #[test]
+    fn nested_parts_are_located() {
+        let structure = compare(NESTED);
+
+        assert_eq!(structure.content_type, "multipart/mixed");
+        assert_eq!(structure.language.as_deref(), Some("de"));
+        assert_eq!(
+            structure.parts[0].text().unwrap(),
+            "Grüße, a long line which is wrapped softly."
+        );
+        assert_eq!(structure.parts[1].text().unwrap(), "<p>Hello</p>");
+
+        let attachment = &structure.parts[2].attachment;
+        assert_eq!(attachment.filename.as_deref(), Some("café.bin"));
+        assert_eq!(attachment.content_id.as_deref(), Some("part@example.com"));
+        assert_eq!(attachment.disposition, Disposition::Attachment);
+        assert_eq!(attachment.decode(NESTED), (0..10).collect::<Vec<u8>>());
+        assert!(structure.parts[2].content.is_none());
+    }
+
+    #[test]
+    fn bare_line_feeds_are_located() {
+        let raw = String::from_utf8(NESTED.to_vec())
+            .unwrap()
+            .replace("\r\n", "\n");
+        compare(raw.as_bytes());
+    }
+
+    #[test]
+    fn single_parts_are_located() {
+        let structure = compare(b"Subject: Hi\r\n\r\nHello\r\nthere\r\n");
+
+        assert_eq!(structure.content_type, "text/plain");
+        assert_eq!(structure.parts[0].text().unwrap(), "Hello\r\nthere\r\n");
+    }
+
+    #[test]
+    fn long_lines_are_read_in_pieces() {
+        let line = "x".repeat(MAX_LINE * 3 + 5);
+        let raw = format!(
+            "Content-Type: multipart/mixed; boundary=b\r\n\r\n--b\r\n\r\n{}\r\n--b--\r\n",
+            line
+        );
+        let structure = compare(raw.as_bytes());
+
+        assert_eq!(structure.parts[0].attachment.size, line.len() as i64);
+    }
+
+    #[test]
+    fn truncated_messages_are_scanned() {
+        let structure =
+            Structure::parse(b"Content-Type: multipart/mixed; boundary=b\r\n\r\n--b\r\n\r\nHel");
+
+        assert_eq!(structure.parts.len(), 1);
+        assert_eq!(structure.parts[0].text().unwrap(), "Hel");
+    }
+}
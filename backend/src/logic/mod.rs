@@ -1,2 +1,27 @@
 pub mod account;
+pub mod attachment;
 pub mod auth;
+pub mod blob;
+pub mod bulk;
+pub mod compose;
+pub mod domain;
+pub mod draft;
+pub mod event;
+pub mod html;
+pub mod label;
+pub mod mailbox;
+pub mod mailbox_key;
+pub mod mailing_list;
+pub mod message;
+pub mod mime;
+pub mod mta_sts;
+pub mod outbound;
+pub mod pgp_key;
+pub mod quota;
+pub mod reply;
+pub mod retention;
+pub mod search;
+pub mod summary;
+pub mod thread;
+pub mod tls_report;
+pub mod upload;
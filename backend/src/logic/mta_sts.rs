@@ -0,0 +1,148 @@
+use sqlx::PgConnection;
+use thiserror::Error;
+use time::OffsetDateTime;
+
+use crate::{database, logic::domain::MtaStsMode};
+
+/// The maximum policy lifetime allowed by RFC 8461, one year.
+const MAX_POLICY_AGE: i64 = 31_557_600;
+
+/// Represents the cached MTA-STS policy of a remote domain.
+#[derive(Debug)]
+pub struct MtaStsPolicy {
+    pub policy_id: String,
+    pub mode: MtaStsMode,
+    pub mx: Vec<String>,
+    pub expires: OffsetDateTime,
+}
+
+impl MtaStsPolicy {
+    /// Find the cached policy of a remote domain.
+    /// Expired policies are not returned.
+    pub async fn find_cached(
+        conn: &mut PgConnection,
+        domain: &str,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let policy = database::mta_sts_cache::find(conn, &domain.to_lowercase()).await?;
+
+        Ok(policy.filter(|p| p.expires > OffsetDateTime::now_utc()))
+    }
+
+    /// Parse a fetched policy file, and store it in the cache.
+    pub async fn store(
+        conn: &mut PgConnection,
+        domain: &str,
+        policy_id: &str,
+        text: &str,
+    ) -> Result<Self, PolicyError> {
+        let mut version = None;
+        let mut mode = None;
+        let mut max_age = None;
+        let mut mx = Vec::new();
+
+        // The policy is a list of `key: value` lines, unknown keys are ignored.
+        for line in text.lines() {
+            let (key, value) = match line.split_once(':') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => continue,
+            };
+
+            match key {
+                "version" => version = Some(value),
+                "mode" => {
+                    mode = Some(match value {
+                        "enforce" => MtaStsMode::Enforce,
+                        "testing" => MtaStsMode::Testing,
+                        "none" => MtaStsMode::None,
+                        _ => return Err(PolicyError::InvalidMode),
+                    })
+                }
+                "max_age" => {
+                    max_age = Some(
+                        value
+                            .parse::<i64>()
+                            .map_err(|_| PolicyError::InvalidMaxAge)?,
+                    )
+                }
+                "mx" => mx.push(value.to_lowercase()),
+                _ => {}
+            }
+        }
+
+        if version != Some("STSv1") {
+            return Err(PolicyError::InvalidVersion);
+        }
+
+        let mode = mode.ok_or(PolicyError::InvalidMode)?;
+        let max_age = max_age.ok_or(PolicyError::InvalidMaxAge)?;
+
+        if !(0..=MAX_POLICY_AGE).contains(&max_age) {
+            return Err(PolicyError::InvalidMaxAge);
+        }
+
+        if mode != MtaStsMode::None && mx.is_empty() {
+            return Err(PolicyError::MissingMx);
+        }
+
+        Ok(database::mta_sts_cache::store(
+            conn,
+            &domain.to_lowercase(),
+            policy_id,
+            mode,
+            &mx,
+            max_age,
+        )
+        .await?)
+    }
+
+    /// Extract the policy ID from the `_mta-sts` TXT record.
+    /// Returns None if the record is not a valid MTA-STS record.
+    pub fn parse_record(record: &str) -> Option<String> {
+        let mut fields = record.split(';').map(|f| f.trim());
+
+        if fields.next() != Some("v=STSv1") {
+            return None;
+        }
+
+        fields
+            .filter_map(|f| f.strip_prefix("id="))
+            .find(|id| !id.is_empty() && id.len() <= 32)
+            .map(|id| id.to_string())
+    }
+
+    /// Check if this policy requires validated TLS for delivery.
+    pub fn enforced(&self) -> bool {
+        self.mode == MtaStsMode::Enforce
+    }
+
+    /// Check if a MX host is allowed by the policy.
+    /// Patterns can start with a wildcard, which matches exactly one label.
+    pub fn matches(&self, host: &str) -> bool {
+        let host = host.trim_end_matches('.').to_lowercase();
+
+        self.mx
+            .iter()
+            .any(|pattern| match pattern.strip_prefix("*.") {
+                Some(suffix) => match host.split_once('.') {
+                    Some((_, rest)) => rest == suffix,
+                    None => false,
+                },
+                None => *pattern == host,
+            })
+    }
+}
+
+/// Possible errors while processing a fetched policy.
+#[derive(Error, Debug)]
+pub enum PolicyError {
+    #[error("The policy version is not supported.")]
+    InvalidVersion,
+    #[error("The policy mode is invalid.")]
+    InvalidMode,
+    #[error("The policy maximum age is invalid.")]
+    InvalidMaxAge,
+    #[error("The policy does not contain any MX patterns.")]
+    MissingMx,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
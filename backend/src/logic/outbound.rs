@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::database;
+
+/// The number of attempts after which delivery is given up.
+const MAX_ATTEMPTS: i32 = 10;
+
+/// Represents a message queued for delivery to a remote domain.
+#[derive(Debug)]
+pub struct Outbound {
+    pub id: Uuid,
+    pub sender: String,
+    pub domain: String,
+    pub recipients: Vec<String>,
+    pub data: Vec<u8>,
+    pub status: OutboundStatus,
+    pub attempts: i32,
+}
+
+/// The delivery status of an outbound message.
+#[derive(Debug, Clone, Copy, PartialEq, sqlx::Type)]
+#[sqlx(type_name = "outbound_status", rename_all = "lowercase")]
+pub enum OutboundStatus {
+    Queued,
+    Delivered,
+    Failed,
+}
+
+impl Outbound {
+    /// Queue a message for delivery.
+    /// Recipients are grouped per domain, as these are delivered to different servers.
+    pub async fn queue(
+        conn: &mut PgConnection,
+        sender: &str,
+        recipients: &[String],
+        data: &[u8],
+    ) -> Result<Vec<Uuid>, sqlx::Error> {
+        let mut domains: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+        for recipient in recipients {
+            let domain = match recipient.rsplit_once('@') {
+                Some((_, domain)) => domain.to_lowercase(),
+                None => continue,
+            };
+
+            domains.entry(domain).or_default().push(recipient.clone());
+        }
+
+        let mut queued = Vec::with_capacity(domains.len());
+        for (domain, recipients) in domains {
+            queued
+                .push(database::outbound::create(conn, sender, &domain, &recipients, data).await?);
+        }
+
+        Ok(queued)
+    }
+
+    /// Claim a batch of messages which are due for delivery.
+    /// Claimed messages are not handed out again for the duration of the lease, in seconds.
+    pub async fn claim_due(
+        conn: &mut PgConnection,
+        limit: i64,
+        lease: i32,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        database::outbound::claim_due(conn, limit, lease).await
+    }
+
+    /// Log a delivery attempt for this message.
+    pub async fn log_attempt(
+        &self,
+        conn: &mut PgConnection,
+        mx: Option<&str>,
+        success: bool,
+        reason: &str,
+    ) -> Result<(), sqlx::Error> {
+        database::delivery_attempt::create(conn, self.id, mx, success, reason).await
+    }
+
+    /// Mark the message as delivered.
+    pub async fn delivered(&mut self, conn: &mut PgConnection) -> Result<(), sqlx::Error> {
+        self.set_status(conn, OutboundStatus::Delivered, 0).await
+    }
+
+    /// Mark the message as permanently failed.
+    pub async fn failed(&mut self, conn: &mut PgConnection) -> Result<(), sqlx::Error> {
+        self.set_status(conn, OutboundStatus::Failed, 0).await
+    }
+
+    /// Schedule another attempt with an exponential backoff.
+    /// The message fails permanently once the maximum amount of attempts is reached.
+    pub async fn retry(&mut self, conn: &mut PgConnection) -> Result<(), sqlx::Error> {
+        if self.attempts + 1 >= MAX_ATTEMPTS {
+            return self.failed(conn).await;
+        }
+
+        // Wait one minute after the first attempt, doubling every time, up to about eight hours.
+        let delay = 60 * 2i32.pow(self.attempts.min(9) as u32);
+
+        self.set_status(conn, OutboundStatus::Queued, delay).await
+    }
+
+    /// Update the status and increase the attempt counter.
+    async fn set_status(
+        &mut self,
+        conn: &mut PgConnection,
+        status: OutboundStatus,
+        delay: i32,
+    ) -> Result<(), sqlx::Error> {
+        database::outbound::update_status(conn, self.id, status, delay).await?;
+
+        self.status = status;
+        self.attempts += 1;
+
+        Ok(())
+    }
+}
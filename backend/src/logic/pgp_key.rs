@@ -0,0 +1,351 @@
+use mailparse::MailHeader;
+use pgp::{
+    crypto::SymmetricKeyAlgorithm,
+    packet::{self, SignatureType},
+    types::KeyTrait,
+    Deserializable, Message as PgpMessage, SignedPublicKey, SignedPublicSubKey,
+};
+use serde::Serialize;
+use sqlx::PgConnection;
+use thiserror::Error;
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    crypto, database,
+    logic::{account::Account, domain::DEFAULT_DOMAIN, mime::Structure},
+};
+
+/// Content types of messages which are already encrypted or signed (RFC 1847, RFC 8551).
+const PROTECTED_TYPES: [&str; 5] = [
+    "multipart/encrypted",
+    "multipart/signed",
+    "application/pgp-encrypted",
+    "application/pkcs7-mime",
+    "application/x-pkcs7-mime",
+];
+
+/// Markers of inline PGP in plain text parts.
+const INLINE_MARKERS: [&str; 2] = [
+    "-----BEGIN PGP MESSAGE-----",
+    "-----BEGIN PGP SIGNED MESSAGE-----",
+];
+
+/// The OpenPGP public key of an account.
+/// Incoming mail is encrypted to it, so only the client of the user can read it.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PgpKey {
+    /// The fingerprint of the primary key, in uppercase hex.
+    pub fingerprint: String,
+    pub armored: String,
+    #[serde(with = "time::serde::timestamp::option")]
+    pub expires: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::timestamp")]
+    pub created: OffsetDateTime,
+}
+
+/// The key which messages are encrypted to, a subkey or the primary key.
+enum Recipient<'a> {
+    Primary(&'a packet::PublicKey),
+    Subkey(&'a SignedPublicSubKey),
+}
+
+impl PgpKey {
+    /// Set the key of an account from its ASCII armor, replacing an existing one.
+    /// The key has to be valid, and able to encrypt right now.
+    pub async fn set(
+        conn: &mut PgConnection,
+        account: Uuid,
+        armored: &str,
+    ) -> Result<Self, SetError> {
+        let key = parse(armored)?;
+
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        if expires(&key).map(|e| e <= now).unwrap_or(false) {
+            return Err(SetError::Expired);
+        }
+        if recipient(&key, now).is_none() {
+            return Err(SetError::NoEncryptionKey);
+        }
+
+        // The key is stored as normalized armor, without anything around it.
+        let armored = key
+            .to_armored_string(None)
+            .map_err(|_| SetError::InvalidKey)?;
+        let fingerprint = hex::encode_upper(key.fingerprint());
+        let expires = expires(&key).map(OffsetDateTime::from_unix_timestamp);
+
+        Ok(database::pgp_key::set(conn, account, &fingerprint, &armored, expires).await?)
+    }
+
+    /// Find the key of an account.
+    pub async fn find(conn: &mut PgConnection, account: Uuid) -> Result<Self, FindError> {
+        let res = database::pgp_key::find(conn, account).await?;
+
+        match res {
+            Some(key) => Ok(key),
+            None => Err(FindError::NotFound),
+        }
+    }
+
+    /// Remove the key of an account, so incoming mail is no longer encrypted.
+    pub async fn delete(conn: &mut PgConnection, account: Uuid) -> Result<(), FindError> {
+        match database::pgp_key::delete(conn, account).await? {
+            true => Ok(()),
+            false => Err(FindError::NotFound),
+        }
+    }
+
+    /// Remember that the account is told its key is no longer usable.
+    /// Returns true only the first time for a key, so the account is told once.
+    pub async fn mark_unusable(
+        conn: &mut PgConnection,
+        account: Uuid,
+    ) -> Result<bool, sqlx::Error> {
+        database::pgp_key::mark_unusable(conn, account).await
+    }
+
+    /// Generate the message telling an account that its key is no longer usable.
+    pub fn warning(&self, account: &Account) -> String {
+        format!(
+            "From: Nexium <postmaster@{domain}>\r\n\
+            To: {0}@{domain}\r\n\
+            Date: {1}\r\n\
+            Message-ID: <{2}@{domain}>\r\n\
+            Auto-Submitted: auto-generated\r\n\
+            Subject: Your OpenPGP key is no longer usable\r\n\
+            Content-Type: text/plain; charset=utf-8\r\n\
+            \r\n\
+            Hello {0},\r\n\
+            \r\n\
+            Your OpenPGP key {3} has expired, or has no usable encryption key anymore.\r\n\
+            New mail to you is encrypted with your mailbox key instead, and readable once you log in.\r\n\
+            Upload a new key to have your mail encrypted to it again.\r\n",
+            account.username,
+            OffsetDateTime::now_utc().format("%a, %d %b %Y %H:%M:%S +0000"),
+            Uuid::new_v4(),
+            self.fingerprint,
+            domain = DEFAULT_DOMAIN
+        )
+    }
+
+    /// Wrap a raw message in PGP/MIME (RFC 3156), encrypted to this key.
+    /// The header fields stay readable, only the content headers are moved into the encrypted part.
+    /// This is CPU heavy for large messages.
+    pub fn encrypt(&self, raw: &[u8]) -> Result<Vec<u8>, EncryptError> {
+        let key = parse(&self.armored).map_err(|_| EncryptError::Unusable)?;
+
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        if expires(&key).map(|e| e <= now).unwrap_or(false) {
+            return Err(EncryptError::Unusable);
+        }
+
+        let (headers, offset) =
+            mailparse::parse_headers(raw).map_err(|_| EncryptError::Malformed)?;
+        let (outer, inner): (Vec<_>, Vec<_>) = headers.iter().partition(|h| !is_content(h));
+
+        // The encrypted part is the original MIME entity, its content headers and body.
+        let mut entity = Vec::with_capacity(raw.len());
+        for header in inner
+            .into_iter()
+            .filter(|h| !h.get_key_ref().eq_ignore_ascii_case("MIME-Version"))
+        {
+            write_header(&mut entity, header);
+        }
+        entity.extend_from_slice(b"\r\n");
+        entity.extend_from_slice(&raw[offset..]);
+
+        let literal = PgpMessage::new_literal_bytes("", &entity);
+        let mut rng = rand::thread_rng();
+        let encrypted = match recipient(&key, now).ok_or(EncryptError::Unusable)? {
+            Recipient::Primary(k) => {
+                literal.encrypt_to_keys(&mut rng, SymmetricKeyAlgorithm::AES256, &[k])
+            }
+            Recipient::Subkey(k) => {
+                literal.encrypt_to_keys(&mut rng, SymmetricKeyAlgorithm::AES256, &[k])
+            }
+        }
+        .and_then(|m| m.to_armored_string(None))
+        .map_err(|_| EncryptError::EncryptionFailed)?;
+
+        let boundary = hex::encode(crypto::random_bytes(16));
+        let mut output = Vec::with_capacity(encrypted.len() + 1024);
+        for header in outer {
+            write_header(&mut output, header);
+        }
+
+        output.extend_from_slice(
+            format!(
+                "MIME-Version: 1.0\r\n\
+                Content-Type: multipart/encrypted; protocol=\"application/pgp-encrypted\"; boundary=\"{0}\"\r\n\
+                \r\n\
+                This is an OpenPGP/MIME encrypted message (RFC 3156).\r\n\
+                --{0}\r\n\
+                Content-Type: application/pgp-encrypted\r\n\
+                Content-Description: PGP/MIME version identification\r\n\
+                \r\n\
+                Version: 1\r\n\
+                \r\n\
+                --{0}\r\n\
+                Content-Type: application/octet-stream; name=\"encrypted.asc\"\r\n\
+                Content-Description: OpenPGP encrypted message\r\n\
+                Content-Disposition: inline; filename=\"encrypted.asc\"\r\n\
+                \r\n\
+                {1}\r\n\
+                --{0}--\r\n",
+                boundary,
+                encrypted.trim_end().replace("\r\n", "\n").replace('\n', "\r\n")
+            )
+            .as_bytes(),
+        );
+
+        Ok(output)
+    }
+}
+
+/// Check if a message is already encrypted or signed, with S/MIME, PGP/MIME or inline PGP.
+/// Those are stored as is, encrypting them again would break the signature or gain nothing.
+pub fn is_protected(structure: &Structure) -> bool {
+    if PROTECTED_TYPES.contains(&structure.content_type.as_str()) {
+        return true;
+    }
+
+    structure.parts.iter().any(|part| {
+        let mimetype = part.attachment.content_type.as_str();
+
+        PROTECTED_TYPES.contains(&mimetype)
+            || (mimetype == "text/plain"
+                && part
+                    .text()
+                    .map(|body| INLINE_MARKERS.iter().any(|m| body.contains(m)))
+                    .unwrap_or(false))
+    })
+}
+
+/// Parse an ASCII armored public key, and verify its self-signatures.
+fn parse(armored: &str) -> Result<SignedPublicKey, SetError> {
+    let (key, _) = SignedPublicKey::from_string(armored).map_err(|_| SetError::InvalidKey)?;
+    key.verify().map_err(|_| SetError::InvalidKey)?;
+
+    if !key.details.revocation_signatures.is_empty() {
+        return Err(SetError::Revoked);
+    }
+
+    Ok(key)
+}
+
+/// Get the expiry of the primary key as a UNIX timestamp, None if it never expires.
+fn expires(key: &SignedPublicKey) -> Option<i64> {
+    let validity = key.details.key_expiration_time()?.num_seconds();
+
+    // A validity of zero means the key does not expire.
+    match validity {
+        0 => None,
+        _ => Some(key.primary_key.created_at().timestamp() + validity),
+    }
+}
+
+/// Find the key to encrypt to at a time, preferring the newest usable encryption subkey.
+fn recipient(key: &SignedPublicKey, now: i64) -> Option<Recipient<'_>> {
+    let subkey = key.public_subkeys.iter().rev().find(|subkey| {
+        // Only signatures made by the primary key count, anyone could attach others.
+        let verified =
+            |s: &&packet::Signature| s.verify_key_binding(&key.primary_key, &subkey.key).is_ok();
+
+        let binding = match subkey
+            .signatures
+            .iter()
+            .filter(|s| s.typ() == SignatureType::SubkeyBinding)
+            .find(verified)
+        {
+            Some(binding) => binding,
+            None => return false,
+        };
+
+        let flags = binding.key_flags();
+        let revoked = subkey
+            .signatures
+            .iter()
+            .filter(|s| s.typ() == SignatureType::SubkeyRevocation)
+            .any(|s| verified(&s));
+        let expired = binding
+            .key_expiration_time()
+            .map(|e| e.timestamp())
+            .filter(|validity| *validity > 0)
+            .map(|validity| subkey.key.created_at().timestamp() + validity <= now)
+            .unwrap_or(false);
+
+        subkey.is_encryption_key()
+            && (flags.encrypt_comms() || flags.encrypt_storage())
+            && !revoked
+            && !expired
+    });
+
+    if let Some(subkey) = subkey {
+        return Some(Recipient::Subkey(subkey));
+    }
+
+    // The primary key is only used when its self-signatures allow encryption.
+    let encrypts = key
+        .details
+        .users
+        .iter()
+        .flat_map(|user| &user.signatures)
+        .any(|s| s.key_flags().encrypt_comms() || s.key_flags().encrypt_storage());
+
+    match key.primary_key.is_encryption_key() && encrypts {
+        true => Some(Recipient::Primary(&key.primary_key)),
+        false => None,
+    }
+}
+
+/// Check if a header belongs to the MIME entity instead of the message.
+fn is_content(header: &MailHeader) -> bool {
+    let key = header.get_key_ref().to_ascii_lowercase();
+    key.starts_with("content-") || key == "mime-version"
+}
+
+/// Write a header field as it was received.
+fn write_header(output: &mut Vec<u8>, header: &MailHeader) {
+    output.extend_from_slice(header.get_key_raw());
+    output.extend_from_slice(b": ");
+    output.extend_from_slice(header.get_value_raw());
+    output.extend_from_slice(b"\r\n");
+}
+
+/// Possible errors with setting a key.
+#[derive(Error, Debug)]
+pub enum SetError {
+    #[error("The key is not a valid OpenPGP public key.")]
+    InvalidKey,
+    #[error("The key was revoked.")]
+    Revoked,
+    #[error("The key has expired.")]
+    Expired,
+    #[error("The key has no usable encryption key.")]
+    NoEncryptionKey,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Possible errors with finding a key.
+#[derive(Error, Debug)]
+pub enum FindError {
+    #[error("The account has no OpenPGP key.")]
+    NotFound,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Possible errors with encrypting a message.
+#[derive(Error, Debug)]
+pub enum EncryptError {
+    #[error("The key has expired, or has no usable encryption key anymore.")]
+    Unusable,
+    #[error("The message could not be parsed.")]
+    Malformed,
+    #[error("The message could not be encrypted.")]
+    EncryptionFailed,
+}
@@ -0,0 +1,258 @@
+use serde::Serialize;
+use sqlx::PgConnection;
+use thiserror::Error;
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    database,
+    logic::{account::Account, domain::DEFAULT_DOMAIN},
+};
+
+/// The percentage of a limit after which the account is warned, once until it drops again.
+const WARNING_PERCENT: i64 = 90;
+
+/// The limits for accounts without limits of their own.
+#[derive(Debug, Clone, Copy)]
+pub struct QuotaConfig {
+    pub max_size: i64,
+    pub max_messages: i64,
+}
+
+impl QuotaConfig {
+    /// Parse the limits from the values of the environment.
+    pub fn parse(max_size: &str, max_messages: &str) -> Result<Self, String> {
+        let parse = |value: &str| match value.parse::<i64>() {
+            Ok(limit) if limit >= 0 => Ok(limit),
+            _ => Err(format!("The quota '{}' is not a valid limit.", value)),
+        };
+
+        Ok(QuotaConfig {
+            max_size: parse(max_size)?,
+            max_messages: parse(max_messages)?,
+        })
+    }
+}
+
+/// The usage and limits of an account as stored.
+#[derive(Debug)]
+pub struct Counters {
+    pub size: i64,
+    pub messages: i64,
+    pub max_size: Option<i64>,
+    pub max_messages: Option<i64>,
+    pub warned: bool,
+}
+
+/// The storage used by the messages of an account, and how much it may use.
+/// Sizes are of the messages as received, in bytes.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Quota {
+    pub size: i64,
+    pub messages: i64,
+    pub max_size: i64,
+    pub max_messages: i64,
+}
+
+impl Quota {
+    /// Get the quota of an account.
+    pub async fn find(
+        conn: &mut PgConnection,
+        account: Uuid,
+        config: &QuotaConfig,
+    ) -> Result<Self, sqlx::Error> {
+        Ok(match database::quota::find(conn, account).await? {
+            Some(counters) => Quota::new(&counters, config),
+            None => Quota {
+                size: 0,
+                messages: 0,
+                max_size: config.max_size,
+                max_messages: config.max_messages,
+            },
+        })
+    }
+
+    /// Set the limits of an account, None uses the system default.
+    pub async fn set_limits(
+        conn: &mut PgConnection,
+        account: Uuid,
+        max_size: Option<i64>,
+        max_messages: Option<i64>,
+        config: &QuotaConfig,
+    ) -> Result<Self, LimitError> {
+        if max_size.unwrap_or(0) < 0 || max_messages.unwrap_or(0) < 0 {
+            return Err(LimitError::InvalidLimit);
+        }
+
+        database::account::find(conn, &account)
+            .await?
+            .ok_or(LimitError::NotFound)?;
+
+        let counters = database::quota::set_limits(conn, account, max_size, max_messages).await?;
+        Ok(Quota::new(&counters, config))
+    }
+
+    /// Count a new message of a size into the usage of an account.
+    /// The usage stays locked until the transaction ends,
+    /// the caller has to roll back when the message is not within the limits.
+    /// Returns the quota after counting, and whether the account should be warned about its usage now.
+    pub async fn count(
+        conn: &mut PgConnection,
+        account: Uuid,
+        size: i64,
+        config: &QuotaConfig,
+    ) -> Result<(Self, bool), sqlx::Error> {
+        let counters = database::quota::add(conn, account, size, 1).await?;
+        let quota = Quota::new(&counters, config);
+        let warn = quota.update_warned(conn, account, &counters).await?;
+
+        Ok((quota, warn))
+    }
+
+    /// Remove deleted messages with a total size from the usage of an account.
+    pub async fn release(
+        conn: &mut PgConnection,
+        account: Uuid,
+        size: i64,
+        messages: i64,
+        config: &QuotaConfig,
+    ) -> Result<(), sqlx::Error> {
+        let counters = database::quota::add(conn, account, -size, -messages).await?;
+        Quota::new(&counters, config)
+            .update_warned(conn, account, &counters)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Check if a message of a size fits, without counting it.
+    pub fn admits(&self, size: i64) -> Result<(), QuotaError> {
+        Quota {
+            size: self.size + size,
+            messages: self.messages + 1,
+            ..self.clone()
+        }
+        .within(size)
+    }
+
+    /// Check if the usage is within the limits, after counting a message of a size.
+    pub fn within(&self, size: i64) -> Result<(), QuotaError> {
+        if size > self.max_size {
+            return Err(QuotaError::TooLarge);
+        }
+        if self.size > self.max_size || self.messages > self.max_messages {
+            return Err(QuotaError::Full);
+        }
+
+        Ok(())
+    }
+
+    /// The highest percentage used of either limit.
+    pub fn percent(&self) -> i64 {
+        let percent = |used: i64, max: i64| match max {
+            0 => 100,
+            _ => used.saturating_mul(100) / max,
+        };
+
+        percent(self.size, self.max_size).max(percent(self.messages, self.max_messages))
+    }
+
+    /// Generate the message warning an account that its storage is almost full.
+    pub fn warning(&self, account: &Account) -> String {
+        format!(
+            "From: Nexium <postmaster@{domain}>\r\n\
+            To: {0}@{domain}\r\n\
+            Date: {1}\r\n\
+            Message-ID: <{2}@{domain}>\r\n\
+            Auto-Submitted: auto-generated\r\n\
+            Subject: Your mailbox is {3}% full\r\n\
+            Content-Type: text/plain; charset=utf-8\r\n\
+            \r\n\
+            Hello {0},\r\n\
+            \r\n\
+            Your mailbox uses {3}% of its storage: {4} of {5}, in {6} of {7} messages.\r\n\
+            Once it is full, new mail to you will be rejected.\r\n\
+            Delete messages you no longer need to free up space.\r\n",
+            account.username,
+            OffsetDateTime::now_utc().format("%a, %d %b %Y %H:%M:%S +0000"),
+            Uuid::new_v4(),
+            self.percent(),
+            format_size(self.size),
+            format_size(self.max_size),
+            self.messages,
+            self.max_messages,
+            domain = DEFAULT_DOMAIN
+        )
+    }
+
+    /// Combine the stored counters with the system default.
+    fn new(counters: &Counters, config: &QuotaConfig) -> Self {
+        Quota {
+            size: counters.size,
+            messages: counters.messages,
+            max_size: counters.max_size.unwrap_or(config.max_size),
+            max_messages: counters.max_messages.unwrap_or(config.max_messages),
+        }
+    }
+
+    /// Remember whether the usage is above the warning threshold.
+    /// Returns true when it just crossed it.
+    async fn update_warned(
+        &self,
+        conn: &mut PgConnection,
+        account: Uuid,
+        counters: &Counters,
+    ) -> Result<bool, sqlx::Error> {
+        let above = self.percent() >= WARNING_PERCENT;
+
+        if above != counters.warned {
+            database::quota::set_warned(conn, account, above).await?;
+        }
+
+        Ok(above && !counters.warned)
+    }
+}
+
+/// Format a size in bytes for people.
+fn format_size(size: i64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    let mut value = size as f64;
+    let mut unit = "bytes";
+    for next in UNITS.iter() {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = next;
+    }
+
+    match unit {
+        "bytes" => format!("{} bytes", size),
+        _ => format!("{:.1} {}", value, unit),
+    }
+}
+
+/// Possible errors with storing a message within the quota.
+#[derive(Error, Debug)]
+pub enum QuotaError {
+    #[error("The mailbox is full.")]
+    Full,
+    #[error("The message is larger than the mailbox.")]
+    TooLarge,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Possible errors with setting the limits of an account.
+#[derive(Error, Debug)]
+pub enum LimitError {
+    #[error("Limits can not be negative.")]
+    InvalidLimit,
+    #[error("The account does not exist.")]
+    NotFound,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
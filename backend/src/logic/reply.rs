@@ -0,0 +1,276 @@
+use mailparse::{MailAddr, MailHeader, MailHeaderMap};
+use serde::Deserialize;
+use sqlx::PgConnection;
+use thiserror::Error;
+use utoipa::ToSchema;
+
+use crate::{
+    logic::{
+        account::Account,
+        compose::{self, Address, Compose, Forwarded},
+        domain::Domain,
+        html,
+        mailbox_key::MailboxKey,
+        message::{Message, ReadError},
+        mime::Structure,
+        thread,
+    },
+    storage::BlobStore,
+};
+
+/// The line above the headers of a forwarded message.
+const FORWARDED: &str = "---------- Forwarded message ----------";
+
+/// How a new message responds to an existing one.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// Answer the sender, or the Reply-To addresses when set.
+    Reply,
+    /// Answer the sender and all other recipients.
+    ReplyAll,
+    /// Pass the message on to new recipients, with its attachments.
+    Forward,
+}
+
+/// Prefill a draft responding to a message of an account.
+/// The original is quoted, and the threading headers point to it.
+/// Forwarded attachments are referenced, so they are only copied when the draft is sent.
+pub async fn draft(
+    conn: &mut PgConnection,
+    store: &dyn BlobStore,
+    mailbox_key: Option<&MailboxKey>,
+    account: &Account,
+    message: &Message,
+    mode: Mode,
+) -> Result<Compose, ReplyError> {
+    let raw = message.raw(conn, store, mailbox_key).await?;
+    let (headers, _) = mailparse::parse_headers(&raw).map_err(|_| ReplyError::Malformed)?;
+    let structure = Structure::parse(&raw);
+    let attachments = structure.attachments();
+    let (text, html) = Message::readable(&raw, &structure);
+    let remote = message.remote_content(conn, account.id).await?;
+
+    let headers = headers.as_slice();
+    let (to, cc) = match mode {
+        Mode::Forward => (Vec::new(), Vec::new()),
+        Mode::Reply | Mode::ReplyAll => {
+            let from = addresses(headers, "From");
+            let reply_to = addresses(headers, "Reply-To");
+
+            // Replying to a message the account sent itself continues with the same recipients.
+            let mut sent = false;
+            for address in &from {
+                sent |= is_own(conn, account, address).await?;
+            }
+            let mut to = match (sent, reply_to.is_empty()) {
+                (true, _) => addresses(headers, "To"),
+                (false, true) => from,
+                (false, false) => reply_to,
+            };
+            let mut cc = Vec::new();
+            if mode == Mode::ReplyAll {
+                if !sent {
+                    to.extend(addresses(headers, "To"));
+                }
+                cc = addresses(headers, "Cc");
+            }
+
+            // Each address once, and never the account itself, on any of the hosted domains.
+            let mut seen = Vec::new();
+            for list in [&mut to, &mut cc] {
+                let mut kept = Vec::new();
+                for address in list.drain(..) {
+                    let lowercase = address.address.to_lowercase();
+                    if seen.contains(&lowercase) || is_own(conn, account, &address).await? {
+                        continue;
+                    }
+                    seen.push(lowercase);
+                    kept.push(address);
+                }
+                *list = kept;
+            }
+
+            (to, cc)
+        }
+    };
+
+    let original = headers.get_first_value("Subject").unwrap_or_default();
+    let subject = match mode {
+        Mode::Forward => prefix(original.trim(), "Fwd", &["fwd:", "fw:"]),
+        Mode::Reply | Mode::ReplyAll => prefix(original.trim(), "Re", &["re:"]),
+    };
+
+    // The reply references the whole conversation, as far as the original knew it.
+    let message_id = headers
+        .get_first_value("Message-ID")
+        .and_then(|value| thread::message_ids(&value).into_iter().next())
+        .filter(|id| compose::validate_message_id(id));
+    let mut references = match headers.get_first_value("References") {
+        Some(value) => thread::message_ids(&value),
+        None => headers
+            .get_first_value("In-Reply-To")
+            .map(|value| thread::message_ids(&value))
+            .unwrap_or_default(),
+    };
+    references.extend(message_id.clone());
+    references.retain(|id| compose::validate_message_id(id));
+    if references.len() > thread::MAX_REFERENCES {
+        references.drain(..references.len() - thread::MAX_REFERENCES);
+    }
+
+    let sender = headers.get_first_value("From").unwrap_or_default();
+    let date = message
+        .sent
+        .unwrap_or(message.received)
+        .format("%a, %d %b %Y %H:%M UTC");
+    let plain = text
+        .or_else(|| html.as_deref().map(html::to_text))
+        .unwrap_or_default();
+
+    let (text, html, forwarded) = match mode {
+        Mode::Forward => {
+            let fields: Vec<(&str, String)> = vec![
+                ("From", sender),
+                ("Date", date),
+                ("Subject", original),
+                ("To", headers.get_all_values("To").join(", ")),
+                ("Cc", headers.get_all_values("Cc").join(", ")),
+            ]
+            .into_iter()
+            .filter(|(_, value)| !value.trim().is_empty())
+            .collect();
+
+            let text = format!(
+                "\n\n{}\n{}\n\n{}",
+                FORWARDED,
+                fields
+                    .iter()
+                    .map(|(name, value)| format!("{}: {}", name, value.trim()))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                plain
+            );
+            let html = html.map(|original| {
+                format!(
+                    "<p><br></p><p>{}<br>{}</p>{}",
+                    FORWARDED,
+                    fields
+                        .iter()
+                        .map(|(name, value)| format!("{}: {}", name, html::escape(value.trim())))
+                        .collect::<Vec<_>>()
+                        .join("<br>"),
+                    html::sanitize(&original, remote).html
+                )
+            });
+
+            // Everything but the readable body is passed on, including inline images.
+            let forwarded = attachments
+                .iter()
+                .filter(|a| !a.is_body())
+                .map(|a| Forwarded {
+                    message: message.id,
+                    part: a.part,
+                })
+                .collect();
+
+            (text, html, forwarded)
+        }
+        Mode::Reply | Mode::ReplyAll => {
+            let attribution = format!("On {}, {} wrote:", date, sender.trim());
+
+            let text = format!("\n\n{}\n{}", attribution, quote(&plain));
+            let html = html.map(|original| {
+                format!(
+                    "<p><br></p><p>{}</p><blockquote type=\"cite\">{}</blockquote>",
+                    html::escape(&attribution),
+                    html::sanitize(&original, remote).html
+                )
+            });
+
+            (text, html, Vec::new())
+        }
+    };
+
+    Ok(Compose {
+        to,
+        cc,
+        subject,
+        text: Some(text),
+        html,
+        forwarded,
+        in_reply_to: message_id,
+        references,
+        ..Compose::default()
+    })
+}
+
+/// Get the addresses in all headers with a name, with the groups flattened.
+/// Headers which can not be parsed are left out.
+fn addresses(headers: &[MailHeader], name: &str) -> Vec<Address> {
+    headers
+        .get_all_headers(name)
+        .into_iter()
+        .filter_map(|header| mailparse::addrparse_header(header).ok())
+        .flat_map(|list| {
+            list.iter()
+                .flat_map(|address| match address {
+                    MailAddr::Single(info) => vec![info.clone()],
+                    MailAddr::Group(group) => group.addrs.clone(),
+                })
+                .collect::<Vec<_>>()
+        })
+        .filter(|info| compose::validate_address(info.addr.trim()))
+        .map(|info| Address {
+            name: info.display_name.filter(|n| !n.trim().is_empty()),
+            address: info.addr.trim().to_string(),
+        })
+        .collect()
+}
+
+/// Check if an address is one of the account itself, its username on any hosted domain.
+async fn is_own(
+    conn: &mut PgConnection,
+    account: &Account,
+    address: &Address,
+) -> Result<bool, sqlx::Error> {
+    match address.address.rsplit_once('@') {
+        Some((local, domain)) if local.eq_ignore_ascii_case(&account.username) => {
+            Domain::is_hosted(conn, domain).await
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Put a prefix like `Re:` before a subject, unless it already starts with one of the existing prefixes.
+fn prefix(subject: &str, prefix: &str, existing: &[&str]) -> String {
+    let lowercase = subject.to_lowercase();
+
+    match existing.iter().any(|p| lowercase.starts_with(p)) {
+        true => subject.to_string(),
+        false => format!("{}: {}", prefix, subject).trim_end().to_string(),
+    }
+}
+
+/// Quote plain text for a reply, nested quotes are not separated by a space.
+fn quote(text: &str) -> String {
+    text.lines()
+        .map(|line| match line {
+            "" => ">".to_string(),
+            line if line.starts_with('>') => format!(">{}", line),
+            line => format!("> {}", line),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Possible errors with prefilling a reply or forward.
+#[derive(Error, Debug)]
+pub enum ReplyError {
+    #[error("The message could not be parsed.")]
+    Malformed,
+    #[error("{0}")]
+    ReadError(#[from] ReadError),
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
@@ -0,0 +1,118 @@
+use sqlx::{Connection, Pool, Postgres};
+use uuid::Uuid;
+
+use crate::{
+    database,
+    logic::{
+        blob::Blob,
+        mailbox::{Mailbox, RetentionAction, Role},
+        quota::{Quota, QuotaConfig},
+    },
+};
+
+/// The amount of messages purged in a single transaction, which keeps the locks short.
+const PURGE_BATCH: i64 = 100;
+
+/// The system default retention of the mailboxes with a role, in days.
+/// Zero keeps the messages forever.
+#[derive(Debug, Clone, Copy)]
+pub struct RetentionConfig {
+    pub trash_days: i32,
+    pub junk_days: i32,
+}
+
+impl RetentionConfig {
+    /// Parse the defaults from the values of the environment.
+    pub fn parse(trash_days: &str, junk_days: &str) -> Result<Self, String> {
+        let parse = |value: &str| match value.parse::<i32>() {
+            Ok(days) if days >= 0 => Ok(days),
+            _ => Err(format!(
+                "The retention '{}' is not a valid amount of days.",
+                value
+            )),
+        };
+
+        Ok(RetentionConfig {
+            trash_days: parse(trash_days)?,
+            junk_days: parse(junk_days)?,
+        })
+    }
+
+    /// Get the policy which applies to a mailbox, None if its messages are kept.
+    pub fn policy(&self, mailbox: &Mailbox) -> Option<(RetentionAction, i32)> {
+        match (mailbox.retention_action, mailbox.retention_days) {
+            (Some(RetentionAction::Keep), _) => None,
+            (Some(action), Some(days)) => Some((action, days)),
+            (_, _) => match mailbox.role {
+                Some(Role::Trash) if self.trash_days > 0 => {
+                    Some((RetentionAction::Delete, self.trash_days))
+                }
+                Some(Role::Junk) if self.junk_days > 0 => {
+                    Some((RetentionAction::Delete, self.junk_days))
+                }
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Apply the retention policies of all mailboxes, in batches.
+/// Every batch is recorded in the purge audit within its transaction.
+/// Returns the amount of deleted or archived messages.
+pub async fn purge(
+    db: &Pool<Postgres>,
+    config: &RetentionConfig,
+    quota: &QuotaConfig,
+) -> Result<usize, sqlx::Error> {
+    let mut conn = db.acquire().await?;
+    let mut purged = 0;
+
+    for mailbox in
+        database::mailbox::with_retention(&mut conn, config.trash_days > 0, config.junk_days > 0)
+            .await?
+    {
+        let (action, days) = match config.policy(&mailbox) {
+            Some(policy) => policy,
+            None => continue,
+        };
+
+        loop {
+            let mut tx = conn.begin().await?;
+            let messages =
+                database::message::lock_expired(&mut tx, mailbox.id, days, PURGE_BATCH).await?;
+
+            if messages.is_empty() {
+                break;
+            }
+
+            let ids: Vec<Uuid> = messages.iter().map(|m| m.id).collect();
+            let size = messages.iter().map(|m| m.size).sum();
+
+            match action {
+                RetentionAction::Delete => {
+                    database::message::delete_many(&mut tx, &ids).await?;
+                    for message in &messages {
+                        Blob::release(&mut tx, &message.blob).await?;
+                    }
+                    Quota::release(&mut tx, mailbox.account, size, ids.len() as i64, quota).await?;
+                }
+                RetentionAction::Archive => {
+                    let archive = Mailbox::role(&mut tx, mailbox.account, Role::Archive).await?;
+                    database::message::move_many(&mut tx, &ids, archive.id).await?;
+                }
+                RetentionAction::Keep => {}
+            }
+
+            database::purge_audit::create(&mut tx, mailbox.account, mailbox.id, action, &ids, size)
+                .await?;
+            tx.commit().await?;
+            purged += ids.len();
+
+            if (ids.len() as i64) < PURGE_BATCH {
+                break;
+            }
+        }
+    }
+
+    Ok(purged)
+}
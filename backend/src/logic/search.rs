@@ -0,0 +1,628 @@
+use thiserror::Error;
+use time::Date;
+
+use crate::logic::{attachment::Disposition, html, mailbox::Role, mime::Structure};
+
+/// The language used when a message does not declare one.
+pub const DEFAULT_LANGUAGE: &str = "english";
+
+/// The maximum amount of body text indexed, the index itself is limited to 1 MiB.
+const MAX_BODY_LENGTH: usize = 256 * 1024;
+
+/// The amount of characters in the snippet of a message.
+const SNIPPET_LENGTH: usize = 200;
+
+/// The PostgreSQL text search configurations for language tags (RFC 5646).
+const LANGUAGES: [(&str, &str); 15] = [
+    ("da", "danish"),
+    ("de", "german"),
+    ("en", "english"),
+    ("es", "spanish"),
+    ("fi", "finnish"),
+    ("fr", "french"),
+    ("hu", "hungarian"),
+    ("it", "italian"),
+    ("nl", "dutch"),
+    ("no", "norwegian"),
+    ("pt", "portuguese"),
+    ("ro", "romanian"),
+    ("ru", "russian"),
+    ("sv", "swedish"),
+    ("tr", "turkish"),
+];
+
+/// The searchable content of a message, besides the metadata from its headers.
+#[derive(Debug)]
+pub struct Document {
+    /// The text search configuration of the subject and body.
+    pub language: &'static str,
+    pub body: String,
+    pub filenames: String,
+    pub has_attachment: bool,
+}
+
+impl Document {
+    /// Extract the searchable content of a scanned message.
+    /// The plain text body is preferred, HTML is only indexed without its tags.
+    pub fn extract(structure: &Structure) -> Self {
+        let language = structure
+            .language
+            .as_deref()
+            .and_then(|tag| {
+                let primary = tag
+                    .split(|c| c == '-' || c == ',')
+                    .next()?
+                    .trim()
+                    .to_lowercase();
+                LANGUAGES
+                    .iter()
+                    .find(|(t, _)| *t == primary)
+                    .map(|(_, l)| *l)
+            })
+            .unwrap_or(DEFAULT_LANGUAGE);
+
+        let mut text = None;
+        let mut html = None;
+        for part in &structure.parts {
+            let attachment = &part.attachment;
+            if attachment.disposition != Disposition::Inline || attachment.filename.is_some() {
+                continue;
+            }
+
+            let target = match attachment.content_type.as_str() {
+                "text/plain" => &mut text,
+                "text/html" => &mut html,
+                _ => continue,
+            };
+
+            if target.is_none() {
+                *target = part.text();
+            }
+        }
+
+        let mut body = text
+            .or_else(|| html.map(|h| html::to_text(&h)))
+            .unwrap_or_default();
+        if body.len() > MAX_BODY_LENGTH {
+            let mut end = MAX_BODY_LENGTH;
+            while !body.is_char_boundary(end) {
+                end -= 1;
+            }
+            body.truncate(end);
+        }
+
+        let filenames: Vec<&str> = structure
+            .parts
+            .iter()
+            .filter_map(|p| p.attachment.filename.as_deref())
+            .collect();
+
+        Document {
+            language,
+            body,
+            filenames: filenames.join(" "),
+            has_attachment: structure
+                .parts
+                .iter()
+                .any(|p| p.attachment.disposition == Disposition::Attachment),
+        }
+    }
+
+    /// The start of the body with whitespace collapsed, to show in listings.
+    pub fn snippet(&self) -> String {
+        self.body
+            .split_whitespace()
+            .flat_map(|word| " ".chars().chain(word.chars()))
+            .skip(1)
+            .take(SNIPPET_LENGTH)
+            .collect()
+    }
+
+    /// Only keep what can be known without reading the content.
+    /// Used for encrypted messages, so the index does not reveal their content.
+    pub fn redact(&self) -> Self {
+        Document {
+            language: self.language,
+            body: String::new(),
+            filenames: String::new(),
+            has_attachment: self.has_attachment,
+        }
+    }
+}
+
+/// A parsed search query.
+/// All terms have to match, the results are ranked by the relevance of the text terms.
+/// Like Gmail, Trash and Junk are left out, unless searched with `in:` or `in:anywhere`.
+#[derive(Debug, PartialEq)]
+pub struct Query {
+    pub terms: Vec<Term>,
+}
+
+/// A single term of a query, which can be negated with a leading `-`.
+#[derive(Debug, PartialEq)]
+pub struct Term {
+    pub negated: bool,
+    pub filter: Filter,
+}
+
+/// What a term matches.
+#[derive(Debug, PartialEq)]
+pub enum Filter {
+    /// Words or a quoted phrase, anywhere in the indexed content.
+    Text(String),
+    /// `from:`, part of the sender.
+    From(String),
+    /// `to:`, part of the recipients.
+    To(String),
+    /// `subject:`, part of the subject.
+    Subject(String),
+    /// `has:attachment`.
+    HasAttachment,
+    /// `before:`, received before the date.
+    Before(Date),
+    /// `after:`, received on or after the date.
+    After(Date),
+    /// `in:`, the mailbox with a role, None for `anywhere` which includes Trash and Junk.
+    In(Option<Role>),
+    /// `is:unread` or `is:read`.
+    Unread(bool),
+    /// `label:`, the name of a label of the message.
+    Label(String),
+}
+
+/// A parameter of the generated SQL.
+#[derive(Debug, PartialEq)]
+pub enum Parameter {
+    Text(String),
+    Date(Date),
+    Role(Role),
+}
+
+/// A query translated to SQL, with numbered parameters.
+/// The conditions expect the messages as `m`.
+#[derive(Debug, PartialEq)]
+pub struct Sql {
+    /// The condition all results have to match.
+    pub condition: String,
+    /// The relevance of a result, higher is better.
+    pub rank: String,
+    pub parameters: Vec<Parameter>,
+}
+
+impl Query {
+    /// Parse a query in the syntax of Gmail.
+    /// Values of operators and phrases can be quoted, unquoted words are matched separately.
+    pub fn parse(query: &str) -> Result<Self, ParseError> {
+        let mut terms = Vec::new();
+
+        for token in tokenize(query)? {
+            let (negated, token) = match token.strip_prefix('-') {
+                Some(rest) if !rest.is_empty() => (true, rest),
+                _ => (false, token.as_str()),
+            };
+
+            // Unknown operators are searched as text, like an URL or `re:meeting`.
+            let filter = match token.split_once(':') {
+                Some((operator, value)) if !value.is_empty() => {
+                    Self::operator(&operator.to_lowercase(), &unquote(value))?
+                        .unwrap_or_else(|| Filter::Text(unquote(token)))
+                }
+                _ => Filter::Text(unquote(token)),
+            };
+
+            terms.push(Term { negated, filter });
+        }
+
+        if terms.is_empty() {
+            return Err(ParseError::Empty);
+        }
+
+        Ok(Query { terms })
+    }
+
+    /// Parse the value of an operator, None if the operator is not known.
+    fn operator(operator: &str, value: &str) -> Result<Option<Filter>, ParseError> {
+        let filter = match operator {
+            "from" => Filter::From(value.to_string()),
+            "to" => Filter::To(value.to_string()),
+            "subject" => Filter::Subject(value.to_string()),
+            "has" if value.eq_ignore_ascii_case("attachment") => Filter::HasAttachment,
+            "before" => Filter::Before(parse_date(value)?),
+            "after" => Filter::After(parse_date(value)?),
+            "in" if value.eq_ignore_ascii_case("anywhere") => Filter::In(None),
+            "in" => match Role::parse(value) {
+                Some(role) => Filter::In(Some(role)),
+                None => return Err(ParseError::UnknownMailbox(value.to_string())),
+            },
+            "is" if value.eq_ignore_ascii_case("unread") => Filter::Unread(true),
+            "is" if value.eq_ignore_ascii_case("read") => Filter::Unread(false),
+            "label" => Filter::Label(value.to_string()),
+            _ => return Ok(None),
+        };
+
+        Ok(Some(filter))
+    }
+
+    /// Check if the query has a term with a filter which is not negated.
+    fn includes(&self, filter: Filter) -> bool {
+        self.terms
+            .iter()
+            .any(|term| !term.negated && term.filter == filter)
+    }
+
+    /// Translate the query to SQL, numbering the parameters after the ones used by the caller.
+    /// Values are never part of the SQL itself.
+    pub fn to_sql(&self, offset: usize) -> Sql {
+        let mut conditions = Vec::new();
+        let mut ranked = Vec::new();
+        let mut parameters = Vec::new();
+
+        for term in &self.terms {
+            let mut parameter = |p: Parameter| {
+                parameters.push(p);
+                format!("${}", offset + parameters.len())
+            };
+
+            let condition = match &term.filter {
+                Filter::Text(text) => {
+                    let p = parameter(Parameter::Text(text.clone()));
+                    let query = format!(
+                        "(phraseto_tsquery('simple', {0}) || phraseto_tsquery(m.language, {0}))",
+                        p
+                    );
+                    if !term.negated {
+                        ranked.push(query.clone());
+                    }
+                    format!("m.search @@ {}", query)
+                }
+                Filter::From(value) => {
+                    format!("m.sender ILIKE {}", parameter(Parameter::Text(like(value))))
+                }
+                Filter::To(value) => {
+                    format!(
+                        "m.recipients ILIKE {}",
+                        parameter(Parameter::Text(like(value)))
+                    )
+                }
+                Filter::Subject(value) => {
+                    format!(
+                        "m.subject ILIKE {}",
+                        parameter(Parameter::Text(like(value)))
+                    )
+                }
+                Filter::HasAttachment => "m.has_attachment".to_string(),
+                Filter::Before(date) => {
+                    format!("m.received < {}::date", parameter(Parameter::Date(*date)))
+                }
+                Filter::After(date) => {
+                    format!("m.received >= {}::date", parameter(Parameter::Date(*date)))
+                }
+                Filter::In(None) => "true".to_string(),
+                Filter::In(Some(role)) => format!(
+                    "m.mailbox IN (SELECT id FROM mailbox WHERE account = m.account AND role = {})",
+                    parameter(Parameter::Role(*role))
+                ),
+                Filter::Unread(true) => "NOT m.seen".to_string(),
+                Filter::Unread(false) => "m.seen".to_string(),
+                Filter::Label(name) => format!(
+                    "EXISTS (SELECT 1 FROM message_label ml JOIN label l ON l.id = ml.label \
+                    WHERE ml.message = m.id AND lower(l.name) = lower({}))",
+                    parameter(Parameter::Text(name.clone()))
+                ),
+            };
+
+            conditions.push(match term.negated {
+                true => format!("NOT ({})", condition),
+                false => condition,
+            });
+        }
+
+        // Trash and Junk are only searched when asked for, `in:` of one only includes that one.
+        let anywhere = self.includes(Filter::In(None));
+        let excluded = [Role::Trash, Role::Junk]
+            .into_iter()
+            .filter(|role| !anywhere && !self.includes(Filter::In(Some(*role))))
+            .map(|role| {
+                parameters.push(Parameter::Role(role));
+                format!("${}", offset + parameters.len())
+            })
+            .collect::<Vec<_>>();
+        if !excluded.is_empty() {
+            conditions.push(format!(
+                "m.mailbox NOT IN (SELECT id FROM mailbox WHERE account = m.account AND role IN ({}))",
+                excluded.join(", ")
+            ));
+        }
+
+        let rank = match ranked.is_empty() {
+            true => "0::real".to_string(),
+            false => format!("ts_rank(m.search, {})", ranked.join(" && ")),
+        };
+
+        Sql {
+            condition: conditions.join(" AND "),
+            rank,
+            parameters,
+        }
+    }
+}
+
+/// Split a query on whitespace, keeping quoted parts together.
+fn tokenize(query: &str) -> Result<Vec<String>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut quoted = false;
+
+    for c in query.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                token.push(c);
+            }
+            c if c.is_whitespace() && !quoted => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            c => token.push(c),
+        }
+    }
+
+    if quoted {
+        return Err(ParseError::UnclosedQuote);
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+/// Remove the quotes around a value.
+fn unquote(value: &str) -> String {
+    value.replace('"', "")
+}
+
+/// Turn a value into a pattern matching it anywhere, escaping the wildcards in it.
+fn like(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+
+    format!("%{}%", escaped)
+}
+
+/// Parse a date as `YYYY/MM/DD` or `YYYY-MM-DD`.
+fn parse_date(value: &str) -> Result<Date, ParseError> {
+    let invalid = || ParseError::InvalidDate(value.to_string());
+    let parts: Vec<&str> = value.split(|c| c == '/' || c == '-').collect();
+
+    if parts.len() != 3 {
+        return Err(invalid());
+    }
+
+    let year = parts[0].parse().map_err(|_| invalid())?;
+    let month = parts[1].parse().map_err(|_| invalid())?;
+    let day = parts[2].parse().map_err(|_| invalid())?;
+
+    Date::try_from_ymd(year, month, day).map_err(|_| invalid())
+}
+
+/// Possible errors with parsing a query.
+#[derive(Error, Debug, PartialEq)]
+pub enum ParseError {
+    #[error("The query is empty.")]
+    Empty,
+    #[error("A quote is not closed.")]
+    UnclosedQuote,
+    #[error("The mailbox {0} does not exist.")]
+    UnknownMailbox(String),
+    #[error("The date {0} is not valid.")]
+    InvalidDate(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parse a query which has to be valid.
+    fn filters(query: &str) -> Vec<(bool, Filter)> {
+        Query::parse(query)
+            .unwrap()
+            .terms
+            .into_iter()
+            .map(|t| (t.negated, t.filter))
+            .collect()
+    }
+
+    fn date(year: i32, month: u8, day: u8) -> Date {
+        Date::try_from_ymd(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn tokens_are_split_outside_of_quotes() {
+        assert_eq!(
+            tokenize("a  \"b c\"\td from:\"x y\"").unwrap(),
+            vec!["a", "\"b c\"", "d", "from:\"x y\""]
+        );
+        assert_eq!(tokenize("  ").unwrap(), Vec::<String>::new());
+        assert_eq!(tokenize("a \"b c"), Err(ParseError::UnclosedQuote));
+    }
+
+    #[test]
+    fn quoted_phrases_are_kept_together() {
+        assert_eq!(
+            filters("\"quarterly report\" budget"),
+            vec![
+                (false, Filter::Text("quarterly report".into())),
+                (false, Filter::Text("budget".into())),
+            ]
+        );
+        assert_eq!(
+            filters("from:\"Jane Doe\" subject:\"a b\""),
+            vec![
+                (false, Filter::From("Jane Doe".into())),
+                (false, Filter::Subject("a b".into())),
+            ]
+        );
+        assert_eq!(Query::parse("from:\"Jane"), Err(ParseError::UnclosedQuote));
+    }
+
+    #[test]
+    fn terms_are_negated() {
+        assert_eq!(
+            filters("-spam -\"two words\" -from:bob -has:attachment"),
+            vec![
+                (true, Filter::Text("spam".into())),
+                (true, Filter::Text("two words".into())),
+                (true, Filter::From("bob".into())),
+                (true, Filter::HasAttachment),
+            ]
+        );
+
+        // A lone dash is searched as is.
+        assert_eq!(filters("-"), vec![(false, Filter::Text("-".into()))]);
+    }
+
+    #[test]
+    fn operators_are_parsed() {
+        assert_eq!(
+            filters("FROM:alice@example.com to:bob has:Attachment in:spam in:anywhere"),
+            vec![
+                (false, Filter::From("alice@example.com".into())),
+                (false, Filter::To("bob".into())),
+                (false, Filter::HasAttachment),
+                (false, Filter::In(Some(Role::Junk))),
+                (false, Filter::In(None)),
+            ]
+        );
+        assert_eq!(
+            filters("before:2021/02/03 after:2020-12-31 is:unread is:read label:work"),
+            vec![
+                (false, Filter::Before(date(2021, 2, 3))),
+                (false, Filter::After(date(2020, 12, 31))),
+                (false, Filter::Unread(true)),
+                (false, Filter::Unread(false)),
+                (false, Filter::Label("work".into())),
+            ]
+        );
+        assert_eq!(
+            Query::parse("in:nowhere"),
+            Err(ParseError::UnknownMailbox("nowhere".into()))
+        );
+    }
+
+    #[test]
+    fn unknown_operators_are_text() {
+        assert_eq!(
+            filters("re:meeting has:pdf from: https://example.com"),
+            vec![
+                (false, Filter::Text("re:meeting".into())),
+                (false, Filter::Text("has:pdf".into())),
+                (false, Filter::Text("from:".into())),
+                (false, Filter::Text("https://example.com".into())),
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_dates_are_rejected() {
+        for value in [
+            "2021/02/30",
+            "2021-13-01",
+            "2021/02",
+            "2021/02/03/04",
+            "yesterday",
+            "2021/-2/03",
+            "2021//03",
+        ] {
+            assert_eq!(
+                Query::parse(&format!("before:{}", value)),
+                Err(ParseError::InvalidDate(value.into())),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn empty_queries_are_rejected() {
+        assert_eq!(Query::parse(""), Err(ParseError::Empty));
+        assert_eq!(Query::parse(" \t\r\n"), Err(ParseError::Empty));
+    }
+
+    #[test]
+    fn wildcards_are_escaped() {
+        assert_eq!(like("50%_off\\"), "%50\\%\\_off\\\\%");
+
+        let sql = Query::parse("from:100% to:a_b subject:c\\d")
+            .unwrap()
+            .to_sql(2);
+        assert_eq!(
+            sql.condition,
+            "m.sender ILIKE $3 AND m.recipients ILIKE $4 AND m.subject ILIKE $5 AND \
+            m.mailbox NOT IN (SELECT id FROM mailbox WHERE account = m.account AND role IN ($6, $7))"
+        );
+        assert_eq!(
+            sql.parameters,
+            vec![
+                Parameter::Text("%100\\%%".into()),
+                Parameter::Text("%a\\_b%".into()),
+                Parameter::Text("%c\\\\d%".into()),
+                Parameter::Role(Role::Trash),
+                Parameter::Role(Role::Junk),
+            ]
+        );
+    }
+
+    #[test]
+    fn negated_text_is_not_ranked() {
+        let sql = Query::parse("-draft").unwrap().to_sql(0);
+
+        assert!(sql.condition.starts_with("NOT (m.search @@"));
+        assert_eq!(sql.rank, "0::real");
+        assert_eq!(
+            sql.parameters,
+            vec![
+                Parameter::Text("draft".into()),
+                Parameter::Role(Role::Trash),
+                Parameter::Role(Role::Junk),
+            ]
+        );
+    }
+
+    #[test]
+    fn trash_and_junk_are_left_out() {
+        let exclusion = |roles: &str| {
+            format!(
+                "m.mailbox NOT IN (SELECT id FROM mailbox WHERE account = m.account AND role IN ({}))",
+                roles
+            )
+        };
+
+        let sql = Query::parse("invoice").unwrap().to_sql(0);
+        assert!(sql.condition.ends_with(&exclusion("$2, $3")));
+        assert_eq!(
+            sql.parameters[1..],
+            [Parameter::Role(Role::Trash), Parameter::Role(Role::Junk)]
+        );
+
+        // Only the mailbox searched in is included.
+        let sql = Query::parse("invoice in:trash").unwrap().to_sql(0);
+        assert!(sql.condition.ends_with(&exclusion("$3")));
+        assert_eq!(sql.parameters[2..], [Parameter::Role(Role::Junk)]);
+
+        // Negating a mailbox does not include it.
+        let sql = Query::parse("invoice -in:spam").unwrap().to_sql(0);
+        assert!(sql.condition.ends_with(&exclusion("$3, $4")));
+    }
+
+    #[test]
+    fn anywhere_includes_trash_and_junk() {
+        let sql = Query::parse("invoice in:anywhere").unwrap().to_sql(0);
+
+        assert!(!sql.condition.contains("NOT IN"));
+        assert_eq!(sql.parameters, vec![Parameter::Text("invoice".into())]);
+    }
+}
@@ -0,0 +1,149 @@
+use serde::{Deserialize, Serialize};
+use sqlx::PgConnection;
+use thiserror::Error;
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{database, logic::mailbox::Mailbox};
+
+/// The highest key of a cursor, the end of the year 9999 in microseconds.
+const MAX_KEY: i64 = 253_402_300_799_999_999;
+
+/// The envelope of a message with its flags, to show in listings.
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Summary {
+    pub id: Uuid,
+    pub thread: Uuid,
+    pub sender: String,
+    pub recipients: String,
+    pub subject: String,
+    pub snippet: String,
+    #[serde(with = "time::serde::timestamp::option")]
+    pub sent: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::timestamp")]
+    pub received: OffsetDateTime,
+    pub seen: bool,
+    pub flagged: bool,
+    pub answered: bool,
+    pub draft: bool,
+    pub deleted: bool,
+    pub keywords: Vec<String>,
+    pub labels: Vec<Uuid>,
+    pub has_attachment: bool,
+    pub size: i64,
+}
+
+/// The order in which messages are listed.
+/// Dates are when the messages were received.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Sort {
+    Newest,
+    Oldest,
+    Largest,
+    Smallest,
+}
+
+impl Default for Sort {
+    fn default() -> Self {
+        Sort::Newest
+    }
+}
+
+/// The flags listed messages need to have, None lists messages with and without a flag.
+#[derive(Debug, Default)]
+pub struct FlagFilter {
+    pub seen: Option<bool>,
+    pub flagged: Option<bool>,
+    pub answered: Option<bool>,
+    pub draft: Option<bool>,
+    pub deleted: Option<bool>,
+    pub has_attachment: Option<bool>,
+}
+
+/// The position after the last message of a page, formatted as `key.id`.
+/// The key is the date in microseconds or the size, depending on the sort.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cursor {
+    pub key: i64,
+    pub id: Uuid,
+}
+
+impl Cursor {
+    /// Parse a cursor as formatted for the client.
+    pub fn parse(cursor: &str) -> Option<Self> {
+        let (key, id) = cursor.split_once('.')?;
+
+        Some(Cursor {
+            key: key.parse().ok()?,
+            id: Uuid::parse_str(id).ok()?,
+        })
+    }
+
+    /// The cursor after a message in a sort.
+    fn after(summary: &Summary, sort: Sort) -> Self {
+        let key = match sort {
+            Sort::Newest | Sort::Oldest => (summary.received.unix_timestamp_nanos() / 1000) as i64,
+            Sort::Largest | Sort::Smallest => summary.size,
+        };
+
+        Cursor {
+            key,
+            id: summary.id,
+        }
+    }
+}
+
+impl std::fmt::Display for Cursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.key, self.id)
+    }
+}
+
+impl Summary {
+    /// List a page of the messages in a mailbox, continuing after a cursor.
+    /// Returns the cursor of the next page, None on the last page.
+    pub async fn list(
+        conn: &mut PgConnection,
+        mailbox: &Mailbox,
+        sort: Sort,
+        filter: &FlagFilter,
+        cursor: Option<&str>,
+        limit: i64,
+    ) -> Result<(Vec<Self>, Option<Cursor>), ListError> {
+        let cursor = match cursor {
+            Some(cursor) => Some(Cursor::parse(cursor).ok_or(ListError::InvalidCursor)?),
+            None => None,
+        };
+        if let Some(cursor) = cursor {
+            if cursor.key < 0 || cursor.key > MAX_KEY {
+                return Err(ListError::InvalidCursor);
+            }
+        }
+
+        // One more message than requested tells if there is a next page.
+        let mut summaries =
+            database::message::list(conn, mailbox.id, sort, filter, cursor, limit + 1).await?;
+
+        let next = match summaries.len() as i64 > limit {
+            true => {
+                summaries.truncate(limit as usize);
+                summaries.last().map(|s| Cursor::after(s, sort))
+            }
+            false => None,
+        };
+
+        Ok((summaries, next))
+    }
+}
+
+/// Possible errors with listing messages.
+#[derive(Error, Debug)]
+pub enum ListError {
+    #[error("The cursor is not valid.")]
+    InvalidCursor,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
@@ -0,0 +1,168 @@
+use serde::Serialize;
+use sqlx::PgConnection;
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{database, logic::message::Metadata};
+
+/// The reply and forward prefixes of common clients and languages, removed to compare subjects.
+const PREFIXES: [&str; 10] = [
+    "re", "fw", "fwd", "aw", "wg", "sv", "vs", "antw", "tr", "rif",
+];
+
+/// Only the newest references are used, so long chains can not slow down delivery.
+pub const MAX_REFERENCES: usize = 50;
+
+/// The amount of days within which messages are grouped by their subject alone.
+const SUBJECT_DAYS: i32 = 30;
+
+/// A conversation, summarized for listings.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Thread {
+    pub id: Uuid,
+    /// The subject of the first message.
+    pub subject: String,
+    /// The senders of the messages, each once.
+    pub participants: Vec<String>,
+    /// The start of the newest message.
+    pub snippet: String,
+    pub messages: i64,
+    pub unread: i64,
+    #[serde(with = "time::serde::timestamp")]
+    pub latest: OffsetDateTime,
+}
+
+impl Thread {
+    /// Find the thread of a new message, following the JWZ algorithm.
+    /// A message joins the threads of its Message-ID, In-Reply-To and References,
+    /// merging them when it connects several. Replies without known references are grouped by subject.
+    /// Has to run in the transaction which stores the message.
+    pub async fn assign(
+        conn: &mut PgConnection,
+        account: Uuid,
+        metadata: &Metadata,
+    ) -> Result<Uuid, sqlx::Error> {
+        // Deliveries into the same account are threaded one after the other.
+        database::thread::lock(conn, account).await?;
+
+        let mut ids = metadata.references.clone();
+        ids.extend(metadata.message_id.as_deref().and_then(message_id));
+
+        let threads = database::thread::find_references(conn, account, &ids).await?;
+        let thread = match threads.split_first() {
+            Some((thread, others)) => {
+                if !others.is_empty() {
+                    database::thread::merge(conn, account, others, *thread).await?;
+                }
+                *thread
+            }
+            None => {
+                let (subject, reply) = normalize_subject(&metadata.subject);
+                let existing = match reply && !subject.is_empty() {
+                    true => {
+                        database::thread::find_subject(conn, account, &subject, SUBJECT_DAYS)
+                            .await?
+                    }
+                    false => None,
+                };
+                existing.unwrap_or_else(Uuid::new_v4)
+            }
+        };
+
+        database::thread::add_references(conn, account, &ids, thread).await?;
+
+        Ok(thread)
+    }
+
+    /// List the threads of an account, with the newest message first.
+    /// With a mailbox or label, only threads with a message in it or carrying it are listed.
+    pub async fn list(
+        conn: &mut PgConnection,
+        account: Uuid,
+        mailbox: Option<Uuid>,
+        label: Option<Uuid>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        database::thread::list(conn, account, mailbox, label, limit, offset).await
+    }
+}
+
+/// Get the Message-IDs in the value of a header, without their angle brackets.
+/// Values without brackets are taken as a single ID, as some clients send them like that.
+pub fn message_ids(value: &str) -> Vec<String> {
+    let mut ids: Vec<String> = value
+        .split('<')
+        .skip(1)
+        .filter_map(|part| part.split_once('>'))
+        .filter_map(|(id, _)| message_id(id))
+        .collect();
+
+    if ids.is_empty() && !value.contains('<') {
+        ids.extend(message_id(value));
+    }
+
+    if ids.len() > MAX_REFERENCES {
+        ids.drain(..ids.len() - MAX_REFERENCES);
+    }
+
+    ids
+}
+
+/// Normalize a single Message-ID, None if it is not one.
+fn message_id(id: &str) -> Option<String> {
+    let id = id
+        .trim()
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .trim();
+
+    match id.is_empty() || id.contains(char::is_whitespace) {
+        true => None,
+        false => Some(id.to_string()),
+    }
+}
+
+/// Remove reply and forward prefixes like `Re:`, `Fwd[2]:` or `AW:`, and mailing list tags like `[list]`.
+/// Returns the lowercase subject, and whether it had a prefix.
+pub fn normalize_subject(subject: &str) -> (String, bool) {
+    let mut subject = subject.trim();
+    let mut reply = false;
+
+    loop {
+        // Mailing lists put their tag in front, before or after the prefix.
+        if subject.starts_with('[') {
+            if let Some(end) = subject.find(']') {
+                subject = subject[end + 1..].trim_start();
+                continue;
+            }
+        }
+
+        let colon = match subject.find(':') {
+            Some(colon) => colon,
+            None => break,
+        };
+
+        // The prefix can carry a counter, like `Re[2]:` or `Re(2):`.
+        let prefix = subject[..colon]
+            .trim_end()
+            .trim_end_matches(|c: char| c.is_ascii_digit() || "[]()".contains(c));
+        if !PREFIXES.iter().any(|p| prefix.eq_ignore_ascii_case(p)) {
+            break;
+        }
+
+        subject = subject[colon + 1..].trim_start();
+        reply = true;
+    }
+
+    (
+        subject
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase(),
+        reply,
+    )
+}
@@ -0,0 +1,199 @@
+use std::io::Read;
+
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
+use sqlx::PgConnection;
+use thiserror::Error;
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    database,
+    logic::{
+        domain::{self, Domain},
+        mime::Structure,
+    },
+};
+
+/// The maximum size of a decompressed report, to protect against compression bombs.
+const MAX_REPORT_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Represents a stored TLS-RPT report.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TlsReport {
+    pub id: Uuid,
+    pub domain: String,
+    pub organization: String,
+    pub report_id: String,
+    #[serde(with = "time::serde::timestamp")]
+    pub start_date: OffsetDateTime,
+    #[serde(with = "time::serde::timestamp")]
+    pub end_date: OffsetDateTime,
+    pub successful: i64,
+    pub failed: i64,
+    pub report: serde_json::Value,
+    #[serde(with = "time::serde::timestamp")]
+    pub received: OffsetDateTime,
+}
+
+/// The JSON report format as defined in RFC 8460, section 4.
+/// Only the fields used for indexing are parsed, the full report is stored as-is.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Report {
+    organization_name: String,
+    date_range: DateRange,
+    report_id: String,
+    policies: Vec<PolicyResult>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct DateRange {
+    start_datetime: String,
+    end_datetime: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct PolicyResult {
+    policy: Policy,
+    summary: Summary,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Policy {
+    policy_domain: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Summary {
+    total_successful_session_count: i64,
+    total_failure_session_count: i64,
+}
+
+impl TlsReport {
+    /// Extract and store all TLS reports attached to a received email.
+    /// Reports which can not be stored are logged and skipped, so the others in the email are kept.
+    /// Returns the reports which were newly stored.
+    pub async fn ingest(
+        conn: &mut PgConnection,
+        structure: &Structure,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let mut stored = Vec::new();
+
+        for raw in Self::extract(structure) {
+            match Self::store(conn, &raw).await {
+                Ok(Some(report)) => stored.push(report),
+                Ok(None) => {}
+                Err(IngestError::DatabaseError(e)) => return Err(e),
+                Err(e) => warn!("Skipped a TLS report: {}", e),
+            }
+        }
+
+        Ok(stored)
+    }
+
+    /// Parse a JSON report and store it for the domain it covers.
+    /// Returns None if the report was already received before.
+    pub async fn store(conn: &mut PgConnection, raw: &[u8]) -> Result<Option<Self>, IngestError> {
+        let value: serde_json::Value = serde_json::from_slice(raw)?;
+        let report: Report = serde_json::from_value(value.clone())?;
+
+        // All policies in a report should be for the same domain, the first one is leading.
+        let policy_domain = report
+            .policies
+            .first()
+            .map(|p| p.policy.policy_domain.to_lowercase())
+            .ok_or(IngestError::NoPolicies)?;
+
+        // Reports for domains which are not ours are dropped.
+        let owner = Domain::find(conn, &policy_domain)
+            .await
+            .map_err(|e| match e {
+                domain::FindError::NotFound => IngestError::UnknownDomain(policy_domain),
+                domain::FindError::DatabaseError(e) => IngestError::DatabaseError(e),
+            })?;
+
+        let successful = report
+            .policies
+            .iter()
+            .map(|p| p.summary.total_successful_session_count)
+            .sum();
+        let failed = report
+            .policies
+            .iter()
+            .map(|p| p.summary.total_failure_session_count)
+            .sum();
+
+        Ok(database::tls_report::create(
+            conn,
+            &owner.name,
+            &report.organization_name,
+            &report.report_id,
+            &report.date_range.start_datetime,
+            &report.date_range.end_datetime,
+            successful,
+            failed,
+            &value,
+        )
+        .await?)
+    }
+
+    /// List all reports for a domain.
+    pub async fn list(conn: &mut PgConnection, domain: &Domain) -> Result<Vec<Self>, sqlx::Error> {
+        database::tls_report::list(conn, &domain.name).await
+    }
+
+    /// Collect the decoded reports from the parts of an email.
+    /// Reports are either plain JSON or gzip compressed, identified by the media type.
+    /// Only the first MiB of a part is kept while scanning, larger reports are not valid.
+    /// Parts which can not be decompressed are logged and skipped.
+    fn extract(structure: &Structure) -> Vec<Vec<u8>> {
+        let mut reports = Vec::new();
+
+        for part in &structure.parts {
+            let content = match &part.content {
+                Some(content) => content,
+                None => continue,
+            };
+
+            match part.attachment.content_type.as_str() {
+                "application/tlsrpt+json" => reports.push(content.clone()),
+                "application/tlsrpt+gzip" => {
+                    let mut decompressed = Vec::new();
+                    let res = GzDecoder::new(content.as_slice())
+                        .take(MAX_REPORT_SIZE)
+                        .read_to_end(&mut decompressed);
+
+                    match res {
+                        Ok(_) => reports.push(decompressed),
+                        Err(_) => warn!("Skipped a TLS report: {}", IngestError::Decompression),
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        reports
+    }
+}
+
+/// Possible errors while ingesting TLS reports.
+#[derive(Error, Debug)]
+pub enum IngestError {
+    #[error("The report could not be decompressed.")]
+    Decompression,
+    #[error("The report is not valid JSON: {0}")]
+    InvalidReport(#[from] serde_json::Error),
+    #[error("The report does not contain any policies.")]
+    NoPolicies,
+    #[error("The report is for unknown domain '{0}'.")]
+    UnknownDomain(String),
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
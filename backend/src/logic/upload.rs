@@ -0,0 +1,199 @@
+use serde::Serialize;
+use sqlx::{Connection, PgConnection, Pool, Postgres};
+use thiserror::Error;
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    database,
+    logic::blob::{Blob, BlobError},
+    storage::{BlobStore, Spooled},
+};
+
+/// The largest file which can be uploaded, in bytes.
+pub const MAX_SIZE: u64 = 25 * 1024 * 1024;
+
+/// The longest filename, in characters.
+const MAX_FILENAME: usize = 255;
+
+/// Hours after which uploads are removed, sent messages and drafts keep their own copy.
+const EXPIRY_HOURS: i32 = 24;
+
+/// The amount of uploads removed in a single transaction.
+const EXPIRY_BATCH: i64 = 100;
+
+/// Represents a file uploaded by an account, to attach to a message.
+/// The content is kept in the blob store, unencrypted until the upload expires.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Upload {
+    pub id: Uuid,
+    pub filename: String,
+    pub content_type: String,
+    pub size: i64,
+    #[serde(skip)]
+    pub blob: String,
+    #[serde(with = "time::serde::timestamp")]
+    pub created: OffsetDateTime,
+}
+
+impl Upload {
+    /// Store a file uploaded by an account, spooled while it was received.
+    /// Without a valid content type, it is guessed from the filename.
+    pub async fn create(
+        conn: &mut PgConnection,
+        store: &dyn BlobStore,
+        account: Uuid,
+        filename: &str,
+        content_type: Option<&str>,
+        spooled: &Spooled,
+    ) -> Result<Self, CreateError> {
+        let filename = filename.trim();
+        if !Self::validate_filename(filename) {
+            return Err(CreateError::InvalidFilename);
+        }
+
+        if spooled.size > MAX_SIZE {
+            return Err(CreateError::TooLarge);
+        }
+
+        let content_type = content_type
+            .and_then(parse_content_type)
+            .unwrap_or_else(|| {
+                mime_guess::from_path(filename)
+                    .first_or_octet_stream()
+                    .essence_str()
+                    .to_string()
+            });
+
+        let mut tx = conn.begin().await?;
+        let blob = Blob::store(&mut tx, store, spooled).await?;
+        let upload = database::upload::create(
+            &mut tx,
+            account,
+            filename,
+            &content_type,
+            blob.size,
+            &blob.hash,
+        )
+        .await?;
+        tx.commit().await?;
+
+        Ok(upload)
+    }
+
+    /// Find uploads of an account by ID, in the order of the IDs.
+    pub async fn find_many(
+        conn: &mut PgConnection,
+        account: Uuid,
+        ids: &[Uuid],
+    ) -> Result<Vec<Self>, FindError> {
+        let mut uploads = database::upload::find_many(conn, account, ids).await?;
+
+        ids.iter()
+            .map(|id| match uploads.iter().position(|u| u.id == *id) {
+                Some(index) => Ok(uploads.swap_remove(index)),
+                None => Err(FindError::NotFound),
+            })
+            .collect()
+    }
+
+    /// Find an upload of an account by ID.
+    pub async fn find(conn: &mut PgConnection, account: Uuid, id: Uuid) -> Result<Self, FindError> {
+        let mut uploads = Self::find_many(conn, account, &[id]).await?;
+        Ok(uploads.remove(0))
+    }
+
+    /// Read the content of the upload.
+    pub async fn load(
+        &self,
+        conn: &mut PgConnection,
+        store: &dyn BlobStore,
+    ) -> Result<Vec<u8>, BlobError> {
+        Blob::load(conn, store, &self.blob, None).await
+    }
+
+    /// Delete the upload, releasing its blob.
+    pub async fn delete(self, conn: &mut PgConnection) -> Result<(), sqlx::Error> {
+        let mut tx = conn.begin().await?;
+        if let Some(blob) = database::upload::delete(&mut tx, self.id).await? {
+            Blob::release(&mut tx, &blob).await?;
+        }
+        tx.commit().await
+    }
+
+    /// Delete all expired uploads, in batches.
+    /// Returns the amount of deleted uploads.
+    pub async fn expire(db: &Pool<Postgres>) -> Result<usize, sqlx::Error> {
+        let mut expired = 0;
+
+        loop {
+            let mut tx = db.begin().await?;
+            let blobs =
+                database::upload::delete_expired(&mut tx, EXPIRY_HOURS, EXPIRY_BATCH).await?;
+
+            for blob in &blobs {
+                Blob::release(&mut tx, blob).await?;
+            }
+
+            tx.commit().await?;
+            expired += blobs.len();
+
+            if (blobs.len() as i64) < EXPIRY_BATCH {
+                return Ok(expired);
+            }
+        }
+    }
+
+    /// Check if a filename is valid for an upload.
+    /// Paths are not accepted, only the name of the file itself.
+    pub fn validate_filename(filename: &str) -> bool {
+        !filename.is_empty()
+            && filename.chars().count() <= MAX_FILENAME
+            && !filename
+                .chars()
+                .any(|c| c == '/' || c == '\\' || c.is_control())
+    }
+}
+
+/// Parse a content type like `image/png`, without its parameters and in lowercase.
+/// None if it is not a valid MIME type.
+fn parse_content_type(value: &str) -> Option<String> {
+    let essence = value.split(';').next()?.trim().to_lowercase();
+    let (kind, subtype) = essence.split_once('/')?;
+
+    // Tokens of RFC 2045, without the characters which are special in headers.
+    let token = |t: &str| {
+        !t.is_empty()
+            && t.chars()
+                .all(|c| c.is_ascii_graphic() && !"()<>@,;:\\\"/[]?=".contains(c))
+    };
+
+    match token(kind) && token(subtype) {
+        true => Some(essence),
+        false => None,
+    }
+}
+
+/// Possible errors with uploading a file.
+#[derive(Error, Debug)]
+pub enum CreateError {
+    #[error("The filename is empty, too long or contains invalid characters.")]
+    InvalidFilename,
+    #[error("The file is too large.")]
+    TooLarge,
+    #[error("{0}")]
+    BlobError(#[from] BlobError),
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Possible errors with finding uploads.
+#[derive(Error, Debug)]
+pub enum FindError {
+    #[error("The upload was not found.")]
+    NotFound,
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
@@ -4,11 +4,14 @@ use std::env;
 extern crate log;
 extern crate actix_web;
 
+mod crypto;
 mod database;
 mod environment;
 mod http;
 mod logic;
+mod proxy;
 mod smtp;
+mod storage;
 
 #[actix_web::main]
 async fn main() {
@@ -32,16 +35,85 @@ async fn main() {
         .await
         .expect("Failed to initialize database.");
 
+    // Give the default domain a row like the configured domains, so its TLS reports are kept.
+    match db.acquire().await {
+        Ok(mut conn) => match logic::domain::Domain::create_default(&mut conn).await {
+            Ok(false) => {}
+            Ok(true) => info!("Created the default domain."),
+            Err(e) => warn!("Failed to create the default domain: {}", e),
+        },
+        Err(e) => warn!(
+            "Failed to acquire connection to create the default domain: {}",
+            e
+        ),
+    }
+
+    // Seal the drafts saved before drafts were sealed.
+    match db.acquire().await {
+        Ok(mut conn) => match logic::draft::Draft::seal_existing(&mut conn).await {
+            Ok(0) => {}
+            Ok(sealed) => info!("Sealed {} existing drafts.", sealed),
+            Err(e) => warn!("Failed to seal existing drafts: {}", e),
+        },
+        Err(e) => warn!("Failed to acquire connection to seal drafts: {}", e),
+    }
+
+    // Connect to the blob store.
+    let store = match storage::connect(&env.blob, db.clone()) {
+        Ok(store) => store,
+        Err(e) => {
+            error!("Failed to initialize blob store: {}", e);
+            return;
+        }
+    };
+
     // Start the SMTP server.
-    let smtp = smtp::start(db.clone());
+    let smtp = smtp::start(db.clone(), store.clone(), env.proxy.clone(), env.quota);
+    // Start the outbound delivery.
+    let outbound = smtp::start_outbound(db.clone(), env.hostname.clone());
+    // Start the mailing list digests.
+    let digests = smtp::start_digests(db.clone());
+    // Start the blob garbage collection.
+    let gc = storage::start_gc(db.clone(), store.clone());
+    // Start the compression of blobs stored before compression was introduced.
+    let compression =
+        storage::start_compression(db.clone(), store.clone(), env.compression_dictionary);
+    // Start the retention policies of mailboxes.
+    let retention = storage::start_retention(db.clone(), env.retention, env.quota);
+    // Start resuming interrupted bulk operations.
+    let bulk = logic::bulk::BulkJob::start_worker(db.clone(), env.quota);
+    // Start the distribution of events to the event streams.
+    let events = logic::event::Events::default();
+    let listener = events.clone().listen(db.clone());
     // Start the HTTP server.
-    let http = http::start(db, env);
+    let http = http::start(db, store, env, events);
 
     // Wait for either future to return, then quit.
     tokio::select! {
         _ = smtp => {
             info!("SMTP service exited, goodbye!");
         }
+        _ = outbound => {
+            info!("Outbound delivery exited, goodbye!");
+        }
+        _ = digests => {
+            info!("Mailing list digests exited, goodbye!");
+        }
+        _ = gc => {
+            info!("Blob garbage collection exited, goodbye!");
+        }
+        _ = compression => {
+            info!("Blob compression exited, goodbye!");
+        }
+        _ = retention => {
+            info!("Mailbox retention exited, goodbye!");
+        }
+        _ = bulk => {
+            info!("Bulk operations exited, goodbye!");
+        }
+        _ = listener => {
+            info!("Event distribution exited, goodbye!");
+        }
         _ = http => {
             info!("HTTP service exited, goodbye!");
         }
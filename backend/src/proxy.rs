@@ -0,0 +1,383 @@
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+use thiserror::Error;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    net::TcpStream,
+    time::timeout,
+};
+
+/// The prefix of a version 1 (text) header.
+const V1_PREFIX: &[u8] = b"PROXY ";
+
+/// The maximum length of a version 1 header, including the line ending.
+const V1_MAX_LENGTH: usize = 107;
+
+/// The signature starting a version 2 (binary) header.
+const V2_SIGNATURE: &[u8] = b"\r\n\r\n\0\r\nQUIT\n";
+
+/// Time a proxy has to send the header after connecting.
+const HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Configuration of the PROXY protocol, as sent by load balancers like HAProxy.
+/// Only peers in the trusted networks are allowed to send a header, and are required to do so.
+#[derive(Debug, Clone, Default)]
+pub struct ProxyConfig {
+    trusted: Vec<TrustedNetwork>,
+}
+
+impl ProxyConfig {
+    /// Parse a comma separated list of trusted addresses and networks, like `10.0.0.0/8, ::1`.
+    /// An empty list disables the PROXY protocol.
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let trusted = input
+            .split(',')
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .map(TrustedNetwork::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(ProxyConfig { trusted })
+    }
+
+    /// Check if a peer is a trusted proxy.
+    fn trusts(&self, ip: IpAddr) -> bool {
+        self.trusted.iter().any(|n| n.contains(ip))
+    }
+
+    /// Find the address of the client behind the connection.
+    /// The header of trusted proxies is consumed, other peers are returned as is.
+    ///
+    /// When the client speaks first, like with HTTP, the connection of an untrusted peer is inspected,
+    /// and rejected when it starts with a header. Other protocols should check the first input with `is_header`.
+    pub async fn accept(
+        &self,
+        stream: &mut TcpStream,
+        peer: SocketAddr,
+        client_first: bool,
+    ) -> Result<SocketAddr, ProxyError> {
+        if !self.trusts(peer.ip()) {
+            if client_first {
+                let mut buff = [0; 12];
+                let n = match timeout(HEADER_TIMEOUT, stream.peek(&mut buff)).await {
+                    Ok(res) => res?,
+                    // Silent clients are handled by the timeouts of the protocol.
+                    Err(_) => return Ok(peer),
+                };
+
+                if is_header(&buff[..n]) {
+                    return Err(ProxyError::Untrusted(peer.ip()));
+                }
+            }
+
+            return Ok(peer);
+        }
+
+        let client = timeout(HEADER_TIMEOUT, read_header(stream))
+            .await
+            .map_err(|_| ProxyError::Timeout)??;
+
+        Ok(client.unwrap_or(peer))
+    }
+}
+
+/// Check if received data starts with a PROXY protocol header.
+pub fn is_header(data: &[u8]) -> bool {
+    data.starts_with(V1_PREFIX) || data.starts_with(V2_SIGNATURE)
+}
+
+/// Read a version 1 or 2 header from the stream, without reading beyond it.
+/// Returns None when the header does not carry a client address, like health checks.
+async fn read_header<S>(stream: &mut S) -> Result<Option<SocketAddr>, ProxyError>
+where
+    S: AsyncRead + Unpin,
+{
+    // Both versions are at least 12 bytes long.
+    let mut start = [0; 12];
+    stream.read_exact(&mut start).await?;
+
+    if start == V2_SIGNATURE {
+        let mut fixed = [0; 4];
+        stream.read_exact(&mut fixed).await?;
+
+        let mut addresses = vec![0; u16::from_be_bytes([fixed[2], fixed[3]]) as usize];
+        stream.read_exact(&mut addresses).await?;
+
+        return parse_v2(fixed[0], fixed[1], &addresses);
+    }
+
+    if !start.starts_with(V1_PREFIX) {
+        return Err(ProxyError::Missing);
+    }
+
+    let mut line = start.to_vec();
+    while !line.ends_with(b"\r\n") {
+        if line.len() >= V1_MAX_LENGTH {
+            return Err(ProxyError::Invalid);
+        }
+
+        line.push(stream.read_u8().await?);
+    }
+
+    parse_v1(&line)
+}
+
+/// Parse a version 1 header line, like `PROXY TCP4 192.0.2.1 192.0.2.2 56324 25\r\n`.
+fn parse_v1(line: &[u8]) -> Result<Option<SocketAddr>, ProxyError> {
+    let line = std::str::from_utf8(line).map_err(|_| ProxyError::Invalid)?;
+    let parts: Vec<&str> = line.trim_end().split(' ').collect();
+
+    match parts.as_slice() {
+        ["PROXY", "UNKNOWN", ..] => Ok(None),
+        ["PROXY", family, source, _, port, _] => {
+            let ip: IpAddr = source.parse().map_err(|_| ProxyError::Invalid)?;
+            let port: u16 = port.parse().map_err(|_| ProxyError::Invalid)?;
+
+            match (*family, ip) {
+                ("TCP4", IpAddr::V4(_)) | ("TCP6", IpAddr::V6(_)) => {
+                    Ok(Some(SocketAddr::new(ip, port)))
+                }
+                _ => Err(ProxyError::Invalid),
+            }
+        }
+        _ => Err(ProxyError::Invalid),
+    }
+}
+
+/// Parse the fields of a version 2 header following the signature.
+fn parse_v2(
+    version_command: u8,
+    family: u8,
+    addresses: &[u8],
+) -> Result<Option<SocketAddr>, ProxyError> {
+    if version_command >> 4 != 2 {
+        return Err(ProxyError::Invalid);
+    }
+
+    match version_command & 0x0f {
+        // LOCAL, the connection was made by the proxy itself.
+        0x0 => return Ok(None),
+        // PROXY, the connection is relayed for a client.
+        0x1 => {}
+        _ => return Err(ProxyError::Invalid),
+    }
+
+    // The high nibble is the address family, the low nibble the transport protocol.
+    match family >> 4 {
+        0x1 if addresses.len() >= 12 => {
+            let mut ip = [0; 4];
+            ip.copy_from_slice(&addresses[0..4]);
+            let port = u16::from_be_bytes([addresses[8], addresses[9]]);
+
+            Ok(Some(SocketAddr::new(Ipv4Addr::from(ip).into(), port)))
+        }
+        0x2 if addresses.len() >= 36 => {
+            let mut ip = [0; 16];
+            ip.copy_from_slice(&addresses[0..16]);
+            let port = u16::from_be_bytes([addresses[32], addresses[33]]);
+
+            Ok(Some(SocketAddr::new(Ipv6Addr::from(ip).into(), port)))
+        }
+        // Unspecified or Unix sockets don't have a usable client address.
+        0x0 | 0x3 => Ok(None),
+        _ => Err(ProxyError::Invalid),
+    }
+}
+
+/// An address or network allowed to send PROXY headers.
+#[derive(Debug, Clone)]
+struct TrustedNetwork {
+    address: IpAddr,
+    prefix: u8,
+}
+
+impl TrustedNetwork {
+    /// Parse an address, optionally followed by a prefix length.
+    fn parse(input: &str) -> Result<Self, String> {
+        let (address, prefix) = match input.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (input, None),
+        };
+
+        let address: IpAddr = address
+            .parse()
+            .map_err(|_| format!("Invalid trusted proxy address '{}'.", input))?;
+        let max = if address.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse()
+                .ok()
+                .filter(|p| *p <= max)
+                .ok_or_else(|| format!("Invalid trusted proxy network '{}'.", input))?,
+            None => max,
+        };
+
+        Ok(TrustedNetwork { address, prefix })
+    }
+
+    /// Check if the network contains an address.
+    fn contains(&self, ip: IpAddr) -> bool {
+        match (self.address, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Possible errors with accepting a proxied connection.
+#[derive(Error, Debug)]
+pub enum ProxyError {
+    #[error("Peer {0} is not a trusted proxy, but sent a PROXY header.")]
+    Untrusted(IpAddr),
+    #[error("The trusted proxy did not send a PROXY header.")]
+    Missing,
+    #[error("The PROXY header is invalid.")]
+    Invalid,
+    #[error("Timed out waiting for the PROXY header.")]
+    Timeout,
+    #[error("Failed to read the PROXY header: {0}")]
+    Io(#[from] io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn read(mut header: &[u8]) -> Result<Option<SocketAddr>, ProxyError> {
+        read_header(&mut header).await
+    }
+
+    fn v2(command: u8, family: u8, addresses: &[u8]) -> Vec<u8> {
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend_from_slice(&[command, family]);
+        header.extend_from_slice(&(addresses.len() as u16).to_be_bytes());
+        header.extend_from_slice(addresses);
+        header
+    }
+
+    fn v4_addresses() -> Vec<u8> {
+        vec![192, 0, 2, 1, 192, 0, 2, 2, 0xdc, 0x04, 0, 25]
+    }
+
+    #[tokio::test]
+    async fn v1_headers() {
+        let client = read(b"PROXY TCP4 192.0.2.1 192.0.2.2 56324 25\r\n").await;
+        assert_eq!(client.unwrap(), Some("192.0.2.1:56324".parse().unwrap()));
+
+        let client = read(b"PROXY TCP6 2001:db8::1 2001:db8::2 56324 25\r\n").await;
+        assert_eq!(
+            client.unwrap(),
+            Some("[2001:db8::1]:56324".parse().unwrap())
+        );
+
+        let client = read(b"PROXY UNKNOWN\r\n").await;
+        assert_eq!(client.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn malformed_v1_headers() {
+        for header in [
+            &b"PROXY TCP4 192.0.2.1 192.0.2.2 56324\r\n"[..],
+            b"PROXY TCP4 2001:db8::1 192.0.2.2 56324 25\r\n",
+            b"PROXY TCP6 192.0.2.1 192.0.2.2 56324 25\r\n",
+            b"PROXY TCP4 192.0.2.1 192.0.2.2 65536 25\r\n",
+            b"PROXY TCP4 192.0.2.256 192.0.2.2 56324 25\r\n",
+            b"PROXY UDP4 192.0.2.1 192.0.2.2 56324 25\r\n",
+            b"PROXY TCP4  192.0.2.1 192.0.2.2 56324 25\r\n",
+            b"PROXY TCP4 \xff.0.2.1 192.0.2.2 56324 25\r\n",
+        ] {
+            assert!(
+                matches!(read(header).await, Err(ProxyError::Invalid)),
+                "{:?}",
+                String::from_utf8_lossy(header)
+            );
+        }
+
+        // Headers without a line ending stop at the maximum length.
+        let mut long = b"PROXY TCP4 ".to_vec();
+        long.resize(200, b'1');
+        assert!(matches!(read(&long).await, Err(ProxyError::Invalid)));
+
+        assert!(matches!(
+            read(b"EHLO example.com\r\n").await,
+            Err(ProxyError::Missing)
+        ));
+    }
+
+    #[tokio::test]
+    async fn truncated_v1_headers() {
+        for header in [
+            &b"PROXY"[..],
+            b"PROXY TCP4 192.0.2.1",
+            b"PROXY TCP4 192.0.2.1 192.0.2.2 56324 25\r",
+        ] {
+            assert!(
+                matches!(read(header).await, Err(ProxyError::Io(_))),
+                "{:?}",
+                String::from_utf8_lossy(header)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn v2_headers() {
+        let client = read(&v2(0x21, 0x11, &v4_addresses())).await;
+        assert_eq!(client.unwrap(), Some("192.0.2.1:56324".parse().unwrap()));
+
+        let mut v6 = vec![0; 36];
+        v6[0..2].copy_from_slice(&[0x20, 0x01]);
+        v6[15] = 1;
+        v6[32..34].copy_from_slice(&56324u16.to_be_bytes());
+        let client = read(&v2(0x21, 0x21, &v6)).await;
+        assert_eq!(client.unwrap(), Some("[2001::1]:56324".parse().unwrap()));
+
+        // Health checks of the proxy itself, and clients without an address.
+        assert_eq!(read(&v2(0x20, 0x00, &[])).await.unwrap(), None);
+        assert_eq!(read(&v2(0x21, 0x31, &[0; 216])).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn malformed_v2_headers() {
+        for (command, family, addresses) in [
+            (0x11, 0x11, v4_addresses()),
+            (0x22, 0x11, v4_addresses()),
+            (0x21, 0x11, vec![192, 0, 2, 1]),
+            (0x21, 0x21, v4_addresses()),
+            (0x21, 0x41, v4_addresses()),
+        ] {
+            assert!(
+                matches!(
+                    read(&v2(command, family, &addresses)).await,
+                    Err(ProxyError::Invalid)
+                ),
+                "{:#x} {:#x}",
+                command,
+                family
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn truncated_v2_headers() {
+        let header = v2(0x21, 0x11, &v4_addresses());
+
+        for length in [6, 12, 14, header.len() - 1] {
+            assert!(
+                matches!(read(&header[..length]).await, Err(ProxyError::Io(_))),
+                "{}",
+                length
+            );
+        }
+    }
+}
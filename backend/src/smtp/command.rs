@@ -0,0 +1,114 @@
+use std::fmt;
+
+/// A command sent by the client.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Helo(String),
+    Ehlo(String),
+    /// The sender is None for the null reverse-path `<>`, used by bounces.
+    Mail {
+        from: Option<Mailbox>,
+        size: Option<u64>,
+    },
+    Rcpt(Mailbox),
+    Data,
+    Rset,
+    Noop,
+    Quit,
+}
+
+/// An address in the envelope.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mailbox {
+    pub local: String,
+    pub domain: String,
+}
+
+impl fmt::Display for Mailbox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{}", self.local, self.domain)
+    }
+}
+
+/// Parse a single command line, without the line ending.
+/// Returns None if the command is unknown or malformed.
+pub fn parse(line: &str) -> Option<Command> {
+    let (verb, args) = match line.split_once(' ') {
+        Some((verb, args)) => (verb, args.trim()),
+        None => (line, ""),
+    };
+
+    match verb.to_ascii_uppercase().as_str() {
+        "HELO" if !args.is_empty() => Some(Command::Helo(args.to_string())),
+        "EHLO" if !args.is_empty() => Some(Command::Ehlo(args.to_string())),
+        "MAIL" => parse_mail(args),
+        "RCPT" => {
+            let (path, _) = parse_path(strip_prefix(args, "TO:")?)?;
+            Some(Command::Rcpt(path?))
+        }
+        "DATA" if args.is_empty() => Some(Command::Data),
+        "RSET" if args.is_empty() => Some(Command::Rset),
+        "NOOP" => Some(Command::Noop),
+        "QUIT" if args.is_empty() => Some(Command::Quit),
+        _ => None,
+    }
+}
+
+/// Parse the arguments of `MAIL FROM:<path> [parameters]`.
+/// The BODY parameter is accepted for both 7BIT and 8BITMIME, as content is stored as is.
+fn parse_mail(args: &str) -> Option<Command> {
+    let (from, params) = parse_path(strip_prefix(args, "FROM:")?)?;
+    let mut size = None;
+
+    for param in params.split_whitespace() {
+        let (key, value) = param.split_once('=').unwrap_or((param, ""));
+
+        match key.to_ascii_uppercase().as_str() {
+            "SIZE" => size = Some(value.parse().ok()?),
+            "BODY" => {}
+            _ => return None,
+        }
+    }
+
+    Some(Command::Mail { from, size })
+}
+
+/// Strip a case insensitive prefix, and any whitespace following it.
+fn strip_prefix<'a>(input: &'a str, prefix: &str) -> Option<&'a str> {
+    if input.len() < prefix.len() || !input[..prefix.len()].eq_ignore_ascii_case(prefix) {
+        return None;
+    }
+
+    Some(input[prefix.len()..].trim_start())
+}
+
+/// Parse a path like `<user@example.com>`, returning the mailbox and the remaining parameters.
+/// The mailbox is None for the null path `<>`.
+fn parse_path(input: &str) -> Option<(Option<Mailbox>, &str)> {
+    let input = input.strip_prefix('<')?;
+    let end = input.find('>')?;
+    let (path, rest) = (&input[..end], &input[end + 1..]);
+
+    if path.is_empty() {
+        return Some((None, rest));
+    }
+
+    // Source routes like `@a,@b:user@c` are obsolete, and only the mailbox is used.
+    let path = match path.starts_with('@') {
+        true => path.split_once(':')?.1,
+        false => path,
+    };
+
+    let (local, domain) = path.rsplit_once('@')?;
+    if local.is_empty() || domain.is_empty() || path.chars().any(|c| c.is_whitespace()) {
+        return None;
+    }
+
+    Some((
+        Some(Mailbox {
+            local: local.to_string(),
+            domain: domain.to_lowercase(),
+        }),
+        rest,
+    ))
+}
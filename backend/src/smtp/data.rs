@@ -0,0 +1,70 @@
+/// Decoder for the content sent after DATA.
+/// It removes the dot-stuffing (RFC 5321, section 4.5.2) and finds the terminating `<CRLF>.<CRLF>`.
+/// Input is handled as raw bytes, so 8-bit and binary content passes through unchanged.
+pub struct DataDecoder {
+    state: State,
+}
+
+/// The position within the current line.
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    /// At the start of a line.
+    LineStart,
+    /// A dot at the start of a line was removed.
+    Dot,
+    /// A dot and carriage return at the start of a line, possibly the end of the data.
+    DotCr,
+    /// Somewhere within a line.
+    Middle,
+    /// A carriage return within a line, possibly the end of the line.
+    Cr,
+}
+
+impl DataDecoder {
+    /// Create a decoder for the start of the data.
+    pub fn new() -> Self {
+        DataDecoder {
+            state: State::LineStart,
+        }
+    }
+
+    /// Decode a chunk of input, appending the content to the output.
+    /// Returns the amount of input consumed when the end of the data was reached,
+    /// the rest of the input belongs to the next commands.
+    pub fn decode(&mut self, input: &[u8], output: &mut Vec<u8>) -> Option<usize> {
+        for (i, byte) in input.iter().copied().enumerate() {
+            self.state = match (self.state, byte) {
+                (State::LineStart, b'.') => State::Dot,
+                (State::Dot, b'\r') => State::DotCr,
+                (State::DotCr, b'\n') => {
+                    // The line ending before the dot belongs to the content.
+                    self.state = State::LineStart;
+                    return Some(i + 1);
+                }
+                (State::DotCr, b) => {
+                    // Not the end after all, restore the carriage return.
+                    output.push(b'\r');
+                    output.push(b);
+                    Self::after(b)
+                }
+                (_, b) => {
+                    output.push(b);
+                    match (self.state, b) {
+                        (State::Cr, b'\n') => State::LineStart,
+                        _ => Self::after(b),
+                    }
+                }
+            };
+        }
+
+        None
+    }
+
+    /// The state after a byte within a line.
+    fn after(byte: u8) -> State {
+        match byte {
+            b'\r' => State::Cr,
+            _ => State::Middle,
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use std::time::Duration;
+
+use sqlx::{PgConnection, Pool, Postgres};
+
+use super::command::Mailbox;
+use crate::logic::mailing_list::{ListAddress, MailingList, PostResult};
+
+/// Time between checks for due digests.
+const DIGEST_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Start the digest worker.
+/// Every list sends its collected posts once a day to the digest subscribers.
+pub async fn start_digests(db: Pool<Postgres>) {
+    info!("Starting mailing list digests");
+
+    loop {
+        if let Err(e) = send_digests(&db).await {
+            warn!("Failed to send mailing list digests: {}", e);
+        }
+
+        tokio::time::sleep(DIGEST_INTERVAL).await;
+    }
+}
+
+/// Send the digests of all lists which are due.
+async fn send_digests(db: &Pool<Postgres>) -> Result<(), sqlx::Error> {
+    let mut conn = db.acquire().await?;
+
+    for list in MailingList::due_digests(&mut conn).await? {
+        let sent = list.send_digest(&mut conn).await?;
+
+        if sent > 0 {
+            info!("Sent digest of {} posts for {}.", sent, list.address());
+        }
+    }
+
+    Ok(())
+}
+
+/// Check if a recipient is one of the addresses of a mailing list.
+pub async fn is_list(conn: &mut PgConnection, recipient: &Mailbox) -> Result<bool, sqlx::Error> {
+    Ok(
+        MailingList::resolve(conn, &recipient.local, &recipient.domain)
+            .await?
+            .is_some(),
+    )
+}
+
+/// Handle a message sent to a mailing list address.
+/// Messages from the null sender are only accepted as bounces.
+pub async fn receive(
+    conn: &mut PgConnection,
+    sender: Option<&str>,
+    recipient: &Mailbox,
+    data: &[u8],
+    mail: &mailparse::ParsedMail<'_>,
+) -> Result<(), sqlx::Error> {
+    let (list, address) =
+        match MailingList::resolve(conn, &recipient.local, &recipient.domain).await? {
+            Some(resolved) => resolved,
+            None => return Ok(()),
+        };
+
+    match (address, sender) {
+        (ListAddress::Bounce(subscriber), _) => {
+            list.bounce(conn, sender, &subscriber, mail).await?
+        }
+        (_, None) => info!(
+            "Ignoring message from the null sender to {}.",
+            list.address()
+        ),
+        (ListAddress::Post, Some(sender)) => match list.post(conn, sender, data).await? {
+            PostResult::Distributed(count) => {
+                info!(
+                    "Distributed post to {} to {} subscribers.",
+                    list.address(),
+                    count
+                )
+            }
+            PostResult::Moderated(id) => {
+                info!("Holding post {} to {} for moderation.", id, list.address())
+            }
+            PostResult::Rejected => {
+                info!("Rejected post from {} to {}.", sender, list.address())
+            }
+        },
+        (ListAddress::Request, Some(sender)) => list.command(conn, sender, mail).await?,
+    }
+
+    Ok(())
+}
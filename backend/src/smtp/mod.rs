@@ -1,40 +1,376 @@
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use postbus::{Handler, SmtpService, SmtpState};
-use sqlx::{Pool, Postgres};
+use sqlx::{PgConnection, Pool, Postgres};
+use tokio::net::TcpListener;
+
+use crate::{
+    logic::{
+        account::Account,
+        domain::{Domain, TLSRPT_LOCAL},
+        message::{DeliverError, Message},
+        mime::Structure,
+        pgp_key::EncryptError,
+        quota::{Quota, QuotaConfig, QuotaError},
+        search::Document,
+        tls_report::TlsReport,
+    },
+    proxy::ProxyConfig,
+    storage::{BlobStore, Spooled},
+};
+
+mod command;
+mod data;
+mod mailing_list;
+mod outbound;
+mod reply;
+mod session;
+
+use command::Mailbox;
+use reply::Reply;
+use session::{Envelope, Handler, SmtpSession};
+
+pub use mailing_list::start_digests;
+pub use outbound::start as start_outbound;
 
 /// Start the SMTP server.
-pub async fn start(db: Pool<Postgres>) {
-    let service = SmtpService::create(
-        "0.0.0.0:2525".parse().unwrap(),
-        "Nexium Relay".into(),
-        Arc::new(SmtpHandler { _db: db }),
-    );
-
-    service.listen().await;
+/// Connections from trusted proxies are required to start with a PROXY header.
+pub async fn start(
+    db: Pool<Postgres>,
+    store: Arc<dyn BlobStore>,
+    proxy: ProxyConfig,
+    quota: QuotaConfig,
+) {
+    let listener = match TcpListener::bind("0.0.0.0:2525").await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Could not listen on the SMTP port: {}", e);
+            return;
+        }
+    };
+    let handler: Arc<dyn Handler> = Arc::new(SmtpHandler { db, store, quota });
+    let proxy = Arc::new(proxy);
+
+    info!("Starting SMTP service");
+
+    loop {
+        let (mut stream, peer) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                warn!("Failed to accept SMTP socket: {}", e);
+                continue;
+            }
+        };
+
+        let handler = handler.clone();
+        let proxy = proxy.clone();
+
+        tokio::spawn(async move {
+            let client = match proxy.accept(&mut stream, peer, false).await {
+                Ok(client) => client,
+                Err(e) => {
+                    warn!("Rejected SMTP connection from {}: {}", peer, e);
+                    return;
+                }
+            };
+
+            SmtpSession::new(stream, client, "Nexium Relay".into(), handler)
+                .handle()
+                .await;
+        });
+    }
 }
 
 struct SmtpHandler {
-    _db: Pool<Postgres>,
+    db: Pool<Postgres>,
+    store: Arc<dyn BlobStore>,
+    quota: QuotaConfig,
+}
+
+impl SmtpHandler {
+    /// Check if a message of a size fits into the quota of an account.
+    /// A full mailbox is a temporary failure, as the user can make room.
+    async fn check_quota(&self, conn: &mut PgConnection, account: &Account, size: u64) -> Reply {
+        let quota = match Quota::find(conn, account.id, &self.quota).await {
+            Ok(quota) => quota,
+            Err(e) => {
+                error!("Failed to get the quota of {}: {}", account.username, e);
+                return Reply::LocalError;
+            }
+        };
+
+        match quota.admits(size as i64) {
+            Ok(()) => Reply::Ok,
+            Err(QuotaError::Full) => Reply::MailboxFull,
+            Err(QuotaError::TooLarge) => Reply::QuotaExceeded,
+            Err(QuotaError::DatabaseError(_)) => Reply::LocalError,
+        }
+    }
+
+    /// Check if a received email fits into the quotas of all accounts it is addressed to.
+    /// Clients which did not declare the size are only rejected after sending the email.
+    async fn check_quotas(&self, envelope: &Envelope, size: u64) -> Reply {
+        let mut conn = match self.db.acquire().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                error!("Failed to acquire connection for quotas: {}", e);
+                return Reply::LocalError;
+            }
+        };
+
+        for recipient in &envelope.recipients {
+            match Domain::is_hosted(&mut conn, &recipient.domain).await {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(e) => {
+                    error!("Failed to check the domain of {}: {}", recipient, e);
+                    return Reply::LocalError;
+                }
+            }
+
+            if let Ok(account) = Account::find_username(&mut conn, &recipient.local).await {
+                let reply = self.check_quota(&mut conn, &account, size).await;
+                if !reply.is_positive() {
+                    return reply;
+                }
+            }
+        }
+
+        Reply::Ok
+    }
+
+    /// Store the TLS-RPT reports contained in a received email.
+    async fn ingest_tls_reports(&self, structure: &Structure) {
+        let mut conn = match self.db.acquire().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                error!("Failed to acquire connection for TLS reports: {}", e);
+                return;
+            }
+        };
+
+        match TlsReport::ingest(&mut conn, structure).await {
+            Ok(reports) => {
+                for report in reports {
+                    info!(
+                        "Received TLS report {} from {} for {}.",
+                        report.report_id, report.organization, report.domain
+                    );
+                }
+            }
+            Err(e) => warn!("Failed to ingest TLS report: {}", e),
+        }
+    }
+
+    /// Deliver a received email into the accounts it is addressed to.
+    /// Accounts with an OpenPGP key get it encrypted, unless it is already encrypted or signed.
+    /// An account of which no key is usable is a temporary failure, as the user can set a new key.
+    async fn deliver_accounts(
+        &self,
+        conn: &mut PgConnection,
+        envelope: &Envelope,
+        message: &Spooled,
+        structure: &Structure,
+    ) -> Result<(), Reply> {
+        let document = Document::extract(structure);
+
+        for recipient in &envelope.recipients {
+            match Domain::is_hosted(conn, &recipient.domain).await {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(e) => {
+                    error!("Failed to check the domain of {}: {}", recipient, e);
+                    return Err(Reply::LocalError);
+                }
+            }
+
+            let account = match Account::find_username(conn, &recipient.local).await {
+                Ok(account) => account,
+                Err(_) => continue,
+            };
+
+            let result = Message::deliver_protected(
+                conn,
+                self.store.as_ref(),
+                &account,
+                message,
+                structure,
+                &document,
+                &self.quota,
+            )
+            .await;
+
+            match result {
+                Ok(_) => {}
+                Err(DeliverError::Encryption(EncryptError::Unusable)) => {
+                    warn!("No usable key to deliver to {}.", account.username);
+                    return Err(Reply::LocalError);
+                }
+                Err(DeliverError::OverQuota(QuotaError::Full)) => return Err(Reply::MailboxFull),
+                Err(DeliverError::OverQuota(QuotaError::TooLarge)) => {
+                    return Err(Reply::QuotaExceeded)
+                }
+                Err(e) => {
+                    error!("Failed to deliver message to {}: {}", account.username, e);
+                    return Err(Reply::LocalError);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Pass a received email to the mailing lists it is addressed to.
+    /// Posts are sent on as a whole, so the email is read into memory when it is addressed to a list.
+    async fn deliver_lists(
+        &self,
+        conn: &mut PgConnection,
+        envelope: &Envelope,
+        message: &Spooled,
+    ) -> Result<(), Reply> {
+        let sender = envelope.from.as_ref().map(|from| from.to_string());
+
+        let mut lists = Vec::new();
+        for recipient in &envelope.recipients {
+            match mailing_list::is_list(conn, recipient).await {
+                Ok(true) => lists.push(recipient),
+                Ok(false) => {}
+                Err(e) => {
+                    error!("Failed to resolve mailing list: {}", e);
+                    return Err(Reply::LocalError);
+                }
+            }
+        }
+        if lists.is_empty() {
+            return Ok(());
+        }
+
+        let data = match message.read().await {
+            Ok(data) => data,
+            Err(e) => {
+                error!("Failed to read the spooled message: {}", e);
+                return Err(Reply::LocalError);
+            }
+        };
+        let mail = match mailparse::parse_mail(&data) {
+            Ok(mail) => mail,
+            Err(e) => {
+                warn!("Failed to parse a message to a mailing list: {}", e);
+                return Err(Reply::TransactionFailed);
+            }
+        };
+
+        for recipient in lists {
+            let result =
+                mailing_list::receive(conn, sender.as_deref(), recipient, &data, &mail).await;
+
+            if let Err(e) = result {
+                error!("Failed to deliver to mailing list: {}", e);
+                return Err(Reply::LocalError);
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[async_trait]
 impl Handler for SmtpHandler {
-    /// Validate the recipient.
-    async fn recipient_local(&self, recipient: &postbus::command::Mailbox) -> bool {
-        println!("Checking recipient {:#?}.", recipient);
+    /// Validate the recipient, and that the message fits into the quota of its account.
+    async fn recipient(&self, recipient: &Mailbox, size: Option<u64>) -> Reply {
+        let mut conn = match self.db.acquire().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                error!("Failed to acquire connection for recipient: {}", e);
+                return Reply::LocalError;
+            }
+        };
+
+        // Reports are received on every domain, as its TLS-RPT record names this address.
+        if recipient.local == TLSRPT_LOCAL {
+            return match Domain::is_hosted(&mut conn, &recipient.domain).await {
+                Ok(true) => Reply::Ok,
+                Ok(false) => Reply::RecipientNotLocal,
+                Err(e) => {
+                    error!("Failed to check the domain of {}: {}", recipient, e);
+                    Reply::LocalError
+                }
+            };
+        }
+
+        let hosted = match Domain::is_hosted(&mut conn, &recipient.domain).await {
+            Ok(hosted) => hosted,
+            Err(e) => {
+                error!("Failed to check the domain of {}: {}", recipient, e);
+                return Reply::LocalError;
+            }
+        };
+
+        if hosted {
+            if let Ok(account) = Account::find_username(&mut conn, &recipient.local).await {
+                return self
+                    .check_quota(&mut conn, &account, size.unwrap_or(0))
+                    .await;
+            }
+        }
 
-        recipient.domain == "nexium.app".into()
+        // Addresses of mailing lists are local as well.
+        let local = mailing_list::is_list(&mut conn, recipient)
+            .await
+            .unwrap_or(false);
+
+        match local {
+            true => Reply::Ok,
+            false => Reply::RecipientNotLocal,
+        }
     }
 
-    /// Save the received email into the database.
-    async fn save(&self, state: &SmtpState) -> bool {
-        println!("Saving mail state:\r\n{:#?}", state);
+    /// Save the received email.
+    /// Its parts are scanned from the spool file, keeping only the text bodies and reports in memory.
+    /// The whole email is only read for accounts with an OpenPGP key and for mailing lists.
+    /// It is delivered to all recipients or none of them, so a retry of the client never duplicates it.
+    async fn save(&self, envelope: &Envelope, message: &Spooled) -> Reply {
+        let structure = match Structure::scan_spooled(message).await {
+            Ok(structure) => structure,
+            Err(e) => {
+                error!("Failed to scan the spooled message: {}", e);
+                return Reply::LocalError;
+            }
+        };
+
+        let reply = self.check_quotas(envelope, message.size).await;
+        if !reply.is_positive() {
+            return reply;
+        }
+
+        let mut tx = match self.db.begin().await {
+            Ok(tx) => tx,
+            Err(e) => {
+                error!("Failed to start the transaction for delivery: {}", e);
+                return Reply::LocalError;
+            }
+        };
+
+        if let Err(reply) = self
+            .deliver_accounts(&mut tx, envelope, message, &structure)
+            .await
+        {
+            return reply;
+        }
+        if let Err(reply) = self.deliver_lists(&mut tx, envelope, message).await {
+            return reply;
+        }
+
+        if let Err(e) = tx.commit().await {
+            error!("Failed to commit the delivery: {}", e);
+            return Reply::LocalError;
+        }
 
-        let parsed = mailparse::parse_mail(state.data.as_bytes()).unwrap();
-        println!("Body:\r\n{:#?}", parsed);
+        // Reports sent to the TLS-RPT address are stored for the administrators.
+        if envelope.recipients.iter().any(|r| r.local == TLSRPT_LOCAL) {
+            self.ingest_tls_reports(&structure).await;
+        }
 
-        true
+        Reply::Ok
     }
 }
@@ -0,0 +1,162 @@
+use std::time::Duration;
+
+use thiserror::Error;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    time::timeout,
+};
+
+/// Timeout for a single reply of the remote server.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// The maximum amount of lines in a single reply.
+const MAX_REPLY_LINES: usize = 100;
+
+/// A reply of the remote server.
+#[derive(Debug)]
+pub struct Reply {
+    pub code: u16,
+    pub lines: Vec<String>,
+}
+
+impl Reply {
+    /// Check if the reply contains a keyword, used for the EHLO capabilities.
+    pub fn has_keyword(&self, keyword: &str) -> bool {
+        self.lines.iter().any(|l| {
+            l.split_whitespace()
+                .next()
+                .map(|k| k.eq_ignore_ascii_case(keyword))
+                .unwrap_or(false)
+        })
+    }
+}
+
+/// Minimal SMTP client, used to deliver mail to remote servers.
+/// It is generic over the stream, so that it can be upgraded to TLS.
+pub struct SmtpClient<S> {
+    stream: BufReader<S>,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> SmtpClient<S> {
+    /// Create a new client on top of a connected stream.
+    pub fn new(stream: S) -> Self {
+        SmtpClient {
+            stream: BufReader::new(stream),
+        }
+    }
+
+    /// Return the underlying stream, used to start TLS.
+    /// This should only be called when the server is waiting for input.
+    pub fn into_inner(self) -> S {
+        self.stream.into_inner()
+    }
+
+    /// Read a reply, and require it to have the expected code.
+    pub async fn expect(&mut self, code: u16) -> Result<Reply, ClientError> {
+        let reply = self.read_reply().await?;
+
+        if reply.code != code {
+            return Err(ClientError::Rejected {
+                code: reply.code,
+                message: reply.lines.join(" "),
+            });
+        }
+
+        Ok(reply)
+    }
+
+    /// Send a command, and require the reply to have the expected code.
+    pub async fn command(&mut self, command: &str, code: u16) -> Result<Reply, ClientError> {
+        self.stream
+            .get_mut()
+            .write_all(format!("{}\r\n", command).as_bytes())
+            .await?;
+        self.stream.get_mut().flush().await?;
+
+        self.expect(code).await
+    }
+
+    /// Send the message content after the DATA command was accepted.
+    /// Lines starting with a dot are escaped, and all line endings are normalized to CRLF.
+    pub async fn data(&mut self, data: &[u8]) -> Result<Reply, ClientError> {
+        let mut escaped = Vec::with_capacity(data.len() + 64);
+
+        for line in data.split(|b| *b == b'\n') {
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+
+            if line.starts_with(b".") {
+                escaped.push(b'.');
+            }
+
+            escaped.extend_from_slice(line);
+            escaped.extend_from_slice(b"\r\n");
+        }
+
+        // A message ending with a newline results in a last empty line, which is not part of it.
+        if data.ends_with(b"\n") {
+            escaped.truncate(escaped.len() - 2);
+        }
+
+        escaped.extend_from_slice(b".\r\n");
+
+        self.stream.get_mut().write_all(&escaped).await?;
+        self.stream.get_mut().flush().await?;
+
+        self.expect(250).await
+    }
+
+    /// Read a, possibly multiline, reply from the server.
+    async fn read_reply(&mut self) -> Result<Reply, ClientError> {
+        let mut lines = Vec::new();
+
+        loop {
+            let mut line = String::new();
+            let read = timeout(REPLY_TIMEOUT, self.stream.read_line(&mut line))
+                .await
+                .map_err(|_| ClientError::Timeout)??;
+
+            if read == 0 {
+                return Err(ClientError::Protocol("Connection closed.".into()));
+            }
+
+            let line = line.trim_end();
+            if line.len() < 3 || !line.is_char_boundary(3) {
+                return Err(ClientError::Protocol(format!("Invalid reply '{}'.", line)));
+            }
+
+            let code = line[..3]
+                .parse::<u16>()
+                .map_err(|_| ClientError::Protocol(format!("Invalid reply '{}'.", line)))?;
+            let last = line.as_bytes().get(3) != Some(&b'-');
+            lines.push(line.get(4..).unwrap_or_default().to_string());
+
+            if last {
+                return Ok(Reply { code, lines });
+            }
+
+            if lines.len() >= MAX_REPLY_LINES {
+                return Err(ClientError::Protocol("Reply is too long.".into()));
+            }
+        }
+    }
+}
+
+/// Possible errors while talking to a remote server.
+#[derive(Error, Debug)]
+pub enum ClientError {
+    #[error("Connection error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("The server did not respond in time.")]
+    Timeout,
+    #[error("Protocol error: {0}")]
+    Protocol(String),
+    #[error("The server replied with {code}: {message}")]
+    Rejected { code: u16, message: String },
+}
+
+impl ClientError {
+    /// Check if the error is permanent, and retrying is useless.
+    pub fn permanent(&self) -> bool {
+        matches!(self, ClientError::Rejected { code, .. } if *code >= 500)
+    }
+}
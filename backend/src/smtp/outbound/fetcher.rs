@@ -0,0 +1,76 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use reqwest::{header, redirect, Client, StatusCode};
+use thiserror::Error;
+
+/// The maximum size of a policy file.
+const MAX_POLICY_SIZE: usize = 64 * 1024;
+
+/// Fetcher for MTA-STS policy files.
+/// This is a trait so that the delivery can be tested against a fake policy server.
+#[async_trait]
+pub trait PolicyFetcher: Send + Sync {
+    /// Fetch the policy file of a domain.
+    async fn fetch(&self, domain: &str) -> Result<String, FetchError>;
+}
+
+/// Fetcher retrieving the policy over HTTPS, as described in RFC 8461, section 3.3.
+pub struct HttpsFetcher {
+    client: Client,
+}
+
+impl HttpsFetcher {
+    /// Create a new fetcher.
+    /// Redirects are not followed, as required by the RFC.
+    pub fn new() -> Result<Self, FetchError> {
+        let client = Client::builder()
+            .redirect(redirect::Policy::none())
+            .timeout(Duration::from_secs(60))
+            .build()?;
+
+        Ok(HttpsFetcher { client })
+    }
+}
+
+#[async_trait]
+impl PolicyFetcher for HttpsFetcher {
+    async fn fetch(&self, domain: &str) -> Result<String, FetchError> {
+        let url = format!("https://mta-sts.{}/.well-known/mta-sts.txt", domain);
+        let res = self.client.get(&url).send().await?;
+
+        if res.status() != StatusCode::OK {
+            return Err(FetchError::Status(res.status().as_u16()));
+        }
+
+        let is_text = res
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|ct| ct.to_str().ok())
+            .map(|ct| ct.to_lowercase().starts_with("text/plain"))
+            .unwrap_or(false);
+        if !is_text {
+            return Err(FetchError::ContentType);
+        }
+
+        let body = res.bytes().await?;
+        if body.len() > MAX_POLICY_SIZE {
+            return Err(FetchError::TooLarge);
+        }
+
+        String::from_utf8(body.to_vec()).map_err(|_| FetchError::ContentType)
+    }
+}
+
+/// Possible errors while fetching a policy.
+#[derive(Error, Debug)]
+pub enum FetchError {
+    #[error("The policy could not be requested: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("The policy server responded with status {0}.")]
+    Status(u16),
+    #[error("The policy is not served as text.")]
+    ContentType,
+    #[error("The policy is too large.")]
+    TooLarge,
+}
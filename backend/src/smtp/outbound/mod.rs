@@ -0,0 +1,571 @@
+use std::{sync::Arc, time::Duration};
+
+use sqlx::{PgConnection, Pool, Postgres};
+use thiserror::Error;
+use tokio::{net::TcpStream, time::timeout};
+use tokio_rustls::TlsConnector;
+use webpki::DNSNameRef;
+
+use crate::logic::{
+    mta_sts::MtaStsPolicy,
+    outbound::{Outbound, OutboundStatus},
+};
+
+mod client;
+mod fetcher;
+mod resolver;
+mod tls;
+
+use client::{ClientError, SmtpClient};
+use fetcher::{HttpsFetcher, PolicyFetcher};
+use resolver::{DnsResolver, Exchanger, ResolveError, Resolver};
+use tls::TlsPolicy;
+
+/// Timeout for connecting to a remote server.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Time between checks of the queue.
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The amount of messages delivered in one batch.
+const BATCH_SIZE: i64 = 20;
+
+/// Seconds a claimed message is hidden from other workers.
+const LEASE: i32 = 15 * 60;
+
+/// Start the outbound delivery worker.
+/// This processes the queue forever, and only returns when it can't be started.
+pub async fn start(db: Pool<Postgres>, hostname: String) {
+    let resolver = match DnsResolver::new() {
+        Ok(resolver) => resolver,
+        Err(e) => {
+            error!("Failed to create DNS resolver: {}", e);
+            return;
+        }
+    };
+    let fetcher = match HttpsFetcher::new() {
+        Ok(fetcher) => fetcher,
+        Err(e) => {
+            error!("Failed to create MTA-STS fetcher: {}", e);
+            return;
+        }
+    };
+
+    let delivery = Delivery {
+        db,
+        hostname,
+        resolver: Arc::new(resolver),
+        fetcher: Arc::new(fetcher),
+    };
+
+    info!("Starting outbound delivery");
+
+    loop {
+        if let Err(e) = delivery.process_queue().await {
+            warn!("Failed to process the outbound queue: {}", e);
+        }
+
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Delivers queued messages to remote servers.
+/// MTA-STS (RFC 8461) and DANE (RFC 7672) policies are enforced for every destination.
+pub struct Delivery {
+    db: Pool<Postgres>,
+    hostname: String,
+    resolver: Arc<dyn Resolver>,
+    fetcher: Arc<dyn PolicyFetcher>,
+}
+
+impl Delivery {
+    /// Deliver all messages which are currently due.
+    pub async fn process_queue(&self) -> Result<(), sqlx::Error> {
+        loop {
+            let mut conn = self.db.acquire().await?;
+            let batch = Outbound::claim_due(&mut conn, BATCH_SIZE, LEASE).await?;
+
+            if batch.is_empty() {
+                return Ok(());
+            }
+
+            for mut message in batch {
+                match self.deliver(&mut conn, &message).await {
+                    Ok(()) => message.delivered(&mut conn).await?,
+                    Err(e) if e.permanent() => message.failed(&mut conn).await?,
+                    Err(_) => message.retry(&mut conn).await?,
+                }
+
+                if message.status != OutboundStatus::Queued {
+                    info!(
+                        "Outbound message {} to {} is {:?}.",
+                        message.id, message.domain, message.status
+                    );
+                }
+            }
+        }
+    }
+
+    /// Try to deliver a message to the exchangers of its domain, in order of preference.
+    /// Every attempt is logged together with the reason of failure.
+    async fn deliver(
+        &self,
+        conn: &mut PgConnection,
+        message: &Outbound,
+    ) -> Result<(), DeliveryError> {
+        let policy = self.mta_sts_policy(conn, &message.domain).await?;
+
+        let exchangers = match self.resolver.mx(&message.domain).await {
+            Ok(exchangers) => exchangers,
+            Err(e) => {
+                let err = DeliveryError::Resolve(e);
+                message
+                    .log_attempt(conn, None, false, &err.to_string())
+                    .await?;
+                return Err(err);
+            }
+        };
+
+        // A single exchanger with the root as host is a null MX, the domain does not accept mail (RFC 7505).
+        if exchangers.len() == 1 && exchangers[0].host.is_empty() {
+            let err = DeliveryError::NullMx;
+            message
+                .log_attempt(conn, None, false, &err.to_string())
+                .await?;
+            return Err(err);
+        }
+
+        let mut last_error = DeliveryError::NoExchangers;
+
+        for exchanger in exchangers {
+            let result = self.deliver_to(&exchanger, policy.as_ref(), message).await;
+
+            match result {
+                Ok(()) => {
+                    message
+                        .log_attempt(conn, Some(&exchanger.host), true, "Delivered.")
+                        .await?;
+                    return Ok(());
+                }
+                Err(e) => {
+                    warn!(
+                        "Delivery of {} to {} failed: {}",
+                        message.id, exchanger.host, e
+                    );
+                    message
+                        .log_attempt(conn, Some(&exchanger.host), false, &e.to_string())
+                        .await?;
+
+                    // A permanent rejection won't be different on the other exchangers.
+                    if e.permanent() {
+                        return Err(e);
+                    }
+
+                    last_error = e;
+                }
+            }
+        }
+
+        Err(last_error)
+    }
+
+    /// Deliver a message to a single exchanger, enforcing the TLS requirements.
+    async fn deliver_to(
+        &self,
+        exchanger: &Exchanger,
+        policy: Option<&MtaStsPolicy>,
+        message: &Outbound,
+    ) -> Result<(), DeliveryError> {
+        // Exchangers not listed in an enforced policy are never used.
+        if let Some(policy) = policy {
+            if policy.enforced() && !policy.matches(&exchanger.host) {
+                return Err(DeliveryError::PolicyMismatch(exchanger.host.clone()));
+            }
+        }
+
+        let tls_policy = self.tls_policy(exchanger, policy).await?;
+
+        let stream = timeout(
+            CONNECT_TIMEOUT,
+            TcpStream::connect((exchanger.host.as_str(), 25)),
+        )
+        .await
+        .map_err(|_| ClientError::Timeout)?
+        .map_err(ClientError::Io)?;
+
+        let mut client = SmtpClient::new(stream);
+        client.expect(220).await?;
+        let ehlo = client
+            .command(&format!("EHLO {}", self.hostname), 250)
+            .await?;
+
+        if !ehlo.has_keyword("STARTTLS") {
+            if tls_policy.required() {
+                return Err(DeliveryError::StartTlsUnavailable);
+            }
+
+            return self.transaction(client, message).await;
+        }
+
+        client.command("STARTTLS", 220).await?;
+
+        let name = DNSNameRef::try_from_ascii_str(&exchanger.host)
+            .map_err(|_| DeliveryError::Tls("Invalid exchanger host name.".into()))?;
+        let stream = TlsConnector::from(tls_policy.config())
+            .connect(name, client.into_inner())
+            .await
+            .map_err(|e| DeliveryError::Tls(e.to_string()))?;
+
+        let mut client = SmtpClient::new(stream);
+        client
+            .command(&format!("EHLO {}", self.hostname), 250)
+            .await?;
+
+        self.transaction(client, message).await
+    }
+
+    /// Decide how the certificate of an exchanger is validated.
+    /// DANE takes precedence over MTA-STS when both are available.
+    /// Failing TLSA lookups defer the delivery, as they may hide the records.
+    async fn tls_policy(
+        &self,
+        exchanger: &Exchanger,
+        policy: Option<&MtaStsPolicy>,
+    ) -> Result<TlsPolicy, DeliveryError> {
+        Ok(match self.resolver.tlsa(&exchanger.host).await? {
+            Some(records) => TlsPolicy::Dane(records),
+            None if policy.map(|p| p.enforced()).unwrap_or(false) => TlsPolicy::Pkix,
+            None => TlsPolicy::Opportunistic,
+        })
+    }
+
+    /// Run the mail transaction on an established session.
+    async fn transaction<S>(
+        &self,
+        mut client: SmtpClient<S>,
+        message: &Outbound,
+    ) -> Result<(), DeliveryError>
+    where
+        S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+    {
+        client
+            .command(&format!("MAIL FROM:<{}>", message.sender), 250)
+            .await?;
+
+        for recipient in &message.recipients {
+            client
+                .command(&format!("RCPT TO:<{}>", recipient), 250)
+                .await?;
+        }
+
+        client.command("DATA", 354).await?;
+        client.data(&message.data).await?;
+
+        // The message is accepted, a failing QUIT does not matter anymore.
+        let _ = client.command("QUIT", 221).await;
+
+        Ok(())
+    }
+
+    /// Discover the MTA-STS policy of a domain.
+    /// The cached policy is enforced until it expires, unless a new one could be fetched and stored.
+    /// Without access to the cache the delivery is deferred, as it might hold a policy.
+    async fn mta_sts_policy(
+        &self,
+        conn: &mut PgConnection,
+        domain: &str,
+    ) -> Result<Option<MtaStsPolicy>, DeliveryError> {
+        let cached = MtaStsPolicy::find_cached(conn, domain).await?;
+
+        let (id, text) = match self.fetch_policy(domain, cached.as_ref()).await {
+            Some(fetched) => fetched,
+            None => return Ok(cached),
+        };
+
+        match MtaStsPolicy::store(conn, domain, &id, &text).await {
+            Ok(policy) => Ok(Some(policy)),
+            Err(e) => {
+                warn!("Failed to store the MTA-STS policy of {}: {}", domain, e);
+                Ok(cached)
+            }
+        }
+    }
+
+    /// Fetch the MTA-STS policy of a domain, returning its ID and text.
+    /// A new policy is only fetched when the ID in DNS differs from the cached one.
+    /// Failures to refresh are logged, and None keeps the cached policy in effect.
+    async fn fetch_policy(
+        &self,
+        domain: &str,
+        cached: Option<&MtaStsPolicy>,
+    ) -> Option<(String, String)> {
+        let records = match self.resolver.txt(&format!("_mta-sts.{}", domain)).await {
+            Ok(records) => records,
+            Err(e) => {
+                warn!("Failed to resolve the MTA-STS record of {}: {}", domain, e);
+                return None;
+            }
+        };
+
+        let id = records
+            .iter()
+            .filter_map(|r| MtaStsPolicy::parse_record(r))
+            .next()?;
+
+        if cached.map(|c| c.policy_id == id).unwrap_or(false) {
+            return None;
+        }
+
+        match self.fetcher.fetch(domain).await {
+            Ok(text) => Some((id, text)),
+            Err(e) => {
+                warn!("Failed to fetch the MTA-STS policy of {}: {}", domain, e);
+                None
+            }
+        }
+    }
+}
+
+/// Possible reasons for a delivery to fail.
+#[derive(Error, Debug)]
+pub enum DeliveryError {
+    #[error("{0}")]
+    Resolve(#[from] ResolveError),
+    #[error("The domain does not accept mail.")]
+    NullMx,
+    #[error("No exchangers available.")]
+    NoExchangers,
+    #[error("MX host {0} is not allowed by the MTA-STS policy.")]
+    PolicyMismatch(String),
+    #[error("The server does not offer STARTTLS, but TLS is required.")]
+    StartTlsUnavailable,
+    #[error("TLS negotiation failed: {0}")]
+    Tls(String),
+    #[error("{0}")]
+    Client(#[from] ClientError),
+    #[error("An internal database error occured.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl DeliveryError {
+    /// Check if the error is permanent, and retrying is useless.
+    pub fn permanent(&self) -> bool {
+        match self {
+            DeliveryError::NullMx => true,
+            DeliveryError::Client(e) => e.permanent(),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use sqlx::postgres::PgPoolOptions;
+    use time::{Duration as TimeDuration, OffsetDateTime};
+
+    use super::*;
+    use crate::logic::domain::MtaStsMode;
+    use fetcher::FetchError;
+    use resolver::TlsaRecord;
+
+    /// A DNS serving fixed answers, or failing.
+    struct FakeResolver {
+        txt: Result<Vec<String>, ()>,
+        tlsa: Result<Option<Vec<TlsaRecord>>, ()>,
+    }
+
+    #[async_trait]
+    impl Resolver for FakeResolver {
+        async fn mx(&self, domain: &str) -> Result<Vec<Exchanger>, ResolveError> {
+            Ok(vec![Exchanger {
+                preference: 10,
+                host: format!("mx.{}", domain),
+            }])
+        }
+
+        async fn txt(&self, _: &str) -> Result<Vec<String>, ResolveError> {
+            self.txt
+                .clone()
+                .map_err(|_| ResolveError::Failed("SERVFAIL".into()))
+        }
+
+        async fn tlsa(&self, host: &str) -> Result<Option<Vec<TlsaRecord>>, ResolveError> {
+            self.tlsa
+                .clone()
+                .map_err(|_| ResolveError::Insecure(host.to_string()))
+        }
+    }
+
+    /// A policy server serving a fixed policy, or failing.
+    struct FakeFetcher {
+        policy: Option<&'static str>,
+    }
+
+    #[async_trait]
+    impl PolicyFetcher for FakeFetcher {
+        async fn fetch(&self, _: &str) -> Result<String, FetchError> {
+            self.policy
+                .map(|p| p.to_string())
+                .ok_or(FetchError::Status(503))
+        }
+    }
+
+    const POLICY: &str =
+        "version: STSv1\r\nmode: enforce\r\nmx: mx.example.com\r\nmax_age: 86400\r\n";
+
+    fn fake_delivery(resolver: FakeResolver, fetcher: FakeFetcher) -> Delivery {
+        // The pool never connects, none of the tested steps use the database.
+        let db = PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/nexium")
+            .unwrap();
+
+        Delivery {
+            db,
+            hostname: "mail.nexium.app".into(),
+            resolver: Arc::new(resolver),
+            fetcher: Arc::new(fetcher),
+        }
+    }
+
+    fn resolver(txt: Result<Vec<String>, ()>) -> FakeResolver {
+        FakeResolver {
+            txt,
+            tlsa: Ok(None),
+        }
+    }
+
+    fn cached(id: &str, mode: MtaStsMode) -> MtaStsPolicy {
+        MtaStsPolicy {
+            policy_id: id.into(),
+            mode,
+            mx: vec!["mx.example.com".into()],
+            expires: OffsetDateTime::now_utc() + TimeDuration::days(1),
+        }
+    }
+
+    fn exchanger() -> Exchanger {
+        Exchanger {
+            preference: 10,
+            host: "mx.example.com".into(),
+        }
+    }
+
+    fn record(id: &str) -> Result<Vec<String>, ()> {
+        Ok(vec![format!("v=STSv1; id={};", id)])
+    }
+
+    #[tokio::test]
+    async fn new_policy_is_fetched() {
+        let delivery = fake_delivery(
+            resolver(record("2")),
+            FakeFetcher {
+                policy: Some(POLICY),
+            },
+        );
+        let cached = cached("1", MtaStsMode::Enforce);
+
+        let fetched = delivery.fetch_policy("example.com", Some(&cached)).await;
+        assert_eq!(fetched, Some(("2".into(), POLICY.into())));
+
+        let fetched = delivery.fetch_policy("example.com", None).await;
+        assert_eq!(fetched, Some(("2".into(), POLICY.into())));
+    }
+
+    #[tokio::test]
+    async fn unchanged_policy_is_not_fetched() {
+        let delivery = fake_delivery(resolver(record("1")), FakeFetcher { policy: None });
+        let cached = cached("1", MtaStsMode::Enforce);
+
+        assert_eq!(
+            delivery.fetch_policy("example.com", Some(&cached)).await,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn cached_policy_survives_failures() {
+        let cached = cached("1", MtaStsMode::Enforce);
+
+        // The policy server is down.
+        let delivery = fake_delivery(resolver(record("2")), FakeFetcher { policy: None });
+        assert_eq!(
+            delivery.fetch_policy("example.com", Some(&cached)).await,
+            None
+        );
+
+        // The DNS is down.
+        let delivery = fake_delivery(
+            resolver(Err(())),
+            FakeFetcher {
+                policy: Some(POLICY),
+            },
+        );
+        assert_eq!(
+            delivery.fetch_policy("example.com", Some(&cached)).await,
+            None
+        );
+
+        // The record was removed, which does not end a policy before it expires.
+        let delivery = fake_delivery(
+            resolver(Ok(vec!["v=spf1 -all".into()])),
+            FakeFetcher {
+                policy: Some(POLICY),
+            },
+        );
+        assert_eq!(
+            delivery.fetch_policy("example.com", Some(&cached)).await,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_tlsa_lookup_defers() {
+        let delivery = fake_delivery(
+            FakeResolver {
+                txt: Ok(Vec::new()),
+                tlsa: Err(()),
+            },
+            FakeFetcher { policy: None },
+        );
+
+        let err = delivery
+            .tls_policy(&exchanger(), None)
+            .await
+            .expect_err("A failed TLSA lookup must not disable DANE.");
+        assert!(matches!(err, DeliveryError::Resolve(_)));
+        assert!(!err.permanent());
+    }
+
+    #[tokio::test]
+    async fn tls_policy_precedence() {
+        let record = TlsaRecord {
+            usage: 3,
+            selector: 1,
+            matching: 1,
+            data: vec![0; 32],
+        };
+        let delivery = fake_delivery(
+            FakeResolver {
+                txt: Ok(Vec::new()),
+                tlsa: Ok(Some(vec![record])),
+            },
+            FakeFetcher { policy: None },
+        );
+        let enforced = cached("1", MtaStsMode::Enforce);
+
+        let policy = delivery.tls_policy(&exchanger(), Some(&enforced)).await;
+        assert!(matches!(policy, Ok(TlsPolicy::Dane(_))));
+
+        let delivery = fake_delivery(resolver(Ok(Vec::new())), FakeFetcher { policy: None });
+        let policy = delivery.tls_policy(&exchanger(), Some(&enforced)).await;
+        assert!(matches!(policy, Ok(TlsPolicy::Pkix)));
+
+        let testing = cached("1", MtaStsMode::Testing);
+        let policy = delivery.tls_policy(&exchanger(), Some(&testing)).await;
+        assert!(matches!(policy, Ok(TlsPolicy::Opportunistic)));
+
+        let policy = delivery.tls_policy(&exchanger(), None).await;
+        assert!(matches!(policy, Ok(TlsPolicy::Opportunistic)));
+    }
+}
@@ -0,0 +1,191 @@
+use async_trait::async_trait;
+use thiserror::Error;
+use trust_dns_resolver::{
+    config::ResolverOpts,
+    error::ResolveErrorKind,
+    proto::{
+        error::ProtoErrorKind,
+        rr::{dnssec::rdata::DNSSECRecordType, RecordType},
+        xfer::DnsRequestOptions,
+    },
+    system_conf, TokioAsyncResolver,
+};
+
+/// A mail exchanger of a domain.
+#[derive(Debug, Clone)]
+pub struct Exchanger {
+    pub preference: u16,
+    pub host: String,
+}
+
+/// A TLSA record, as defined in RFC 6698.
+#[derive(Debug, Clone)]
+pub struct TlsaRecord {
+    pub usage: u8,
+    pub selector: u8,
+    pub matching: u8,
+    pub data: Vec<u8>,
+}
+
+/// Resolver for the DNS records required for outbound delivery.
+/// This is a trait so that the delivery can be tested against a fake DNS.
+#[async_trait]
+pub trait Resolver: Send + Sync {
+    /// Resolve the mail exchangers of a domain, sorted by preference.
+    /// Falls back to the domain itself when no MX records exist.
+    async fn mx(&self, domain: &str) -> Result<Vec<Exchanger>, ResolveError>;
+
+    /// Resolve the TXT records of a name.
+    async fn txt(&self, name: &str) -> Result<Vec<String>, ResolveError>;
+
+    /// Resolve the TLSA records of a SMTP server.
+    /// Only returns records which were validated with DNSSEC.
+    /// None means DANE is not available: the records are denied, or the zone of the host is not signed.
+    /// Every other failure is an error, as a forged answer could otherwise disable DANE.
+    async fn tlsa(&self, host: &str) -> Result<Option<Vec<TlsaRecord>>, ResolveError>;
+}
+
+/// Resolver using the system DNS configuration.
+pub struct DnsResolver {
+    plain: TokioAsyncResolver,
+    secure: TokioAsyncResolver,
+}
+
+impl DnsResolver {
+    /// Create a new resolver from the system configuration.
+    /// A second, validating resolver is used for the DNSSEC protected lookups.
+    pub fn new() -> Result<Self, ResolveError> {
+        let (config, opts) = system_conf::read_system_conf().map_err(ResolveError::from)?;
+
+        let plain = TokioAsyncResolver::tokio(config.clone(), opts)?;
+        let secure = TokioAsyncResolver::tokio(
+            config,
+            ResolverOpts {
+                validate: true,
+                ..opts
+            },
+        )?;
+
+        Ok(DnsResolver { plain, secure })
+    }
+
+    /// Check if the host or one of its parent domains publishes DNSKEY records.
+    /// Unsigned answers for names in a signed zone were stripped of their signatures.
+    /// The top-level domain is not checked, as nearly all of them are signed.
+    async fn signed(&self, host: &str) -> Result<bool, ResolveError> {
+        let labels: Vec<&str> = host.split('.').collect();
+
+        for i in 0..labels.len().saturating_sub(1) {
+            let name = format!("{}.", labels[i..].join("."));
+            let kind = RecordType::DNSSEC(DNSSECRecordType::DNSKEY);
+
+            match self
+                .plain
+                .lookup(name, kind, DnsRequestOptions::default())
+                .await
+            {
+                Ok(lookup) if lookup.iter().next().is_some() => return Ok(true),
+                Ok(_) => {}
+                Err(e) => match e.kind() {
+                    ResolveErrorKind::NoRecordsFound { .. } => {}
+                    _ => return Err(e.into()),
+                },
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+#[async_trait]
+impl Resolver for DnsResolver {
+    async fn mx(&self, domain: &str) -> Result<Vec<Exchanger>, ResolveError> {
+        let lookup = match self.plain.mx_lookup(format!("{}.", domain)).await {
+            Ok(lookup) => lookup,
+            Err(e) => match e.kind() {
+                // Without MX records the domain itself is the implicit exchanger (RFC 5321, 5.1).
+                ResolveErrorKind::NoRecordsFound { .. } => {
+                    return Ok(vec![Exchanger {
+                        preference: 0,
+                        host: domain.to_string(),
+                    }])
+                }
+                _ => return Err(e.into()),
+            },
+        };
+
+        let mut exchangers: Vec<Exchanger> = lookup
+            .iter()
+            .map(|mx| Exchanger {
+                preference: mx.preference(),
+                host: mx.exchange().to_utf8().trim_end_matches('.').to_lowercase(),
+            })
+            .collect();
+        exchangers.sort_by_key(|mx| mx.preference);
+
+        Ok(exchangers)
+    }
+
+    async fn txt(&self, name: &str) -> Result<Vec<String>, ResolveError> {
+        match self.plain.txt_lookup(format!("{}.", name)).await {
+            Ok(lookup) => Ok(lookup.iter().map(|txt| txt.to_string()).collect()),
+            Err(e) => match e.kind() {
+                ResolveErrorKind::NoRecordsFound { .. } => Ok(Vec::new()),
+                _ => Err(e.into()),
+            },
+        }
+    }
+
+    async fn tlsa(&self, host: &str) -> Result<Option<Vec<TlsaRecord>>, ResolveError> {
+        let lookup = match self.secure.tlsa_lookup(format!("_25._tcp.{}.", host)).await {
+            Ok(lookup) => lookup,
+            Err(e) => match e.kind() {
+                // The denial was validated, the host has no TLSA records.
+                ResolveErrorKind::NoRecordsFound { .. } => return Ok(None),
+                // Answers from unsigned zones carry no signatures, which is only fine if the zone is really unsigned.
+                ResolveErrorKind::Proto(proto)
+                    if matches!(proto.kind(), ProtoErrorKind::RrsigsNotPresent { .. }) =>
+                {
+                    return match self.signed(host).await? {
+                        false => Ok(None),
+                        true => Err(ResolveError::Insecure(host.to_string())),
+                    };
+                }
+                _ => return Err(e.into()),
+            },
+        };
+
+        let records: Vec<TlsaRecord> = lookup
+            .iter()
+            .map(|tlsa| TlsaRecord {
+                usage: tlsa.cert_usage().into(),
+                selector: tlsa.selector().into(),
+                matching: tlsa.matching().into(),
+                data: tlsa.cert_data().to_vec(),
+            })
+            .collect();
+
+        Ok(Some(records).filter(|r| !r.is_empty()))
+    }
+}
+
+/// Possible errors while resolving.
+#[derive(Error, Debug)]
+pub enum ResolveError {
+    #[error("DNS resolution failed: {0}")]
+    Failed(String),
+    #[error("The DNSSEC signatures of {0} are missing.")]
+    Insecure(String),
+}
+
+impl From<trust_dns_resolver::error::ResolveError> for ResolveError {
+    fn from(err: trust_dns_resolver::error::ResolveError) -> Self {
+        ResolveError::Failed(err.to_string())
+    }
+}
+
+impl From<std::io::Error> for ResolveError {
+    fn from(err: std::io::Error) -> Self {
+        ResolveError::Failed(err.to_string())
+    }
+}
@@ -0,0 +1,177 @@
+use std::{sync::Arc, time::SystemTime};
+
+use rustls::{
+    Certificate, ClientConfig, RootCertStore, ServerCertVerified, ServerCertVerifier, TLSError,
+};
+use sha2::{Digest, Sha256, Sha512};
+use webpki::{DNSNameRef, EndEntityCert, TLSServerTrustAnchors};
+
+use super::resolver::TlsaRecord;
+
+/// Signature algorithms accepted when validating a chain against a DANE trust anchor.
+static SIGNATURE_ALGORITHMS: &[&webpki::SignatureAlgorithm] = &[
+    &webpki::ECDSA_P256_SHA256,
+    &webpki::ECDSA_P256_SHA384,
+    &webpki::ECDSA_P384_SHA256,
+    &webpki::ECDSA_P384_SHA384,
+    &webpki::ED25519,
+    &webpki::RSA_PSS_2048_8192_SHA256_LEGACY_KEY,
+    &webpki::RSA_PSS_2048_8192_SHA384_LEGACY_KEY,
+    &webpki::RSA_PSS_2048_8192_SHA512_LEGACY_KEY,
+    &webpki::RSA_PKCS1_2048_8192_SHA256,
+    &webpki::RSA_PKCS1_2048_8192_SHA384,
+    &webpki::RSA_PKCS1_2048_8192_SHA512,
+    &webpki::RSA_PKCS1_3072_8192_SHA384,
+];
+
+/// The way the certificate of a remote server is validated.
+#[derive(Debug, Clone)]
+pub enum TlsPolicy {
+    /// Encrypt if possible, but accept any certificate.
+    Opportunistic,
+    /// Require a certificate valid for the host according to the Web PKI, used by MTA-STS.
+    Pkix,
+    /// Require a certificate matching the DNSSEC validated TLSA records (RFC 7672).
+    Dane(Vec<TlsaRecord>),
+}
+
+impl TlsPolicy {
+    /// Check if this policy forbids falling back to plaintext.
+    pub fn required(&self) -> bool {
+        !matches!(self, TlsPolicy::Opportunistic)
+    }
+
+    /// Create a TLS client configuration which enforces this policy.
+    pub fn config(&self) -> Arc<ClientConfig> {
+        let mut config = ClientConfig::new();
+
+        match self {
+            TlsPolicy::Opportunistic => config
+                .dangerous()
+                .set_certificate_verifier(Arc::new(OpportunisticVerifier)),
+            TlsPolicy::Pkix => config
+                .root_store
+                .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS),
+            TlsPolicy::Dane(records) => {
+                config
+                    .dangerous()
+                    .set_certificate_verifier(Arc::new(DaneVerifier {
+                        records: records.clone(),
+                    }))
+            }
+        }
+
+        Arc::new(config)
+    }
+}
+
+/// Verifier accepting any certificate.
+struct OpportunisticVerifier;
+
+impl ServerCertVerifier for OpportunisticVerifier {
+    fn verify_server_cert(
+        &self,
+        _roots: &RootCertStore,
+        _presented_certs: &[Certificate],
+        _dns_name: DNSNameRef,
+        _ocsp_response: &[u8],
+    ) -> Result<ServerCertVerified, TLSError> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+/// Verifier validating the certificate chain against TLSA records.
+/// Only the DANE-TA(2) and DANE-EE(3) usages are supported, as recommended for SMTP by RFC 7672.
+struct DaneVerifier {
+    records: Vec<TlsaRecord>,
+}
+
+impl ServerCertVerifier for DaneVerifier {
+    fn verify_server_cert(
+        &self,
+        _roots: &RootCertStore,
+        presented_certs: &[Certificate],
+        dns_name: DNSNameRef,
+        _ocsp_response: &[u8],
+    ) -> Result<ServerCertVerified, TLSError> {
+        let leaf = presented_certs
+            .first()
+            .ok_or(TLSError::NoCertificatesPresented)?;
+
+        for record in &self.records {
+            match record.usage {
+                // DANE-EE, the leaf certificate has to match, its name and validity are ignored.
+                3 if matches(record, &leaf.0) => return Ok(ServerCertVerified::assertion()),
+                // DANE-TA, a certificate in the chain is the trust anchor for the leaf.
+                2 => {
+                    for anchor in presented_certs.iter().skip(1) {
+                        if matches(record, &anchor.0)
+                            && verify_with_anchor(leaf, presented_certs, anchor, dns_name)
+                        {
+                            return Ok(ServerCertVerified::assertion());
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Err(TLSError::General(
+            "Certificate does not match the TLSA records.".into(),
+        ))
+    }
+}
+
+/// Check if a certificate matches a TLSA record.
+fn matches(record: &TlsaRecord, cert: &[u8]) -> bool {
+    let selected = match record.selector {
+        // The full certificate.
+        0 => cert.to_vec(),
+        // The subject public key info.
+        1 => match x509_parser::parse_x509_certificate(cert) {
+            Ok((_, parsed)) => parsed.tbs_certificate.subject_pki.raw.to_vec(),
+            Err(_) => return false,
+        },
+        _ => return false,
+    };
+
+    let digest = match record.matching {
+        0 => selected,
+        1 => Sha256::digest(&selected).to_vec(),
+        2 => Sha512::digest(&selected).to_vec(),
+        _ => return false,
+    };
+
+    digest == record.data
+}
+
+/// Validate the leaf certificate for the host, using the given certificate as the only trust anchor.
+fn verify_with_anchor(
+    leaf: &Certificate,
+    chain: &[Certificate],
+    anchor: &Certificate,
+    dns_name: DNSNameRef,
+) -> bool {
+    let anchor = match webpki::trust_anchor_util::cert_der_as_trust_anchor(&anchor.0) {
+        Ok(anchor) => anchor,
+        Err(_) => return false,
+    };
+    let leaf = match EndEntityCert::from(&leaf.0) {
+        Ok(leaf) => leaf,
+        Err(_) => return false,
+    };
+    let time = match webpki::Time::try_from(SystemTime::now()) {
+        Ok(time) => time,
+        Err(_) => return false,
+    };
+    let intermediates: Vec<&[u8]> = chain.iter().skip(1).map(|c| c.0.as_slice()).collect();
+
+    leaf.verify_is_valid_tls_server_cert(
+        SIGNATURE_ALGORITHMS,
+        &TLSServerTrustAnchors(&[anchor]),
+        &intermediates,
+        time,
+    )
+    .is_ok()
+        && leaf.verify_is_valid_for_dns_name(dns_name).is_ok()
+}
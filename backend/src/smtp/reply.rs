@@ -0,0 +1,56 @@
+/// All replies sent by the server.
+#[derive(Debug, PartialEq)]
+pub enum Reply {
+    Greeting(String),
+    Helo(String),
+    /// The EHLO reply, listing the supported extensions.
+    Ehlo(String, u64),
+    Goodbye,
+    /// The client was idle for too long, the connection is closed.
+    Timeout,
+    Ok,
+    StartData,
+    LocalError,
+    TooManyRecipients,
+    MailboxFull,
+    SyntaxError,
+    LineTooLong,
+    OutOfSequence,
+    RecipientNotLocal,
+    MessageTooLarge,
+    QuotaExceeded,
+    TransactionFailed,
+}
+
+impl Reply {
+    /// The reply as sent on the wire.
+    pub fn to_response(&self) -> String {
+        match self {
+            Reply::Greeting(name) => format!("220 {} ESMTP\r\n", name),
+            Reply::Helo(name) => format!("250 {}\r\n", name),
+            Reply::Ehlo(name, size) => format!(
+                "250-{}\r\n250-PIPELINING\r\n250-8BITMIME\r\n250 SIZE {}\r\n",
+                name, size
+            ),
+            Reply::Goodbye => "221 Goodbye!\r\n".into(),
+            Reply::Timeout => "421 Timeout exceeded, closing connection\r\n".into(),
+            Reply::Ok => "250 Ok\r\n".into(),
+            Reply::StartData => "354 Go ahead, end with <CRLF>.<CRLF>\r\n".into(),
+            Reply::LocalError => "451 Local error in processing\r\n".into(),
+            Reply::TooManyRecipients => "452 Too many recipients\r\n".into(),
+            Reply::MailboxFull => "452 Mailbox full\r\n".into(),
+            Reply::SyntaxError => "500 Syntax error\r\n".into(),
+            Reply::LineTooLong => "500 Line too long\r\n".into(),
+            Reply::OutOfSequence => "503 Command out of sequence\r\n".into(),
+            Reply::RecipientNotLocal => "550 User not local\r\n".into(),
+            Reply::MessageTooLarge => "552 Message exceeds fixed maximum message size\r\n".into(),
+            Reply::QuotaExceeded => "552 Message exceeds storage allocation\r\n".into(),
+            Reply::TransactionFailed => "554 Transaction failed\r\n".into(),
+        }
+    }
+
+    /// Check if the reply is a positive completion.
+    pub fn is_positive(&self) -> bool {
+        self.to_response().starts_with('2')
+    }
+}
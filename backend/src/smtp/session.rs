@@ -0,0 +1,491 @@
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    time::timeout,
+};
+
+use super::{
+    command::{self, Command, Mailbox},
+    data::DataDecoder,
+    reply::Reply,
+};
+use crate::{
+    proxy,
+    storage::{Spool, Spooled},
+};
+
+/// The maximum amount of recipients in one transaction.
+const MAX_RECIPIENTS: usize = 100;
+
+/// The maximum size of a message in bytes.
+const MAX_MESSAGE_SIZE: u64 = 64 * 1024 * 1024;
+
+/// The maximum length of a command line, generous compared to the 512 of RFC 5321.
+const MAX_LINE_LENGTH: usize = 4096;
+
+/// Time the client has to send the next command, as recommended by RFC 5321, section 4.5.3.2.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Time the client has to send the next part of the content.
+const DATA_TIMEOUT: Duration = Duration::from_secs(3 * 60);
+
+/// The size of the read buffer, which bounds the memory used for incoming content.
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Handler for the transactions of a session.
+#[async_trait]
+pub trait Handler: Send + Sync {
+    /// Check if a recipient is accepted, for a message of the size declared by the client.
+    async fn recipient(&self, recipient: &Mailbox, size: Option<u64>) -> Reply;
+    /// Store a received message.
+    async fn save(&self, envelope: &Envelope, message: &Spooled) -> Reply;
+}
+
+/// The envelope of a mail transaction.
+#[derive(Debug)]
+pub struct Envelope {
+    /// The sender, None for the null sender of bounces.
+    pub from: Option<Mailbox>,
+    pub recipients: Vec<Mailbox>,
+    /// The size of the message declared with the SIZE extension.
+    pub size: Option<u64>,
+}
+
+/// A single SMTP connection.
+/// The client address is the one of the original client, when relayed by a trusted proxy.
+pub struct SmtpSession {
+    stream: TcpStream,
+    client: SocketAddr,
+    server_name: String,
+    handler: Arc<dyn Handler>,
+    /// The client identified itself with HELO or EHLO.
+    greeted: bool,
+    envelope: Option<Envelope>,
+    data: Option<DataState>,
+}
+
+/// The state while receiving message content.
+struct DataState {
+    decoder: DataDecoder,
+    /// The spool, None when the content is discarded.
+    spool: Option<Spool>,
+    /// The reply to send when the content was discarded.
+    failure: Reply,
+}
+
+impl SmtpSession {
+    /// Create a new session.
+    pub fn new(
+        stream: TcpStream,
+        client: SocketAddr,
+        server_name: String,
+        handler: Arc<dyn Handler>,
+    ) -> Self {
+        SmtpSession {
+            stream,
+            client,
+            server_name,
+            handler,
+            greeted: false,
+            envelope: None,
+            data: None,
+        }
+    }
+
+    /// Handle the session, reading and writing.
+    /// Returns when the connection should be dropped.
+    pub async fn handle(mut self) {
+        let mut buff = vec![0; BUFFER_SIZE];
+        let mut line = Vec::with_capacity(128);
+        let mut first = true;
+
+        debug!("Accepted new client {}.", self.client);
+
+        if self
+            .send(&Reply::Greeting(self.server_name.clone()))
+            .await
+            .is_err()
+        {
+            return;
+        }
+
+        loop {
+            let idle = match self.data {
+                Some(_) => DATA_TIMEOUT,
+                None => COMMAND_TIMEOUT,
+            };
+
+            let n = match timeout(idle, self.stream.read(&mut buff)).await {
+                Ok(Ok(0)) => break,
+                Ok(Ok(n)) => n,
+                Ok(Err(e)) => {
+                    warn!("Received error while reading socket: {}.", e);
+                    break;
+                }
+                Err(_) => {
+                    debug!("Client {} timed out.", self.client);
+                    let _ = self.send(&Reply::Timeout).await;
+                    break;
+                }
+            };
+
+            // Headers from trusted proxies are consumed before the session starts.
+            if first && proxy::is_header(&buff[..n]) {
+                warn!("Rejected PROXY header from untrusted peer {}.", self.client);
+                break;
+            }
+            first = false;
+
+            if self.input(&buff[..n], &mut line).await {
+                debug!("Server indicated to quit.");
+                break;
+            }
+        }
+    }
+
+    /// Handle new incoming input, which can contain both commands and content.
+    /// Returns true when the connection should be closed.
+    async fn input(&mut self, mut input: &[u8], line: &mut Vec<u8>) -> bool {
+        while !input.is_empty() {
+            if self.data.is_some() {
+                match self.receive_data(input).await {
+                    Ok(Some(consumed)) => input = &input[consumed..],
+                    Ok(None) => return false,
+                    Err(_) => return true,
+                }
+
+                continue;
+            }
+
+            let end = match input.iter().position(|b| *b == b'\n') {
+                Some(end) => end,
+                None => {
+                    line.extend_from_slice(input);
+
+                    if line.len() > MAX_LINE_LENGTH {
+                        let _ = self.send(&Reply::LineTooLong).await;
+                        return true;
+                    }
+
+                    return false;
+                }
+            };
+
+            line.extend_from_slice(&input[..end]);
+            input = &input[end + 1..];
+
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+
+            let reply = match std::str::from_utf8(line) {
+                Ok(_) if line.len() > MAX_LINE_LENGTH => Reply::LineTooLong,
+                Ok(text) => match command::parse(text) {
+                    Some(c) => self.process_command(c).await,
+                    None => Reply::SyntaxError,
+                },
+                Err(_) => Reply::SyntaxError,
+            };
+            line.clear();
+
+            if self.send(&reply).await.is_err() || reply == Reply::Goodbye {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Pass content to the spool, without keeping it in memory.
+    /// Returns the amount of input consumed when the end of the content was reached.
+    async fn receive_data(&mut self, input: &[u8]) -> Result<Option<usize>, std::io::Error> {
+        let state = self
+            .data
+            .as_mut()
+            .expect("Only called while receiving data.");
+
+        let mut content = Vec::with_capacity(input.len());
+        let end = state.decoder.decode(input, &mut content);
+
+        if let Some(spool) = &mut state.spool {
+            if spool.size() + content.len() as u64 > MAX_MESSAGE_SIZE {
+                // The rest of the content is still read, to stay in sync with the client.
+                state.spool = None;
+                state.failure = Reply::MessageTooLarge;
+            } else if let Err(e) = spool.write(&content).await {
+                error!("Failed to write to the spool: {}", e);
+                state.spool = None;
+                state.failure = Reply::LocalError;
+            }
+        }
+
+        let consumed = match end {
+            Some(consumed) => consumed,
+            None => return Ok(None),
+        };
+
+        let reply = self.finish_data().await;
+        self.send(&reply).await?;
+
+        Ok(Some(consumed))
+    }
+
+    /// Store the received message, and end the transaction.
+    async fn finish_data(&mut self) -> Reply {
+        let state = self.data.take().expect("Only called while receiving data.");
+        let envelope = self.envelope.take().expect("Content requires an envelope.");
+
+        let spool = match state.spool {
+            Some(spool) => spool,
+            None => return state.failure,
+        };
+
+        let message = match spool.finish().await {
+            Ok(message) => message,
+            Err(e) => {
+                error!("Failed to finish the spool: {}", e);
+                return Reply::LocalError;
+            }
+        };
+
+        let reply = self.handler.save(&envelope, &message).await;
+
+        info!(
+            "Received message of {} bytes from {} for {} recipients: {:?}.",
+            message.size,
+            self.client,
+            envelope.recipients.len(),
+            reply
+        );
+
+        reply
+    }
+
+    /// Process a single command.
+    async fn process_command(&mut self, command: Command) -> Reply {
+        debug!("Processing command {:?}.", command);
+
+        match command {
+            Command::Helo(_) => self.process_helo(false),
+            Command::Ehlo(_) => self.process_helo(true),
+            Command::Mail { from, size } => self.process_mail(from, size),
+            Command::Rcpt(recipient) => self.process_rcpt(recipient).await,
+            Command::Data => self.process_data().await,
+            Command::Rset => {
+                self.envelope = None;
+                Reply::Ok
+            }
+            Command::Noop => Reply::Ok,
+            Command::Quit => Reply::Goodbye,
+        }
+    }
+
+    fn process_helo(&mut self, extended: bool) -> Reply {
+        self.greeted = true;
+        self.envelope = None;
+
+        match extended {
+            true => Reply::Ehlo(self.server_name.clone(), MAX_MESSAGE_SIZE),
+            false => Reply::Helo(self.server_name.clone()),
+        }
+    }
+
+    fn process_mail(&mut self, from: Option<Mailbox>, size: Option<u64>) -> Reply {
+        if !self.greeted || self.envelope.is_some() {
+            return Reply::OutOfSequence;
+        }
+
+        if size.unwrap_or(0) > MAX_MESSAGE_SIZE {
+            return Reply::MessageTooLarge;
+        }
+
+        self.envelope = Some(Envelope {
+            from,
+            recipients: Vec::new(),
+            size,
+        });
+        Reply::Ok
+    }
+
+    async fn process_rcpt(&mut self, recipient: Mailbox) -> Reply {
+        let (count, size) = match &self.envelope {
+            Some(envelope) => (envelope.recipients.len(), envelope.size),
+            None => return Reply::OutOfSequence,
+        };
+
+        if count >= MAX_RECIPIENTS {
+            return Reply::TooManyRecipients;
+        }
+
+        let reply = self.handler.recipient(&recipient, size).await;
+
+        if let (true, Some(envelope)) = (reply.is_positive(), &mut self.envelope) {
+            envelope.recipients.push(recipient);
+        }
+
+        reply
+    }
+
+    async fn process_data(&mut self) -> Reply {
+        match &self.envelope {
+            Some(envelope) if !envelope.recipients.is_empty() => {}
+            _ => return Reply::OutOfSequence,
+        }
+
+        let spool = match Spool::create().await {
+            Ok(spool) => spool,
+            Err(e) => {
+                error!("Failed to create a spool: {}", e);
+                return Reply::LocalError;
+            }
+        };
+
+        self.data = Some(DataState {
+            decoder: DataDecoder::new(),
+            spool: Some(spool),
+            failure: Reply::LocalError,
+        });
+        Reply::StartData
+    }
+
+    /// Send a reply to the client.
+    async fn send(&mut self, reply: &Reply) -> Result<(), std::io::Error> {
+        debug!("Sending `{:?}`.", reply);
+
+        self.stream.write_all(reply.to_response().as_bytes()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use sha2::{Digest, Sha256};
+    use tokio::{
+        io::{AsyncBufReadExt, BufReader},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    /// A message as it was passed to the handler.
+    struct Received {
+        hash: String,
+        headers: Vec<u8>,
+        content: Vec<u8>,
+    }
+
+    /// A handler accepting every recipient, and keeping the received messages.
+    #[derive(Default)]
+    struct Collector {
+        messages: Mutex<Vec<Received>>,
+    }
+
+    #[async_trait]
+    impl Handler for Collector {
+        async fn recipient(&self, _: &Mailbox, _: Option<u64>) -> Reply {
+            Reply::Ok
+        }
+
+        async fn save(&self, _: &Envelope, message: &Spooled) -> Reply {
+            let received = Received {
+                hash: message.hash.clone(),
+                headers: message.headers.clone(),
+                content: message.read().await.unwrap(),
+            };
+            self.messages.lock().unwrap().push(received);
+
+            Reply::Ok
+        }
+    }
+
+    /// Run a session for the given client input, returning the replies and received messages.
+    async fn transact(chunks: &[&[u8]]) -> (String, Vec<Received>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let collector = Arc::new(Collector::default());
+
+        let handler = collector.clone();
+        let server = tokio::spawn(async move {
+            let (stream, client) = listener.accept().await.unwrap();
+            SmtpSession::new(stream, client, "test".into(), handler)
+                .handle()
+                .await;
+        });
+
+        let mut client = BufReader::new(TcpStream::connect(address).await.unwrap());
+        for chunk in chunks {
+            client.get_mut().write_all(chunk).await.unwrap();
+            client.get_mut().flush().await.unwrap();
+        }
+
+        let mut replies = String::new();
+        while client.read_line(&mut replies).await.unwrap() > 0 {}
+        server.await.unwrap();
+
+        let messages = std::mem::take(&mut *collector.messages.lock().unwrap());
+        (replies, messages)
+    }
+
+    #[tokio::test]
+    async fn eight_bit_content_is_kept() {
+        // Latin-1 text, and a binary attachment with bytes which are invalid UTF-8.
+        let mut binary: Vec<u8> = (0..=255).collect();
+        binary.extend_from_slice(b"\r\n.\r\n\0\r\r\n\xff\xfe");
+
+        let mut message = b"Subject: Caf\xe9\r\n\
+            Content-Type: multipart/mixed; boundary=b\r\n\
+            \r\n\
+            --b\r\n\
+            Content-Type: text/plain; charset=iso-8859-1\r\n\
+            Content-Transfer-Encoding: 8bit\r\n\
+            \r\n\
+            Gr\xfc\xdfe aus K\xf6ln, \xe0 bient\xf4t.\r\n\
+            .A line starting with a dot.\r\n\
+            --b\r\n\
+            Content-Type: application/octet-stream\r\n\
+            Content-Transfer-Encoding: binary\r\n\
+            \r\n"
+            .to_vec();
+        message.extend_from_slice(&binary);
+        message.extend_from_slice(b"\r\n--b--\r\n");
+
+        // Lines starting with a dot are stuffed by the client.
+        let mut stuffed = Vec::new();
+        for line in message.split_inclusive(|b| *b == b'\n') {
+            if line.starts_with(b".") {
+                stuffed.push(b'.');
+            }
+            stuffed.extend_from_slice(line);
+        }
+
+        let mut data =
+            b"EHLO client\r\nMAIL FROM:<a@example.com>\r\nRCPT TO:<b@example.com>\r\nDATA\r\n"
+                .to_vec();
+        data.extend_from_slice(&stuffed);
+        data.extend_from_slice(b".\r\nQUIT\r\n");
+
+        // Once at a time, and split within the line endings and the stuffing.
+        let split: Vec<&[u8]> = data.chunks(7).collect();
+        for chunks in [vec![&data[..]], split] {
+            let (replies, messages) = transact(&chunks).await;
+
+            assert!(replies.contains("354 "), "{}", replies);
+            assert!(
+                replies.ends_with("250 Ok\r\n221 Goodbye!\r\n"),
+                "{}",
+                replies
+            );
+            assert_eq!(messages.len(), 1);
+
+            let received = &messages[0];
+            assert_eq!(received.content, message);
+            assert_eq!(received.hash, hex::encode(Sha256::digest(&message)));
+            assert!(received.headers.starts_with(b"Subject: Caf\xe9\r\n"));
+            assert!(received.headers.ends_with(b"boundary=b\r\n\r\n"));
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Read},
+    path::Path,
+    sync::Arc,
+};
+
+use super::spool::TempPath;
+
+/// The zstd compression level, which is fast while mail still compresses well.
+const LEVEL: i32 = 3;
+
+/// The maximum size of a trained dictionary.
+const DICTIONARY_SIZE: usize = 112 * 1024;
+
+/// A compressed copy of content in a temporary file, ready to be put in the blob store.
+pub struct Compressed {
+    path: TempPath,
+    pub size: u64,
+}
+
+impl Compressed {
+    /// Compress all content of a reader with zstd, optionally using a dictionary.
+    /// The compression runs on the blocking thread pool.
+    pub async fn create<R: Read + Send + 'static>(
+        reader: R,
+        dictionary: Option<Arc<Vec<u8>>>,
+    ) -> io::Result<Self> {
+        let path = TempPath::create("zst").await?;
+        let target = path.0.clone();
+
+        let size = tokio::task::spawn_blocking(move || {
+            compress(reader, &target, dictionary.as_deref().map(Vec::as_slice))
+        })
+        .await
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))??;
+
+        Ok(Compressed { path, size })
+    }
+
+    /// The location of the compressed file.
+    pub fn path(&self) -> &Path {
+        &self.path.0
+    }
+}
+
+/// Compress a reader into a file, returning the compressed size.
+fn compress<R: Read>(mut reader: R, target: &Path, dictionary: Option<&[u8]>) -> io::Result<u64> {
+    let file = BufWriter::new(File::create(target)?);
+    let mut encoder = match dictionary {
+        Some(dictionary) => zstd::Encoder::with_dictionary(file, LEVEL, dictionary)?,
+        None => zstd::Encoder::new(file, LEVEL)?,
+    };
+    encoder.include_checksum(true)?;
+
+    io::copy(&mut reader, &mut encoder)?;
+
+    let file = encoder.finish()?.into_inner().map_err(|e| e.into_error())?;
+    file.sync_all()?;
+
+    Ok(file.metadata()?.len())
+}
+
+/// Decompress a range of content, with the dictionary it was compressed with.
+/// The content before the range is decompressed as a stream and dropped.
+pub fn decompress(
+    data: &[u8],
+    dictionary: Option<&[u8]>,
+    offset: u64,
+    length: u64,
+) -> io::Result<Vec<u8>> {
+    // An empty dictionary is the same as none.
+    let mut decoder = zstd::Decoder::with_dictionary(data, dictionary.unwrap_or(&[]))?;
+    io::copy(&mut (&mut decoder).take(offset), &mut io::sink())?;
+
+    let mut output = Vec::new();
+    decoder.take(length).read_to_end(&mut output)?;
+
+    Ok(output)
+}
+
+/// Train a dictionary from samples of stored content.
+/// Returns the dictionary with its ID.
+pub fn train(samples: &[Vec<u8>]) -> io::Result<(u32, Vec<u8>)> {
+    let dictionary = zstd::dict::from_samples(samples, DICTIONARY_SIZE)?;
+
+    Ok((zstd_safe::get_dict_id_from_dict(&dictionary), dictionary))
+}
@@ -0,0 +1,42 @@
+use std::{io, path::Path};
+
+use sha2::{Digest, Sha256};
+use tokio::fs;
+
+use super::{spool::TempPath, Compressed};
+use crate::crypto::{self, Key};
+
+/// An encrypted copy of compressed content in a temporary file, ready to be put in the blob store.
+/// The content is encrypted in memory, which is bounded by the compressed size.
+pub struct Encrypted {
+    path: TempPath,
+    /// The hash of the ciphertext, which is the key in the blob store.
+    pub hash: String,
+    pub size: u64,
+}
+
+impl Encrypted {
+    /// Encrypt compressed content with a content key.
+    pub async fn create(compressed: &Compressed, key: &Key) -> io::Result<Self> {
+        let data = fs::read(compressed.path()).await?;
+        let key = *key;
+
+        let ciphertext = tokio::task::spawn_blocking(move || crypto::encrypt(&key, &data))
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+        let path = TempPath::create("enc").await?;
+        fs::write(&path.0, &ciphertext).await?;
+
+        Ok(Encrypted {
+            path,
+            hash: hex::encode(Sha256::digest(&ciphertext)),
+            size: ciphertext.len() as u64,
+        })
+    }
+
+    /// The location of the encrypted file.
+    pub fn path(&self) -> &Path {
+        &self.path.0
+    }
+}
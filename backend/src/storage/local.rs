@@ -0,0 +1,74 @@
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use tokio::fs;
+use uuid::Uuid;
+
+use super::{validate_key, BlobStore, StoreError};
+
+/// Blob store keeping every blob in a file.
+/// Files are spread over subdirectories by the start of the key, like `ab/cd/abcd...`.
+pub struct LocalStore {
+    root: PathBuf,
+}
+
+impl LocalStore {
+    /// Create a store in a directory, which is created when needed.
+    pub fn new(root: impl AsRef<Path>) -> Self {
+        LocalStore {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    /// The path of the file of a key.
+    fn path(&self, key: &str) -> Result<PathBuf, StoreError> {
+        validate_key(key)?;
+
+        Ok(self.root.join(&key[0..2]).join(&key[2..4]).join(key))
+    }
+}
+
+#[async_trait]
+impl BlobStore for LocalStore {
+    async fn put(&self, key: &str, source: &Path) -> Result<(), StoreError> {
+        let path = self.path(key)?;
+
+        let dir = path.parent().expect("Blob paths always have a parent.");
+        fs::create_dir_all(dir).await?;
+
+        // Link or copy to a temporary file first, so readers never see a partial blob.
+        // Renaming replaces existing content at once, as when it gets compressed.
+        let temp = dir.join(format!(".{}.tmp", Uuid::new_v4()));
+        if fs::hard_link(source, &temp).await.is_err() {
+            if let Err(e) = fs::copy(source, &temp).await {
+                let _ = fs::remove_file(&temp).await;
+                return Err(e.into());
+            }
+        }
+
+        if let Err(e) = fs::rename(&temp, &path).await {
+            let _ = fs::remove_file(&temp).await;
+            return Err(e.into());
+        }
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, StoreError> {
+        match fs::read(self.path(key)?).await {
+            Ok(data) => Ok(data),
+            Err(e) if e.kind() == ErrorKind::NotFound => Err(StoreError::NotFound(key.to_string())),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StoreError> {
+        match fs::remove_file(self.path(key)?).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use std::{path::Path, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+
+use crate::logic::{
+    blob::Blob,
+    quota::QuotaConfig,
+    retention::{self, RetentionConfig},
+    upload::Upload,
+};
+
+mod compression;
+mod encryption;
+mod local;
+mod s3;
+mod spool;
+mod tracked;
+
+pub use compression::{decompress, train, Compressed};
+pub use encryption::Encrypted;
+pub use local::LocalStore;
+pub use s3::S3Store;
+pub use spool::{Spool, Spooled};
+pub use tracked::TrackedStore;
+
+/// Time between garbage collection runs.
+const GC_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Time between checks for blobs to compress.
+const COMPRESSION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Time between runs of the retention policies.
+const RETENTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Storage of content addressed blobs.
+/// Keys are the hex encoded SHA-256 hash of the original content, so writing the same key twice is harmless.
+/// The stored content itself is usually compressed, encrypted content is keyed by the hash of the ciphertext.
+#[async_trait]
+pub trait BlobStore: Send + Sync {
+    /// Store the content of a file under the key.
+    /// The file is streamed, and left in place.
+    async fn put(&self, key: &str, path: &Path) -> Result<(), StoreError>;
+    /// Read the content of a key.
+    async fn get(&self, key: &str) -> Result<Vec<u8>, StoreError>;
+    /// Remove the content of a key, removing a missing key is not an error.
+    async fn delete(&self, key: &str) -> Result<(), StoreError>;
+}
+
+/// The configured backend for blobs.
+#[derive(Debug, Clone)]
+pub enum BlobConfig {
+    /// Blobs are stored as files in a directory.
+    Local { path: String },
+    /// Blobs are stored in a bucket of an S3-compatible service.
+    S3 {
+        endpoint: String,
+        bucket: String,
+        region: String,
+        access_key: String,
+        secret_key: String,
+    },
+}
+
+/// Create the blob store from the configuration.
+/// Writes are recorded in the database, so that content of rolled back transactions is collected.
+pub fn connect(config: &BlobConfig, db: Pool<Postgres>) -> Result<Arc<dyn BlobStore>, StoreError> {
+    let store: Arc<dyn BlobStore> = match config {
+        BlobConfig::Local { path } => Arc::new(LocalStore::new(path)),
+        BlobConfig::S3 {
+            endpoint,
+            bucket,
+            region,
+            access_key,
+            secret_key,
+        } => Arc::new(S3Store::new(
+            endpoint, bucket, region, access_key, secret_key,
+        )?),
+    };
+
+    Ok(Arc::new(TrackedStore::new(store, db)))
+}
+
+/// Start the garbage collection of unreferenced blobs, and of content never registered as a blob.
+/// Expired uploads are removed first, so their blobs are collected as well.
+pub async fn start_gc(db: Pool<Postgres>, store: Arc<dyn BlobStore>) {
+    info!("Starting blob garbage collection");
+
+    loop {
+        match Upload::expire(&db).await {
+            Ok(0) => {}
+            Ok(count) => info!("Removed {} expired uploads.", count),
+            Err(e) => warn!("Failed to remove expired uploads: {}", e),
+        }
+
+        match Blob::collect_garbage(&db, store.as_ref()).await {
+            Ok(0) => {}
+            Ok(count) => info!("Removed {} unreferenced blobs.", count),
+            Err(e) => warn!("Failed to collect unreferenced blobs: {}", e),
+        }
+
+        match Blob::collect_abandoned(&db, store.as_ref()).await {
+            Ok(0) => {}
+            Ok(count) => info!("Removed {} blobs of rolled back transactions.", count),
+            Err(e) => warn!("Failed to collect abandoned blobs: {}", e),
+        }
+
+        tokio::time::sleep(GC_INTERVAL).await;
+    }
+}
+
+/// Start the background compression of blobs stored before compression was introduced.
+/// When enabled, a dictionary is trained once enough blobs are stored.
+pub async fn start_compression(db: Pool<Postgres>, store: Arc<dyn BlobStore>, dictionary: bool) {
+    info!("Starting blob compression");
+
+    loop {
+        if dictionary {
+            match Blob::train_dictionary(&db, store.as_ref()).await {
+                Ok(Some(id)) => info!("Trained compression dictionary {}.", id),
+                Ok(None) => {}
+                Err(e) => warn!("Failed to train a compression dictionary: {}", e),
+            }
+        }
+
+        match Blob::compress_existing(&db, store.as_ref()).await {
+            Ok(0) => {}
+            Ok(count) => info!("Compressed {} existing blobs.", count),
+            Err(e) => warn!("Failed to compress existing blobs: {}", e),
+        }
+
+        tokio::time::sleep(COMPRESSION_INTERVAL).await;
+    }
+}
+
+/// Start applying the retention policies of mailboxes.
+/// Deleted messages release their blobs, which are then removed by the garbage collection.
+pub async fn start_retention(db: Pool<Postgres>, config: RetentionConfig, quota: QuotaConfig) {
+    info!("Starting mailbox retention");
+
+    loop {
+        match retention::purge(&db, &config, &quota).await {
+            Ok(0) => {}
+            Ok(count) => info!("Purged {} messages by retention policies.", count),
+            Err(e) => warn!("Failed to apply retention policies: {}", e),
+        }
+
+        tokio::time::sleep(RETENTION_INTERVAL).await;
+    }
+}
+
+/// Possible errors of a blob store.
+#[derive(Error, Debug)]
+pub enum StoreError {
+    #[error("The blob {0} does not exist.")]
+    NotFound(String),
+    #[error("The blob key '{0}' is invalid.")]
+    InvalidKey(String),
+    #[error("Blob storage failed: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Blob storage request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Blob storage responded with status {0}.")]
+    Status(u16),
+    #[error("The blob storage endpoint '{0}' is invalid.")]
+    InvalidEndpoint(String),
+    #[error("Recording the blob failed: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// Check that a key is a hex encoded SHA-256 hash.
+/// This prevents keys from escaping the directory or bucket.
+fn validate_key(key: &str) -> Result<(), StoreError> {
+    if key.len() == 64 && key.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+        Ok(())
+    } else {
+        Err(StoreError::InvalidKey(key.to_string()))
+    }
+}
@@ -0,0 +1,358 @@
+use std::path::Path;
+
+use async_trait::async_trait;
+use hmac::{Hmac, Mac, NewMac};
+use reqwest::{Body, Client, Method, StatusCode, Url};
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+use tokio::{fs::File, io::AsyncReadExt};
+use tokio_util::io::ReaderStream;
+
+use super::{validate_key, BlobStore, StoreError};
+
+/// Blob store keeping blobs in a bucket of an S3-compatible service, like AWS S3 or MinIO.
+/// Requests use path-style addressing, and are signed with AWS Signature Version 4.
+pub struct S3Store {
+    client: Client,
+    endpoint: Url,
+    host: String,
+    bucket: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+}
+
+impl S3Store {
+    /// Create a store for a bucket on an endpoint, like `https://s3.eu-west-1.amazonaws.com`.
+    pub fn new(
+        endpoint: &str,
+        bucket: &str,
+        region: &str,
+        access_key: &str,
+        secret_key: &str,
+    ) -> Result<Self, StoreError> {
+        let endpoint =
+            Url::parse(endpoint).map_err(|_| StoreError::InvalidEndpoint(endpoint.to_string()))?;
+        let host = match (endpoint.host_str(), endpoint.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => return Err(StoreError::InvalidEndpoint(endpoint.to_string())),
+        };
+
+        Ok(S3Store {
+            client: Client::builder().build()?,
+            endpoint,
+            host,
+            bucket: bucket.to_string(),
+            region: region.to_string(),
+            access_key: access_key.to_string(),
+            secret_key: secret_key.to_string(),
+        })
+    }
+
+    /// Send a signed request for a key, without a body.
+    async fn request(&self, method: Method, key: &str) -> Result<reqwest::Response, StoreError> {
+        let empty_hash = hex::encode(Sha256::digest(b""));
+
+        self.request_with_body(method, key, Body::from(Vec::new()), 0, &empty_hash)
+            .await
+    }
+
+    /// Send a signed request for a key.
+    /// The body is streamed, so its hash has to be known in advance.
+    async fn request_with_body(
+        &self,
+        method: Method,
+        key: &str,
+        body: Body,
+        length: u64,
+        payload_hash: &str,
+    ) -> Result<reqwest::Response, StoreError> {
+        validate_key(key)?;
+
+        let path = format!("/{}/{}", self.bucket, key);
+        let url = self
+            .endpoint
+            .join(&path)
+            .map_err(|_| StoreError::InvalidEndpoint(self.endpoint.to_string()))?;
+
+        let now = OffsetDateTime::now_utc();
+        let timestamp = now.format("%Y%m%dT%H%M%SZ");
+        let date = now.format("%Y%m%d");
+
+        let canonical_request = format!(
+            "{}\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            method.as_str(),
+            path,
+            self.host,
+            payload_hash,
+            timestamp,
+            SIGNED_HEADERS,
+            payload_hash
+        );
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            timestamp,
+            scope,
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let key = hmac(
+            format!("AWS4{}", self.secret_key).as_bytes(),
+            date.as_bytes(),
+        );
+        let key = hmac(&key, self.region.as_bytes());
+        let key = hmac(&key, b"s3");
+        let key = hmac(&key, b"aws4_request");
+        let signature = hex::encode(hmac(&key, string_to_sign.as_bytes()));
+
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key, scope, SIGNED_HEADERS, signature
+        );
+
+        Ok(self
+            .client
+            .request(method, url)
+            .header("x-amz-content-sha256", payload_hash)
+            .header("x-amz-date", timestamp)
+            .header("content-length", length)
+            .header("authorization", authorization)
+            .body(body)
+            .send()
+            .await?)
+    }
+}
+
+/// The headers included in the signature.
+const SIGNED_HEADERS: &str = "host;x-amz-content-sha256;x-amz-date";
+
+/// Calculate a HMAC-SHA256.
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_varkey(key).expect("HMAC accepts keys of any length.");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Calculate the hex encoded SHA-256 hash of a file, reading it in chunks.
+async fn hash_file(path: &Path) -> Result<String, std::io::Error> {
+    let mut file = File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buff = vec![0; 64 * 1024];
+
+    loop {
+        match file.read(&mut buff).await? {
+            0 => return Ok(hex::encode(hasher.finalize())),
+            n => hasher.update(&buff[..n]),
+        }
+    }
+}
+
+#[async_trait]
+impl BlobStore for S3Store {
+    async fn put(&self, key: &str, path: &Path) -> Result<(), StoreError> {
+        // The stored content can differ from the content the key was derived from.
+        let payload_hash = hash_file(path).await?;
+
+        let file = File::open(path).await?;
+        let length = file.metadata().await?.len();
+        let body = Body::wrap_stream(ReaderStream::new(file));
+
+        let res = self
+            .request_with_body(Method::PUT, key, body, length, &payload_hash)
+            .await?;
+
+        match res.status() {
+            s if s.is_success() => Ok(()),
+            s => Err(StoreError::Status(s.as_u16())),
+        }
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, StoreError> {
+        let res = self.request(Method::GET, key).await?;
+
+        match res.status() {
+            s if s.is_success() => Ok(res.bytes().await?.to_vec()),
+            StatusCode::NOT_FOUND => Err(StoreError::NotFound(key.to_string())),
+            s => Err(StoreError::Status(s.as_u16())),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StoreError> {
+        let res = self.request(Method::DELETE, key).await?;
+
+        match res.status() {
+            s if s.is_success() || s == StatusCode::NOT_FOUND => Ok(()),
+            s => Err(StoreError::Status(s.as_u16())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    type Objects = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
+    /// Serve a bucket like MinIO does, checking the parts of each request the signature covers.
+    async fn serve(listener: TcpListener, objects: Objects) {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let objects = objects.clone();
+
+            tokio::spawn(async move {
+                let mut stream = BufReader::new(stream);
+
+                loop {
+                    let mut line = String::new();
+                    if stream.read_line(&mut line).await.unwrap_or(0) == 0 {
+                        return;
+                    }
+                    let mut parts = line.split_whitespace();
+                    let method = parts.next().unwrap().to_string();
+                    let path = parts.next().unwrap().to_string();
+
+                    let mut headers = HashMap::new();
+                    loop {
+                        let mut line = String::new();
+                        stream.read_line(&mut line).await.unwrap();
+                        match line.trim_end().split_once(':') {
+                            Some((name, value)) => {
+                                headers.insert(name.to_lowercase(), value.trim().to_string())
+                            }
+                            None => break,
+                        };
+                    }
+
+                    let length: usize = headers["content-length"].parse().unwrap();
+                    let mut body = vec![0; length];
+                    stream.read_exact(&mut body).await.unwrap();
+
+                    let authorized = headers["authorization"]
+                        .starts_with("AWS4-HMAC-SHA256 Credential=access/")
+                        && headers["authorization"].contains("/eu-central-1/s3/aws4_request, ")
+                        && headers["authorization"]
+                            .contains(&format!("SignedHeaders={}, Signature=", SIGNED_HEADERS))
+                        && headers["x-amz-content-sha256"] == hex::encode(Sha256::digest(&body))
+                        && headers.contains_key("x-amz-date");
+
+                    let key = path.strip_prefix("/bucket/").map(str::to_string);
+                    let (status, content) = respond(&objects, authorized, &method, key, body);
+
+                    let head = format!(
+                        "HTTP/1.1 {}\r\ncontent-length: {}\r\n\r\n",
+                        status,
+                        content.len()
+                    );
+                    let stream = stream.get_mut();
+                    stream.write_all(head.as_bytes()).await.unwrap();
+                    stream.write_all(&content).await.unwrap();
+                }
+            });
+        }
+    }
+
+    /// Apply a request to the objects of the bucket.
+    fn respond(
+        objects: &Objects,
+        authorized: bool,
+        method: &str,
+        key: Option<String>,
+        body: Vec<u8>,
+    ) -> (&'static str, Vec<u8>) {
+        let mut objects = objects.lock().unwrap();
+
+        match (authorized, method, key) {
+            (false, _, _) => ("403 Forbidden", Vec::new()),
+            (_, _, None) => ("404 Not Found", Vec::new()),
+            (_, "PUT", Some(key)) => {
+                objects.insert(key, body);
+                ("200 OK", Vec::new())
+            }
+            (_, "GET", Some(key)) => match objects.get(&key) {
+                Some(content) => ("200 OK", content.clone()),
+                None => ("404 Not Found", Vec::new()),
+            },
+            (_, "DELETE", Some(key)) => {
+                objects.remove(&key);
+                ("204 No Content", Vec::new())
+            }
+            _ => ("405 Method Not Allowed", Vec::new()),
+        }
+    }
+
+    async fn start() -> (S3Store, Objects) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let objects = Objects::default();
+
+        tokio::spawn(serve(listener, objects.clone()));
+
+        let store = S3Store::new(&endpoint, "bucket", "eu-central-1", "access", "secret").unwrap();
+        (store, objects)
+    }
+
+    #[tokio::test]
+    async fn put_get_delete() {
+        let (store, objects) = start().await;
+        let content = b"Subject: Hello\r\n\r\nWorld\r\n".repeat(10_000);
+        let key = hex::encode(Sha256::digest(&content));
+
+        let path = std::env::temp_dir().join(format!("s3-test-{}", key));
+        tokio::fs::write(&path, &content).await.unwrap();
+        let put = store.put(&key, &path).await;
+        tokio::fs::remove_file(&path).await.unwrap();
+        put.unwrap();
+
+        assert_eq!(objects.lock().unwrap()[&key], content);
+        assert_eq!(store.get(&key).await.unwrap(), content);
+
+        store.delete(&key).await.unwrap();
+        assert!(objects.lock().unwrap().is_empty());
+        assert!(matches!(
+            store.get(&key).await,
+            Err(StoreError::NotFound(_))
+        ));
+
+        // Removing a missing key is not an error.
+        store.delete(&key).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn invalid_keys_are_not_requested() {
+        let (store, _) = start().await;
+
+        let result = store.get("../other-bucket/key").await;
+        assert!(matches!(result, Err(StoreError::InvalidKey(_))));
+    }
+
+    #[tokio::test]
+    async fn rejected_requests_fail() {
+        let (store, _) = start().await;
+        let store = S3Store {
+            bucket: "other".into(),
+            ..store
+        };
+
+        let result = store.get(&"0".repeat(64)).await;
+        assert!(matches!(result, Err(StoreError::NotFound(_))));
+
+        let store = S3Store {
+            access_key: "intruder".into(),
+            ..store
+        };
+        let result = store.delete(&"0".repeat(64)).await;
+        assert!(matches!(result, Err(StoreError::Status(403))));
+    }
+}
@@ -0,0 +1,145 @@
+use std::{env, io, path::PathBuf, sync::Arc};
+
+use sha2::{Digest, Sha256};
+use tokio::{
+    fs::{self, File},
+    io::{AsyncWriteExt, BufWriter},
+};
+use uuid::Uuid;
+
+use super::Compressed;
+
+/// The maximum size of the header section kept in memory.
+const MAX_HEADER_SIZE: usize = 256 * 1024;
+
+/// Temporary file receiving a message before it is moved into the blob store.
+/// The content is hashed while it is written, and only the header section is kept in memory.
+pub struct Spool {
+    file: BufWriter<File>,
+    path: TempPath,
+    hasher: Sha256,
+    size: u64,
+    headers: Vec<u8>,
+    headers_complete: bool,
+}
+
+/// A completely written spool file.
+pub struct Spooled {
+    path: TempPath,
+    pub hash: String,
+    pub size: u64,
+    /// The header section of the message, including the empty line ending it.
+    pub headers: Vec<u8>,
+}
+
+impl Spool {
+    /// Create a new spool file in the temporary directory.
+    pub async fn create() -> io::Result<Self> {
+        let path = TempPath::create("spool").await?;
+        let file = File::create(&path.0).await?;
+
+        Ok(Spool {
+            file: BufWriter::new(file),
+            path,
+            hasher: Sha256::new(),
+            size: 0,
+            headers: Vec::new(),
+            headers_complete: false,
+        })
+    }
+
+    /// The amount of bytes written so far.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Append content to the spool.
+    pub async fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        self.hasher.update(data);
+        self.size += data.len() as u64;
+
+        if !self.headers_complete {
+            self.collect_headers(data);
+        }
+
+        self.file.write_all(data).await
+    }
+
+    /// Flush the content to disk and finish the hash.
+    pub async fn finish(mut self) -> io::Result<Spooled> {
+        self.file.flush().await?;
+        self.file.get_mut().sync_all().await?;
+
+        Ok(Spooled {
+            path: self.path,
+            hash: hex::encode(self.hasher.finalize()),
+            size: self.size,
+            headers: self.headers,
+        })
+    }
+
+    /// Keep the bytes up to the first empty line.
+    /// The search starts a few bytes back, as the line ending can be split over writes.
+    fn collect_headers(&mut self, data: &[u8]) {
+        let start = self.headers.len().saturating_sub(3);
+        let available = MAX_HEADER_SIZE - self.headers.len();
+        self.headers
+            .extend_from_slice(&data[..data.len().min(available)]);
+
+        let end = find(&self.headers[start..], b"\r\n\r\n")
+            .map(|i| start + i + 4)
+            .or_else(|| find(&self.headers[start..], b"\n\n").map(|i| start + i + 2));
+
+        if let Some(end) = end {
+            self.headers.truncate(end);
+            self.headers_complete = true;
+        } else if self.headers.len() >= MAX_HEADER_SIZE {
+            self.headers_complete = true;
+        }
+    }
+}
+
+impl Spooled {
+    /// Compress the content into a new temporary file.
+    pub async fn compress(&self, dictionary: Option<Arc<Vec<u8>>>) -> io::Result<Compressed> {
+        Compressed::create(self.open().await?, dictionary).await
+    }
+
+    /// Open the content for reading on the blocking thread pool.
+    pub async fn open(&self) -> io::Result<std::fs::File> {
+        Ok(File::open(&self.path.0).await?.into_std().await)
+    }
+
+    /// Read the entire content into memory.
+    pub async fn read(&self) -> io::Result<Vec<u8>> {
+        fs::read(&self.path.0).await
+    }
+}
+
+/// Path of a temporary file, which is removed when dropped.
+pub(super) struct TempPath(pub(super) PathBuf);
+
+impl TempPath {
+    /// Reserve a new path in the temporary directory, with the given extension.
+    pub(super) async fn create(extension: &str) -> io::Result<Self> {
+        let dir = env::temp_dir().join("nexium");
+        fs::create_dir_all(&dir).await?;
+
+        Ok(TempPath(dir.join(format!(
+            "{}.{}",
+            Uuid::new_v4(),
+            extension
+        ))))
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Find the position of a needle in a haystack.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
@@ -0,0 +1,40 @@
+use std::{path::Path, sync::Arc};
+
+use async_trait::async_trait;
+use sqlx::{Pool, Postgres};
+
+use super::{BlobStore, StoreError};
+use crate::database;
+
+/// Blob store recording every write in the database before it happens.
+/// Content is written ahead of the transaction registering it, the record is committed on its own,
+/// so the garbage collection can remove the content when that transaction is rolled back.
+pub struct TrackedStore {
+    inner: Arc<dyn BlobStore>,
+    db: Pool<Postgres>,
+}
+
+impl TrackedStore {
+    /// Wrap a store, recording its writes in the database.
+    pub fn new(inner: Arc<dyn BlobStore>, db: Pool<Postgres>) -> Self {
+        TrackedStore { inner, db }
+    }
+}
+
+#[async_trait]
+impl BlobStore for TrackedStore {
+    async fn put(&self, key: &str, path: &Path) -> Result<(), StoreError> {
+        let mut conn = self.db.acquire().await?;
+        database::blob_pending::record(&mut conn, key).await?;
+
+        self.inner.put(key, path).await
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, StoreError> {
+        self.inner.get(key).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StoreError> {
+        self.inner.delete(key).await
+    }
+}